@@ -0,0 +1,11 @@
+package com.jfairbairn.powersync
+
+import android.app.Activity
+import app.tauri.annotation.TauriPlugin
+import app.tauri.plugin.Plugin
+
+// All database work happens in Rust; the native side only exists so the
+// plugin is registered with the Android runtime. libpowersync.so is packaged
+// in jniLibs and resolved by name when the extension is loaded.
+@TauriPlugin
+class PowerSyncPlugin(private val activity: Activity) : Plugin(activity)
@@ -0,0 +1,122 @@
+//! Benchmarks for the hot paths between SQLite and IPC
+//!
+//! Run with `cargo bench -p powersync-sqlite`.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use powersync_sqlite::rusqlite::ToSql;
+use powersync_sqlite::{DatabaseManager, PowerSyncConnection, SqlParam};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+const ROWS: i64 = 10_000;
+
+struct BenchDb {
+    conn: Arc<Mutex<PowerSyncConnection>>,
+    dir: PathBuf,
+}
+
+impl BenchDb {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("powersync-bench-{}", uuid::Uuid::new_v4()));
+        let mut manager = DatabaseManager::new(dir.clone(), None);
+        manager.open(name).unwrap();
+        let conn = manager.get(name).unwrap();
+        conn.lock()
+            .unwrap()
+            .execute(
+                "CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT, price REAL, data BLOB)",
+                &[],
+            )
+            .unwrap();
+        Self { conn, dir }
+    }
+
+    fn seeded(name: &str) -> Self {
+        let db = Self::new(name);
+        db.conn
+            .lock()
+            .unwrap()
+            .execute_batch(
+                "INSERT INTO items (name, price, data) VALUES (?, ?, ?)",
+                &(0..ROWS).map(item_params).collect::<Vec<_>>(),
+            )
+            .unwrap();
+        db
+    }
+}
+
+impl Drop for BenchDb {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn item_params(i: i64) -> Vec<SqlParam> {
+    vec![
+        SqlParam::Text(format!("item {}", i)),
+        SqlParam::Real(i as f64 * 1.5),
+        SqlParam::Blob(vec![0u8; 32]),
+    ]
+}
+
+fn bench_get_all(c: &mut Criterion) {
+    let db = BenchDb::seeded("get_all");
+    let conn = db.conn.lock().unwrap();
+    c.bench_function("get_all 10k rows", |b| {
+        b.iter(|| black_box(conn.get_all("SELECT * FROM items", &[]).unwrap()))
+    });
+}
+
+fn bench_param_conversion(c: &mut Criterion) {
+    let params: Vec<SqlParam> = (0..1_000).flat_map(item_params).collect();
+    c.bench_function("SqlParam binding 3k params", |b| {
+        b.iter(|| {
+            black_box(
+                params
+                    .iter()
+                    .map(|param| param.to_sql().unwrap())
+                    .collect::<Vec<_>>(),
+            )
+        })
+    });
+}
+
+fn bench_batch_insert(c: &mut Criterion) {
+    let batch: Vec<Vec<SqlParam>> = (0..1_000).map(item_params).collect();
+    c.bench_function("execute_batch 1k inserts", |b| {
+        b.iter_batched(
+            || BenchDb::new("batch"),
+            |db| {
+                db.conn
+                    .lock()
+                    .unwrap()
+                    .execute_batch("INSERT INTO items (name, price, data) VALUES (?, ?, ?)", &batch)
+                    .unwrap();
+                db
+            },
+            BatchSize::PerIteration,
+        )
+    });
+}
+
+fn bench_serialize_result(c: &mut Criterion) {
+    let db = BenchDb::seeded("serialize");
+    let result = db
+        .conn
+        .lock()
+        .unwrap()
+        .get_all("SELECT * FROM items", &[])
+        .unwrap();
+    c.bench_function("serialize 10k row result to JSON", |b| {
+        b.iter(|| black_box(serde_json::to_vec(&result).unwrap()))
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_get_all,
+    bench_param_conversion,
+    bench_batch_insert,
+    bench_serialize_result
+);
+criterion_main!(benches);
@@ -0,0 +1,334 @@
+//! Encrypted export bundles
+//!
+//! A bundle is a compressed copy of a database, encrypted with a key derived
+//! from a passphrase, for "back up to a file" and "move to a new machine"
+//! features. Its layout is:
+//!
+//! - the magic bytes `PSJFBNDL` and a format version byte
+//! - an Argon2id salt (16 bytes) and an XChaCha20-Poly1305 STREAM nonce
+//!   prefix (19 bytes)
+//! - the gzip-compressed database file, encrypted in 64 KiB chunks
+//!
+//! Every chunk is authenticated together with the header, so a wrong
+//! passphrase, a truncated file or tampering fails the import instead of
+//! producing a damaged database.
+
+use crate::error::{Error, Result};
+use argon2::Argon2;
+use chacha20poly1305::aead::generic_array::GenericArray;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::stream::{DecryptorBE32, EncryptorBE32};
+use chacha20poly1305::aead::{KeyInit, OsRng, Payload};
+use chacha20poly1305::XChaCha20Poly1305;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"PSJFBNDL";
+const VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+/// XChaCha20's 24-byte nonce, less STREAM's 4-byte counter and last-chunk flag
+const NONCE_PREFIX_LEN: usize = 19;
+const HEADER_LEN: usize = MAGIC.len() + 1 + SALT_LEN + NONCE_PREFIX_LEN;
+/// Plaintext bytes per encrypted chunk
+const CHUNK_LEN: usize = 64 * 1024;
+/// Poly1305 tag appended to each chunk
+const TAG_LEN: usize = 16;
+
+/// What an encrypted export wrote
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct EncryptedExport {
+    /// Size of the bundle
+    pub bytes: i64,
+    /// Size of the database it holds, uncompressed
+    pub database_bytes: i64,
+}
+
+/// Compress and encrypt the database image `image` into a bundle at `dest`.
+/// A partly written bundle is removed.
+pub(crate) fn write(image: &[u8], dest: &Path, passphrase: &str) -> Result<EncryptedExport> {
+    check_passphrase(passphrase)?;
+    let result = write_bundle(image, dest, passphrase);
+    if result.is_err() {
+        let _ = std::fs::remove_file(dest);
+    }
+    result
+}
+
+fn write_bundle(image: &[u8], dest: &Path, passphrase: &str) -> Result<EncryptedExport> {
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(MAGIC);
+    header.push(VERSION);
+    let mut random = [0u8; SALT_LEN + NONCE_PREFIX_LEN];
+    OsRng.fill_bytes(&mut random);
+    header.extend_from_slice(&random);
+    let encryptor = encryptor(&header, passphrase)?;
+
+    let mut out = BufWriter::new(File::create(dest)?);
+    out.write_all(&header)?;
+    let writer = ChunkWriter {
+        encryptor: Some(encryptor),
+        header: header.clone(),
+        buffer: Vec::with_capacity(CHUNK_LEN),
+        out,
+    };
+    let mut gzip = GzEncoder::new(writer, Compression::default());
+    gzip.write_all(image)?;
+    let mut out = gzip.finish()?.finish()?;
+    out.flush()?;
+    out.get_ref().sync_all()?;
+
+    Ok(EncryptedExport {
+        bytes: out.get_ref().metadata()?.len() as i64,
+        database_bytes: image.len() as i64,
+    })
+}
+
+/// Decrypt and decompress the bundle at `src` into the database image it holds
+pub(crate) fn read(src: &Path, passphrase: &str) -> Result<Vec<u8>> {
+    check_passphrase(passphrase)?;
+    let mut input = BufReader::new(File::open(src)?);
+    let mut header = vec![0u8; HEADER_LEN];
+    input
+        .read_exact(&mut header)
+        .map_err(|_| Error::InvalidBundle("file is too short".to_string()))?;
+    if &header[..MAGIC.len()] != MAGIC {
+        return Err(Error::InvalidBundle("not an export bundle".to_string()));
+    }
+    if header[MAGIC.len()] != VERSION {
+        return Err(Error::InvalidBundle(format!(
+            "unsupported format version {}",
+            header[MAGIC.len()]
+        )));
+    }
+
+    let reader = ChunkReader {
+        decryptor: Some(decryptor(&header, passphrase)?),
+        header,
+        input,
+        chunk: Vec::new(),
+        position: 0,
+    };
+    let mut image = Vec::new();
+    GzDecoder::new(reader).read_to_end(&mut image).map_err(|e| {
+        match e
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<DecryptFailed>())
+        {
+            Some(_) => {
+                Error::InvalidBundle("wrong passphrase, or the bundle is damaged".to_string())
+            }
+            None => Error::Io(e),
+        }
+    })?;
+    Ok(image)
+}
+
+fn check_passphrase(passphrase: &str) -> Result<()> {
+    if passphrase.is_empty() {
+        return Err(Error::InvalidParameter(
+            "passphrase must not be empty".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn cipher(header: &[u8], passphrase: &str) -> Result<XChaCha20Poly1305> {
+    let salt = &header[MAGIC.len() + 1..][..SALT_LEN];
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| Error::InvalidParameter(format!("can't derive key: {}", e)))?;
+    Ok(XChaCha20Poly1305::new(&key.into()))
+}
+
+fn nonce_prefix(header: &[u8]) -> &GenericArray<u8, chacha20poly1305::consts::U19> {
+    GenericArray::from_slice(&header[HEADER_LEN - NONCE_PREFIX_LEN..])
+}
+
+fn encryptor(header: &[u8], passphrase: &str) -> Result<EncryptorBE32<XChaCha20Poly1305>> {
+    let cipher = cipher(header, passphrase)?;
+    Ok(EncryptorBE32::from_aead(cipher, nonce_prefix(header)))
+}
+
+fn decryptor(header: &[u8], passphrase: &str) -> Result<DecryptorBE32<XChaCha20Poly1305>> {
+    let cipher = cipher(header, passphrase)?;
+    Ok(DecryptorBE32::from_aead(cipher, nonce_prefix(header)))
+}
+
+/// Encrypts what is written to it in chunks of [`CHUNK_LEN`] bytes. The
+/// last chunk is always shorter, so readers can tell it apart.
+struct ChunkWriter<W: Write> {
+    encryptor: Option<EncryptorBE32<XChaCha20Poly1305>>,
+    header: Vec<u8>,
+    buffer: Vec<u8>,
+    out: W,
+}
+
+impl<W: Write> ChunkWriter<W> {
+    /// Encrypt the last chunk, returning the underlying writer
+    fn finish(mut self) -> std::io::Result<W> {
+        let encryptor = self.encryptor.take().ok_or_else(encrypt_failed)?;
+        let payload = Payload {
+            msg: &self.buffer,
+            aad: &self.header,
+        };
+        let chunk = encryptor
+            .encrypt_last(payload)
+            .map_err(|_| encrypt_failed())?;
+        self.out.write_all(&chunk)?;
+        Ok(self.out)
+    }
+}
+
+impl<W: Write> Write for ChunkWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        while self.buffer.len() >= CHUNK_LEN {
+            let encryptor = self.encryptor.as_mut().ok_or_else(encrypt_failed)?;
+            let payload = Payload {
+                msg: &self.buffer[..CHUNK_LEN],
+                aad: &self.header,
+            };
+            let chunk = encryptor
+                .encrypt_next(payload)
+                .map_err(|_| encrypt_failed())?;
+            self.out.write_all(&chunk)?;
+            self.buffer.drain(..CHUNK_LEN);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.out.flush()
+    }
+}
+
+fn encrypt_failed() -> std::io::Error {
+    std::io::Error::other("encryption failed")
+}
+
+/// A chunk that failed authentication
+#[derive(Debug)]
+struct DecryptFailed;
+
+impl std::fmt::Display for DecryptFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("decryption failed")
+    }
+}
+
+impl std::error::Error for DecryptFailed {}
+
+/// Decrypts the chunks [`ChunkWriter`] wrote
+struct ChunkReader<R: Read> {
+    decryptor: Option<DecryptorBE32<XChaCha20Poly1305>>,
+    header: Vec<u8>,
+    input: R,
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+impl<R: Read> ChunkReader<R> {
+    /// Decrypt the next chunk into `self.chunk`. Leaves it empty at the end.
+    fn next_chunk(&mut self) -> std::io::Result<()> {
+        self.chunk.clear();
+        self.position = 0;
+        let Some(mut decryptor) = self.decryptor.take() else {
+            return Ok(());
+        };
+        let mut sealed = Vec::with_capacity(CHUNK_LEN + TAG_LEN);
+        (&mut self.input)
+            .take((CHUNK_LEN + TAG_LEN) as u64)
+            .read_to_end(&mut sealed)?;
+        let payload = Payload {
+            msg: &sealed,
+            aad: &self.header,
+        };
+        let decrypted = if sealed.len() == CHUNK_LEN + TAG_LEN {
+            let decrypted = decryptor.decrypt_next(payload);
+            self.decryptor = Some(decryptor);
+            decrypted
+        } else {
+            decryptor.decrypt_last(payload)
+        };
+        self.chunk = decrypted.map_err(|_| std::io::Error::other(DecryptFailed))?;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for ChunkReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.position == self.chunk.len() {
+            if self.decryptor.is_none() {
+                return Ok(0);
+            }
+            self.next_chunk()?;
+        }
+        let n = buf.len().min(self.chunk.len() - self.position);
+        buf[..n].copy_from_slice(&self.chunk[self.position..][..n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "powersync-bundle-{}-{}",
+            uuid::Uuid::new_v4(),
+            name
+        ))
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let bundle = temp_path("bundle");
+        // Incompressible, so the bundle spans several chunks
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let contents: Vec<u8> = (0..CHUNK_LEN * 3 + 100)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+
+        let export = write(&contents, &bundle, "correct horse").unwrap();
+        assert_eq!(export.database_bytes, contents.len() as i64);
+        assert_eq!(
+            export.bytes,
+            std::fs::metadata(&bundle).unwrap().len() as i64
+        );
+
+        assert_eq!(read(&bundle, "correct horse").unwrap(), contents);
+
+        let wrong = read(&bundle, "battery staple");
+        assert!(matches!(wrong, Err(Error::InvalidBundle(_))), "{:?}", wrong);
+        assert!(write(&contents, &bundle, "").is_err());
+        assert!(bundle.exists());
+
+        // Dropping the last chunk must not go unnoticed
+        let sealed = std::fs::read(&bundle).unwrap();
+        std::fs::write(&bundle, &sealed[..sealed.len() - 10]).unwrap();
+        assert!(read(&bundle, "correct horse").is_err());
+
+        std::fs::write(&bundle, b"not a bundle at all, just some text").unwrap();
+        assert!(matches!(
+            read(&bundle, "correct horse"),
+            Err(Error::InvalidBundle(_))
+        ));
+
+        std::fs::remove_file(bundle).unwrap();
+    }
+}
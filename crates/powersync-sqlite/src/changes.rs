@@ -0,0 +1,461 @@
+//! Table change subscriptions for Rust consumers
+//!
+//! SQLite update hooks are buffered per transaction and broadcast on commit,
+//! so other Rust plugins/services can react to data changes without going
+//! through the webview event system. Rolled back changes are discarded.
+//!
+//! Each commit is also summarized as the set of tables it touched, computed
+//! once however many watchers need to decide whether to re-run.
+//!
+//! Changes written while applying sync data are also tallied per table and
+//! reported once per checkpoint, for consumers that refresh caches in bulk.
+
+use crate::control::CheckpointFailure;
+use crate::correlation;
+use crate::schema::SchemaChange;
+use crate::uploads::UploadError;
+use rusqlite::hooks::Action;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+/// Capacity of each subscriber's broadcast channel
+const CHANNEL_CAPACITY: usize = 256;
+
+/// Prefixes PowerSync uses for the tables backing schema views
+const PS_DATA_PREFIXES: &[&str] = &["ps_data_local__", "ps_data__"];
+
+/// Kind of row change reported by SQLite
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ChangeOperation {
+    Insert,
+    Update,
+    Delete,
+}
+
+/// A single committed row change
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChangeEvent {
+    /// Table name, with PowerSync's `ps_data__` prefix stripped so it
+    /// matches the view name from the schema
+    pub table: String,
+    pub operation: ChangeOperation,
+    pub rowid: i64,
+}
+
+/// Tables changed by one committed transaction
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct CommitEvent {
+    /// View names of the changed user tables; PowerSync's internal `ps_*`
+    /// tables are omitted
+    pub tables: BTreeSet<String>,
+    /// Correlation id of the call that committed, if it had one
+    pub correlation_id: Option<String>,
+}
+
+/// Number of row operations applied to one table
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct TableOpCounts {
+    pub inserts: u32,
+    pub updates: u32,
+    pub deletes: u32,
+}
+
+impl TableOpCounts {
+    pub fn total(&self) -> u32 {
+        self.inserts + self.updates + self.deletes
+    }
+}
+
+/// A sync checkpoint that has been fully applied to the local database
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct CheckpointEvent {
+    /// Operations per user table changed since the previous checkpoint,
+    /// keyed by view name. PowerSync's internal `ps_*` tables are omitted.
+    pub tables: BTreeMap<String, TableOpCounts>,
+    /// Correlation id of the `powersync_control` call that completed it, if
+    /// it had one
+    pub correlation_id: Option<String>,
+}
+
+impl CheckpointEvent {
+    /// Names of the tables the checkpoint changed
+    pub fn table_names(&self) -> Vec<&str> {
+        self.tables.keys().map(String::as_str).collect()
+    }
+}
+
+struct Subscription {
+    /// Tables of interest; empty means every table
+    tables: HashSet<String>,
+    sender: broadcast::Sender<ChangeEvent>,
+}
+
+impl Subscription {
+    fn wants(&self, table: &str) -> bool {
+        self.tables.is_empty() || self.tables.contains(table)
+    }
+}
+
+/// Collects row changes for a connection and fans them out to subscribers
+pub struct ChangeNotifier {
+    subscriptions: Mutex<Vec<Subscription>>,
+    pending: Mutex<Vec<ChangeEvent>>,
+    /// User tables changed by the open transaction
+    dirty: Mutex<BTreeSet<String>>,
+    commits: broadcast::Sender<CommitEvent>,
+    /// Set while sync data is being applied
+    recording_sync: Mutex<bool>,
+    /// Changes applied by sync since the last checkpoint
+    sync_ops: Mutex<BTreeMap<String, TableOpCounts>>,
+    checkpoints: broadcast::Sender<CheckpointEvent>,
+    checkpoint_failures: broadcast::Sender<CheckpointFailure>,
+    schema_changes: broadcast::Sender<SchemaChange>,
+    upload_errors: broadcast::Sender<UploadError>,
+    /// Commits so far, numbering transactions for [`crate::undo`]
+    commit_count: Arc<AtomicU64>,
+}
+
+impl Default for ChangeNotifier {
+    fn default() -> Self {
+        Self {
+            subscriptions: Mutex::default(),
+            pending: Mutex::default(),
+            dirty: Mutex::default(),
+            commits: broadcast::channel(CHANNEL_CAPACITY).0,
+            recording_sync: Mutex::default(),
+            sync_ops: Mutex::default(),
+            checkpoints: broadcast::channel(CHANNEL_CAPACITY).0,
+            checkpoint_failures: broadcast::channel(CHANNEL_CAPACITY).0,
+            schema_changes: broadcast::channel(CHANNEL_CAPACITY).0,
+            upload_errors: broadcast::channel(CHANNEL_CAPACITY).0,
+            commit_count: Arc::default(),
+        }
+    }
+}
+
+impl ChangeNotifier {
+    /// Install the update, commit and rollback hooks on a connection
+    pub fn install(self: &Arc<Self>, conn: &Connection) {
+        let notifier = Arc::clone(self);
+        conn.update_hook(Some(move |action: Action, _db: &str, table: &str, rowid: i64| {
+            let operation = match action {
+                Action::SQLITE_INSERT => ChangeOperation::Insert,
+                Action::SQLITE_UPDATE => ChangeOperation::Update,
+                Action::SQLITE_DELETE => ChangeOperation::Delete,
+                _ => return,
+            };
+            notifier.record(ChangeEvent {
+                table: user_table_name(table).to_string(),
+                operation,
+                rowid,
+            });
+        }));
+
+        let notifier = Arc::clone(self);
+        conn.commit_hook(Some(move || {
+            notifier.commit_count.fetch_add(1, Ordering::Relaxed);
+            notifier.flush();
+            // Returning false lets the commit proceed
+            false
+        }));
+
+        let notifier = Arc::clone(self);
+        conn.rollback_hook(Some(move || notifier.discard()));
+    }
+
+    /// Count of commits so far, which numbers the open transaction
+    pub(crate) fn commit_count(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.commit_count)
+    }
+
+    /// Subscribe to changes on the given tables (all tables if empty)
+    pub fn subscribe(&self, tables: &[&str]) -> broadcast::Receiver<ChangeEvent> {
+        let (sender, receiver) = broadcast::channel(CHANNEL_CAPACITY);
+        let subscription = Subscription {
+            tables: tables.iter().map(|t| t.to_string()).collect(),
+            sender,
+        };
+        if let Ok(mut subscriptions) = self.subscriptions.lock() {
+            subscriptions.push(subscription);
+        }
+        receiver
+    }
+
+    /// Subscribe to the set of tables changed by each commit
+    pub fn subscribe_commits(&self) -> broadcast::Receiver<CommitEvent> {
+        self.commits.subscribe()
+    }
+
+    /// Subscribe to fully applied sync checkpoints
+    pub fn subscribe_checkpoints(&self) -> broadcast::Receiver<CheckpointEvent> {
+        self.checkpoints.subscribe()
+    }
+
+    /// Subscribe to checkpoints that failed validation or couldn't be applied
+    pub fn subscribe_checkpoint_failures(&self) -> broadcast::Receiver<CheckpointFailure> {
+        self.checkpoint_failures.subscribe()
+    }
+
+    /// Subscribe to schema changes applied by `replace_schema`
+    pub fn subscribe_schema_changes(&self) -> broadcast::Receiver<SchemaChange> {
+        self.schema_changes.subscribe()
+    }
+
+    /// Subscribe to failed uploads reported by the app
+    pub fn subscribe_upload_errors(&self) -> broadcast::Receiver<UploadError> {
+        self.upload_errors.subscribe()
+    }
+
+    /// Report tables as changed outside of a transaction SQLite hooks see,
+    /// such as a restore
+    pub fn notify_commit(&self, event: CommitEvent) {
+        if !event.tables.is_empty() {
+            let _ = self.commits.send(event);
+        }
+    }
+
+    /// Report an applied schema change
+    pub fn notify_schema_change(&self, change: SchemaChange) {
+        let _ = self.schema_changes.send(change);
+    }
+
+    /// Report a failed upload
+    pub fn notify_upload_error(&self, error: UploadError) {
+        let _ = self.upload_errors.send(error);
+    }
+
+    /// Report a failed checkpoint
+    pub fn fail_checkpoint(&self, failure: CheckpointFailure) {
+        let _ = self.checkpoint_failures.send(failure);
+    }
+
+    /// Tally row changes towards the next checkpoint while `recording` is set
+    pub fn set_recording_sync(&self, recording: bool) {
+        if let Ok(mut recording_sync) = self.recording_sync.lock() {
+            *recording_sync = recording;
+        }
+    }
+
+    /// Report a checkpoint as applied, with the changes tallied since the last one
+    pub fn complete_checkpoint(&self) -> CheckpointEvent {
+        let tables = self
+            .sync_ops
+            .lock()
+            .map(|mut ops| std::mem::take(&mut *ops))
+            .unwrap_or_default();
+        let event = CheckpointEvent {
+            tables,
+            correlation_id: correlation::current(),
+        };
+        // No receivers is fine; the event is also returned
+        let _ = self.checkpoints.send(event.clone());
+        event
+    }
+
+    fn record_sync(&self, event: &ChangeEvent) {
+        // Internal bookkeeping (oplog, buckets, ...) isn't interesting to consumers
+        if event.table.starts_with("ps_") {
+            return;
+        }
+        if !self.recording_sync.lock().map(|r| *r).unwrap_or(false) {
+            return;
+        }
+        if let Ok(mut ops) = self.sync_ops.lock() {
+            let counts = ops.entry(event.table.clone()).or_default();
+            match event.operation {
+                ChangeOperation::Insert => counts.inserts += 1,
+                ChangeOperation::Update => counts.updates += 1,
+                ChangeOperation::Delete => counts.deletes += 1,
+            }
+        }
+    }
+
+    fn has_subscribers(&self) -> bool {
+        self.subscriptions
+            .lock()
+            .map(|s| !s.is_empty())
+            .unwrap_or(false)
+    }
+
+    fn record_dirty(&self, event: &ChangeEvent) {
+        if event.table.starts_with("ps_") || self.commits.receiver_count() == 0 {
+            return;
+        }
+        if let Ok(mut dirty) = self.dirty.lock() {
+            // Most changes hit a table already in the set; avoid allocating for those
+            if !dirty.contains(&event.table) {
+                dirty.insert(event.table.clone());
+            }
+        }
+    }
+
+    fn record(&self, event: ChangeEvent) {
+        self.record_sync(&event);
+        self.record_dirty(&event);
+        if !self.has_subscribers() {
+            return;
+        }
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.push(event);
+        }
+    }
+
+    fn flush(&self) {
+        let tables = self
+            .dirty
+            .lock()
+            .map(|mut dirty| std::mem::take(&mut *dirty))
+            .unwrap_or_default();
+        if !tables.is_empty() {
+            let _ = self.commits.send(CommitEvent {
+                tables,
+                correlation_id: correlation::current(),
+            });
+        }
+
+        let events = match self.pending.lock() {
+            Ok(mut pending) => std::mem::take(&mut *pending),
+            Err(_) => return,
+        };
+        if events.is_empty() {
+            return;
+        }
+
+        let Ok(mut subscriptions) = self.subscriptions.lock() else {
+            return;
+        };
+        // Drop subscriptions whose receivers have all gone away
+        subscriptions.retain(|s| s.sender.receiver_count() > 0);
+        for event in &events {
+            for subscription in subscriptions.iter().filter(|s| s.wants(&event.table)) {
+                let _ = subscription.sender.send(event.clone());
+            }
+        }
+    }
+
+    fn discard(&self) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.clear();
+        }
+        if let Ok(mut dirty) = self.dirty.lock() {
+            dirty.clear();
+        }
+    }
+}
+
+/// Map a PowerSync backing table name to the user-facing view name
+fn user_table_name(table: &str) -> &str {
+    PS_DATA_PREFIXES
+        .iter()
+        .find_map(|prefix| table.strip_prefix(prefix))
+        .unwrap_or(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changes_broadcast_on_commit() {
+        let conn = Connection::open_in_memory().unwrap();
+        let notifier = Arc::new(ChangeNotifier::default());
+        notifier.install(&conn);
+
+        let mut todos = notifier.subscribe(&["todos"]);
+        let mut lists = notifier.subscribe(&["lists"]);
+
+        conn.execute_batch(
+            "CREATE TABLE ps_data__todos (id TEXT PRIMARY KEY, data TEXT);
+             CREATE TABLE lists (id TEXT PRIMARY KEY);
+             INSERT INTO ps_data__todos (id, data) VALUES ('1', '{}');",
+        )
+        .unwrap();
+
+        let event = todos.try_recv().unwrap();
+        assert_eq!(event.table, "todos");
+        assert_eq!(event.operation, ChangeOperation::Insert);
+        assert!(lists.try_recv().is_err());
+
+        // Rolled back changes are never delivered
+        conn.execute_batch("BEGIN; DELETE FROM ps_data__todos; ROLLBACK;")
+            .unwrap();
+        assert!(todos.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_commit_reports_changed_tables_once() {
+        let conn = Connection::open_in_memory().unwrap();
+        let notifier = Arc::new(ChangeNotifier::default());
+        notifier.install(&conn);
+        let mut commits = notifier.subscribe_commits();
+
+        conn.execute_batch(
+            "CREATE TABLE ps_data__todos (id TEXT PRIMARY KEY, data TEXT);
+             CREATE TABLE ps_oplog (id INTEGER PRIMARY KEY);
+             CREATE TABLE lists (id TEXT PRIMARY KEY);
+             BEGIN;
+             INSERT INTO ps_data__todos (id, data) VALUES ('1', '{}'), ('2', '{}');
+             INSERT INTO ps_oplog (id) VALUES (1);
+             INSERT INTO lists (id) VALUES ('a');
+             COMMIT;",
+        )
+        .unwrap();
+
+        let event = commits.try_recv().unwrap();
+        assert_eq!(event.tables, BTreeSet::from(["lists".to_string(), "todos".to_string()]));
+        assert_eq!(event.correlation_id, None);
+        assert!(commits.try_recv().is_err());
+
+        conn.execute_batch("BEGIN; DELETE FROM lists WHERE 1; ROLLBACK;").unwrap();
+        assert!(commits.try_recv().is_err());
+
+        let scope = correlation::enter(Some("save-1".to_string()));
+        // Without a WHERE clause SQLite truncates and skips the update hook
+        conn.execute_batch("DELETE FROM lists WHERE 1").unwrap();
+        drop(scope);
+        let event = commits.try_recv().unwrap();
+        assert_eq!(event.correlation_id.as_deref(), Some("save-1"));
+    }
+
+    #[test]
+    fn test_checkpoint_tallies_sync_changes() {
+        let conn = Connection::open_in_memory().unwrap();
+        let notifier = Arc::new(ChangeNotifier::default());
+        notifier.install(&conn);
+        let mut checkpoints = notifier.subscribe_checkpoints();
+
+        conn.execute_batch(
+            "CREATE TABLE ps_data__todos (id TEXT PRIMARY KEY, data TEXT);
+             CREATE TABLE ps_oplog (id INTEGER PRIMARY KEY);
+             INSERT INTO ps_data__todos (id, data) VALUES ('local', '{}');",
+        )
+        .unwrap();
+
+        notifier.set_recording_sync(true);
+        conn.execute_batch(
+            "INSERT INTO ps_oplog (id) VALUES (1);
+             INSERT INTO ps_data__todos (id, data) VALUES ('1', '{}');
+             UPDATE ps_data__todos SET data = '[]' WHERE id = '1';",
+        )
+        .unwrap();
+        notifier.set_recording_sync(false);
+
+        let event = notifier.complete_checkpoint();
+        assert_eq!(event.table_names(), vec!["todos"]);
+        assert_eq!(event.tables["todos"].total(), 2);
+        assert_eq!(checkpoints.try_recv().unwrap(), event);
+
+        // The tally starts over after each checkpoint
+        assert!(notifier.complete_checkpoint().tables.is_empty());
+    }
+}
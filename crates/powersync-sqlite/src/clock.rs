@@ -0,0 +1,261 @@
+//! Timestamps for rows written offline
+//!
+//! `created_at`/`updated_at` columns are written on many devices and only
+//! compared once synced, so they are only as good as each device's clock.
+//! [`Clock`] guards against the two problems that can be fixed locally: a
+//! wall clock stepping backwards (timestamps never repeat or decrease), and
+//! a wall clock that is simply wrong (an offset, e.g. measured against an
+//! NTP server with [`Clock::sync_ntp`]).
+//!
+//! One clock is shared by the whole process ([`Clock::global`]), so
+//! timestamps stay ordered across databases and connections.
+
+use crate::error::{Error, Result};
+use serde::Serialize;
+use std::io::ErrorKind;
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A timestamp read from a [`Clock`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct Timestamp {
+    /// ISO 8601 in UTC with milliseconds, e.g. `2024-05-01T12:30:00.000Z`,
+    /// as `strftime('%Y-%m-%dT%H:%M:%fZ')` formats it, so it sorts as text
+    pub utc: String,
+    /// Milliseconds since the Unix epoch
+    pub unix_ms: i64,
+    /// Correction applied to the system clock, in milliseconds
+    pub offset_ms: i64,
+}
+
+/// Wall clock that never goes backwards, optionally corrected by an offset
+#[derive(Debug)]
+pub struct Clock {
+    state: Mutex<ClockState>,
+}
+
+#[derive(Debug)]
+struct ClockState {
+    /// Last reading handed out, in milliseconds since the Unix epoch
+    last_ms: i64,
+    offset_ms: i64,
+}
+
+static GLOBAL: Clock = Clock::new();
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock {
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(ClockState {
+                last_ms: 0,
+                offset_ms: 0,
+            }),
+        }
+    }
+
+    /// The clock of this process, used by the `now()` SQL function
+    pub fn global() -> &'static Clock {
+        &GLOBAL
+    }
+
+    /// Current time in milliseconds since the Unix epoch.
+    ///
+    /// Each reading is later than the previous one: if the corrected system
+    /// clock hasn't advanced (or went back), the reading is 1ms past the
+    /// last one until it catches up.
+    pub fn now_ms(&self) -> i64 {
+        let mut state = self.state();
+        let now = system_ms() + state.offset_ms;
+        state.last_ms = now.max(state.last_ms + 1);
+        state.last_ms
+    }
+
+    /// Current time, see [`now_ms`](Self::now_ms)
+    pub fn now(&self) -> Timestamp {
+        let unix_ms = self.now_ms();
+        Timestamp {
+            utc: format_utc(unix_ms),
+            unix_ms,
+            offset_ms: self.offset_ms(),
+        }
+    }
+
+    /// Correction applied to the system clock, in milliseconds
+    pub fn offset_ms(&self) -> i64 {
+        self.state().offset_ms
+    }
+
+    /// Correct the system clock by `offset_ms`. Readings still never go
+    /// backwards, so a negative change holds the clock until it catches up.
+    pub fn set_offset_ms(&self, offset_ms: i64) {
+        self.state().offset_ms = offset_ms;
+    }
+
+    /// Measure the system clock's offset against NTP server `server`
+    /// (`host` or `host:port`), waiting at most `timeout` for its reply,
+    /// and correct the clock by it. Returns the offset in milliseconds.
+    ///
+    /// Blocks on the network, so call it off the async runtime.
+    pub fn sync_ntp(&self, server: &str, timeout: Duration) -> Result<i64> {
+        let offset_ms = ntp_offset(server, timeout)?;
+        self.set_offset_ms(offset_ms);
+        Ok(offset_ms)
+    }
+
+    fn state(&self) -> MutexGuard<'_, ClockState> {
+        // The state is two integers, always consistent
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Format milliseconds since the Unix epoch as ISO 8601 in UTC
+pub fn format_utc(unix_ms: i64) -> String {
+    let days = unix_ms.div_euclid(86_400_000);
+    let ms_of_day = unix_ms.rem_euclid(86_400_000);
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        ms_of_day / 3_600_000,
+        ms_of_day / 60_000 % 60,
+        ms_of_day / 1000 % 60,
+        ms_of_day % 1000
+    )
+}
+
+/// Year, month and day of a count of days since 1970-01-01, in the
+/// proleptic Gregorian calendar (Howard Hinnant's algorithm)
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+fn system_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as i64)
+}
+
+/// Seconds from the NTP epoch (1900) to the Unix epoch
+const NTP_UNIX_OFFSET: i64 = 2_208_988_800;
+
+/// Offset of the system clock from an NTP server's, by a single SNTP
+/// exchange (RFC 4330)
+fn ntp_offset(server: &str, timeout: Duration) -> Result<i64> {
+    let addr = match server.to_socket_addrs() {
+        Ok(mut addrs) => addrs.next(),
+        Err(_) => (server, 123).to_socket_addrs()?.next(),
+    }
+    .ok_or_else(|| Error::InvalidParameter(format!("NTP server not found: {}", server)))?;
+
+    let bind = if addr.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(bind)?;
+    socket.set_read_timeout(Some(timeout))?;
+    socket.connect(addr)?;
+
+    // Version 4, client mode
+    let mut request = [0u8; 48];
+    request[0] = 0x23;
+    let sent = system_ms();
+    socket.send(&request)?;
+    let mut reply = [0u8; 48];
+    let len = socket.recv(&mut reply)?;
+    let received = system_ms();
+
+    // Server mode, and not a "kiss-o'-death" (stratum 0) telling us to back off
+    if len < 48 || reply[0] & 0x07 != 4 || reply[1] == 0 {
+        return Err(Error::Io(std::io::Error::new(
+            ErrorKind::InvalidData,
+            format!("Invalid NTP reply from {}", server),
+        )));
+    }
+    let server_received = ntp_ms(&reply[32..40]);
+    let server_sent = ntp_ms(&reply[40..48]);
+    Ok(((server_received - sent) + (server_sent - received)) / 2)
+}
+
+/// An NTP timestamp (seconds since 1900 and a 32-bit fraction) in
+/// milliseconds since the Unix epoch
+fn ntp_ms(bytes: &[u8]) -> i64 {
+    let seconds = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as i64;
+    let fraction = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as i64;
+    (seconds - NTP_UNIX_OFFSET) * 1000 + ((fraction * 1000) >> 32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_utc() {
+        assert_eq!(format_utc(0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(format_utc(951_782_400_000), "2000-02-29T00:00:00.000Z");
+        assert_eq!(format_utc(1_700_000_000_123), "2023-11-14T22:13:20.123Z");
+        assert_eq!(format_utc(-1), "1969-12-31T23:59:59.999Z");
+    }
+
+    #[test]
+    fn test_never_goes_backwards() {
+        let clock = Clock::new();
+        let first = clock.now_ms();
+        clock.set_offset_ms(-60_000);
+        let second = clock.now_ms();
+        assert_eq!(second, first + 1);
+        assert!(clock.now_ms() > second);
+
+        clock.set_offset_ms(60_000);
+        let now = clock.now();
+        assert!(now.unix_ms >= system_ms() + 59_000);
+        assert_eq!(now.offset_ms, 60_000);
+    }
+
+    #[test]
+    fn test_sync_ntp() {
+        // A server whose clock is a minute ahead
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            let mut request = [0u8; 48];
+            let (_, client) = server.recv_from(&mut request).unwrap();
+            let ahead = system_ms() + 60_000;
+            let seconds = (ahead / 1000 + NTP_UNIX_OFFSET) as u32;
+            let fraction = (((ahead % 1000) << 32) / 1000) as u32;
+            let mut reply = [0u8; 48];
+            reply[0] = 0x24;
+            reply[1] = 2;
+            for range in [32..40, 40..48] {
+                reply[range.start..range.start + 4].copy_from_slice(&seconds.to_be_bytes());
+                reply[range.start + 4..range.end].copy_from_slice(&fraction.to_be_bytes());
+            }
+            server.send_to(&reply, client).unwrap();
+        });
+
+        let clock = Clock::new();
+        let offset = clock.sync_ntp(&addr, Duration::from_secs(5)).unwrap();
+        assert!((59_000..=61_000).contains(&offset), "{}", offset);
+        assert_eq!(clock.offset_ms(), offset);
+    }
+}
@@ -0,0 +1,228 @@
+//! Opt-in checking of text parameters against declared column types
+//!
+//! JavaScript callers easily bind numbers as strings (an `id` read from a
+//! URL, a form value). SQLite's type affinity hides this for plain tables,
+//! but PowerSync views store values as JSON, where `"5"` and `5` differ.
+//! With coercion on, text bound to an INTEGER or REAL column is converted
+//! (or rejected) before it reaches the database.
+//!
+//! Coercion applies where the target column of each parameter is known:
+//! `insert_many`, and `INSERT INTO t (a, b) VALUES (?, ?)` statements whose
+//! values are all plain placeholders.
+
+use crate::database::SqlParam;
+use crate::error::{Error, Result};
+use regex::Regex;
+use rusqlite::Connection;
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::sync::OnceLock;
+
+/// What to do with text parameters bound to numeric columns
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub enum ParamCoercion {
+    /// Bind parameters as given
+    #[default]
+    Off,
+    /// Convert numeric text to a number; reject text that isn't numeric
+    Coerce,
+    /// Reject all text bound to numeric columns
+    Reject,
+}
+
+/// Numeric type of a column, from its declared type's affinity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NumericColumn {
+    Integer,
+    Real,
+}
+
+/// Declared numeric types of the columns a statement binds to, in parameter order
+pub(crate) struct BoundColumns {
+    table: String,
+    columns: Vec<(String, Option<NumericColumn>)>,
+}
+
+impl BoundColumns {
+    /// Look up the declared types of `columns` of `table`
+    pub(crate) fn lookup(conn: &Connection, table: &str, columns: &[String]) -> Result<Self> {
+        let mut stmt = conn.prepare("SELECT name, type FROM pragma_table_info(?)")?;
+        let declared = stmt
+            .query_map([table], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let columns = columns
+            .iter()
+            .map(|column| {
+                let kind = declared
+                    .iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case(column))
+                    .and_then(|(_, declared_type)| numeric_column(declared_type));
+                (column.clone(), kind)
+            })
+            .collect();
+        Ok(Self {
+            table: table.to_string(),
+            columns,
+        })
+    }
+
+    /// For a statement `insert_target` recognizes, look up its columns
+    pub(crate) fn for_insert(conn: &Connection, sql: &str) -> Result<Option<Self>> {
+        match insert_target(sql) {
+            Some((table, columns)) => Ok(Some(Self::lookup(conn, &table, &columns)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Check one row of parameters, converting them if needed
+    pub(crate) fn apply<'a>(
+        &self,
+        params: &'a [SqlParam],
+        mode: ParamCoercion,
+    ) -> Result<Cow<'a, [SqlParam]>> {
+        let mut params = Cow::Borrowed(params);
+        for (i, (column, kind)) in self.columns.iter().enumerate() {
+            let (Some(kind), Some(SqlParam::Text(text))) = (kind, params.get(i)) else {
+                continue;
+            };
+            let coerced = match mode {
+                ParamCoercion::Off => continue,
+                ParamCoercion::Coerce => parse_number(text, *kind),
+                ParamCoercion::Reject => None,
+            };
+            let Some(coerced) = coerced else {
+                return Err(Error::InvalidParameter(format!(
+                    "text value {:?} bound to {} column {}.{}",
+                    text,
+                    match kind {
+                        NumericColumn::Integer => "INTEGER",
+                        NumericColumn::Real => "REAL",
+                    },
+                    self.table,
+                    column
+                )));
+            };
+            params.to_mut()[i] = coerced;
+        }
+        Ok(params)
+    }
+}
+
+/// SQLite's affinity rules, for the numeric affinities
+fn numeric_column(declared_type: &str) -> Option<NumericColumn> {
+    let declared_type = declared_type.to_ascii_uppercase();
+    if declared_type.contains("INT") {
+        Some(NumericColumn::Integer)
+    } else if ["CHAR", "CLOB", "TEXT"]
+        .iter()
+        .any(|t| declared_type.contains(t))
+    {
+        None
+    } else if ["REAL", "FLOA", "DOUB"]
+        .iter()
+        .any(|t| declared_type.contains(t))
+    {
+        Some(NumericColumn::Real)
+    } else {
+        None
+    }
+}
+
+fn parse_number(text: &str, kind: NumericColumn) -> Option<SqlParam> {
+    let text = text.trim();
+    match kind {
+        NumericColumn::Integer => text.parse().ok().map(SqlParam::Int),
+        NumericColumn::Real => text
+            .parse::<f64>()
+            .ok()
+            .filter(|value| value.is_finite())
+            .map(SqlParam::Real),
+    }
+}
+
+/// Table and columns of `INSERT INTO t (a, b) VALUES (?, ?)`, if every
+/// value is a plain placeholder
+fn insert_target(sql: &str) -> Option<(String, Vec<String>)> {
+    static INSERT: OnceLock<Regex> = OnceLock::new();
+    let insert = INSERT.get_or_init(|| {
+        Regex::new(
+            r#"(?is)^\s*(?:INSERT(?:\s+OR\s+\w+)?|REPLACE)\s+INTO\s+["`\[]?(\w+)["`\]]?\s*\(([^)]*)\)\s*VALUES\s*\(([^)]*)\)\s*;?\s*$"#,
+        )
+        .expect("valid INSERT pattern")
+    });
+    let captures = insert.captures(sql)?;
+    let columns: Vec<String> = captures[2]
+        .split(',')
+        .map(|column| {
+            column
+                .trim()
+                .trim_matches(|c| matches!(c, '"' | '`' | '[' | ']'))
+                .to_string()
+        })
+        .collect();
+    let values: Vec<&str> = captures[3].split(',').map(str::trim).collect();
+    if values.len() != columns.len() || values.iter().any(|value| *value != "?") {
+        return None;
+    }
+    Some((captures[1].to_string(), columns))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_target() {
+        assert_eq!(
+            insert_target(r#"INSERT OR REPLACE INTO "todos" (id, "count") VALUES (?, ?)"#),
+            Some((
+                "todos".to_string(),
+                vec!["id".to_string(), "count".to_string()]
+            ))
+        );
+        assert_eq!(
+            insert_target("INSERT INTO todos (id, n) VALUES (?, 1)"),
+            None
+        );
+        assert_eq!(insert_target("INSERT INTO todos SELECT * FROM other"), None);
+        assert_eq!(insert_target("UPDATE todos SET n = ?"), None);
+    }
+
+    #[test]
+    fn test_apply() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE t (id TEXT, n INTEGER, x DOUBLE PRECISION)")
+            .unwrap();
+        let columns = ["id", "n", "x"].map(String::from);
+        let bound = BoundColumns::lookup(&conn, "t", &columns).unwrap();
+        let text = |s: &str| SqlParam::Text(s.to_string());
+
+        let params = [text("1"), text(" 42 "), text("2.5")];
+        let coerced = bound.apply(&params, ParamCoercion::Coerce).unwrap();
+        assert!(matches!(
+            &coerced[..],
+            [SqlParam::Text(id), SqlParam::Int(42), SqlParam::Real(x)] if id == "1" && *x == 2.5
+        ));
+
+        assert!(bound
+            .apply(
+                &[text("1"), text("abc"), SqlParam::Null],
+                ParamCoercion::Coerce
+            )
+            .is_err());
+        assert!(bound.apply(&params, ParamCoercion::Reject).is_err());
+        assert!(matches!(
+            bound.apply(&params, ParamCoercion::Off).unwrap(),
+            Cow::Borrowed(_)
+        ));
+        let numbers = [text("1"), SqlParam::Int(42), SqlParam::Real(2.5)];
+        assert!(matches!(
+            bound.apply(&numbers, ParamCoercion::Reject).unwrap(),
+            Cow::Borrowed(_)
+        ));
+    }
+}
@@ -0,0 +1,90 @@
+//! Unicode-aware collations
+//!
+//! SQLite's built-in `NOCASE` only folds ASCII, so `ORDER BY name COLLATE
+//! NOCASE` misorders anything outside English. With the `unicode-collation`
+//! feature every connection gets ICU-backed collations instead:
+//!
+//! - `UNICODE`: root-locale ordering (case and accent sensitive)
+//! - `UNICODE_NOCASE`: root-locale ordering ignoring case
+//!
+//! They can be used in `ORDER BY`, `CREATE INDEX` and column definitions.
+//! For language-specific rules (e.g. Swedish `å` after `z`), register a
+//! locale collation from the connection initializer with [`register_locale`].
+
+use icu_collator::{Collator, CollatorOptions, Strength};
+use icu_locid::Locale;
+use rusqlite::Connection;
+use std::cmp::Ordering;
+use std::panic::AssertUnwindSafe;
+
+/// Register `UNICODE` and `UNICODE_NOCASE` on a connection
+pub fn register(conn: &Connection) -> rusqlite::Result<()> {
+    register_locale(conn, "UNICODE", "und", false)?;
+    register_locale(conn, "UNICODE_NOCASE", "und", true)
+}
+
+/// Register a collation `name` ordering text by the rules of `locale`
+/// (a BCP 47 tag such as `"sv"` or `"de-AT"`), optionally ignoring case
+pub fn register_locale(
+    conn: &Connection,
+    name: &str,
+    locale: &str,
+    ignore_case: bool,
+) -> rusqlite::Result<()> {
+    let locale: Locale = locale
+        .parse()
+        .map_err(|e| user_error(format!("Invalid collation locale {}: {}", locale, e)))?;
+
+    let mut options = CollatorOptions::new();
+    if ignore_case {
+        // Secondary strength: base letters and accents count, case doesn't
+        options.strength = Some(Strength::Secondary);
+    }
+    let collator = Collator::try_new(&(&locale).into(), options)
+        .map_err(|e| user_error(format!("Failed to load collation {}: {}", name, e)))?;
+
+    // Comparisons don't mutate the collator, so a panic can't leave it broken
+    let collator = AssertUnwindSafe(collator);
+    conn.create_collation(name, move |a, b| compare(&collator, a, b))
+}
+
+fn user_error(message: String) -> rusqlite::Error {
+    rusqlite::Error::UserFunctionError(message.into())
+}
+
+fn compare(collator: &AssertUnwindSafe<Collator>, a: &str, b: &str) -> Ordering {
+    collator.compare(a, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(conn: &Connection, collation: &str) -> Vec<String> {
+        let sql = format!(
+            "SELECT column1 FROM (VALUES ('b'), ('Å'), ('a'), ('É'), ('z')) ORDER BY column1 COLLATE {}",
+            collation
+        );
+        let mut stmt = conn.prepare(&sql).unwrap();
+        stmt.query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_unicode_ordering() {
+        let conn = Connection::open_in_memory().unwrap();
+        register(&conn).unwrap();
+        register_locale(&conn, "SWEDISH", "sv", true).unwrap();
+
+        assert_eq!(sorted(&conn, "NOCASE"), ["a", "b", "z", "Å", "É"]);
+        assert_eq!(sorted(&conn, "UNICODE"), ["a", "Å", "b", "É", "z"]);
+        assert_eq!(sorted(&conn, "SWEDISH"), ["a", "b", "É", "z", "Å"]);
+
+        let equal: bool = conn
+            .query_row("SELECT 'Ärger' = 'äRGER' COLLATE UNICODE_NOCASE", [], |row| row.get(0))
+            .unwrap();
+        assert!(equal);
+    }
+}
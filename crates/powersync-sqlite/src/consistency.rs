@@ -0,0 +1,100 @@
+//! Read-after-sync consistency
+//!
+//! A frontend that changes data through its own backend API, rather than
+//! through the upload queue, can't tell when sync has brought the change
+//! down. The checkpoint token is the highest op id applied to the local
+//! tables; taking it before making the API call and then waiting for a
+//! checkpoint past it with [`wait_for_checkpoint`] holds off queries until
+//! sync has applied newer data.
+//!
+//! The token says nothing about which change a checkpoint carries: a
+//! checkpoint the service produced before the API call made its change, but
+//! that is only applied after the token was taken, also ends the wait.
+
+use crate::database::PowerSyncConnection;
+use crate::error::{Error, Result};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+
+/// Where a database's sync stands
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct LocalSyncStatus {
+    /// Highest op id applied to the local tables, "0" before the first
+    /// checkpoint. See [`wait_for_checkpoint`].
+    pub checkpoint_token: String,
+    /// When the last full sync completed, in milliseconds since the Unix epoch
+    pub last_synced_at: Option<i64>,
+    /// Write checkpoint local changes are waiting on, if any
+    pub write_checkpoint: Option<String>,
+}
+
+/// Wait until sync has applied a checkpoint past `token`, for at most
+/// `timeout` if given, and return the new checkpoint token.
+///
+/// Returns at once if one was applied since the token was taken. Fails with
+/// [`Error::CheckpointTimeout`] once the timeout passes, and with
+/// [`Error::DatabaseNotFound`] if the database is closed in the meantime.
+/// The connection is only locked to read the token, so sync can apply
+/// checkpoints while this waits.
+pub async fn wait_for_checkpoint(
+    conn: &Arc<Mutex<PowerSyncConnection>>,
+    token: &str,
+    timeout: Option<Duration>,
+) -> Result<String> {
+    let after = parse_token(token)?;
+    // Don't keep the connection open for a closed database
+    let conn = Arc::downgrade(conn);
+    let current = |conn: &PowerSyncConnection| -> Result<Option<String>> {
+        let current = conn.checkpoint_token()?;
+        Ok((parse_token(&current)? > after).then_some(current))
+    };
+
+    let mut checkpoints = {
+        let conn = conn.upgrade().ok_or_else(closed)?;
+        let conn = conn.lock().map_err(|e| Error::Lock(e.to_string()))?;
+        // Subscribe before reading the token, so a checkpoint in between isn't missed
+        let checkpoints = conn.subscribe_checkpoints();
+        if let Some(current) = current(&conn)? {
+            return Ok(current);
+        }
+        checkpoints
+    };
+
+    let wait = async {
+        loop {
+            match checkpoints.recv().await {
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => return Err(closed()),
+            }
+            let conn = conn.upgrade().ok_or_else(closed)?;
+            let conn = conn.lock().map_err(|e| Error::Lock(e.to_string()))?;
+            if let Some(current) = current(&conn)? {
+                return Ok(current);
+            }
+        }
+    };
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, wait).await.map_err(|_| {
+            Error::CheckpointTimeout(format!(
+                "no checkpoint past {} was applied within {}ms",
+                token,
+                timeout.as_millis()
+            ))
+        })?,
+        None => wait.await,
+    }
+}
+
+fn parse_token(token: &str) -> Result<i64> {
+    token
+        .parse()
+        .map_err(|_| Error::InvalidParameter(format!("Invalid checkpoint token: {}", token)))
+}
+
+fn closed() -> Error {
+    Error::DatabaseNotFound("closed while waiting for a checkpoint".to_string())
+}
@@ -0,0 +1,377 @@
+//! Typed `powersync_control` calls and interpretation of their responses
+//!
+//! Calls are [`ControlOp`]s rather than free-form `(op, payload)` strings, so
+//! a misspelt operation or a sync line that isn't JSON is rejected when the
+//! call is deserialized, before it reaches the extension.
+//!
+//! The extension answers each control call with a JSON array of instructions
+//! for the sync client (`LogLine`, `UpdateSyncStatus`, `DidCompleteSync`, ...).
+//! The client still receives the raw JSON; this module only picks out what
+//! the Rust side reacts to itself.
+
+use crate::error::{Error, Result};
+use rusqlite::types::Value;
+use rusqlite::ErrorCode;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value as JsonValue};
+use std::time::Duration;
+
+/// Retries of an extension call failing with a transient error
+const TRANSIENT_RETRIES: u32 = 3;
+
+/// Delay before the first retry, doubled before each further one
+const RETRY_BACKOFF: Duration = Duration::from_millis(10);
+
+/// A `powersync_control` call, e.g. `{"op": "line_text", "payload": "{...}"}`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(tag = "op", content = "payload", rename_all = "snake_case")]
+pub enum ControlOp {
+    /// Start a sync iteration, with options such as client `parameters`
+    Start(Option<Map<String, JsonValue>>),
+    /// End the sync iteration
+    Stop,
+    /// A line of the sync stream in its JSON encoding
+    LineText(SyncLine),
+    /// A line of the sync stream in its BSON encoding
+    LineBinary(Vec<u8>),
+    /// The client fetched a new token after being asked to
+    RefreshedToken,
+    /// The upload queue was drained, so a pending checkpoint may be applied
+    CompletedUpload,
+    /// The sync stream was established or ended
+    Connection(ConnectionEvent),
+}
+
+impl ControlOp {
+    /// Operation name the extension expects
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Start(_) => "start",
+            Self::Stop => "stop",
+            Self::LineText(_) => "line_text",
+            Self::LineBinary(_) => "line_binary",
+            Self::RefreshedToken => "refreshed_token",
+            Self::CompletedUpload => "completed_upload",
+            Self::Connection(_) => "connection",
+        }
+    }
+
+    /// Payload to bind next to the operation name
+    pub(crate) fn payload(&self) -> Result<Value> {
+        Ok(match self {
+            Self::Start(None) | Self::Stop | Self::RefreshedToken | Self::CompletedUpload => {
+                Value::Null
+            }
+            Self::Start(Some(options)) => Value::Text(serde_json::to_string(options)?),
+            Self::LineText(line) => Value::Text(line.0.clone()),
+            Self::LineBinary(line) => Value::Blob(line.clone()),
+            Self::Connection(event) => Value::Text(event.as_str().to_string()),
+        })
+    }
+}
+
+/// Sync stream events reported through [`ControlOp::Connection`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionEvent {
+    Established,
+    End,
+}
+
+impl ConnectionEvent {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Established => "established",
+            Self::End => "end",
+        }
+    }
+}
+
+/// A sync line in its JSON encoding, checked to be a JSON object
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct SyncLine(String);
+
+impl SyncLine {
+    pub fn new(line: impl Into<String>) -> Result<Self> {
+        let line = line.into();
+        match serde_json::from_str::<JsonValue>(&line) {
+            Ok(JsonValue::Object(_)) => Ok(Self(line)),
+            Ok(_) => Err(Error::InvalidParameter(
+                "Sync line must be a JSON object".to_string(),
+            )),
+            Err(e) => Err(Error::InvalidParameter(format!(
+                "Sync line is not valid JSON: {}",
+                e
+            ))),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SyncLine {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let line = String::deserialize(deserializer)?;
+        Self::new(line).map_err(serde::de::Error::custom)
+    }
+}
+
+/// What went wrong while validating or applying a checkpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub enum CheckpointFailureKind {
+    /// Bucket checksums didn't match the checkpoint; data will be re-fetched
+    ChecksumMismatch,
+    /// A `checkpoint_diff` couldn't be applied, e.g. without a base checkpoint
+    InvalidDiff,
+}
+
+/// A checkpoint that failed validation or couldn't be applied
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct CheckpointFailure {
+    pub kind: CheckpointFailureKind,
+    /// Message reported by the extension
+    pub message: String,
+}
+
+impl CheckpointFailure {
+    /// Classify an extension message, if it describes a checkpoint failure
+    pub fn from_message(message: &str) -> Option<Self> {
+        let lower = message.to_lowercase();
+        let kind = if lower.contains("checksum") {
+            CheckpointFailureKind::ChecksumMismatch
+        } else if lower.contains("checkpoint_diff") || lower.contains("checkpoint diff") {
+            CheckpointFailureKind::InvalidDiff
+        } else {
+            return None;
+        };
+        Some(Self {
+            kind,
+            message: message.to_string(),
+        })
+    }
+}
+
+/// Parse a control response; anything but a JSON array yields no instructions
+pub fn parse_instructions(response: &str) -> Vec<JsonValue> {
+    serde_json::from_str(response).unwrap_or_default()
+}
+
+/// Whether the instructions report a completed sync, i.e. a checkpoint has
+/// been fully applied to the local tables
+pub fn completes_sync(instructions: &[JsonValue]) -> bool {
+    instructions
+        .iter()
+        .any(|instruction| instruction.get("DidCompleteSync").is_some())
+}
+
+/// Checkpoint failures reported through warning or error log lines
+pub fn checkpoint_failures(instructions: &[JsonValue]) -> Vec<CheckpointFailure> {
+    instructions
+        .iter()
+        .filter_map(|instruction| instruction.get("LogLine"))
+        .filter(|log| {
+            matches!(
+                log.get("severity").and_then(JsonValue::as_str),
+                Some("WARNING" | "ERROR")
+            )
+        })
+        .filter_map(|log| log.get("line").and_then(JsonValue::as_str))
+        .filter_map(CheckpointFailure::from_message)
+        .collect()
+}
+
+/// Whether an extension call failed only because another connection held
+/// a lock or the call was interrupted. SQLite rolled the statement back, so
+/// it can be run again as is.
+pub(crate) fn is_transient(error: &Error) -> bool {
+    let Error::Database(e) = error else {
+        return false;
+    };
+    matches!(
+        e.sqlite_error_code(),
+        Some(ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked | ErrorCode::OperationInterrupted)
+    )
+}
+
+/// Run extension call `call`, named `what` for logging, retrying it with
+/// backoff while it fails transiently, so a momentary lock doesn't abort
+/// the sync client's whole iteration.
+///
+/// `call` should lock the connection itself and release it before
+/// returning, so no lock is held while backing off.
+pub async fn with_retries<T>(what: &str, mut call: impl FnMut() -> Result<T>) -> Result<T> {
+    let mut backoff = RETRY_BACKOFF;
+    for retry in 1..=TRANSIENT_RETRIES {
+        match call() {
+            Err(e) if is_transient(&e) => {
+                log::debug!("Retrying {} ({}/{}): {}", what, retry, TRANSIENT_RETRIES, e);
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+            result => return result,
+        }
+    }
+    call()
+}
+
+/// [`with_retries`] for callers already holding the connection, such as
+/// [`PowerSyncConnection::powersync_control`](crate::database::PowerSyncConnection::powersync_control)
+/// and opening a database. The backoff blocks the thread, for at most 70ms
+/// in total.
+pub fn with_retries_blocking<T>(what: &str, mut call: impl FnMut() -> Result<T>) -> Result<T> {
+    let mut backoff = RETRY_BACKOFF;
+    for retry in 1..=TRANSIENT_RETRIES {
+        match call() {
+            Err(e) if is_transient(&e) => {
+                log::debug!("Retrying {} ({}/{}): {}", what, retry, TRANSIENT_RETRIES, e);
+                std::thread::sleep(backoff);
+                backoff *= 2;
+            }
+            result => return result,
+        }
+    }
+    call()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::ffi;
+
+    fn failure(code: std::ffi::c_int) -> Error {
+        Error::Database(rusqlite::Error::SqliteFailure(ffi::Error::new(code), None))
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn test_retries_transient_failures() {
+        let mut calls = 0;
+        let result = block_on(with_retries("test", || {
+            calls += 1;
+            match calls {
+                1 => Err(failure(ffi::SQLITE_BUSY)),
+                2 => Err(failure(ffi::SQLITE_LOCKED)),
+                _ => Ok("[]"),
+            }
+        }));
+        assert_eq!(result.unwrap(), "[]");
+        assert_eq!(calls, 3);
+
+        // Bounded for transient failures, none for others
+        calls = 0;
+        let result: Result<()> = block_on(with_retries("test", || {
+            calls += 1;
+            Err(failure(ffi::SQLITE_INTERRUPT))
+        }));
+        assert!(is_transient(&result.unwrap_err()));
+        assert_eq!(calls, TRANSIENT_RETRIES + 1);
+
+        calls = 0;
+        let result: Result<()> = block_on(with_retries("test", || {
+            calls += 1;
+            Err(failure(ffi::SQLITE_CONSTRAINT))
+        }));
+        assert!(!is_transient(&result.unwrap_err()));
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn test_retries_blocking() {
+        let mut calls = 0;
+        let result = with_retries_blocking("test", || {
+            calls += 1;
+            match calls {
+                1 => Err(failure(ffi::SQLITE_INTERRUPT)),
+                _ => Ok("[]"),
+            }
+        });
+        assert_eq!(result.unwrap(), "[]");
+        assert_eq!(calls, 2);
+
+        calls = 0;
+        let result: Result<()> = with_retries_blocking("test", || {
+            calls += 1;
+            Err(failure(ffi::SQLITE_BUSY))
+        });
+        assert!(is_transient(&result.unwrap_err()));
+        assert_eq!(calls, TRANSIENT_RETRIES + 1);
+    }
+
+    #[test]
+    fn test_control_op_validation() {
+        let op: ControlOp =
+            serde_json::from_str(r#"{"op": "line_text", "payload": "{\"checkpoint\": {}}"}"#)
+                .unwrap();
+        assert_eq!(op.name(), "line_text");
+
+        let start: ControlOp =
+            serde_json::from_str(r#"{"op": "start", "payload": {"parameters": {}}}"#).unwrap();
+        assert_eq!(
+            start.payload().unwrap(),
+            Value::Text(r#"{"parameters":{}}"#.to_string())
+        );
+        let stop: ControlOp = serde_json::from_str(r#"{"op": "stop"}"#).unwrap();
+        assert_eq!(stop.payload().unwrap(), Value::Null);
+        let connection: ControlOp =
+            serde_json::from_str(r#"{"op": "connection", "payload": "end"}"#).unwrap();
+        assert_eq!(
+            connection.payload().unwrap(),
+            Value::Text("end".to_string())
+        );
+
+        for invalid in [
+            r#"{"op": "line_txt", "payload": "{}"}"#,
+            r#"{"op": "line_text", "payload": "not json"}"#,
+            r#"{"op": "line_text", "payload": "[]"}"#,
+            r#"{"op": "start", "payload": "{}"}"#,
+            r#"{"op": "connection", "payload": "closed"}"#,
+        ] {
+            assert!(
+                serde_json::from_str::<ControlOp>(invalid).is_err(),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_completes_sync() {
+        let done = parse_instructions(r#"[{"LogLine":{}},{"DidCompleteSync":{}}]"#);
+        assert!(completes_sync(&done));
+        assert!(!completes_sync(&parse_instructions(r#"[{"UpdateSyncStatus":{}}]"#)));
+        assert!(!completes_sync(&parse_instructions("not json")));
+    }
+
+    #[test]
+    fn test_checkpoint_failures_from_log_lines() {
+        let instructions = parse_instructions(
+            r#"[
+                {"LogLine": {"severity": "DEBUG", "line": "checksum ok"}},
+                {"LogLine": {"severity": "WARNING", "line": "Checksums didn't match, failed for: b1"}},
+                {"CloseSyncStream": {}}
+            ]"#,
+        );
+        let failures = checkpoint_failures(&instructions);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].kind, CheckpointFailureKind::ChecksumMismatch);
+    }
+
+    #[test]
+    fn test_classify_error_message() {
+        let failure =
+            CheckpointFailure::from_message("checkpoint_diff without previous checkpoint").unwrap();
+        assert_eq!(failure.kind, CheckpointFailureKind::InvalidDiff);
+        assert!(CheckpointFailure::from_message("database is locked").is_none());
+    }
+}
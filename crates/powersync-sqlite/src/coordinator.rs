@@ -0,0 +1,117 @@
+//! Best-effort atomic writes spanning two databases
+//!
+//! SQLite commits each database file on its own, so a write spanning two
+//! databases can't be made atomic (in WAL mode not even by attaching one to
+//! the other). [`execute_across`] gets as close as it can in two phases: it
+//! runs each database's statements in a write transaction of its own, then
+//! commits them one after the other. A failure before the first commit
+//! rolls both back. If the second commit fails once the first succeeded,
+//! caller-supplied compensation statements undo the first database's
+//! changes.
+//!
+//! Limitations:
+//! - Not isolated: between the two commits, readers can see the first
+//!   database's changes without the second's.
+//! - Not crash safe: if the process dies between the two commits, the first
+//!   database stays committed and the compensation never runs.
+//! - Compensation is a new write, not a rollback. On a synced database it is
+//!   uploaded like any other change, and it may fail if something changed
+//!   the rows in between, leaving the databases out of step
+//!   ([`Error::PartialCommit`]).
+//!
+//! So commit first the database that is cheapest to undo: to move an item
+//! from a local-only database to a synced one, delete it locally first and
+//! compensate by re-inserting it, rather than uploading an insert and its
+//! undoing delete.
+
+use crate::database::{ExecuteResult, PowerSyncConnection, Statement};
+use crate::error::{Error, Result};
+use serde::Serialize;
+
+/// Results of an [`execute_across`] call, one per statement
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct CrossDatabaseResult {
+    pub first: Vec<ExecuteResult>,
+    pub second: Vec<ExecuteResult>,
+}
+
+/// Run `first_statements` on `first` and `second_statements` on `second`,
+/// committing both or, on a best-effort basis, neither. See the module docs
+/// for the limitations.
+///
+/// `compensation` undoes `first_statements` if `second` fails to commit
+/// after `first` committed. Without it, that failure leaves `first`
+/// committed and is reported as [`Error::PartialCommit`], as is a failing
+/// compensation. Neither connection may have a transaction open.
+pub fn execute_across(
+    first: &mut PowerSyncConnection,
+    first_statements: &[Statement],
+    compensation: &[Statement],
+    second: &mut PowerSyncConnection,
+    second_statements: &[Statement],
+) -> Result<CrossDatabaseResult> {
+    if first.in_transaction() || second.in_transaction() {
+        return Err(Error::TransactionOrder(
+            "cannot write across databases inside an open transaction".to_string(),
+        ));
+    }
+
+    // Prepare both sides, leaving their transactions open
+    let (first_tx, first_results) = prepare(first, first_statements)?;
+    let (second_tx, second_results) = match prepare(second, second_statements) {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            let _ = first.rollback_transaction(&first_tx);
+            return Err(e);
+        }
+    };
+
+    if let Err(e) = first.commit_transaction(&first_tx) {
+        let _ = first.rollback_transaction(&first_tx);
+        let _ = second.rollback_transaction(&second_tx);
+        return Err(e);
+    }
+    if let Err(e) = second.commit_transaction(&second_tx) {
+        let _ = second.rollback_transaction(&second_tx);
+        if compensation.is_empty() {
+            return Err(Error::PartialCommit(format!(
+                "the second database failed to commit after the first committed: {}",
+                e
+            )));
+        }
+        return match first.execute_transaction(compensation) {
+            Ok(_) => Err(e),
+            Err(undo) => Err(Error::PartialCommit(format!(
+                "the second database failed to commit ({}) and compensating the first failed: {}",
+                e, undo
+            ))),
+        };
+    }
+
+    Ok(CrossDatabaseResult {
+        first: first_results,
+        second: second_results,
+    })
+}
+
+/// Begin a write transaction and run `statements` in it, rolling it back
+/// if one fails
+fn prepare(
+    conn: &mut PowerSyncConnection,
+    statements: &[Statement],
+) -> Result<(String, Vec<ExecuteResult>)> {
+    let tx_id = conn.begin_transaction(true)?;
+    let results = statements
+        .iter()
+        .map(|statement| conn.execute(&statement.sql, &statement.params))
+        .collect::<Result<Vec<_>>>();
+    match results {
+        Ok(results) => Ok((tx_id, results)),
+        Err(e) => {
+            let _ = conn.rollback_transaction(&tx_id);
+            Err(e)
+        }
+    }
+}
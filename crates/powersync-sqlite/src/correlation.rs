@@ -0,0 +1,68 @@
+//! Correlation ids tying work to the call that caused it
+//!
+//! A caller (typically a frontend action reaching the Tauri plugin) enters a
+//! [`CorrelationScope`] around the synchronous work it does on a
+//! connection. Commit and checkpoint events raised while the scope is
+//! active carry its id, and log bridges can tag records with [`current`],
+//! so one action can be followed across IPC, SQL and sync activity.
+//!
+//! The id is kept per thread: a scope can't be held across an `.await`
+//! (it isn't `Send`), since the task may resume on another thread.
+
+use std::cell::RefCell;
+use std::marker::PhantomData;
+
+thread_local! {
+    static CURRENT: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Correlation id of the scope active on this thread, if any
+pub fn current() -> Option<String> {
+    CURRENT.with(|current| current.borrow().clone())
+}
+
+/// Make `id` the current correlation id until the returned scope is
+/// dropped. `None` clears it, so work without an id isn't attributed to an
+/// enclosing scope.
+pub fn enter(id: Option<String>) -> CorrelationScope {
+    let previous = CURRENT.with(|current| current.replace(id));
+    CorrelationScope {
+        previous,
+        _not_send: PhantomData,
+    }
+}
+
+/// Restores the previous correlation id when dropped
+pub struct CorrelationScope {
+    previous: Option<String>,
+    /// The id is thread-local, so the scope must stay on its thread
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for CorrelationScope {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scopes_nest() {
+        assert_eq!(current(), None);
+        {
+            let _outer = enter(Some("outer".to_string()));
+            {
+                let _inner = enter(None);
+                assert_eq!(current(), None);
+            }
+            assert_eq!(current().as_deref(), Some("outer"));
+            // Other threads don't see it
+            assert_eq!(std::thread::spawn(current).join().unwrap(), None);
+        }
+        assert_eq!(current(), None);
+    }
+}
@@ -0,0 +1,3348 @@
+use crate::bundle::{self, EncryptedExport};
+use crate::changes::{ChangeEvent, ChangeNotifier, CheckpointEvent, CommitEvent};
+use crate::coercion::{BoundColumns, ParamCoercion};
+use crate::consistency::LocalSyncStatus;
+use crate::control::{self, CheckpointFailure, ControlOp};
+use crate::correlation;
+use crate::devtools::{self, OplogSummary, RedactedCrudEntry, SchemaTable, SeedData};
+use crate::diagnostics;
+use crate::error::{Error, Result};
+use crate::export::{ExportFormat, ExportResult, ExportWriter};
+use crate::extension;
+use crate::faults::FaultInjection;
+use crate::ids::IdGenerator;
+use crate::profiles;
+use crate::queue::{WritePermit, WriteQueue};
+use crate::reconcile::{self, LocalDataMigration, LocalDataStrategy};
+use crate::recovery::{self, DatabaseRecovery};
+use crate::retention::{self, RetentionReport, RetentionRule};
+use crate::schema::{self, SchemaChange};
+use crate::soft_delete;
+use crate::sync_rules::{self, SyncRulesIssue};
+use crate::tuning::ConnectionTuning;
+use crate::undo::{self, UndoState, UndoStep};
+use crate::uploads::{self, ConflictResolution, UploadConflict, UploadError, UploadQueueStats};
+use crate::views::{self, ViewInfo};
+use indexmap::IndexMap;
+use rusqlite::backup::{Backup, Progress};
+use rusqlite::ffi;
+use rusqlite::serialize::OwnedData;
+use rusqlite::types::{ToSqlOutput, ValueRef};
+use rusqlite::{params_from_iter, Connection, DatabaseName, OpenFlags, OptionalExtension, ToSql};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
+use std::ffi::c_int;
+use std::path::{Path, PathBuf};
+use std::ptr::NonNull;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// A SQL parameter with explicit type information.
+/// This allows proper handling of blobs vs arrays.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(tag = "type", content = "value")]
+pub enum SqlParam {
+    #[serde(rename = "null")]
+    Null,
+    #[serde(rename = "bool")]
+    Bool(bool),
+    #[serde(rename = "int")]
+    Int(i64),
+    #[serde(rename = "real")]
+    Real(f64),
+    #[serde(rename = "text")]
+    Text(String),
+    #[serde(rename = "blob")]
+    Blob(Vec<u8>),
+}
+
+impl SqlParam {
+    /// Convert to an owned rusqlite Value.
+    ///
+    /// Copies text and blobs; statements bind params through [`ToSql`],
+    /// which borrows them instead.
+    pub fn to_sql_value(&self) -> rusqlite::types::Value {
+        match self {
+            SqlParam::Null => rusqlite::types::Value::Null,
+            SqlParam::Bool(b) => rusqlite::types::Value::Integer(if *b { 1 } else { 0 }),
+            SqlParam::Int(i) => rusqlite::types::Value::Integer(*i),
+            SqlParam::Real(f) => rusqlite::types::Value::Real(*f),
+            SqlParam::Text(s) => rusqlite::types::Value::Text(s.clone()),
+            SqlParam::Blob(b) => rusqlite::types::Value::Blob(b.clone()),
+        }
+    }
+}
+
+impl ToSql for SqlParam {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        // Text and blobs are borrowed, so SQLite copies them straight out of the param
+        Ok(ToSqlOutput::Borrowed(match self {
+            SqlParam::Null => ValueRef::Null,
+            SqlParam::Bool(b) => ValueRef::Integer(*b as i64),
+            SqlParam::Int(i) => ValueRef::Integer(*i),
+            SqlParam::Real(f) => ValueRef::Real(*f),
+            SqlParam::Text(s) => ValueRef::Text(s.as_bytes()),
+            SqlParam::Blob(b) => ValueRef::Blob(b),
+        }))
+    }
+}
+
+/// How REAL values JSON can't represent (NaN, ±Infinity) appear in results.
+///
+/// SQLite stores NaN as NULL, so in practice this concerns infinities.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub enum NonFiniteReals {
+    /// As `null`, indistinguishable from SQL NULL
+    #[default]
+    Null,
+    /// As the strings `"NaN"`, `"Infinity"` and `"-Infinity"`
+    String,
+    /// Fail the query
+    Error,
+}
+
+/// How TEXT values that aren't valid UTF-8 appear in results
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub enum InvalidUtf8 {
+    /// Replace invalid sequences with U+FFFD, losing the original bytes
+    #[default]
+    Replace,
+    /// As base64, like a BLOB, keeping the bytes intact
+    Blob,
+    /// Fail the query, naming the row and column
+    Error,
+}
+
+/// How SQLite values without a direct JSON equivalent are converted
+#[derive(Debug, Clone, Copy, Default)]
+struct ValueFormat {
+    non_finite_reals: NonFiniteReals,
+    invalid_utf8: InvalidUtf8,
+}
+
+/// A single statement of a [`PowerSyncConnection::execute_transaction`] call
+#[derive(Debug, Clone, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct Statement {
+    pub sql: String,
+    #[serde(default)]
+    pub params: Vec<SqlParam>,
+}
+
+/// What result of a [`TransactionGuard`]'s query aborts the transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub enum AbortIf {
+    /// No rows, e.g. the row to update is gone or no longer matches
+    Empty,
+    /// Any row, e.g. a conflicting row already exists
+    NotEmpty,
+}
+
+/// A read-only query deciding whether a
+/// [`PowerSyncConnection::with_transaction`] call commits
+#[derive(Debug, Clone, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionGuard {
+    pub sql: String,
+    #[serde(default)]
+    pub params: Vec<SqlParam>,
+    pub abort_if: AbortIf,
+    /// Run after the statements instead of before them, to check their
+    /// outcome (e.g. that a balance didn't go negative)
+    #[serde(default)]
+    pub after: bool,
+}
+
+/// Outcome of a [`PowerSyncConnection::with_transaction`] call
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct GuardedTransaction {
+    pub committed: bool,
+    /// One result per statement, empty if a guard aborted the transaction
+    pub results: Vec<ExecuteResult>,
+    /// Index of the guard that aborted the transaction
+    pub aborted_by: Option<usize>,
+}
+
+/// One operation of a [`PowerSyncConnection::pipeline`] call
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "op", rename_all = "camelCase")]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub enum PipelineOp {
+    Execute {
+        sql: String,
+        #[serde(default)]
+        params: Vec<SqlParam>,
+    },
+    GetAll {
+        sql: String,
+        #[serde(default)]
+        params: Vec<SqlParam>,
+    },
+    GetOptional {
+        sql: String,
+        #[serde(default)]
+        params: Vec<SqlParam>,
+    },
+}
+
+impl PipelineOp {
+    pub fn sql(&self) -> &str {
+        match self {
+            PipelineOp::Execute { sql, .. }
+            | PipelineOp::GetAll { sql, .. }
+            | PipelineOp::GetOptional { sql, .. } => sql,
+        }
+    }
+
+    /// Whether the operation may write, and so needs a turn in the write queue
+    pub fn is_write(&self) -> bool {
+        matches!(self, PipelineOp::Execute { .. })
+    }
+}
+
+/// Result of one [`PipelineOp`], shaped as the matching single command returns it
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(untagged)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub enum PipelineResult {
+    Execute(ExecuteResult),
+    GetAll(QueryResult),
+    GetOptional(
+        #[cfg_attr(feature = "specta", specta(type = Option<SpectaRow>))] Option<RowResult>,
+    ),
+}
+
+/// Callback run against each newly opened connection, after the PowerSync
+/// extension is initialized and before the connection is handed to the manager.
+/// Receives the connection and the database name.
+pub type ConnectionInitializer =
+    Arc<dyn Fn(&Connection, &str) -> rusqlite::Result<()> + Send + Sync>;
+
+/// An entry on a connection's transaction stack
+pub struct Transaction {
+    pub id: String,
+    #[allow(dead_code)]
+    pub is_write: bool,
+    /// Commit was requested while nested transactions were still active.
+    /// It is applied once everything nested inside has been resolved.
+    pub commit_pending: bool,
+    /// Savepoint name for nested transactions; None for the outermost one
+    pub savepoint_name: Option<String>,
+    /// Caller that began the transaction (e.g. a window label), if tracked
+    pub owner: Option<String>,
+    /// Named savepoints created with `create_savepoint`, oldest first
+    pub savepoints: Vec<String>,
+    /// Turn in the write queue, released when the transaction ends
+    permit: Option<WritePermit>,
+}
+
+/// A statement prepared with [`PowerSyncConnection::prepare`]
+struct PreparedStatement {
+    sql: String,
+    /// Columns its parameters bind to, for parameter coercion
+    bound: Option<BoundColumns>,
+    /// Caller that prepared it (e.g. a window label), if tracked
+    owner: Option<String>,
+}
+
+/// Outcome of [`PowerSyncConnection::run_batch`]
+struct BatchRun {
+    /// Results of the parameter sets that ran
+    entries: Vec<BatchEntry>,
+    /// Index and error of the parameter set that failed the batch
+    failure: Option<(usize, Error)>,
+}
+
+/// A PowerSync-enabled SQLite connection
+pub struct PowerSyncConnection {
+    conn: Connection,
+    /// Open transactions, outermost first. Nested entries are savepoints.
+    transactions: Vec<Transaction>,
+    db_path: PathBuf,
+    powersync_loaded: bool,
+    /// Fans out committed row changes to Rust subscribers
+    changes: Arc<ChangeNotifier>,
+    /// Tables whose writes are recorded for undo
+    undo_tables: Vec<String>,
+    /// Whether opening this connection created the database file
+    created: bool,
+    /// Why the PowerSync extension failed to load, if it did
+    load_error: Option<String>,
+    /// Row changes after which the planner statistics are refreshed, if set
+    auto_analyze: Option<u64>,
+    /// `sqlite3_total_changes64` when statistics were last refreshed
+    changes_at_analyze: u64,
+    value_format: ValueFormat,
+    param_coercion: ParamCoercion,
+    id_generator: IdGenerator,
+    /// Snapshot files by id, deleted when the connection is dropped
+    snapshots: HashMap<String, PathBuf>,
+    faults: FaultInjection,
+    /// Schema JSON last applied, re-applied if the database loses it (a
+    /// restored snapshot, a file replaced on disk)
+    last_schema: Option<String>,
+    /// The damaged database this one replaced when it was opened
+    recovery: Option<DatabaseRecovery>,
+    /// Statements prepared for repeated use, by handle id
+    prepared: HashMap<String, PreparedStatement>,
+    /// Whether diagnostics are collected unless the database recorded otherwise
+    diagnostics_default: bool,
+}
+
+impl PowerSyncConnection {
+    /// Open a new PowerSync database connection
+    #[allow(unused_variables)]
+    pub fn open(
+        name: &str,
+        app_data_dir: &Path,
+        resource_dir: Option<&PathBuf>,
+        initializer: Option<&ConnectionInitializer>,
+    ) -> Result<Self> {
+        let db_path = app_data_dir.join(format!("{}.db", name));
+
+        // Ensure parent directory exists
+        if let Some(parent) = db_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        // On iOS, register the statically linked extension before opening any connection.
+        // sqlite3_auto_extension makes it load automatically for all new connections.
+        #[cfg(powersync_static)]
+        {
+            static INIT: std::sync::Once = std::sync::Once::new();
+            INIT.call_once(|| {
+                unsafe extern "C" {
+                    fn powersync_init_static() -> std::ffi::c_int;
+                }
+                let rc = unsafe { powersync_init_static() };
+                if rc != 0 {
+                    log::error!("powersync_init_static() failed: {}", rc);
+                }
+            });
+        }
+
+        let created = !db_path.exists();
+        let conn = Connection::open_with_flags(
+            &db_path,
+            OpenFlags::SQLITE_OPEN_READ_WRITE
+                | OpenFlags::SQLITE_OPEN_CREATE
+                | OpenFlags::SQLITE_OPEN_URI
+                | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?;
+
+        // Enable WAL mode for better concurrent access
+        conn.execute_batch("PRAGMA journal_mode=WAL;")?;
+
+        // Why the extension couldn't be loaded, for `powersync_status`
+        #[allow(unused_mut)]
+        let mut load_error = None;
+
+        // On non-iOS: try dynamic extension loading
+        #[cfg(not(powersync_static))]
+        {
+            let mut powersync_loaded = false;
+
+            // Try build-time path first (set by build.rs during compilation)
+            if let Some(build_path) = extension::get_build_time_extension_path() {
+                if build_path.exists() {
+                    match extension::load_extension(&conn, &build_path) {
+                        Ok(()) => {
+                            log::info!("Loaded PowerSync extension from build path: {:?}", build_path);
+                            powersync_loaded = true;
+                        }
+                        Err(e) => {
+                            log::warn!("Failed to load PowerSync extension from build path: {}", e);
+                            load_error = Some(format!("{:?}: {}", build_path, e));
+                        }
+                    }
+                }
+            }
+
+            // Fall back to resource directory (for bundled apps)
+            if !powersync_loaded {
+                if let Some(res_dir) = resource_dir {
+                    match extension::find_extension(res_dir) {
+                        Ok(ext_path) => {
+                            match extension::load_extension(&conn, &ext_path) {
+                                Ok(()) => {
+                                    log::info!("Loaded PowerSync extension from {:?}", ext_path);
+                                    load_error = None;
+                                }
+                                Err(e) => {
+                                    log::warn!("Failed to load PowerSync extension: {}", e);
+                                    load_error = Some(format!("{:?}: {}", ext_path, e));
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            log::debug!("PowerSync extension not found: {}", e);
+                            load_error.get_or_insert_with(|| e.to_string());
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut conn = Self::from_connection(conn, name, db_path, initializer)?;
+        conn.created = created;
+        if !conn.powersync_loaded {
+            conn.load_error = load_error;
+        }
+        Ok(conn)
+    }
+
+    /// Finish setting up a freshly opened connection.
+    ///
+    /// Initializes PowerSync if its functions are available (from auto_extension
+    /// on iOS, dynamic loading, or a stand-in such as the test-util mock),
+    /// installs the change hooks and runs the app's initializer.
+    pub fn from_connection(
+        conn: Connection,
+        name: &str,
+        db_path: PathBuf,
+        initializer: Option<&ConnectionInitializer>,
+    ) -> Result<Self> {
+        let powersync_loaded = extension::has_powersync(&conn);
+        let mut last_schema = None;
+        if powersync_loaded {
+            extension::init_powersync(&conn)?;
+            log::info!("PowerSync initialized");
+            last_schema = schema::applied(&conn)?.map(|applied| applied.schema);
+        }
+
+        let changes = Arc::new(ChangeNotifier::default());
+        changes.install(&conn);
+
+        #[cfg(feature = "unicode-collation")]
+        crate::collation::register(&conn)?;
+        crate::functions::register_now(&conn)?;
+
+        // Let the app apply its own pragmas, collations, attachments, etc.
+        if let Some(initializer) = initializer {
+            initializer(&conn, name)?;
+        }
+
+        Ok(Self {
+            conn,
+            transactions: Vec::new(),
+            db_path,
+            powersync_loaded,
+            changes,
+            undo_tables: Vec::new(),
+            created: false,
+            load_error: None,
+            auto_analyze: None,
+            changes_at_analyze: 0,
+            value_format: ValueFormat::default(),
+            param_coercion: ParamCoercion::default(),
+            id_generator: IdGenerator::default(),
+            snapshots: HashMap::new(),
+            faults: FaultInjection::default(),
+            last_schema,
+            recovery: None,
+            prepared: HashMap::new(),
+            diagnostics_default: true,
+        })
+    }
+
+    /// Execute a SQL statement with parameters
+    ///
+    /// If the SQL is a SELECT statement, it will be executed as a query
+    /// and the results will be returned in the `rows` field.
+    /// This is needed because PowerSync extension functions use SELECT to return values.
+    pub fn execute(&mut self, sql: &str, params: &[SqlParam]) -> Result<ExecuteResult> {
+        self.faults.before_statement()?;
+        let sql_upper = sql.trim_start().to_uppercase();
+
+        // Check if this is a SELECT or other query that returns results
+        if sql_upper.starts_with("SELECT") || sql_upper.starts_with("PRAGMA") {
+            // For SELECT statements, actually return the results
+            // This is needed because SqliteBucketStorage uses execute() for
+            // PowerSync extension functions like powersync_sync_data()
+            let (columns, rows) =
+                query_rows(&mut self.conn.prepare(sql)?, params, self.value_format)?;
+            self.analyze_if_due();
+
+            return Ok(ExecuteResult {
+                changes: 0,
+                last_insert_rowid: 0,
+                columns: Some(columns),
+                rows: Some(rows),
+            });
+        }
+
+        let params = match self.bound_columns(sql)? {
+            Some(bound) => bound.apply(params, self.param_coercion)?,
+            None => Cow::Borrowed(params),
+        };
+        let changes = self.conn.execute(sql, params_from_iter(params.iter()))?;
+        let last_insert_rowid = self.conn.last_insert_rowid();
+        self.analyze_if_due();
+
+        Ok(ExecuteResult {
+            changes: changes as i64,
+            last_insert_rowid,
+            columns: None,
+            rows: None,
+        })
+    }
+
+    /// Execute a batch of SQL statements
+    /// Uses a savepoint to allow nesting within an existing transaction.
+    pub fn execute_batch(
+        &mut self,
+        sql: &str,
+        params_batch: &[Vec<SqlParam>],
+    ) -> Result<ExecuteResult> {
+        let BatchRun { entries, failure } = self.run_batch(sql, params_batch)?;
+        if let Some(failure) = failure {
+            return Err(failure.1);
+        }
+        Ok(ExecuteResult {
+            changes: entries.iter().map(|entry| entry.changes).sum(),
+            last_insert_rowid: entries.last().map_or(0, |entry| entry.last_insert_rowid),
+            columns: None,
+            rows: None,
+        })
+    }
+
+    /// Like [`execute_batch`](Self::execute_batch), but reporting the result
+    /// of every parameter set, and a failing parameter set as
+    /// [`BatchResult::failure`] rather than as an error. The batch is still
+    /// all or nothing: after a failure, nothing is left applied.
+    pub fn execute_batch_detailed(
+        &mut self,
+        sql: &str,
+        params_batch: &[Vec<SqlParam>],
+    ) -> Result<BatchResult> {
+        let BatchRun { entries, failure } = self.run_batch(sql, params_batch)?;
+        Ok(BatchResult {
+            changes: match failure {
+                Some(_) => 0,
+                None => entries.iter().map(|entry| entry.changes).sum(),
+            },
+            last_insert_rowid: entries.last().map_or(0, |entry| entry.last_insert_rowid),
+            entries: Some(entries),
+            failure: failure.map(|(index, e)| BatchFailure {
+                index,
+                error: e.to_string(),
+            }),
+        })
+    }
+
+    /// Run `sql` once per parameter set in a savepoint, stopping at the first
+    /// failing set, which rolls the savepoint back. Returns the results of
+    /// the sets that ran, and the index and error of the failing one.
+    fn run_batch(
+        &mut self,
+        sql: &str,
+        params_batch: &[Vec<SqlParam>],
+    ) -> Result<BatchRun> {
+        // Use savepoint instead of transaction to support nesting
+        let savepoint_name = format!("batch_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
+        self.conn.execute(&format!("SAVEPOINT {}", savepoint_name), [])?;
+
+        let bound = match self.bound_columns(sql) {
+            Ok(bound) => bound,
+            Err(e) => {
+                let _ = self.conn.execute(&format!("ROLLBACK TO SAVEPOINT {}", savepoint_name), []);
+                let _ = self.conn.execute(&format!("RELEASE SAVEPOINT {}", savepoint_name), []);
+                return Err(e);
+            }
+        };
+
+        let mut entries = Vec::with_capacity(params_batch.len());
+        let mut failure = None;
+        for (index, params) in params_batch.iter().enumerate() {
+            match self.run_batch_entry(sql, bound.as_ref(), params) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => {
+                    failure = Some((index, e));
+                    break;
+                }
+            }
+        }
+
+        if failure.is_none() {
+            self.conn.execute(&format!("RELEASE SAVEPOINT {}", savepoint_name), [])?;
+            self.analyze_if_due();
+        } else {
+            let _ = self.conn.execute(&format!("ROLLBACK TO SAVEPOINT {}", savepoint_name), []);
+            let _ = self.conn.execute(&format!("RELEASE SAVEPOINT {}", savepoint_name), []);
+        }
+        Ok(BatchRun { entries, failure })
+    }
+
+    fn run_batch_entry(
+        &self,
+        sql: &str,
+        bound: Option<&BoundColumns>,
+        params: &[SqlParam],
+    ) -> Result<BatchEntry> {
+        self.faults.before_statement()?;
+        let params = match bound {
+            Some(bound) => bound.apply(params, self.param_coercion)?,
+            None => Cow::Borrowed(params),
+        };
+        let changes = self.conn.execute(sql, params_from_iter(params.iter()))?;
+        Ok(BatchEntry {
+            changes: changes as i64,
+            last_insert_rowid: self.conn.last_insert_rowid(),
+        })
+    }
+
+    /// Insert `rows` into `table` using multi-row `VALUES` statements, all in
+    /// one savepoint. Each row holds one value per entry of `columns`.
+    ///
+    /// Much faster than [`execute_batch`](Self::execute_batch) for initial
+    /// loads. Statements are sized to stay within SQLite's limit on bound
+    /// variables.
+    pub fn insert_many(
+        &mut self,
+        table: &str,
+        columns: &[String],
+        rows: &[Vec<SqlParam>],
+    ) -> Result<ExecuteResult> {
+        if columns.is_empty() {
+            return Err(Error::InvalidParameter(
+                "insert_many needs at least one column".to_string(),
+            ));
+        }
+        if let Some(row) = rows.iter().find(|row| row.len() != columns.len()) {
+            return Err(Error::InvalidParameter(format!(
+                "expected {} values per row, got {}",
+                columns.len(),
+                row.len()
+            )));
+        }
+
+        let rows: Vec<Cow<[SqlParam]>> = if self.param_coercion == ParamCoercion::Off {
+            rows.iter().map(|row| Cow::Borrowed(row.as_slice())).collect()
+        } else {
+            let bound = BoundColumns::lookup(&self.conn, table, columns)?;
+            rows.iter()
+                .map(|row| bound.apply(row, self.param_coercion))
+                .collect::<Result<_>>()?
+        };
+
+        let rows_per_statement = (variable_limit(&self.conn) / columns.len()).max(1);
+        let insert = format!(
+            "INSERT INTO {} ({}) VALUES ",
+            quote_identifier(table),
+            columns.iter().map(|c| quote_identifier(c)).collect::<Vec<_>>().join(", ")
+        );
+        let row_placeholders = format!("({})", vec!["?"; columns.len()].join(", "));
+
+        let savepoint_name =
+            format!("insert_many_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
+        self.conn.execute(&format!("SAVEPOINT {}", savepoint_name), [])?;
+
+        let mut total_changes = 0i64;
+        let result = (|| {
+            for chunk in rows.chunks(rows_per_statement) {
+                self.faults.before_statement()?;
+                let values = vec![row_placeholders.as_str(); chunk.len()].join(", ");
+                let mut stmt = self.conn.prepare_cached(&format!("{}{}", insert, values))?;
+                let params = chunk.iter().flat_map(|row| row.iter());
+                total_changes += stmt.execute(params_from_iter(params))? as i64;
+            }
+            Ok::<_, crate::error::Error>(())
+        })();
+
+        match result {
+            Ok(()) => {
+                let last_insert_rowid = self.conn.last_insert_rowid();
+                self.conn.execute(&format!("RELEASE SAVEPOINT {}", savepoint_name), [])?;
+                self.analyze_if_due();
+                Ok(ExecuteResult {
+                    changes: total_changes,
+                    last_insert_rowid,
+                    columns: None,
+                    rows: None,
+                })
+            }
+            Err(e) => {
+                let _ = self.conn.execute(&format!("ROLLBACK TO SAVEPOINT {}", savepoint_name), []);
+                let _ = self.conn.execute(&format!("RELEASE SAVEPOINT {}", savepoint_name), []);
+                Err(e)
+            }
+        }
+    }
+
+    /// Insert a row into `table`, with one value per entry of `columns`,
+    /// and an `id` from the connection's [`IdGenerator`]. Returns the id.
+    ///
+    /// `columns` must not include `id`.
+    pub fn insert_with_id(
+        &mut self,
+        table: &str,
+        columns: &[String],
+        values: &[SqlParam],
+    ) -> Result<String> {
+        if columns.len() != values.len() {
+            return Err(Error::InvalidParameter(format!(
+                "expected {} values, got {}",
+                columns.len(),
+                values.len()
+            )));
+        }
+        if columns.iter().any(|c| c.eq_ignore_ascii_case("id")) {
+            return Err(Error::InvalidParameter(
+                "insert_with_id generates the id column; leave it out".to_string(),
+            ));
+        }
+
+        let id = self.id_generator.generate(table);
+        let column_list: String = columns
+            .iter()
+            .map(|c| format!(", {}", quote_identifier(c)))
+            .collect();
+        let sql = format!(
+            "INSERT INTO {} (id{}) VALUES (?{})",
+            quote_identifier(table),
+            column_list,
+            ", ?".repeat(columns.len())
+        );
+        let mut params = Vec::with_capacity(values.len() + 1);
+        params.push(SqlParam::Text(id.clone()));
+        params.extend_from_slice(values);
+        self.execute(&sql, &params)?;
+        Ok(id)
+    }
+
+    /// Execute statements atomically in a single write transaction.
+    ///
+    /// Returns one result per statement; SELECT/PRAGMA statements carry their
+    /// rows as with [`execute`](Self::execute). If any statement fails, all of
+    /// them are rolled back. Nests as a savepoint inside an open transaction.
+    pub fn execute_transaction(
+        &mut self,
+        statements: &[Statement],
+    ) -> Result<Vec<ExecuteResult>> {
+        let tx_id = self.begin_transaction(true)?;
+
+        let result = statements
+            .iter()
+            .map(|statement| self.execute(&statement.sql, &statement.params))
+            .collect::<Result<Vec<_>>>();
+
+        match result {
+            Ok(results) => {
+                self.commit_transaction(&tx_id)?;
+                self.analyze_if_due();
+                Ok(results)
+            }
+            Err(e) => {
+                let _ = self.rollback_transaction(&tx_id);
+                Err(e)
+            }
+        }
+    }
+
+    /// Execute statements atomically like
+    /// [`execute_transaction`](Self::execute_transaction), committing only
+    /// if none of the `guards` aborts.
+    ///
+    /// Guards run in order, those with `after` set once the statements have
+    /// run. The first to abort rolls the transaction back and is reported in
+    /// the outcome; a failing statement or guard rolls it back too and is
+    /// returned as the error.
+    pub fn with_transaction(
+        &mut self,
+        statements: &[Statement],
+        guards: &[TransactionGuard],
+    ) -> Result<GuardedTransaction> {
+        let tx_id = self.begin_transaction(true)?;
+
+        match self.run_guarded(statements, guards) {
+            Ok(outcome) if outcome.committed => {
+                self.commit_transaction(&tx_id)?;
+                self.analyze_if_due();
+                Ok(outcome)
+            }
+            Ok(outcome) => {
+                self.rollback_transaction(&tx_id)?;
+                Ok(outcome)
+            }
+            Err(e) => {
+                let _ = self.rollback_transaction(&tx_id);
+                Err(e)
+            }
+        }
+    }
+
+    fn run_guarded(
+        &mut self,
+        statements: &[Statement],
+        guards: &[TransactionGuard],
+    ) -> Result<GuardedTransaction> {
+        let aborted = |index| GuardedTransaction {
+            committed: false,
+            results: Vec::new(),
+            aborted_by: Some(index),
+        };
+        if let Some(index) = self.aborting_guard(guards, false)? {
+            return Ok(aborted(index));
+        }
+        let results = statements
+            .iter()
+            .map(|statement| self.execute(&statement.sql, &statement.params))
+            .collect::<Result<Vec<_>>>()?;
+        if let Some(index) = self.aborting_guard(guards, true)? {
+            return Ok(aborted(index));
+        }
+        Ok(GuardedTransaction {
+            committed: true,
+            results,
+            aborted_by: None,
+        })
+    }
+
+    /// Index of the first guard run `after` the statements (or before) that
+    /// aborts the transaction
+    fn aborting_guard(&self, guards: &[TransactionGuard], after: bool) -> Result<Option<usize>> {
+        for (index, guard) in guards.iter().enumerate() {
+            if guard.after != after {
+                continue;
+            }
+            let mut stmt = self.conn.prepare(&guard.sql)?;
+            if !stmt.readonly() {
+                return Err(Error::InvalidParameter(format!(
+                    "transaction guards must be read-only: {}",
+                    guard.sql
+                )));
+            }
+            let found = stmt.exists(params_from_iter(&guard.params))?;
+            let abort = match guard.abort_if {
+                AbortIf::Empty => !found,
+                AbortIf::NotEmpty => found,
+            };
+            if abort {
+                return Ok(Some(index));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Run operations back to back, returning one result per operation.
+    ///
+    /// Unlike [`execute_transaction`](Self::execute_transaction) this is not
+    /// atomic: if an operation fails, the writes before it stay applied.
+    pub fn pipeline(&mut self, ops: &[PipelineOp]) -> Result<Vec<PipelineResult>> {
+        ops.iter()
+            .map(|op| {
+                Ok(match op {
+                    PipelineOp::Execute { sql, params } => {
+                        PipelineResult::Execute(self.execute(sql, params)?)
+                    }
+                    PipelineOp::GetAll { sql, params } => {
+                        PipelineResult::GetAll(self.get_all(sql, params)?)
+                    }
+                    PipelineOp::GetOptional { sql, params } => {
+                        PipelineResult::GetOptional(self.get_optional(sql, params)?)
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Prepare `sql` for repeated use by [`bind_and_step`](Self::bind_and_step),
+    /// returning its handle id
+    pub fn prepare(&mut self, sql: &str) -> Result<String> {
+        self.prepare_owned(sql, None)
+    }
+
+    /// Prepare `sql` on behalf of `owner`, finalized by
+    /// [`finalize_owned`](Self::finalize_owned) once the owner goes away.
+    ///
+    /// The statement is kept in the connection's statement cache, which grows
+    /// by one per handle, so each step only binds parameters and runs it.
+    pub fn prepare_owned(&mut self, sql: &str, owner: Option<&str>) -> Result<String> {
+        self.conn.set_prepared_statement_cache_capacity(
+            STATEMENT_CACHE_CAPACITY + self.prepared.len() + 1,
+        );
+        self.conn.prepare_cached(sql)?;
+        let bound = self.bound_columns(sql)?;
+        let stmt_id = Uuid::new_v4().to_string();
+        self.prepared.insert(
+            stmt_id.clone(),
+            PreparedStatement {
+                sql: sql.to_string(),
+                bound,
+                owner: owner.map(str::to_string),
+            },
+        );
+        Ok(stmt_id)
+    }
+
+    /// Bind `params` to a prepared statement and run it, returning rows for
+    /// statements that produce them as [`execute`](Self::execute) does
+    pub fn bind_and_step(&mut self, stmt_id: &str, params: &[SqlParam]) -> Result<ExecuteResult> {
+        self.faults.before_statement()?;
+        let prepared = self
+            .prepared
+            .get(stmt_id)
+            .ok_or_else(|| Error::StatementNotFound(stmt_id.to_string()))?;
+        let params = match &prepared.bound {
+            Some(bound) => bound.apply(params, self.param_coercion)?,
+            None => Cow::Borrowed(params),
+        };
+        let result = {
+            // Re-prepared transparently if it was evicted, e.g. by a restore
+            let mut stmt = self.conn.prepare_cached(&prepared.sql)?;
+            if stmt.column_count() > 0 {
+                let (columns, rows) = query_rows(&mut stmt, &params, self.value_format)?;
+                ExecuteResult {
+                    changes: 0,
+                    last_insert_rowid: 0,
+                    columns: Some(columns),
+                    rows: Some(rows),
+                }
+            } else {
+                let changes = stmt.execute(params_from_iter(params.iter()))?;
+                ExecuteResult {
+                    changes: changes as i64,
+                    last_insert_rowid: self.conn.last_insert_rowid(),
+                    columns: None,
+                    rows: None,
+                }
+            }
+        };
+        self.analyze_if_due();
+        Ok(result)
+    }
+
+    /// Release a statement prepared by [`prepare`](Self::prepare)
+    pub fn finalize(&mut self, stmt_id: &str) -> Result<()> {
+        self.prepared
+            .remove(stmt_id)
+            .ok_or_else(|| Error::StatementNotFound(stmt_id.to_string()))?;
+        self.shrink_statement_cache();
+        Ok(())
+    }
+
+    /// Release every statement prepared by `owner`, returning how many
+    pub fn finalize_owned(&mut self, owner: &str) -> usize {
+        let before = self.prepared.len();
+        self.prepared
+            .retain(|_, prepared| prepared.owner.as_deref() != Some(owner));
+        self.shrink_statement_cache();
+        before - self.prepared.len()
+    }
+
+    /// Let the statement cache evict statements no handle needs anymore
+    fn shrink_statement_cache(&self) {
+        self.conn
+            .set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY + self.prepared.len());
+    }
+
+    /// Apply a JSON merge patch (RFC 7396) to a JSON column of the row with
+    /// the given `id`, in a single statement.
+    ///
+    /// A NULL column is treated as an empty object. Returns the number of
+    /// rows changed (0 if there is no such row).
+    pub fn patch_json(
+        &mut self,
+        table: &str,
+        column: &str,
+        id: &str,
+        patch: &str,
+    ) -> Result<ExecuteResult> {
+        let sql = format!(
+            "UPDATE {table} SET {column} = json_patch(COALESCE({column}, '{{}}'), ?) WHERE id = ?",
+            table = quote_identifier(table),
+            column = quote_identifier(column),
+        );
+        self.execute(
+            &sql,
+            &[
+                SqlParam::Text(patch.to_string()),
+                SqlParam::Text(id.to_string()),
+            ],
+        )
+    }
+
+    /// Create view `name` defined by the SELECT statement `select`, e.g. a
+    /// join or aggregate to query like a table. Replaces a view of that name
+    /// created this way if `replace` is set. See [`views`].
+    pub fn create_view(&mut self, name: &str, select: &str, replace: bool) -> Result<ViewInfo> {
+        views::create(&self.conn, name, select, replace)
+    }
+
+    /// Drop a view created with [`create_view`](Self::create_view)
+    pub fn drop_view(&mut self, name: &str) -> Result<()> {
+        views::drop(&self.conn, name)
+    }
+
+    /// Views created with [`create_view`](Self::create_view), by name
+    pub fn list_views(&self) -> Result<Vec<ViewInfo>> {
+        views::list(&self.conn)
+    }
+
+    /// Record writes to `tables` for [`undo`](Self::undo) and
+    /// [`redo`](Self::redo), replacing the tables tracked before and
+    /// clearing the history. An empty list turns tracking off. See [`crate::undo`].
+    pub fn enable_undo(&mut self, tables: &[String]) -> Result<()> {
+        undo::enable(&self.conn, self.changes.commit_count(), tables)?;
+        self.undo_tables = tables.to_vec();
+        Ok(())
+    }
+
+    /// Revert the latest transaction written to the tracked tables. Returns
+    /// `None` if there is nothing to undo.
+    pub fn undo(&mut self) -> Result<Option<UndoStep>> {
+        soft_delete::with_deleted(&self.conn, || undo::undo(&self.conn))
+    }
+
+    /// Write the transaction last undone again. Returns `None` if there is
+    /// nothing to redo.
+    pub fn redo(&mut self) -> Result<Option<UndoStep>> {
+        soft_delete::with_deleted(&self.conn, || undo::redo(&self.conn))
+    }
+
+    /// Tables tracked for undo and the steps available
+    pub fn undo_state(&self) -> Result<UndoState> {
+        undo::state(&self.conn, &self.undo_tables)
+    }
+
+    /// Enable soft delete for a table, using `column` as its tombstone.
+    ///
+    /// Tombstoned rows are filtered out of the PowerSync-generated view of
+    /// the table, and filtered again whenever [`replace_schema`] recreates
+    /// it. The column is recorded in the database, so soft delete stays
+    /// enabled when it is reopened.
+    ///
+    /// [`replace_schema`]: Self::replace_schema
+    pub fn enable_soft_delete(&mut self, table: &str, column: &str) -> Result<()> {
+        soft_delete::enable(&self.conn, table, column)?;
+        // Recreating the view dropped the undo triggers on it
+        if self.undo_tables.iter().any(|t| t == table) {
+            undo::reinstall(&self.conn, &self.undo_tables)?;
+        }
+        Ok(())
+    }
+
+    /// Tombstone the row with the given `id` instead of deleting it
+    pub fn soft_delete_row(&mut self, table: &str, id: &str) -> Result<ExecuteResult> {
+        let column = self.tombstone_column(table)?;
+        let sql = format!(
+            "UPDATE {} SET {} = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = ?",
+            quote_identifier(table),
+            quote_identifier(&column),
+        );
+        self.execute(&sql, &[SqlParam::Text(id.to_string())])
+    }
+
+    /// Clear the tombstone of a soft-deleted row
+    pub fn restore_row(&mut self, table: &str, id: &str) -> Result<ExecuteResult> {
+        let column = self.tombstone_column(table)?;
+        let sql = format!(
+            "UPDATE {} SET {} = NULL WHERE id = ?",
+            quote_identifier(table),
+            quote_identifier(&column),
+        );
+        self.execute_with_deleted(&sql, &[SqlParam::Text(id.to_string())])
+    }
+
+    /// Permanently delete soft-deleted rows, optionally only those
+    /// tombstoned before `before` (an ISO 8601 timestamp)
+    pub fn purge_deleted(&mut self, table: &str, before: Option<&str>) -> Result<ExecuteResult> {
+        let column = quote_identifier(&self.tombstone_column(table)?);
+        let sql = format!(
+            "DELETE FROM {} WHERE {column} IS NOT NULL AND (?1 IS NULL OR {column} < ?1)",
+            quote_identifier(table),
+        );
+        let before = before.map_or(SqlParam::Null, |b| SqlParam::Text(b.to_string()));
+        self.execute_with_deleted(&sql, &[before])
+    }
+
+    /// [`execute`](Self::execute) with tombstoned rows visible in the views
+    fn execute_with_deleted(&self, sql: &str, params: &[SqlParam]) -> Result<ExecuteResult> {
+        soft_delete::with_deleted(&self.conn, || {
+            let changes = self.conn.execute(sql, params_from_iter(params.iter()))?;
+            Ok(ExecuteResult {
+                changes: changes as i64,
+                last_insert_rowid: self.conn.last_insert_rowid(),
+                columns: None,
+                rows: None,
+            })
+        })
+    }
+
+    fn tombstone_column(&self, table: &str) -> Result<String> {
+        soft_delete::column(&self.conn, table)?.ok_or_else(|| {
+            Error::InvalidParameter(format!("Soft delete is not enabled for {}", table))
+        })
+    }
+
+    /// Delete old rows of local-only and raw tables as `rules` describe, in
+    /// one transaction. Returns one report per rule. See [`retention`].
+    pub fn apply_retention(&mut self, rules: &[RetentionRule]) -> Result<Vec<RetentionReport>> {
+        let tx_id = self.begin_transaction(true)?;
+        let reports = rules
+            .iter()
+            .map(|rule| {
+                Ok(RetentionReport {
+                    table: rule.table.clone(),
+                    deleted: retention::prune(&self.conn, rule)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>();
+        match reports {
+            Ok(reports) => {
+                self.commit_transaction(&tx_id)?;
+                Ok(reports)
+            }
+            Err(e) => {
+                let _ = self.rollback_transaction(&tx_id);
+                Err(e)
+            }
+        }
+    }
+
+    /// Record row changes of `tables` in the `_changes` feed table.
+    ///
+    /// Triggers append `(seq, table_name, pk, op)` for every insert, update
+    /// and delete, whether made locally or applied by sync, until consumers
+    /// acknowledge them with [`Self::ack_change_feed`]. For schema views the
+    /// triggers go on the PowerSync table backing the view. Enabling a table
+    /// twice is a no-op.
+    pub fn enable_change_feed(&mut self, tables: &[&str]) -> Result<()> {
+        self.conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS _changes (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                table_name TEXT NOT NULL,
+                pk TEXT NOT NULL,
+                op TEXT NOT NULL
+            )",
+        )?;
+        for table in tables {
+            let source = self.change_feed_source(table)?;
+            let has_id: bool = self.conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM pragma_table_info(?) WHERE name = 'id')",
+                [&source],
+                |row| row.get(0),
+            )?;
+            let pk = if has_id { "id" } else { "rowid" };
+
+            let mut sql = String::new();
+            for (op, row) in [("INSERT", "NEW"), ("UPDATE", "NEW"), ("DELETE", "OLD")] {
+                sql.push_str(&format!(
+                    "CREATE TRIGGER IF NOT EXISTS {trigger} AFTER {op} ON {source} BEGIN
+                        INSERT INTO _changes (table_name, pk, op)
+                        VALUES ({name}, CAST({row}.{pk} AS TEXT), '{op}');
+                    END;",
+                    trigger = quote_identifier(&format!(
+                        "_changes_{}_{}",
+                        table,
+                        op.to_lowercase()
+                    )),
+                    source = quote_identifier(&source),
+                    name = quote_literal(table),
+                ));
+            }
+            self.conn.execute_batch(&sql)?;
+        }
+        Ok(())
+    }
+
+    /// Oldest unacknowledged entries of the change feed, in order
+    pub fn read_change_feed(&self, limit: i64) -> Result<Vec<ChangeFeedEntry>> {
+        if !self.has_table("_changes")? {
+            return Ok(Vec::new());
+        }
+        let mut stmt = self
+            .conn
+            .prepare("SELECT seq, table_name, pk, op FROM _changes ORDER BY seq LIMIT ?")?;
+        let entries = stmt
+            .query_map([limit], |row| {
+                Ok(ChangeFeedEntry {
+                    seq: row.get(0)?,
+                    table: row.get(1)?,
+                    pk: row.get(2)?,
+                    op: row.get(3)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(entries)
+    }
+
+    /// Remove change feed entries up to and including `seq`
+    pub fn ack_change_feed(&mut self, seq: i64) -> Result<()> {
+        if self.has_table("_changes")? {
+            self.conn.execute("DELETE FROM _changes WHERE seq <= ?", [seq])?;
+        }
+        Ok(())
+    }
+
+    /// Table whose rows back `table`: the table itself, or the PowerSync
+    /// table behind a schema view
+    fn change_feed_source(&self, table: &str) -> Result<String> {
+        let candidates = [
+            table.to_string(),
+            format!("ps_data__{}", table),
+            format!("ps_data_local__{}", table),
+        ];
+        for candidate in candidates {
+            if self.has_table(&candidate)? {
+                return Ok(candidate);
+            }
+        }
+        Err(Error::InvalidParameter(format!("No such table: {}", table)))
+    }
+
+    fn has_table(&self, name: &str) -> Result<bool> {
+        let exists = self.conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?)",
+            [name],
+            |row| row.get(0),
+        )?;
+        Ok(exists)
+    }
+
+    /// Query and return all matching rows
+    pub fn get_all(&self, sql: &str, params: &[SqlParam]) -> Result<QueryResult> {
+        self.faults.before_statement()?;
+        let mut stmt = self.conn.prepare(sql)?;
+        let (columns, rows) = query_rows(&mut stmt, params, self.value_format)?;
+        Ok(QueryResult { columns, rows })
+    }
+
+    /// Run a read-only query, writing its rows to the file at `dest` as they
+    /// are read instead of collecting them, for results too large to hold
+    /// in memory. The file is replaced if it exists and removed if the
+    /// query fails part way. See [`crate::export`].
+    pub fn query_to_file(
+        &self,
+        sql: &str,
+        params: &[SqlParam],
+        dest: &Path,
+        format: ExportFormat,
+    ) -> Result<ExportResult> {
+        self.faults.before_statement()?;
+        let mut stmt = self.conn.prepare(sql)?;
+        if !stmt.readonly() {
+            return Err(Error::InvalidParameter(format!(
+                "exported queries must be read-only: {}",
+                sql
+            )));
+        }
+        let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+        let column_count = columns.len();
+        let mut writer = ExportWriter::create(dest, format, columns)?;
+        let mut rows = stmt.query(params_from_iter(params))?;
+        let mut row_index = 0;
+        while let Some(row) = rows.next()? {
+            let values = (0..column_count)
+                .map(|i| sqlite_value_to_json(row, row_index, i, self.value_format))
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            writer.write_row(&values)?;
+            row_index += 1;
+        }
+        writer.finish()
+    }
+
+    /// Run read-only queries against one snapshot of the database.
+    ///
+    /// Outside a transaction the queries share a `BEGIN`...`COMMIT`, so a
+    /// list and a count over it always agree, even if commits land between
+    /// IPC calls. Inside a transaction they see its state, as any read does.
+    pub fn get_all_snapshot(&mut self, queries: &[Statement]) -> Result<Vec<QueryResult>> {
+        self.faults.before_statement()?;
+        let mut statements = Vec::with_capacity(queries.len());
+        for query in queries {
+            let stmt = self.conn.prepare(&query.sql)?;
+            if !stmt.readonly() {
+                return Err(Error::InvalidParameter(format!(
+                    "snapshot queries must be read-only: {}",
+                    query.sql
+                )));
+            }
+            statements.push(stmt);
+        }
+
+        let own_transaction = self.conn.is_autocommit();
+        if own_transaction {
+            self.conn.execute_batch("BEGIN")?;
+        }
+        let results = statements
+            .iter_mut()
+            .zip(queries)
+            .map(|(stmt, query)| {
+                let (columns, rows) = query_rows(stmt, &query.params, self.value_format)?;
+                Ok(QueryResult { columns, rows })
+            })
+            .collect::<Result<Vec<_>>>();
+        drop(statements);
+        if own_transaction {
+            self.conn.execute_batch("COMMIT")?;
+        }
+        results
+    }
+
+    /// Query and return a single optional row
+    ///
+    /// Stops stepping after the first row, so the rest of a large result set
+    /// is never read.
+    pub fn get_optional(&self, sql: &str, params: &[SqlParam]) -> Result<Option<RowResult>> {
+        self.faults.before_statement()?;
+        let mut stmt = self.conn.prepare(sql)?;
+        let keys = column_keys(&stmt);
+        let mut rows = stmt.query(params_from_iter(params))?;
+        let row = rows.next()?;
+        Ok(row.map(|row| row_result(row, 0, &keys, self.value_format)).transpose()?)
+    }
+
+    /// Query and map all matching rows into `T` by column name
+    pub fn query_as<T: DeserializeOwned>(&self, sql: &str, params: &[SqlParam]) -> Result<Vec<T>> {
+        let result = self.get_all(sql, params)?;
+        result.rows.into_iter().map(row_into).collect()
+    }
+
+    /// Query and map a single optional row into `T` by column name
+    pub fn query_optional_as<T: DeserializeOwned>(
+        &self,
+        sql: &str,
+        params: &[SqlParam],
+    ) -> Result<Option<T>> {
+        self.get_optional(sql, params)?.map(row_into).transpose()
+    }
+
+    /// Begin a new transaction or savepoint if already in a transaction
+    pub fn begin_transaction(&mut self, is_write: bool) -> Result<String> {
+        self.begin_owned_transaction(is_write, None)
+    }
+
+    /// Begin a transaction on behalf of `owner`.
+    ///
+    /// Callers can then use [`check_transaction_owner`](Self::check_transaction_owner)
+    /// before committing or rolling back, and
+    /// [`rollback_owned_transactions`](Self::rollback_owned_transactions)
+    /// once the owner goes away.
+    pub fn begin_owned_transaction(
+        &mut self,
+        is_write: bool,
+        owner: Option<&str>,
+    ) -> Result<String> {
+        // A transaction ended behind our back (e.g. a raw COMMIT) leaves a stale stack
+        if !self.transactions.is_empty() && self.conn.is_autocommit() {
+            log::warn!(
+                "Discarding {} stale transaction(s) on {:?}",
+                self.transactions.len(),
+                self.db_path
+            );
+            self.transactions.clear();
+        }
+
+        let tx_id = Uuid::new_v4().to_string();
+
+        let savepoint_name = if self.transactions.is_empty() {
+            // Start a real transaction
+            let sql = if is_write { "BEGIN IMMEDIATE" } else { "BEGIN" };
+            self.conn.execute(sql, [])?;
+            None
+        } else {
+            // Already in a transaction, use savepoint for nesting
+            let savepoint_name = format!("sp_{}", tx_id.replace("-", ""));
+            self.conn.execute(&format!("SAVEPOINT {}", savepoint_name), [])?;
+            Some(savepoint_name)
+        };
+
+        self.transactions.push(Transaction {
+            id: tx_id.clone(),
+            is_write,
+            commit_pending: false,
+            savepoint_name,
+            owner: owner.map(str::to_string),
+            savepoints: Vec::new(),
+            permit: None,
+        });
+        Ok(tx_id)
+    }
+
+    /// Commit a transaction or release savepoint.
+    ///
+    /// If transactions nested inside it are still active, the commit is
+    /// deferred until they have all been committed or rolled back.
+    pub fn commit_transaction(&mut self, tx_id: &str) -> Result<()> {
+        let index = self.find_transaction(tx_id)?;
+        self.transactions[index].commit_pending = true;
+        self.apply_pending_commits()?;
+        self.analyze_if_due();
+        Ok(())
+    }
+
+    /// Rollback a transaction or savepoint.
+    ///
+    /// Fails if transactions nested inside it are still active; they must be
+    /// resolved first.
+    pub fn rollback_transaction(&mut self, tx_id: &str) -> Result<()> {
+        let index = self.find_transaction(tx_id)?;
+        if let Some(nested) = self.transactions.get(index + 1) {
+            return Err(Error::TransactionOrder(format!(
+                "cannot roll back {} while nested transaction {} is active",
+                tx_id, nested.id
+            )));
+        }
+
+        self.rollback_top()?;
+
+        // An enclosing transaction may have been waiting on this one to commit
+        self.apply_pending_commits()
+    }
+
+    /// Hold a write queue permit until the transaction commits or rolls back
+    pub fn attach_write_permit(&mut self, tx_id: &str, permit: WritePermit) -> Result<()> {
+        let index = self.find_transaction(tx_id)?;
+        self.transactions[index].permit = Some(permit);
+        Ok(())
+    }
+
+    /// A permit nested under the one held by the transaction or an
+    /// enclosing one, for a write run inside it, or `None` if none of them
+    /// holds one (e.g. a read transaction)
+    pub fn reenter_write_permit(&self, tx_id: &str) -> Result<Option<WritePermit>> {
+        let index = self.find_transaction(tx_id)?;
+        Ok(self.transactions[..=index]
+            .iter()
+            .rev()
+            .find_map(|tx| tx.permit.as_ref())
+            .map(WritePermit::reenter))
+    }
+
+    /// Create a named savepoint inside a transaction.
+    ///
+    /// The transaction must be the innermost one still active. Names are
+    /// scoped to the transaction and must be unique within it.
+    pub fn create_savepoint(&mut self, tx_id: &str, name: &str) -> Result<()> {
+        validate_savepoint_name(name)?;
+        let index = self.find_innermost_transaction(tx_id)?;
+        if self.transactions[index].savepoints.iter().any(|sp| sp == name) {
+            return Err(Error::InvalidParameter(format!(
+                "savepoint {} already exists in transaction {}",
+                name, tx_id
+            )));
+        }
+
+        let sql_name = savepoint_sql_name(&self.transactions[index], name);
+        self.conn.execute(&format!("SAVEPOINT {}", sql_name), [])?;
+        self.transactions[index].savepoints.push(name.to_string());
+        Ok(())
+    }
+
+    /// Release a named savepoint, keeping its changes in the transaction.
+    ///
+    /// Savepoints created after it are released as well.
+    pub fn release_savepoint(&mut self, tx_id: &str, name: &str) -> Result<()> {
+        let (index, position) = self.find_savepoint(tx_id, name)?;
+        let sql_name = savepoint_sql_name(&self.transactions[index], name);
+        self.conn.execute(&format!("RELEASE SAVEPOINT {}", sql_name), [])?;
+        self.transactions[index].savepoints.truncate(position);
+        Ok(())
+    }
+
+    /// Undo changes made since a named savepoint was created.
+    ///
+    /// The savepoint itself stays in place and can be rolled back to again;
+    /// savepoints created after it are discarded.
+    pub fn rollback_to_savepoint(&mut self, tx_id: &str, name: &str) -> Result<()> {
+        let (index, position) = self.find_savepoint(tx_id, name)?;
+        let sql_name = savepoint_sql_name(&self.transactions[index], name);
+        self.conn.execute(&format!("ROLLBACK TO SAVEPOINT {}", sql_name), [])?;
+        self.transactions[index].savepoints.truncate(position + 1);
+        Ok(())
+    }
+
+    /// Find a transaction that can take named savepoints: it must be active
+    /// and have nothing nested inside it
+    fn find_innermost_transaction(&self, tx_id: &str) -> Result<usize> {
+        let index = self.find_transaction(tx_id)?;
+        if let Some(nested) = self.transactions.get(index + 1) {
+            return Err(Error::TransactionOrder(format!(
+                "transaction {} has active nested transaction {}",
+                tx_id, nested.id
+            )));
+        }
+        Ok(index)
+    }
+
+    /// Find a named savepoint, returning the transaction index and its
+    /// position in that transaction's savepoints
+    fn find_savepoint(&self, tx_id: &str, name: &str) -> Result<(usize, usize)> {
+        let index = self.find_innermost_transaction(tx_id)?;
+        let position = self.transactions[index]
+            .savepoints
+            .iter()
+            .position(|sp| sp == name)
+            .ok_or_else(|| Error::SavepointNotFound(name.to_string()))?;
+        Ok((index, position))
+    }
+
+    /// Check that `caller` may commit or roll back a transaction.
+    ///
+    /// Transactions begun without an owner can be resolved by anyone.
+    pub fn check_transaction_owner(&self, tx_id: &str, caller: &str) -> Result<()> {
+        let tx = self
+            .transactions
+            .iter()
+            .find(|tx| tx.id == tx_id)
+            .ok_or_else(|| Error::TransactionNotFound(tx_id.to_string()))?;
+        match &tx.owner {
+            Some(owner) if owner != caller => Err(Error::TransactionOwner(format!(
+                "{} belongs to {}, not {}",
+                tx_id, owner, caller
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Roll back every open transaction begun by `owner`.
+    ///
+    /// Transactions nested inside the outermost owned one are rolled back as
+    /// well, whoever owns them. Returns the number of transactions rolled back.
+    pub fn rollback_owned_transactions(&mut self, owner: &str) -> Result<usize> {
+        let Some(index) = self
+            .transactions
+            .iter()
+            .position(|tx| tx.owner.as_deref() == Some(owner))
+        else {
+            return Ok(0);
+        };
+
+        let count = self.transactions.len() - index;
+        while self.transactions.len() > index {
+            self.rollback_top()?;
+        }
+        self.apply_pending_commits()?;
+        Ok(count)
+    }
+
+    /// Roll back the innermost transaction and remove it from the stack
+    fn rollback_top(&mut self) -> Result<()> {
+        let Some(top) = self.transactions.last() else {
+            return Ok(());
+        };
+        match &top.savepoint_name {
+            Some(savepoint_name) => {
+                self.conn.execute(&format!("ROLLBACK TO SAVEPOINT {}", savepoint_name), [])?;
+                self.conn.execute(&format!("RELEASE SAVEPOINT {}", savepoint_name), [])?;
+            }
+            // SQLite may already have rolled back on its own (e.g. SQLITE_FULL)
+            None if self.conn.is_autocommit() => {}
+            None => {
+                self.conn.execute("ROLLBACK", [])?;
+            }
+        }
+        self.transactions.pop();
+        Ok(())
+    }
+
+    /// Find an open transaction that hasn't been committed yet
+    fn find_transaction(&self, tx_id: &str) -> Result<usize> {
+        let index = self
+            .transactions
+            .iter()
+            .position(|tx| tx.id == tx_id)
+            .ok_or_else(|| Error::TransactionNotFound(tx_id.to_string()))?;
+        if self.transactions[index].commit_pending {
+            return Err(Error::TransactionCompleted(tx_id.to_string()));
+        }
+        Ok(index)
+    }
+
+    /// Commit transactions from the top of the stack down, in order, for as
+    /// long as they have a pending commit
+    fn apply_pending_commits(&mut self) -> Result<()> {
+        while let Some(top) = self.transactions.last() {
+            if !top.commit_pending {
+                break;
+            }
+
+            let sql = match &top.savepoint_name {
+                Some(savepoint_name) => format!("RELEASE SAVEPOINT {}", savepoint_name),
+                None => "COMMIT".to_string(),
+            };
+            if let Err(e) = self.conn.execute(&sql, []) {
+                // Leave the transaction open so the caller can retry or roll back
+                if let Some(top) = self.transactions.last_mut() {
+                    top.commit_pending = false;
+                }
+                return Err(e.into());
+            }
+            self.transactions.pop();
+        }
+        Ok(())
+    }
+
+    /// Whether a transaction begun through this connection is still open
+    pub(crate) fn in_transaction(&self) -> bool {
+        !self.transactions.is_empty() && !self.conn.is_autocommit()
+    }
+
+    /// Get the database file path
+    pub fn path(&self) -> &PathBuf {
+        &self.db_path
+    }
+
+    /// Subscribe to committed row changes on the given tables (all tables if empty)
+    pub fn subscribe_changes(&self, tables: &[&str]) -> broadcast::Receiver<ChangeEvent> {
+        self.changes.subscribe(tables)
+    }
+
+    /// Check if PowerSync extension is loaded
+    pub fn is_powersync_loaded(&self) -> bool {
+        self.powersync_loaded
+    }
+
+    /// Whether the PowerSync extension is loaded, its version, and why
+    /// loading it failed if it isn't
+    pub fn powersync_status(&self) -> PowerSyncStatus {
+        PowerSyncStatus {
+            loaded: self.powersync_loaded,
+            version: self.get_powersync_version().ok(),
+            load_error: self.load_error.clone(),
+        }
+    }
+
+    /// Path, extension state and extension version of this connection
+    pub fn info(&self) -> ConnectionInfo {
+        ConnectionInfo {
+            path: self.db_path.clone(),
+            powersync_loaded: self.powersync_loaded,
+            powersync_version: self.get_powersync_version().ok(),
+        }
+    }
+
+    /// What `open` reports: the connection info, whether the file is new
+    /// and whether it replaced a damaged one
+    pub fn open_result(&self) -> OpenResult {
+        OpenResult {
+            info: self.info(),
+            created: self.created,
+            recovery: self.recovery.clone(),
+        }
+    }
+
+    /// How results represent non-finite REAL values
+    pub fn set_non_finite_reals(&mut self, non_finite_reals: NonFiniteReals) {
+        self.value_format.non_finite_reals = non_finite_reals;
+    }
+
+    /// How results represent TEXT values that aren't valid UTF-8
+    pub fn set_invalid_utf8(&mut self, invalid_utf8: InvalidUtf8) {
+        self.value_format.invalid_utf8 = invalid_utf8;
+    }
+
+    /// Whether text parameters bound to INTEGER or REAL columns are
+    /// converted or rejected. See [`ParamCoercion`].
+    pub fn set_param_coercion(&mut self, param_coercion: ParamCoercion) {
+        self.param_coercion = param_coercion;
+    }
+
+    /// Where [`insert_with_id`](Self::insert_with_id) gets ids from
+    pub fn set_id_generator(&mut self, id_generator: IdGenerator) {
+        self.id_generator = id_generator;
+    }
+
+    /// A new id for a row of `table`, as
+    /// [`insert_with_id`](Self::insert_with_id) would generate it
+    pub fn generate_id(&self, table: &str) -> String {
+        self.id_generator.generate(table)
+    }
+
+    /// Simulate latency and failures on this connection's statements and
+    /// sync. See [`FaultInjection`]; for development only.
+    pub fn set_fault_injection(&mut self, faults: FaultInjection) {
+        if faults.is_enabled() {
+            log::warn!("Fault injection enabled on {:?}: {:?}", self.db_path, faults);
+        }
+        self.faults = faults;
+    }
+
+    /// Columns `sql` binds its parameters to, when coercion is on and they are known
+    fn bound_columns(&self, sql: &str) -> Result<Option<BoundColumns>> {
+        if self.param_coercion == ParamCoercion::Off {
+            return Ok(None);
+        }
+        BoundColumns::for_insert(&self.conn, sql)
+    }
+
+    /// Apply performance pragmas, e.g. a [`TuningPreset`] chosen when the
+    /// database is opened. Fails inside a transaction.
+    ///
+    /// [`TuningPreset`]: crate::tuning::TuningPreset
+    pub fn apply_tuning(&self, tuning: &ConnectionTuning) -> Result<()> {
+        tuning.apply(&self.conn)?;
+        Ok(())
+    }
+
+    /// Refresh planner statistics automatically once `after_changes` rows
+    /// have been inserted, updated or deleted (including by sync) since the
+    /// last refresh. `None` turns this off.
+    pub fn set_auto_analyze(&mut self, after_changes: Option<u64>) {
+        self.auto_analyze = after_changes;
+        self.changes_at_analyze = total_changes(&self.conn);
+    }
+
+    /// Gather planner statistics for one table, or the whole database
+    pub fn analyze(&mut self, table: Option<&str>) -> Result<()> {
+        match table {
+            Some(table) => {
+                self.conn.execute_batch(&format!("ANALYZE {}", quote_identifier(table)))?;
+            }
+            None => {
+                self.conn.execute_batch("ANALYZE")?;
+                self.changes_at_analyze = total_changes(&self.conn);
+            }
+        }
+        Ok(())
+    }
+
+    /// Run `PRAGMA optimize` if enough rows changed since the last refresh.
+    ///
+    /// Waits until no transaction is open. Analysis is limited per index to
+    /// keep this quick on large tables, and failures are only logged so they
+    /// don't fail the write that triggered them.
+    fn analyze_if_due(&mut self) {
+        let Some(after_changes) = self.auto_analyze else {
+            return;
+        };
+        let changes = total_changes(&self.conn);
+        if !self.conn.is_autocommit() || changes - self.changes_at_analyze < after_changes {
+            return;
+        }
+        self.changes_at_analyze = changes;
+        let optimized = self.conn.execute_batch(
+            "PRAGMA analysis_limit = 1000; PRAGMA optimize = 0x10002; PRAGMA analysis_limit = 0;",
+        );
+        if let Err(e) = optimized {
+            log::warn!("Automatic PRAGMA optimize failed: {}", e);
+        }
+    }
+
+    /// Memory use and page cache counters of this connection
+    pub fn stats(&self) -> Result<ConnectionStats> {
+        Ok(ConnectionStats {
+            cache_used: db_status(&self.conn, ffi::SQLITE_DBSTATUS_CACHE_USED)?,
+            schema_used: db_status(&self.conn, ffi::SQLITE_DBSTATUS_SCHEMA_USED)?,
+            statements_used: db_status(&self.conn, ffi::SQLITE_DBSTATUS_STMT_USED)?,
+            cache_hits: db_status(&self.conn, ffi::SQLITE_DBSTATUS_CACHE_HIT)?,
+            cache_misses: db_status(&self.conn, ffi::SQLITE_DBSTATUS_CACHE_MISS)?,
+            cache_writes: db_status(&self.conn, ffi::SQLITE_DBSTATUS_CACHE_WRITE)?,
+            prepared_statements: prepared_statement_count(&self.conn),
+        })
+    }
+
+    /// Up to `max_errors` problems `PRAGMA integrity_check` finds, none if
+    /// the database is intact. Reads every page, so it is slow on large
+    /// databases.
+    pub fn integrity_check(&self, max_errors: u32) -> Result<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!("PRAGMA integrity_check({})", max_errors))?;
+        let problems = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(problems.into_iter().filter(|p| p != "ok").collect())
+    }
+
+    /// Whether diagnostics are collected for databases that never recorded
+    /// a choice with [`Self::set_diagnostics_enabled`]
+    pub fn set_diagnostics_default(&mut self, enabled: bool) {
+        self.diagnostics_default = enabled;
+    }
+
+    /// Whether diagnostics (support bundle metrics, events and failing
+    /// uploads) may be collected for this database
+    pub fn diagnostics_enabled(&self) -> Result<bool> {
+        Ok(diagnostics::recorded(&self.conn)?.unwrap_or(self.diagnostics_default))
+    }
+
+    /// Record whether diagnostics may be collected for this database, e.g.
+    /// once the user answered a consent prompt. Kept in the database, so it
+    /// outlives the connection.
+    pub fn set_diagnostics_enabled(&mut self, enabled: bool) -> Result<()> {
+        diagnostics::record(&self.conn, enabled)
+    }
+
+    /// Get the PowerSync extension version
+    pub fn get_powersync_version(&self) -> Result<String> {
+        if !self.powersync_loaded {
+            return Err(Error::PowerSyncNotInitialized);
+        }
+        extension::get_powersync_version(&self.conn)
+    }
+
+    /// Get the client ID the sync protocol identifies this database by
+    pub fn get_client_id(&self) -> Result<String> {
+        if !self.powersync_loaded {
+            return Err(Error::PowerSyncNotInitialized);
+        }
+        let client_id: String = self.conn.query_row(
+            "SELECT powersync_client_id()",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(client_id)
+    }
+
+    /// Replace the PowerSync schema, unless it is the schema applied last.
+    ///
+    /// Returns the change, also sent to [`subscribe_schema_changes`]
+    /// subscribers, or `None` if the schema was already applied.
+    ///
+    /// [`subscribe_schema_changes`]: Self::subscribe_schema_changes
+    pub fn replace_schema(&mut self, schema_json: &str) -> Result<Option<SchemaChange>> {
+        if !self.powersync_loaded {
+            return Err(Error::PowerSyncNotInitialized);
+        }
+        let hash = schema::hash(schema_json)?;
+        if schema::applied(&self.conn)?.is_some_and(|applied| applied.hash == hash) {
+            return Ok(None);
+        }
+        let views = schema::views(&self.conn)?;
+        self.conn.query_row(
+            "SELECT powersync_replace_schema(?)",
+            [schema_json],
+            |_| Ok(()),
+        )?;
+        // Only a schema the extension accepted is worth re-applying later
+        self.last_schema = Some(schema_json.to_string());
+        let version = schema::record(&self.conn, schema_json, &hash)?;
+        let change = schema::change(version, hash, &views, &schema::views(&self.conn)?);
+        if let Err(e) = soft_delete::reapply(&self.conn) {
+            log::warn!("Failed to filter soft-deleted rows out of the views: {}", e);
+        }
+        // Dropping the views dropped the undo triggers on them
+        if !self.undo_tables.is_empty() {
+            if let Err(e) = undo::reinstall(&self.conn, &self.undo_tables) {
+                log::warn!("Failed to track {:?} for undo: {}", self.undo_tables, e);
+            }
+        }
+        self.changes.notify_schema_change(change.clone());
+        Ok(Some(change))
+    }
+
+    /// Apply the last known schema again if the database doesn't record it
+    /// as applied, so it is usable without the frontend replaying its setup
+    fn reapply_schema(&mut self) -> Result<()> {
+        let Some(schema_json) = self.last_schema.clone() else {
+            return Ok(());
+        };
+        if !self.powersync_loaded {
+            return Ok(());
+        }
+        if let Some(change) = self.replace_schema(&schema_json)? {
+            log::info!(
+                "Re-applied schema version {} to {:?}",
+                change.version,
+                self.db_path
+            );
+        }
+        Ok(())
+    }
+
+    /// Subscribe to schema changes applied by [`replace_schema`](Self::replace_schema)
+    pub fn subscribe_schema_changes(&self) -> broadcast::Receiver<SchemaChange> {
+        self.changes.subscribe_schema_changes()
+    }
+
+    /// Report schema tables and columns that no data query in `sync_rules`
+    /// (the sync rules YAML) will ever fill. See [`sync_rules::lint`].
+    pub fn lint_sync_rules(&self, sync_rules: &str) -> Result<Vec<SyncRulesIssue>> {
+        sync_rules::lint(&self.conn, sync_rules)
+    }
+
+    /// Pick up schema changes without reopening the database.
+    ///
+    /// Re-runs PowerSync initialization (when the extension is loaded), drops
+    /// cached prepared statements and reloads SQLite's schema cache, so views
+    /// regenerated by `replace_schema` or tables created externally are seen.
+    /// The last applied schema is applied again if the database lost it.
+    pub fn refresh_schema(&mut self) -> Result<()> {
+        if self.powersync_loaded {
+            extension::init_powersync(&self.conn)?;
+            self.reapply_schema()?;
+        }
+        self.conn.flush_prepared_statement_cache();
+        self.conn
+            .query_row("SELECT COUNT(*) FROM sqlite_master", [], |_| Ok(()))?;
+        Ok(())
+    }
+
+    /// Execute a PowerSync control operation.
+    ///
+    /// Calls failing because the database is momentarily locked or the call
+    /// was interrupted are retried a few times before the error is returned.
+    /// Callers that can release the connection while backing off should use
+    /// [`control::with_retries`] around [`Self::powersync_control_once`]
+    /// instead.
+    pub fn powersync_control(&self, op: &ControlOp) -> Result<String> {
+        control::with_retries_blocking(op.name(), || self.powersync_control_once(op))
+    }
+
+    /// Execute a PowerSync control operation once, without retrying
+    /// transient failures
+    pub fn powersync_control_once(&self, op: &ControlOp) -> Result<String> {
+        if !self.powersync_loaded {
+            return Err(Error::PowerSyncNotInitialized);
+        }
+        let payload = op.payload()?;
+        self.faults.before_control(op.name())?;
+        self.changes.set_recording_sync(true);
+        let result = self.conn.query_row(
+            "SELECT powersync_control(?, ?)",
+            rusqlite::params![op.name(), payload],
+            |row| row.get::<_, String>(0),
+        );
+        self.changes.set_recording_sync(false);
+
+        let result = match result {
+            Ok(result) => result,
+            Err(e) => {
+                // Surface checkpoint problems as such rather than as a generic SQL error
+                return Err(match CheckpointFailure::from_message(&e.to_string()) {
+                    Some(failure) => {
+                        self.changes.fail_checkpoint(failure.clone());
+                        Error::CheckpointValidation(failure.message)
+                    }
+                    None => e.into(),
+                });
+            }
+        };
+
+        let instructions = control::parse_instructions(&result);
+        for failure in control::checkpoint_failures(&instructions) {
+            log::warn!("Checkpoint failed on {:?}: {}", self.db_path, failure.message);
+            self.changes.fail_checkpoint(failure);
+        }
+        if control::completes_sync(&instructions) {
+            self.changes.complete_checkpoint();
+        }
+        Ok(result)
+    }
+
+    /// Subscribe to the set of tables each commit changed, e.g. to decide
+    /// once per commit which watched queries need to re-run
+    pub fn subscribe_commits(&self) -> broadcast::Receiver<CommitEvent> {
+        self.changes.subscribe_commits()
+    }
+
+    /// Subscribe to sync checkpoints once they are fully applied locally
+    pub fn subscribe_checkpoints(&self) -> broadcast::Receiver<CheckpointEvent> {
+        self.changes.subscribe_checkpoints()
+    }
+
+    /// Subscribe to checkpoints that failed validation or couldn't be applied
+    pub fn subscribe_checkpoint_failures(&self) -> broadcast::Receiver<CheckpointFailure> {
+        self.changes.subscribe_checkpoint_failures()
+    }
+
+    /// Get a batch of pending CRUD entries
+    pub fn get_crud_batch(&self, limit: i64) -> Result<Vec<CrudEntry>> {
+        if !self.powersync_loaded {
+            return Err(Error::PowerSyncNotInitialized);
+        }
+        let mut stmt = self.conn.prepare(
+            "SELECT id, tx_id, data FROM ps_crud ORDER BY id LIMIT ?"
+        )?;
+        let entries = stmt
+            .query_map([limit], |row| {
+                Ok(CrudEntry {
+                    id: row.get(0)?,
+                    tx_id: row.get(1)?,
+                    data: row.get(2)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(entries)
+    }
+
+    /// Remove CRUD entries up to and including the given ID
+    pub fn remove_crud(&mut self, crud_id: i64) -> Result<()> {
+        if !self.powersync_loaded {
+            return Err(Error::PowerSyncNotInitialized);
+        }
+        self.conn.execute(
+            "DELETE FROM ps_crud WHERE id <= ?",
+            [crud_id],
+        )?;
+        uploads::clear_through(&self.conn, crud_id)
+    }
+
+    /// Record a failed attempt to upload CRUD entry `crud_id`
+    ///
+    /// Failures are counted per entry until [`remove_crud`] removes it, and
+    /// sent to [`subscribe_upload_errors`].
+    ///
+    /// [`remove_crud`]: Self::remove_crud
+    /// [`subscribe_upload_errors`]: Self::subscribe_upload_errors
+    pub fn report_upload_error(&mut self, crud_id: i64, error: &str) -> Result<UploadError> {
+        if !self.powersync_loaded {
+            return Err(Error::PowerSyncNotInitialized);
+        }
+        let upload_error = uploads::record(&self.conn, crud_id, error)?;
+        self.changes.notify_upload_error(upload_error.clone());
+        Ok(upload_error)
+    }
+
+    /// The queued entries up to and including `crud_id`, as a conflict the
+    /// backend rejected with `status` and `error`
+    pub fn upload_conflict(
+        &self,
+        crud_id: i64,
+        status: Option<i64>,
+        error: &str,
+    ) -> Result<UploadConflict> {
+        if !self.powersync_loaded {
+            return Err(Error::PowerSyncNotInitialized);
+        }
+        uploads::conflict(&self.conn, crud_id, status, error)
+    }
+
+    /// Settle an upload conflict, so the upload queue moves on
+    pub fn resolve_upload_conflict(
+        &mut self,
+        conflict: &UploadConflict,
+        resolution: &ConflictResolution,
+    ) -> Result<()> {
+        if !self.powersync_loaded {
+            return Err(Error::PowerSyncNotInitialized);
+        }
+        uploads::resolve(&mut self.conn, conflict, resolution)
+    }
+
+    /// Pending upload counts and the failures reported for pending entries
+    pub fn get_upload_queue_stats(&self) -> Result<UploadQueueStats> {
+        if !self.powersync_loaded {
+            return Err(Error::PowerSyncNotInitialized);
+        }
+        uploads::stats(&self.conn)
+    }
+
+    /// Subscribe to failed uploads reported with [`report_upload_error`]
+    ///
+    /// [`report_upload_error`]: Self::report_upload_error
+    pub fn subscribe_upload_errors(&self) -> broadcast::Receiver<UploadError> {
+        self.changes.subscribe_upload_errors()
+    }
+
+    /// Check if there are any CRUD entries pending
+    pub fn has_pending_crud(&self) -> Result<bool> {
+        if !self.powersync_loaded {
+            return Err(Error::PowerSyncNotInitialized);
+        }
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM ps_crud",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    /// Get the write checkpoint local changes are waiting on: the `target_op`
+    /// of the `$local` bucket, or None if no local changes await confirmation
+    pub fn get_write_checkpoint(&self) -> Result<Option<String>> {
+        if !self.powersync_loaded {
+            return Err(Error::PowerSyncNotInitialized);
+        }
+        let target_op: Option<i64> = self
+            .conn
+            .query_row(
+                "SELECT target_op FROM ps_buckets WHERE name = '$local'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(target_op.map(|op| op.to_string()))
+    }
+
+    /// Highest op id applied to the local tables, "0" before the first
+    /// checkpoint. See [`consistency::wait_for_checkpoint`](crate::consistency::wait_for_checkpoint).
+    pub fn checkpoint_token(&self) -> Result<String> {
+        if !self.powersync_loaded {
+            return Err(Error::PowerSyncNotInitialized);
+        }
+        let last_applied_op: i64 = self.conn.query_row(
+            "SELECT IFNULL(MAX(last_applied_op), 0) FROM ps_buckets",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(last_applied_op.to_string())
+    }
+
+    /// Checkpoint token, last sync time and write checkpoint in one go
+    pub fn sync_status(&self) -> Result<LocalSyncStatus> {
+        Ok(LocalSyncStatus {
+            checkpoint_token: self.checkpoint_token()?,
+            last_synced_at: self.get_last_synced_at()?,
+            write_checkpoint: self.get_write_checkpoint()?,
+        })
+    }
+
+    /// Rows of `ps_buckets`, for inspector panels
+    pub fn inspect_buckets(&self) -> Result<QueryResult> {
+        if !self.powersync_loaded {
+            return Err(Error::PowerSyncNotInitialized);
+        }
+        self.get_all("SELECT * FROM ps_buckets ORDER BY name", &[])
+    }
+
+    /// Operation counts in `ps_oplog` per bucket and row type
+    pub fn inspect_oplog(&self) -> Result<Vec<OplogSummary>> {
+        if !self.powersync_loaded {
+            return Err(Error::PowerSyncNotInitialized);
+        }
+        devtools::oplog_summary(&self.conn)
+    }
+
+    /// The first `limit` pending uploads, with their values redacted
+    pub fn inspect_crud(&self, limit: i64) -> Result<Vec<RedactedCrudEntry>> {
+        if !self.powersync_loaded {
+            return Err(Error::PowerSyncNotInitialized);
+        }
+        devtools::pending_crud(&self.conn, limit)
+    }
+
+    /// The applied schema, read back from the views PowerSync created
+    pub fn inspect_schema(&self) -> Result<Vec<SchemaTable>> {
+        devtools::applied_schema(&self.conn)
+    }
+
+    /// Copy the database to a temporary file with the backup API, returning
+    /// an id to [`restore`](Self::restore) it by.
+    ///
+    /// For resetting state between test scenarios. Snapshot files are
+    /// deleted when the connection is dropped.
+    pub fn snapshot(&mut self) -> Result<String> {
+        let id = Uuid::new_v4().simple().to_string();
+        let path = std::env::temp_dir().join(format!("powersync-snapshot-{}.db", id));
+        self.conn.backup(DatabaseName::Main, &path, None)?;
+        self.snapshots.insert(id.clone(), path);
+        Ok(id)
+    }
+
+    /// Replace the database contents with a snapshot taken on this connection.
+    ///
+    /// Reported to commit subscribers as a change to every user table, so
+    /// watches re-run against the restored data. If the snapshot predates the
+    /// last applied schema, that schema is applied again.
+    pub fn restore(&mut self, snapshot_id: &str) -> Result<()> {
+        let path = self
+            .snapshots
+            .get(snapshot_id)
+            .ok_or_else(|| Error::InvalidParameter(format!("unknown snapshot {}", snapshot_id)))?
+            .clone();
+        self.restore_file(&path)
+    }
+
+    /// Write a compressed copy of the database to `dest`, encrypted with a
+    /// key derived from `passphrase`. See [`bundle`].
+    ///
+    /// The copy is taken in memory, so the plaintext never reaches the disk.
+    pub fn export_encrypted(&self, dest: &Path, passphrase: &str) -> Result<EncryptedExport> {
+        let image = self.conn.serialize(DatabaseName::Main)?;
+        bundle::write(&image, dest, passphrase)
+    }
+
+    /// Replace the database contents with those of a bundle written by
+    /// [`export_encrypted`](Self::export_encrypted), as
+    /// [`restore`](Self::restore) does for snapshots
+    pub fn import_encrypted(&mut self, src: &Path, passphrase: &str) -> Result<()> {
+        let imported = deserialize(bundle::read(src, passphrase)?)?;
+        self.replace_contents(|conn| {
+            Backup::new(&imported, conn)?.run_to_completion(100, Duration::from_millis(100), None)
+        })
+    }
+
+    fn restore_file(&mut self, path: &Path) -> Result<()> {
+        self.replace_contents(|conn| conn.restore(DatabaseName::Main, path, None::<fn(Progress)>))
+    }
+
+    /// Replace the database contents using `copy`, then bring the schema
+    /// and subscribers up to date
+    fn replace_contents(
+        &mut self,
+        copy: impl FnOnce(&mut Connection) -> rusqlite::Result<()>,
+    ) -> Result<()> {
+        if !self.transactions.is_empty() || !self.conn.is_autocommit() {
+            return Err(Error::TransactionOrder(
+                "cannot replace the database contents inside a transaction".to_string(),
+            ));
+        }
+
+        let mut tables = self.user_tables()?;
+        copy(&mut self.conn)?;
+        // The snapshot may predate the current schema
+        self.reapply_schema()?;
+        tables.extend(self.user_tables()?);
+        self.changes.notify_commit(CommitEvent {
+            tables,
+            correlation_id: correlation::current(),
+        });
+        Ok(())
+    }
+
+    /// Names of the tables and views outside PowerSync's and SQLite's own
+    fn user_tables(&self) -> Result<BTreeSet<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT name FROM sqlite_master WHERE type IN ('table', 'view')
+             AND substr(name, 1, 3) != 'ps_' AND substr(name, 1, 7) != 'sqlite_'",
+        )?;
+        let tables = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(tables)
+    }
+
+    /// Load fixture data in one transaction, returning the number of rows
+    /// inserted from tables.
+    ///
+    /// Writes go through the schema views like any other, so rows seeded
+    /// into synced tables are queued for upload.
+    pub fn seed(&mut self, data: &SeedData) -> Result<usize> {
+        let tx_id = self.begin_transaction(true)?;
+        match devtools::seed(&self.conn, data) {
+            Ok(inserted) => {
+                self.commit_transaction(&tx_id)?;
+                self.analyze_if_due();
+                Ok(inserted)
+            }
+            Err(e) => {
+                let _ = self.rollback_transaction(&tx_id);
+                Err(e)
+            }
+        }
+    }
+
+    /// Copy the rows of local-only table `from` into synced table `to`, in
+    /// one transaction, e.g. once a user who started out anonymously signs
+    /// in. The rows go through `to`'s view, so each is queued for upload.
+    ///
+    /// Columns are matched by name; those `to` doesn't have are left behind.
+    /// With `delete_source`, `from` is emptied in the same transaction.
+    /// Returns the number of rows copied.
+    pub fn migrate_local_table(
+        &mut self,
+        from: &str,
+        to: &str,
+        delete_source: bool,
+    ) -> Result<usize> {
+        if !self.powersync_loaded {
+            return Err(Error::PowerSyncNotInitialized);
+        }
+        let backed_by = |prefix: &str, table: &str| -> Result<bool> {
+            let exists = self.conn.query_row(
+                "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?)",
+                [format!("{}{}", prefix, table)],
+                |row| row.get(0),
+            )?;
+            Ok(exists)
+        };
+        if !backed_by("ps_data_local__", from)? {
+            return Err(Error::InvalidParameter(format!(
+                "{} is not a local-only table",
+                from
+            )));
+        }
+        if !backed_by("ps_data__", to)? {
+            return Err(Error::InvalidParameter(format!("{} is not a synced table", to)));
+        }
+
+        let tx_id = self.begin_transaction(true)?;
+        match copy_rows(&self.conn, from, to, delete_source) {
+            Ok(copied) => {
+                self.commit_transaction(&tx_id)?;
+                Ok(copied)
+            }
+            Err(e) => {
+                let _ = self.rollback_transaction(&tx_id);
+                Err(e)
+            }
+        }
+    }
+
+    /// Re-home the rows created before the user signed in, as `strategy`
+    /// describes, in one transaction. See [`reconcile`].
+    pub fn migrate_local_data(
+        &mut self,
+        strategy: &LocalDataStrategy,
+    ) -> Result<LocalDataMigration> {
+        let tx_id = self.begin_transaction(true)?;
+        match reconcile::migrate(&self.conn, strategy) {
+            Ok(migration) => {
+                self.commit_transaction(&tx_id)?;
+                Ok(migration)
+            }
+            Err(e) => {
+                let _ = self.rollback_transaction(&tx_id);
+                Err(e)
+            }
+        }
+    }
+
+    /// Time the last sync completed, in milliseconds since the Unix epoch
+    pub fn get_last_synced_at(&self) -> Result<Option<i64>> {
+        if !self.powersync_loaded {
+            return Err(Error::PowerSyncNotInitialized);
+        }
+        // The extension reports a UTC "YYYY-MM-DD HH:MM:SS" string
+        let millis: Option<i64> = self.conn.query_row(
+            "SELECT unixepoch(powersync_last_synced_at()) * 1000",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(millis)
+    }
+
+    /// Whether a full sync has completed since the database was created
+    pub fn has_synced(&self) -> Result<bool> {
+        Ok(self.get_last_synced_at()?.is_some())
+    }
+}
+
+impl Drop for PowerSyncConnection {
+    fn drop(&mut self) {
+        for path in self.snapshots.values() {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// A CRUD entry from ps_crud table
+#[derive(Debug, Clone, serde::Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct CrudEntry {
+    pub id: i64,
+    #[serde(rename = "txId")]
+    pub tx_id: Option<i64>,
+    pub data: String,
+}
+
+/// An entry of the `_changes` feed
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct ChangeFeedEntry {
+    /// Increasing position in the feed; acknowledge up to it when consumed
+    pub seq: i64,
+    pub table: String,
+    /// Primary key (`id`, or rowid for tables without one) as text
+    pub pk: String,
+    /// `INSERT`, `UPDATE` or `DELETE`
+    pub op: String,
+}
+
+/// Copy the rows of `from` into `to` through its view, by matching column
+/// names, then optionally delete them from `from`
+fn copy_rows(conn: &Connection, from: &str, to: &str, delete_source: bool) -> Result<usize> {
+    let mut stmt = conn.prepare(
+        "SELECT name FROM pragma_table_info(?1)
+         WHERE name IN (SELECT name FROM pragma_table_info(?2))",
+    )?;
+    let columns = stmt
+        .query_map([to, from], |row| row.get::<_, String>(0))?
+        .map(|name| name.map(|name| quote_identifier(&name)))
+        .collect::<rusqlite::Result<Vec<_>>>()?
+        .join(", ");
+    let from = quote_identifier(from);
+    // Inserts into a view count no changes, so count the rows up front
+    let copied: i64 = conn.query_row(&format!("SELECT count(*) FROM {}", from), [], |row| {
+        row.get(0)
+    })?;
+    conn.execute(
+        &format!(
+            "INSERT INTO {} ({columns}) SELECT {columns} FROM {}",
+            quote_identifier(to),
+            from
+        ),
+        [],
+    )?;
+    if delete_source {
+        conn.execute(&format!("DELETE FROM {}", from), [])?;
+    }
+    Ok(copied as usize)
+}
+
+/// Quote a table or column name for interpolation into SQL
+pub(crate) fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Quote a string literal for interpolation into SQL
+pub(crate) fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Savepoint names are interpolated into SQL, so only identifier
+/// characters are accepted
+fn validate_savepoint_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(Error::InvalidParameter(format!(
+            "invalid savepoint name: {:?}",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// SQL name of a named savepoint, prefixed with its transaction so it can't
+/// clash with savepoints of other transactions
+fn savepoint_sql_name(tx: &Transaction, name: &str) -> String {
+    format!("user_{}_{}", tx.id.replace("-", ""), name)
+}
+
+/// Result of an execute operation
+#[derive(Debug, Clone, serde::Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct ExecuteResult {
+    pub changes: i64,
+    #[serde(rename = "lastInsertRowid")]
+    pub last_insert_rowid: i64,
+    /// For SELECT statements, the query results
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub columns: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "specta", specta(type = Option<Vec<SpectaRow>>))]
+    pub rows: Option<Vec<RowResult>>,
+}
+
+/// Result of one parameter set of a batch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct BatchEntry {
+    pub changes: i64,
+    pub last_insert_rowid: i64,
+}
+
+/// The parameter set that failed a batch
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct BatchFailure {
+    /// Position of the parameter set in the batch
+    pub index: usize,
+    pub error: String,
+}
+
+/// Result of a batch, with per parameter set details if requested
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct BatchResult {
+    /// Rows changed by the batch, 0 if it failed and was rolled back
+    pub changes: i64,
+    pub last_insert_rowid: i64,
+    /// Results of the parameter sets that ran, in order. Those before a
+    /// failure were rolled back with the rest of the batch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entries: Option<Vec<BatchEntry>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure: Option<BatchFailure>,
+}
+
+impl From<ExecuteResult> for BatchResult {
+    fn from(result: ExecuteResult) -> Self {
+        Self {
+            changes: result.changes,
+            last_insert_rowid: result.last_insert_rowid,
+            entries: None,
+            failure: None,
+        }
+    }
+}
+
+/// Progress of a batch executed in chunks, reported after each chunk commits
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct BatchProgress {
+    /// Parameter sets executed so far
+    pub processed: usize,
+    /// Parameter sets in the whole batch
+    pub total: usize,
+    /// Rows changed so far
+    pub changes: i64,
+}
+
+/// Result of a query operation
+#[derive(Debug, Clone, serde::Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    #[cfg_attr(feature = "specta", specta(type = Vec<SpectaRow>))]
+    pub rows: Vec<RowResult>,
+}
+
+/// A result row keyed by column name, in SELECT column order.
+///
+/// Keys are shared between all rows of a result, so large result sets don't
+/// allocate a fresh copy of every column name per row.
+pub type RowResult = IndexMap<Arc<str>, JsonValue>;
+
+/// How a [`RowResult`] is described to specta, which has no `Arc<str>` impl
+#[cfg(feature = "specta")]
+type SpectaRow = IndexMap<String, JsonValue>;
+
+/// Shape in which `execute`/`get_all` results are returned over IPC
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "lowercase")]
+pub enum ResultFormat {
+    /// `ExecuteResult` / `QueryResult` as defined by this plugin
+    #[default]
+    Native,
+    /// `QueryResult` as defined by `@powersync/common`
+    PowerSync,
+}
+
+impl ResultFormat {
+    /// Reshape a result according to this format
+    pub fn apply<T: Into<PowerSyncQueryResult>>(self, result: T) -> FormattedResult<T> {
+        match self {
+            ResultFormat::Native => FormattedResult::Native(result),
+            ResultFormat::PowerSync => FormattedResult::PowerSync(result.into()),
+        }
+    }
+}
+
+/// A result in either the native or the `@powersync/common` shape
+#[derive(Debug, Clone, serde::Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(untagged)]
+pub enum FormattedResult<T> {
+    Native(T),
+    PowerSync(PowerSyncQueryResult),
+}
+
+/// Result shaped like `@powersync/common`'s `QueryResult`.
+/// `rows.item()` can't cross IPC, so the client attaches it.
+#[derive(Debug, Clone, serde::Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct PowerSyncQueryResult {
+    #[serde(rename = "insertId", skip_serializing_if = "Option::is_none")]
+    pub insert_id: Option<i64>,
+    #[serde(rename = "rowsAffected")]
+    pub rows_affected: i64,
+    pub rows: PowerSyncRows,
+}
+
+/// Row container of a `PowerSyncQueryResult`
+#[derive(Debug, Clone, serde::Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct PowerSyncRows {
+    #[serde(rename = "_array")]
+    #[cfg_attr(feature = "specta", specta(type = Vec<SpectaRow>))]
+    pub array: Vec<RowResult>,
+    pub length: usize,
+}
+
+impl From<Vec<RowResult>> for PowerSyncRows {
+    fn from(array: Vec<RowResult>) -> Self {
+        let length = array.len();
+        Self { array, length }
+    }
+}
+
+impl From<ExecuteResult> for PowerSyncQueryResult {
+    fn from(result: ExecuteResult) -> Self {
+        Self {
+            insert_id: Some(result.last_insert_rowid),
+            rows_affected: result.changes,
+            rows: result.rows.unwrap_or_default().into(),
+        }
+    }
+}
+
+impl From<QueryResult> for PowerSyncQueryResult {
+    fn from(result: QueryResult) -> Self {
+        Self {
+            insert_id: None,
+            rows_affected: 0,
+            rows: result.rows.into(),
+        }
+    }
+}
+
+/// Connection counters from `sqlite3_db_status`, for spotting performance
+/// regressions in the field. Memory figures are in bytes.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionStats {
+    /// Heap used by the page cache
+    pub cache_used: i64,
+    /// Heap used by the parsed schema
+    pub schema_used: i64,
+    /// Heap used by prepared statements
+    pub statements_used: i64,
+    pub cache_hits: i64,
+    pub cache_misses: i64,
+    /// Dirty pages written out of the cache
+    pub cache_writes: i64,
+    /// Prepared statements alive on the connection, including cached ones
+    pub prepared_statements: i64,
+}
+
+/// What an open database is backed by
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionInfo {
+    pub path: PathBuf,
+    pub powersync_loaded: bool,
+    /// Version of the loaded PowerSync extension
+    pub powersync_version: Option<String>,
+}
+
+/// State of the PowerSync extension on a connection
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct PowerSyncStatus {
+    pub loaded: bool,
+    /// Version of the loaded extension
+    pub version: Option<String>,
+    /// Why the extension couldn't be loaded when opening the database
+    pub load_error: Option<String>,
+}
+
+/// Outcome of opening a database
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct OpenResult {
+    #[serde(flatten)]
+    pub info: ConnectionInfo,
+    /// Whether the database file didn't exist before it was opened, e.g. to
+    /// seed a fresh database. Stays the same for repeated opens.
+    pub created: bool,
+    /// The damaged database moved aside to open this one, if it was
+    /// recovered. See [`recovery`].
+    pub recovery: Option<DatabaseRecovery>,
+}
+
+/// Everything needed to open connections, detached from the manager so a
+/// slow open (extension loading, WAL recovery) doesn't hold it
+#[derive(Clone)]
+pub struct ConnectionOpener {
+    app_data_dir: PathBuf,
+    resource_dir: Option<PathBuf>,
+    initializer: Option<ConnectionInitializer>,
+    auto_analyze: Option<u64>,
+    non_finite_reals: NonFiniteReals,
+    invalid_utf8: InvalidUtf8,
+    param_coercion: ParamCoercion,
+    id_generator: IdGenerator,
+    faults: FaultInjection,
+    recover_corrupt: bool,
+    diagnostics: bool,
+}
+
+impl ConnectionOpener {
+    /// Open a connection to database `name`, without registering it.
+    ///
+    /// If the database file is damaged, it is [recovered](Self::recover)
+    /// when corruption recovery is on, and fails with
+    /// [`Error::CorruptDatabase`] otherwise.
+    pub fn open(&self, name: &str) -> Result<PowerSyncConnection> {
+        match self.open_file(name) {
+            Err(e) if recovery::is_corruption(&e) => {
+                if self.recover_corrupt {
+                    self.recover(name, Some(e.to_string()))
+                } else {
+                    Err(Error::CorruptDatabase(format!("{}: {}", name, e)))
+                }
+            }
+            result => result,
+        }
+    }
+
+    /// Move database `name`'s files aside and open a fresh database in
+    /// their place, applying the schema of the old one if it can be read.
+    /// `error` is why, for the [`DatabaseRecovery`] reported by
+    /// [`PowerSyncConnection::open_result`]. The database must not be open.
+    pub fn recover(&self, name: &str, error: Option<String>) -> Result<PowerSyncConnection> {
+        let db_path = self.app_data_dir.join(format!("{}.db", name));
+        let schema_json = recovery::salvage_schema(&db_path);
+        let backup_path = recovery::quarantine(&db_path)?;
+        log::warn!(
+            "Moved database {} aside to {:?} and opened a fresh one ({})",
+            name,
+            backup_path,
+            error.as_deref().unwrap_or("requested")
+        );
+        let mut conn = self.open_file(name)?;
+        let schema_restored = match schema_json {
+            Some(schema_json) if conn.is_powersync_loaded() => {
+                match conn.replace_schema(&schema_json) {
+                    Ok(_) => true,
+                    Err(e) => {
+                        log::warn!("Failed to restore the schema of {}: {}", name, e);
+                        false
+                    }
+                }
+            }
+            _ => false,
+        };
+        conn.recovery = Some(DatabaseRecovery {
+            error,
+            backup_path,
+            schema_restored,
+        });
+        Ok(conn)
+    }
+
+    fn open_file(&self, name: &str) -> Result<PowerSyncConnection> {
+        let mut conn = PowerSyncConnection::open(
+            name,
+            &self.app_data_dir,
+            self.resource_dir.as_ref(),
+            self.initializer.as_ref(),
+        )?;
+        conn.set_auto_analyze(self.auto_analyze);
+        conn.set_non_finite_reals(self.non_finite_reals);
+        conn.set_invalid_utf8(self.invalid_utf8);
+        conn.set_param_coercion(self.param_coercion);
+        conn.set_id_generator(self.id_generator.clone());
+        conn.set_fault_injection(self.faults);
+        conn.set_diagnostics_default(self.diagnostics);
+        Ok(conn)
+    }
+}
+
+/// Whether a database was opened or closed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "lowercase")]
+pub enum LifecycleKind {
+    Opened,
+    Closed,
+}
+
+/// A database opened or closed by a [`DatabaseManager`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct DatabaseLifecycleEvent {
+    pub kind: LifecycleKind,
+    pub name: String,
+    pub path: PathBuf,
+    pub powersync_loaded: bool,
+    /// Why the PowerSync extension couldn't be loaded, on open
+    pub powersync_load_error: Option<String>,
+    /// The damaged database replaced by a fresh one, on open
+    pub recovery: Option<DatabaseRecovery>,
+}
+
+/// Lifecycle events buffered per subscriber before it starts lagging
+const LIFECYCLE_CAPACITY: usize = 64;
+
+/// Statements rusqlite caches per connection besides prepared handles
+const STATEMENT_CACHE_CAPACITY: usize = 16;
+
+/// Database manager holding all open connections
+pub struct DatabaseManager {
+    databases: HashMap<String, Arc<Mutex<PowerSyncConnection>>>,
+    /// Writer queue per open database, kept outside the connection mutex so
+    /// writers can wait without blocking the current writer
+    write_queues: HashMap<String, Arc<WriteQueue>>,
+    app_data_dir: PathBuf,
+    resource_dir: Option<PathBuf>,
+    initializer: Option<ConnectionInitializer>,
+    /// Row changes after which connections refresh planner statistics
+    auto_analyze: Option<u64>,
+    non_finite_reals: NonFiniteReals,
+    invalid_utf8: InvalidUtf8,
+    param_coercion: ParamCoercion,
+    id_generator: IdGenerator,
+    faults: FaultInjection,
+    /// Write queue timeout of databases opened from now on
+    lock_timeout: Option<Duration>,
+    sync_batch: Option<usize>,
+    contention_threshold: Option<Duration>,
+    /// Replace damaged databases with fresh ones when opening them
+    recover_corrupt: bool,
+    /// Collect diagnostics for databases that recorded no choice
+    diagnostics: bool,
+    lifecycle: broadcast::Sender<DatabaseLifecycleEvent>,
+    /// Held while a database is being opened, so concurrent opens of the
+    /// same name wait for the first instead of initializing it twice
+    open_locks: HashMap<String, Arc<tokio::sync::Mutex<()>>>,
+}
+
+impl DatabaseManager {
+    pub fn new(app_data_dir: PathBuf, resource_dir: Option<PathBuf>) -> Self {
+        Self {
+            databases: HashMap::new(),
+            write_queues: HashMap::new(),
+            app_data_dir,
+            resource_dir,
+            initializer: None,
+            auto_analyze: None,
+            non_finite_reals: NonFiniteReals::default(),
+            invalid_utf8: InvalidUtf8::default(),
+            param_coercion: ParamCoercion::default(),
+            id_generator: IdGenerator::default(),
+            faults: FaultInjection::default(),
+            lock_timeout: None,
+            sync_batch: None,
+            contention_threshold: None,
+            recover_corrupt: false,
+            diagnostics: true,
+            lifecycle: broadcast::channel(LIFECYCLE_CAPACITY).0,
+            open_locks: HashMap::new(),
+        }
+    }
+
+    /// Directory database files are stored in
+    pub fn app_data_dir(&self) -> &Path {
+        &self.app_data_dir
+    }
+
+    /// Set the callback run against each newly opened connection
+    pub fn with_initializer(mut self, initializer: Option<ConnectionInitializer>) -> Self {
+        self.initializer = initializer;
+        self
+    }
+
+    /// Refresh planner statistics on every connection opened from now on
+    /// once `after_changes` rows have changed.
+    /// See [`PowerSyncConnection::set_auto_analyze`].
+    pub fn with_auto_analyze(mut self, after_changes: Option<u64>) -> Self {
+        self.auto_analyze = after_changes;
+        self
+    }
+
+    /// How connections opened from now on represent non-finite REAL values
+    pub fn with_non_finite_reals(mut self, non_finite_reals: NonFiniteReals) -> Self {
+        self.non_finite_reals = non_finite_reals;
+        self
+    }
+
+    /// How connections opened from now on represent TEXT that isn't valid UTF-8
+    pub fn with_invalid_utf8(mut self, invalid_utf8: InvalidUtf8) -> Self {
+        self.invalid_utf8 = invalid_utf8;
+        self
+    }
+
+    /// How connections opened from now on treat text bound to numeric columns
+    pub fn with_param_coercion(mut self, param_coercion: ParamCoercion) -> Self {
+        self.param_coercion = param_coercion;
+        self
+    }
+
+    /// Where connections opened from now on get the ids of
+    /// [`PowerSyncConnection::insert_with_id`] from
+    pub fn with_id_generator(mut self, id_generator: impl Into<IdGenerator>) -> Self {
+        self.id_generator = id_generator.into();
+        self
+    }
+
+    /// Faults injected into connections opened from now on
+    pub fn with_fault_injection(mut self, faults: FaultInjection) -> Self {
+        self.faults = faults;
+        self
+    }
+
+    /// How long writers to databases opened from now on wait for their turn
+    /// before failing. See [`WriteQueue::set_timeout`].
+    pub fn with_lock_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.lock_timeout = timeout;
+        self
+    }
+
+    /// Sync writes to databases opened from now on that are served in a
+    /// row before other writers get a turn. See [`WriteQueue::set_sync_batch`].
+    pub fn with_sync_batch(mut self, batch: Option<usize>) -> Self {
+        self.sync_batch = batch;
+        self
+    }
+
+    /// How long writers to databases opened from now on wait before they are
+    /// reported as contended. See [`WriteQueue::set_contention_threshold`].
+    pub fn with_contention_threshold(mut self, threshold: Option<Duration>) -> Self {
+        self.contention_threshold = threshold;
+        self
+    }
+
+    /// Whether opening a damaged database replaces it with a fresh one
+    /// instead of failing. See [`ConnectionOpener::open`].
+    pub fn with_corruption_recovery(mut self, recover: bool) -> Self {
+        self.recover_corrupt = recover;
+        self
+    }
+
+    /// Whether diagnostics are collected for databases opened from now on
+    /// that recorded no choice. See
+    /// [`PowerSyncConnection::set_diagnostics_enabled`].
+    pub fn with_diagnostics(mut self, enabled: bool) -> Self {
+        self.diagnostics = enabled;
+        self
+    }
+
+    /// Whether diagnostics are collected for databases that recorded no choice
+    pub fn diagnostics_default(&self) -> bool {
+        self.diagnostics
+    }
+
+    /// Open database `name` unless it is already open
+    pub fn open(&mut self, name: &str) -> Result<OpenResult> {
+        if let Some(result) = self.open_result(name)? {
+            return Ok(result);
+        }
+        let conn = self.opener().open(name)?;
+        let result = conn.open_result();
+        self.insert(name, conn);
+        Ok(result)
+    }
+
+    /// Close database `name` if it is open, move its files aside and open
+    /// a fresh database in their place. See [`ConnectionOpener::recover`].
+    pub fn recover(&mut self, name: &str) -> Result<OpenResult> {
+        self.close(name)?;
+        let conn = self.opener().recover(name, None)?;
+        let result = conn.open_result();
+        self.insert(name, conn);
+        Ok(result)
+    }
+
+    /// Names of the open databases, sorted
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self.databases.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Info of database `name`, if it is open
+    pub fn info(&self, name: &str) -> Result<Option<ConnectionInfo>> {
+        Ok(self.open_result(name)?.map(|result| result.info))
+    }
+
+    /// What opening database `name` reported, if it is open
+    pub fn open_result(&self, name: &str) -> Result<Option<OpenResult>> {
+        let Some(conn) = self.databases.get(name) else {
+            return Ok(None);
+        };
+        let conn = conn.lock().map_err(|e| Error::Lock(e.to_string()))?;
+        Ok(Some(conn.open_result()))
+    }
+
+    /// Settings for opening connections outside the manager.
+    ///
+    /// For callers that share the manager behind a lock: take the
+    /// [`open_lock`](Self::open_lock) for the name, check it isn't open yet,
+    /// open through this without holding the manager, then
+    /// [`insert`](Self::insert) the connection.
+    pub fn opener(&self) -> ConnectionOpener {
+        ConnectionOpener {
+            app_data_dir: self.app_data_dir.clone(),
+            resource_dir: self.resource_dir.clone(),
+            initializer: self.initializer.clone(),
+            auto_analyze: self.auto_analyze,
+            non_finite_reals: self.non_finite_reals,
+            invalid_utf8: self.invalid_utf8,
+            param_coercion: self.param_coercion,
+            id_generator: self.id_generator.clone(),
+            faults: self.faults,
+            recover_corrupt: self.recover_corrupt,
+            diagnostics: self.diagnostics,
+        }
+    }
+
+    /// Lock serializing opens of database `name`.
+    ///
+    /// Locks are only kept while someone holds them: unused ones are dropped
+    /// here and on [`close`](Self::close), so names opened once don't pile up.
+    pub fn open_lock(&mut self, name: &str) -> Arc<tokio::sync::Mutex<()>> {
+        self.open_locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+        Arc::clone(self.open_locks.entry(name.to_string()).or_default())
+    }
+
+    /// Register a connection opened elsewhere, replacing any open under the same name
+    pub fn insert(&mut self, name: &str, conn: PowerSyncConnection) {
+        self.announce(LifecycleKind::Opened, name, &conn);
+        self.databases
+            .insert(name.to_string(), Arc::new(Mutex::new(conn)));
+        let queue = WriteQueue::new();
+        queue.set_timeout(self.lock_timeout);
+        queue.set_sync_batch(self.sync_batch);
+        queue.set_contention_threshold(self.contention_threshold);
+        self.write_queues.insert(name.to_string(), queue);
+    }
+
+    pub fn close(&mut self, name: &str) -> Result<()> {
+        self.write_queues.remove(name);
+        if self
+            .open_locks
+            .get(name)
+            .is_some_and(|lock| Arc::strong_count(lock) == 1)
+        {
+            self.open_locks.remove(name);
+        }
+        if let Some(conn) = self.databases.remove(name) {
+            // A poisoned connection is being discarded anyway
+            let conn = conn.lock().unwrap_or_else(|e| e.into_inner());
+            self.announce(LifecycleKind::Closed, name, &conn);
+        }
+        Ok(())
+    }
+
+    /// Subscribe to databases being opened and closed, e.g. to attach to
+    /// databases opened by the webview
+    pub fn subscribe_lifecycle(&self) -> broadcast::Receiver<DatabaseLifecycleEvent> {
+        self.lifecycle.subscribe()
+    }
+
+    fn announce(&self, kind: LifecycleKind, name: &str, conn: &PowerSyncConnection) {
+        // Sending only fails when nobody is subscribed
+        let _ = self.lifecycle.send(DatabaseLifecycleEvent {
+            kind,
+            name: name.to_string(),
+            path: conn.path().clone(),
+            powersync_loaded: conn.is_powersync_loaded(),
+            powersync_load_error: match kind {
+                LifecycleKind::Opened => conn.load_error.clone(),
+                LifecycleKind::Closed => None,
+            },
+            recovery: match kind {
+                LifecycleKind::Opened => conn.recovery.clone(),
+                LifecycleKind::Closed => None,
+            },
+        });
+    }
+
+    /// Open `user_id`'s profile database, returning its database name
+    pub fn open_profile(&mut self, user_id: &str) -> Result<String> {
+        let name = profiles::database_name(user_id)?;
+        self.open(&name)?;
+        Ok(name)
+    }
+
+    /// User IDs of all profiles on disk, open or not
+    pub fn list_profiles(&self) -> Result<Vec<String>> {
+        profiles::list(&self.app_data_dir)
+    }
+
+    /// Close `user_id`'s profile database and delete its files
+    pub fn delete_profile(&mut self, user_id: &str) -> Result<()> {
+        let name = profiles::database_name(user_id)?;
+        self.close(&name)?;
+        profiles::remove_files(&self.app_data_dir, user_id)
+    }
+
+    /// Get the write queue of an open database
+    pub fn write_queue(&self, name: &str) -> Result<Arc<WriteQueue>> {
+        self.write_queues
+            .get(name)
+            .cloned()
+            .ok_or_else(|| Error::DatabaseNotFound(name.to_string()))
+    }
+
+    /// Roll back the transactions `owner` left open on every database, and
+    /// finalize the statements it prepared.
+    ///
+    /// Failures are logged rather than returned so one broken connection
+    /// doesn't keep the others from being cleaned up.
+    pub fn rollback_owned_transactions(&self, owner: &str) {
+        for (name, conn) in &self.databases {
+            let result = conn
+                .lock()
+                .map_err(|e| Error::Lock(e.to_string()))
+                .and_then(|mut conn| {
+                    conn.finalize_owned(owner);
+                    conn.rollback_owned_transactions(owner)
+                });
+            match result {
+                Ok(0) => {}
+                Ok(count) => log::info!(
+                    "Rolled back {} transaction(s) left open by {} on {}",
+                    count,
+                    owner,
+                    name
+                ),
+                Err(e) => log::warn!(
+                    "Failed to roll back transactions of {} on {}: {}",
+                    owner,
+                    name,
+                    e
+                ),
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Result<Arc<Mutex<PowerSyncConnection>>> {
+        self.databases
+            .get(name)
+            .cloned()
+            .ok_or_else(|| Error::DatabaseNotFound(name.to_string()))
+    }
+}
+
+/// Deserialize a row into a user type, matching fields to column names
+fn row_into<T: DeserializeOwned>(row: RowResult) -> Result<T> {
+    let object: serde_json::Map<String, JsonValue> = row
+        .into_iter()
+        .map(|(column, value)| (column.to_string(), value))
+        .collect();
+    Ok(serde_json::from_value(JsonValue::Object(object))?)
+}
+
+/// Open an in-memory database holding a copy of the database image `image`
+fn deserialize(mut image: Vec<u8>) -> Result<Connection> {
+    // An image of a WAL database would make SQLite look for a WAL the
+    // in-memory database can't have; read it as a rollback journal one
+    if image.len() > 19 && image[18] == 2 && image[19] == 2 {
+        image[18] = 1;
+        image[19] = 1;
+    }
+    let mut conn = Connection::open_in_memory()?;
+    // SAFETY: the buffer comes from sqlite3_malloc64, as OwnedData requires,
+    // and is fully written before SQLite takes ownership of it
+    let data = unsafe {
+        let ptr = ffi::sqlite3_malloc64(image.len().max(1) as u64).cast::<u8>();
+        let Some(ptr) = NonNull::new(ptr) else {
+            return Err(
+                rusqlite::Error::SqliteFailure(ffi::Error::new(ffi::SQLITE_NOMEM), None).into(),
+            );
+        };
+        std::ptr::copy_nonoverlapping(image.as_ptr(), ptr.as_ptr(), image.len());
+        OwnedData::from_raw_nonnull(ptr, image.len())
+    };
+    conn.deserialize(DatabaseName::Main, data, false)?;
+    Ok(conn)
+}
+
+/// Read the current value of a `sqlite3_db_status` counter
+fn db_status(conn: &Connection, op: c_int) -> Result<i64> {
+    let (mut current, mut highwater): (c_int, c_int) = (0, 0);
+    // SAFETY: the handle is valid while `conn` is borrowed, and the call
+    // only reads counters into the two out-params
+    let rc = unsafe { ffi::sqlite3_db_status(conn.handle(), op, &mut current, &mut highwater, 0) };
+    if rc != ffi::SQLITE_OK {
+        return Err(rusqlite::Error::SqliteFailure(ffi::Error::new(rc), None).into());
+    }
+    Ok(current.into())
+}
+
+/// Maximum number of bound variables in one statement
+fn variable_limit(conn: &Connection) -> usize {
+    // SAFETY: the handle is valid while `conn` is borrowed; -1 only reads the limit
+    let limit = unsafe { ffi::sqlite3_limit(conn.handle(), ffi::SQLITE_LIMIT_VARIABLE_NUMBER, -1) };
+    limit.max(1) as usize
+}
+
+/// Rows changed on the connection since it was opened, including by triggers
+fn total_changes(conn: &Connection) -> u64 {
+    // SAFETY: the handle is valid while `conn` is borrowed
+    unsafe { ffi::sqlite3_total_changes64(conn.handle()) as u64 }
+}
+
+/// Count the connection's prepared statements
+fn prepared_statement_count(conn: &Connection) -> i64 {
+    let mut count = 0;
+    // SAFETY: statements are only walked, not used; nothing else can
+    // prepare or finalize on the connection while `conn` is borrowed here
+    unsafe {
+        let db = conn.handle();
+        let mut stmt = ffi::sqlite3_next_stmt(db, std::ptr::null_mut());
+        while !stmt.is_null() {
+            count += 1;
+            stmt = ffi::sqlite3_next_stmt(db, stmt);
+        }
+    }
+    count
+}
+
+/// Run a prepared query and collect its column names and rows
+fn query_rows(
+    stmt: &mut rusqlite::Statement<'_>,
+    params: &[SqlParam],
+    format: ValueFormat,
+) -> Result<(Vec<String>, Vec<RowResult>)> {
+    let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+    let keys = column_keys(stmt);
+    let mut row_index = 0;
+    let rows = stmt
+        .query_map(params_from_iter(params), |row| {
+            row_index += 1;
+            row_result(row, row_index - 1, &keys, format)
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok((columns, rows))
+}
+
+/// Column names of a statement, interned once; every row's keys are clones of these
+fn column_keys(stmt: &rusqlite::Statement<'_>) -> Vec<Arc<str>> {
+    stmt.column_names().into_iter().map(Arc::from).collect()
+}
+
+fn row_result(
+    row: &rusqlite::Row,
+    row_index: usize,
+    keys: &[Arc<str>],
+    format: ValueFormat,
+) -> rusqlite::Result<RowResult> {
+    let mut row_data = RowResult::with_capacity(keys.len());
+    for (i, key) in keys.iter().enumerate() {
+        row_data.insert(Arc::clone(key), sqlite_value_to_json(row, row_index, i, format)?);
+    }
+    Ok(row_data)
+}
+
+/// Convert a SQLite row value to JSON
+fn sqlite_value_to_json(
+    row: &rusqlite::Row,
+    row_index: usize,
+    idx: usize,
+    format: ValueFormat,
+) -> rusqlite::Result<JsonValue> {
+    Ok(match row.get_ref(idx) {
+        Ok(ValueRef::Null) => JsonValue::Null,
+        Ok(ValueRef::Integer(i)) => JsonValue::Number(i.into()),
+        Ok(ValueRef::Real(f)) => match serde_json::Number::from_f64(f) {
+            Some(number) => JsonValue::Number(number),
+            None => non_finite_to_json(f, idx, format.non_finite_reals)?,
+        },
+        Ok(ValueRef::Text(s)) => match std::str::from_utf8(s) {
+            Ok(text) => JsonValue::String(text.to_string()),
+            Err(e) => match format.invalid_utf8 {
+                InvalidUtf8::Replace => JsonValue::String(String::from_utf8_lossy(s).into_owned()),
+                InvalidUtf8::Blob => blob_to_json(s),
+                InvalidUtf8::Error => {
+                    let column = row.as_ref().column_name(idx).unwrap_or("?");
+                    return Err(rusqlite::Error::FromSqlConversionFailure(
+                        idx,
+                        rusqlite::types::Type::Text,
+                        format!("row {} column {}: {}", row_index, column, e).into(),
+                    ));
+                }
+            },
+        },
+        Ok(ValueRef::Blob(b)) => blob_to_json(b),
+        Err(_) => JsonValue::Null,
+    })
+}
+
+/// Encode a blob as a base64 string
+fn blob_to_json(bytes: &[u8]) -> JsonValue {
+    use base64::Engine;
+    JsonValue::String(base64::engine::general_purpose::STANDARD.encode(bytes))
+}
+
+fn non_finite_to_json(
+    value: f64,
+    idx: usize,
+    non_finite_reals: NonFiniteReals,
+) -> rusqlite::Result<JsonValue> {
+    match non_finite_reals {
+        NonFiniteReals::Null => Ok(JsonValue::Null),
+        NonFiniteReals::String => {
+            let name = if value.is_nan() {
+                "NaN"
+            } else if value > 0.0 {
+                "Infinity"
+            } else {
+                "-Infinity"
+            };
+            Ok(JsonValue::from(name))
+        }
+        NonFiniteReals::Error => Err(rusqlite::Error::FromSqlConversionFailure(
+            idx,
+            rusqlite::types::Type::Real,
+            format!("{} can't be represented in JSON", value).into(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sql_param_deserialization() {
+        // Test null
+        let json = r#"{"type":"null"}"#;
+        let param: SqlParam = serde_json::from_str(json).unwrap();
+        assert!(matches!(param, SqlParam::Null));
+
+        // Test text
+        let json = r#"{"type":"text","value":"hello"}"#;
+        let param: SqlParam = serde_json::from_str(json).unwrap();
+        assert!(matches!(param, SqlParam::Text(s) if s == "hello"));
+
+        // Test int
+        let json = r#"{"type":"int","value":42}"#;
+        let param: SqlParam = serde_json::from_str(json).unwrap();
+        assert!(matches!(param, SqlParam::Int(42)));
+
+        // Test blob
+        let json = r#"{"type":"blob","value":[1,2,3]}"#;
+        let param: SqlParam = serde_json::from_str(json).unwrap();
+        assert!(matches!(param, SqlParam::Blob(b) if b == vec![1, 2, 3]));
+
+        // Test array of params
+        let json = r#"[{"type":"text","value":"schema json here"}]"#;
+        let params: Vec<SqlParam> = serde_json::from_str(json).unwrap();
+        assert_eq!(params.len(), 1);
+        assert!(matches!(&params[0], SqlParam::Text(s) if s == "schema json here"));
+    }
+
+    #[test]
+    fn test_row_into_struct() {
+        #[derive(Deserialize)]
+        struct Todo {
+            id: String,
+            completed: bool,
+            note: Option<String>,
+        }
+
+        let mut row = RowResult::new();
+        row.insert("id".into(), JsonValue::from("1"));
+        row.insert("completed".into(), JsonValue::from(true));
+        row.insert("note".into(), JsonValue::Null);
+
+        let todo: Todo = row_into(row).unwrap();
+        assert_eq!(todo.id, "1");
+        assert!(todo.completed);
+        assert!(todo.note.is_none());
+    }
+
+    #[test]
+    fn test_powersync_result_format() {
+        let mut row = RowResult::new();
+        row.insert("id".into(), JsonValue::from("1"));
+        let result = ExecuteResult {
+            changes: 1,
+            last_insert_rowid: 7,
+            columns: Some(vec!["id".to_string()]),
+            rows: Some(vec![row]),
+        };
+
+        let json = serde_json::to_value(ResultFormat::PowerSync.apply(result)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "insertId": 7,
+                "rowsAffected": 1,
+                "rows": { "_array": [{ "id": "1" }], "length": 1 }
+            })
+        );
+    }
+}
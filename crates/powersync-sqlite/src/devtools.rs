@@ -0,0 +1,292 @@
+//! Read-only views of PowerSync's internal tables, for inspector panels,
+//! and fixture loading for demo and test environments
+//!
+//! Pending uploads are redacted to the columns they change: their values
+//! can hold anything the user typed, and inspectors tend to end up in
+//! screenshots and bug reports.
+
+use crate::database::{quote_identifier, SqlParam};
+use crate::error::{Error, Result};
+use indexmap::IndexMap;
+use rusqlite::{params_from_iter, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+/// Operations in `ps_oplog` for one bucket and row type
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct OplogSummary {
+    /// Bucket name, or its id if `ps_buckets` has no such bucket
+    pub bucket: String,
+    pub row_type: Option<String>,
+    pub ops: i64,
+    pub last_op_id: i64,
+}
+
+/// A pending upload from `ps_crud`, without its values
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct RedactedCrudEntry {
+    pub id: i64,
+    pub tx_id: Option<i64>,
+    /// `PUT`, `PATCH` or `DELETE`
+    pub op: Option<String>,
+    pub table: Option<String>,
+    pub row_id: Option<String>,
+    /// Columns the operation sets
+    pub columns: Vec<String>,
+}
+
+/// A table of the applied schema, in the JSON shape `replace_schema` takes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct SchemaTable {
+    pub name: String,
+    pub columns: Vec<SchemaColumn>,
+    pub local_only: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct SchemaColumn {
+    pub name: String,
+    /// Type SQLite reports for the view column
+    #[serde(rename = "type")]
+    pub column_type: String,
+}
+
+/// Operation counts per bucket and row type
+pub fn oplog_summary(conn: &Connection) -> Result<Vec<OplogSummary>> {
+    let mut stmt = conn.prepare(
+        "SELECT coalesce(b.name, CAST(o.bucket AS TEXT)), o.row_type, count(*), max(o.op_id)
+         FROM ps_oplog o LEFT JOIN ps_buckets b ON b.id = o.bucket
+         GROUP BY o.bucket, o.row_type
+         ORDER BY 1, 2",
+    )?;
+    let summary = stmt
+        .query_map([], |row| {
+            Ok(OplogSummary {
+                bucket: row.get(0)?,
+                row_type: row.get(1)?,
+                ops: row.get(2)?,
+                last_op_id: row.get(3)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(summary)
+}
+
+/// The first `limit` pending uploads, redacted
+pub fn pending_crud(conn: &Connection, limit: i64) -> Result<Vec<RedactedCrudEntry>> {
+    let mut stmt = conn.prepare("SELECT id, tx_id, data FROM ps_crud ORDER BY id LIMIT ?")?;
+    let entries = stmt
+        .query_map([limit], |row| {
+            let data: Option<String> = row.get(2)?;
+            Ok(redact(row.get(0)?, row.get(1)?, data.as_deref()))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(entries)
+}
+
+fn redact(id: i64, tx_id: Option<i64>, data: Option<&str>) -> RedactedCrudEntry {
+    let data: JsonValue = data
+        .and_then(|data| serde_json::from_str(data).ok())
+        .unwrap_or_default();
+    let text = |key: &str| {
+        data.get(key)
+            .and_then(JsonValue::as_str)
+            .map(str::to_string)
+    };
+    RedactedCrudEntry {
+        id,
+        tx_id,
+        op: text("op"),
+        table: text("type"),
+        row_id: text("id"),
+        columns: data
+            .get("data")
+            .and_then(JsonValue::as_object)
+            .map(|values| values.keys().cloned().collect())
+            .unwrap_or_default(),
+    }
+}
+
+/// The schema as applied, read back from the views PowerSync created
+pub fn applied_schema(conn: &Connection) -> Result<Vec<SchemaTable>> {
+    let mut stmt = conn.prepare(
+        "SELECT name, instr(sql, 'ps_data_local__') > 0 FROM sqlite_master
+         WHERE type = 'view' AND (instr(sql, 'ps_data__') > 0 OR instr(sql, 'ps_data_local__') > 0)
+         ORDER BY name",
+    )?;
+    let views = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, bool>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut columns =
+        conn.prepare("SELECT name, type FROM pragma_table_info(?) WHERE name != 'id'")?;
+    let mut tables = Vec::with_capacity(views.len());
+    for (name, local_only) in views {
+        let table_columns = columns
+            .query_map([&name], |row| {
+                Ok(SchemaColumn {
+                    name: row.get(0)?,
+                    column_type: row.get(1)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        tables.push(SchemaTable {
+            name,
+            columns: table_columns,
+            local_only,
+        });
+    }
+    Ok(tables)
+}
+
+/// Fixture data for [`PowerSyncConnection::seed`](crate::PowerSyncConnection::seed)
+#[derive(Debug, Clone, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(untagged)]
+pub enum SeedData {
+    /// SQL statements, run as one script
+    Script(String),
+    /// Rows by table, in insertion order; each row maps columns to values
+    Tables(IndexMap<String, Vec<IndexMap<String, JsonValue>>>),
+}
+
+/// Load `data`, returning the number of rows inserted from tables (0 for
+/// scripts). The caller provides the transaction.
+pub(crate) fn seed(conn: &Connection, data: &SeedData) -> Result<usize> {
+    let tables = match data {
+        SeedData::Script(script) => {
+            conn.execute_batch(script)?;
+            return Ok(0);
+        }
+        SeedData::Tables(tables) => tables,
+    };
+
+    let mut inserted = 0;
+    for (table, rows) in tables {
+        for row in rows {
+            if row.is_empty() {
+                return Err(Error::InvalidParameter(format!(
+                    "seed row for {} has no columns",
+                    table
+                )));
+            }
+            // Rows may set different columns, so defaults apply to the rest
+            let sql = format!(
+                "INSERT INTO {} ({}) VALUES ({})",
+                quote_identifier(table),
+                row.keys()
+                    .map(|c| quote_identifier(c))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                vec!["?"; row.len()].join(", ")
+            );
+            let params: Vec<SqlParam> = row.values().map(seed_param).collect();
+            inserted += conn
+                .prepare_cached(&sql)?
+                .execute(params_from_iter(params.iter()))?;
+        }
+    }
+    Ok(inserted)
+}
+
+/// Arrays and objects are stored as JSON text, as PowerSync expects
+fn seed_param(value: &JsonValue) -> SqlParam {
+    match value {
+        JsonValue::Null => SqlParam::Null,
+        JsonValue::Bool(b) => SqlParam::Bool(*b),
+        JsonValue::Number(n) => match n.as_i64() {
+            Some(i) => SqlParam::Int(i),
+            None => SqlParam::Real(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        JsonValue::String(s) => SqlParam::Text(s.clone()),
+        JsonValue::Array(_) | JsonValue::Object(_) => SqlParam::Text(value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact() {
+        let data =
+            r#"{"op":"PUT","type":"todos","id":"t1","data":{"description":"secret","done":0}}"#;
+        let entry = redact(1, Some(2), Some(data));
+        assert_eq!(entry.op.as_deref(), Some("PUT"));
+        assert_eq!(entry.table.as_deref(), Some("todos"));
+        assert_eq!(entry.row_id.as_deref(), Some("t1"));
+        assert_eq!(entry.columns, vec!["description", "done"]);
+        assert!(!serde_json::to_string(&entry).unwrap().contains("secret"));
+
+        let entry = redact(3, None, Some("not json"));
+        assert_eq!((entry.op, entry.columns.len()), (None, 0));
+    }
+
+    #[test]
+    fn test_applied_schema() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE ps_data__todos (id, data);
+             CREATE TABLE ps_data_local__drafts (id, data);
+             CREATE VIEW todos (id, description, done) AS
+                 SELECT id, CAST(data AS TEXT), CAST(data AS INTEGER) FROM ps_data__todos;
+             CREATE VIEW drafts (id, body) AS
+                 SELECT id, CAST(data AS TEXT) FROM ps_data_local__drafts;",
+        )
+        .unwrap();
+
+        let schema = applied_schema(&conn).unwrap();
+        let names: Vec<_> = schema
+            .iter()
+            .map(|t| (t.name.as_str(), t.local_only))
+            .collect();
+        assert_eq!(names, vec![("drafts", true), ("todos", false)]);
+        let json = serde_json::to_value(&schema[1]).unwrap();
+        assert_eq!(json["columns"][1]["name"], "done");
+        assert_eq!(json["local_only"], false);
+    }
+
+    #[test]
+    fn test_seed() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE lists (id TEXT, name TEXT, tags TEXT, n INTEGER DEFAULT 7)",
+        )
+        .unwrap();
+
+        let data: SeedData = serde_json::from_str(
+            r#"{"lists": [{"id": "l1", "name": "Groceries", "tags": ["a"]}, {"id": "l2", "n": 2}]}"#,
+        )
+        .unwrap();
+        assert_eq!(seed(&conn, &data).unwrap(), 2);
+        let rows: Vec<(String, Option<String>, i64)> = conn
+            .prepare("SELECT id, tags, n FROM lists ORDER BY id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                ("l1".to_string(), Some(r#"["a"]"#.to_string()), 7),
+                ("l2".to_string(), None, 2)
+            ]
+        );
+
+        let script: SeedData = serde_json::from_str(r#""DELETE FROM lists""#).unwrap();
+        assert_eq!(seed(&conn, &script).unwrap(), 0);
+        let count: i64 = conn
+            .query_row("SELECT count(*) FROM lists", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+}
@@ -0,0 +1,70 @@
+//! Consent to diagnostics collection
+//!
+//! Support bundles gather metrics, recent events and failing uploads of a
+//! database. Apps that ask their users before collecting any of that record
+//! the answer per database; it is stored in the database itself, so it
+//! survives restarts and travels with snapshots and exports. Databases that
+//! never recorded an answer follow the app's default.
+
+use crate::error::Result;
+use rusqlite::{Connection, OptionalExtension};
+
+/// Plugin table holding the recorded consent. The `ps_` prefix keeps it out
+/// of user table listings and change notifications.
+const CONSENT_TABLE: &str = "ps_jf_diagnostics";
+
+/// Whether diagnostics collection was enabled for the database, `None` if
+/// that was never recorded
+pub fn recorded(conn: &Connection) -> Result<Option<bool>> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?)",
+        [CONSENT_TABLE],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        return Ok(None);
+    }
+    let enabled = conn
+        .query_row(
+            &format!("SELECT enabled FROM {} WHERE id = 1", CONSENT_TABLE),
+            [],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(enabled)
+}
+
+/// Record whether diagnostics may be collected for the database
+pub(crate) fn record(conn: &Connection, enabled: bool) -> Result<()> {
+    conn.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            enabled INTEGER NOT NULL
+        )",
+        CONSENT_TABLE
+    ))?;
+    conn.execute(
+        &format!(
+            "INSERT INTO {} (id, enabled) VALUES (1, ?)
+             ON CONFLICT (id) DO UPDATE SET enabled = excluded.enabled",
+            CONSENT_TABLE
+        ),
+        [enabled],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert_eq!(recorded(&conn).unwrap(), None);
+        record(&conn, false).unwrap();
+        assert_eq!(recorded(&conn).unwrap(), Some(false));
+        record(&conn, true).unwrap();
+        assert_eq!(recorded(&conn).unwrap(), Some(true));
+    }
+}
@@ -16,12 +16,27 @@ pub enum Error {
     #[error("Transaction already completed: {0}")]
     TransactionCompleted(String),
 
+    #[error("Savepoint not found: {0}")]
+    SavepointNotFound(String),
+
+    #[error("Statement not found: {0}")]
+    StatementNotFound(String),
+
+    #[error("Transaction order error: {0}")]
+    TransactionOrder(String),
+
+    #[error("Transaction owned by another caller: {0}")]
+    TransactionOwner(String),
+
     #[error("Invalid parameter: {0}")]
     InvalidParameter(String),
 
     #[error("Lock error: {0}")]
     Lock(String),
 
+    #[error("Lock timeout: {0}")]
+    LockTimeout(crate::queue::LockTimeout),
+
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
 
@@ -34,6 +49,21 @@ pub enum Error {
     #[error("Extension load error: {0}")]
     ExtensionLoad(String),
 
+    #[error("Checkpoint validation failed: {0}")]
+    CheckpointValidation(String),
+
+    #[error("Database corrupt: {0}")]
+    CorruptDatabase(String),
+
+    #[error("Invalid bundle: {0}")]
+    InvalidBundle(String),
+
+    #[error("Partial commit: {0}")]
+    PartialCommit(String),
+
+    #[error("Checkpoint timeout: {0}")]
+    CheckpointTimeout(String),
+
     #[error("PowerSync not initialized")]
     PowerSyncNotInitialized,
 
@@ -49,3 +79,11 @@ impl Serialize for Error {
         serializer.serialize_str(&self.to_string())
     }
 }
+
+/// Errors cross IPC as their display string
+#[cfg(feature = "specta")]
+impl specta::Type for Error {
+    fn inline(type_map: &mut specta::TypeCollection, generics: specta::Generics) -> specta::DataType {
+        String::inline(type_map, generics)
+    }
+}
@@ -0,0 +1,186 @@
+//! Writing query results straight to files
+//!
+//! For reports over more rows than the webview can hold: rows are written
+//! as they are read, so memory use doesn't grow with the result. Values are
+//! converted as for query results (see [`crate::database::NonFiniteReals`]
+//! and [`crate::database::InvalidUtf8`]).
+
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// File format of an export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// One JSON object per line, keyed by column name
+    Ndjson,
+    /// RFC 4180 CSV with a header row. NULL is an empty field.
+    Csv,
+}
+
+/// What an export wrote
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct ExportResult {
+    pub rows: i64,
+    /// Size of the written file
+    pub bytes: i64,
+}
+
+/// Writes rows to a file, removing it unless [`finish`](Self::finish)ed
+pub(crate) struct ExportWriter {
+    out: BufWriter<File>,
+    path: PathBuf,
+    format: ExportFormat,
+    columns: Vec<String>,
+    rows: i64,
+    finished: bool,
+}
+
+impl ExportWriter {
+    /// Create or truncate the file at `path`, writing the CSV header
+    pub(crate) fn create(path: &Path, format: ExportFormat, columns: Vec<String>) -> Result<Self> {
+        let mut writer = Self {
+            out: BufWriter::new(File::create(path)?),
+            path: path.to_path_buf(),
+            format,
+            columns,
+            rows: 0,
+            finished: false,
+        };
+        if format == ExportFormat::Csv {
+            let header = writer
+                .columns
+                .iter()
+                .map(|c| csv_field(c))
+                .collect::<Vec<_>>();
+            writeln!(writer.out, "{}", header.join(","))?;
+        }
+        Ok(writer)
+    }
+
+    /// Write a row's values, in column order
+    pub(crate) fn write_row(&mut self, values: &[JsonValue]) -> Result<()> {
+        match self.format {
+            ExportFormat::Ndjson => {
+                self.out.write_all(b"{")?;
+                for (i, (column, value)) in self.columns.iter().zip(values).enumerate() {
+                    if i > 0 {
+                        self.out.write_all(b",")?;
+                    }
+                    serde_json::to_writer(&mut self.out, column)?;
+                    self.out.write_all(b":")?;
+                    serde_json::to_writer(&mut self.out, value)?;
+                }
+                self.out.write_all(b"}\n")?;
+            }
+            ExportFormat::Csv => {
+                let fields = values.iter().map(csv_value).collect::<Vec<_>>();
+                writeln!(self.out, "{}", fields.join(","))?;
+            }
+        }
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// Flush the file to disk and report what was written
+    pub(crate) fn finish(mut self) -> Result<ExportResult> {
+        self.out.flush()?;
+        self.out.get_ref().sync_all()?;
+        self.finished = true;
+        Ok(ExportResult {
+            rows: self.rows,
+            bytes: self.out.get_ref().metadata()?.len() as i64,
+        })
+    }
+}
+
+impl Drop for ExportWriter {
+    fn drop(&mut self) {
+        // Don't leave a truncated report behind
+        if !self.finished {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+fn csv_value(value: &JsonValue) -> String {
+    match value {
+        JsonValue::Null => String::new(),
+        JsonValue::String(s) => csv_field(s),
+        other => csv_field(&other.to_string()),
+    }
+}
+
+/// Quote a field if it contains a delimiter, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn export(format: ExportFormat, rows: &[Vec<JsonValue>]) -> (String, ExportResult) {
+        let path = std::env::temp_dir().join(format!("powersync-export-{}", uuid::Uuid::new_v4()));
+        let columns = vec!["id".to_string(), "note, \"quoted\"".to_string()];
+        let mut writer = ExportWriter::create(&path, format, columns).unwrap();
+        for row in rows {
+            writer.write_row(row).unwrap();
+        }
+        let result = writer.finish().unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        (contents, result)
+    }
+
+    #[test]
+    fn test_formats() {
+        let rows = [
+            vec![json!(1), json!("line\nbreak")],
+            vec![json!(2.5), JsonValue::Null],
+        ];
+
+        let (csv, result) = export(ExportFormat::Csv, &rows);
+        assert_eq!(
+            csv,
+            "id,\"note, \"\"quoted\"\"\"\n1,\"line\nbreak\"\n2.5,\n"
+        );
+        assert_eq!(result.rows, 2);
+        assert_eq!(result.bytes, csv.len() as i64);
+
+        let (ndjson, _) = export(ExportFormat::Ndjson, &rows);
+        let lines: Vec<JsonValue> = ndjson
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                json!({"id": 1, "note, \"quoted\"": "line\nbreak"}),
+                json!({"id": 2.5, "note, \"quoted\"": null}),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unfinished_export_is_removed() {
+        let path = std::env::temp_dir().join(format!("powersync-export-{}", uuid::Uuid::new_v4()));
+        let writer =
+            ExportWriter::create(&path, ExportFormat::Csv, vec!["id".to_string()]).unwrap();
+        assert!(path.exists());
+        drop(writer);
+        assert!(!path.exists());
+    }
+}
@@ -22,7 +22,7 @@ pub fn get_extension_filename() -> &'static str {
     {
         "libpowersync.dylib"
     }
-    #[cfg(target_os = "linux")]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
     {
         "libpowersync.so"
     }
@@ -55,6 +55,11 @@ pub fn find_extension(resource_dir: &PathBuf) -> Result<PathBuf> {
         return Ok(path);
     }
 
+    // On Android the extension ships in jniLibs, where dlopen resolves it by name
+    #[cfg(target_os = "android")]
+    return Ok(PathBuf::from(filename));
+
+    #[allow(unreachable_code)]
     Err(Error::ExtensionNotFound(format!(
         "PowerSync extension '{}' not found in {:?}",
         filename, resource_dir
@@ -89,10 +94,13 @@ pub fn load_extension(
     Ok(())
 }
 
-/// Initialize PowerSync after the extension is loaded
+/// Initialize PowerSync after the extension is loaded, retrying while the
+/// database is momentarily locked or the call is interrupted
 pub fn init_powersync(conn: &rusqlite::Connection) -> Result<()> {
-    conn.query_row("SELECT powersync_init()", [], |_| Ok(()))?;
-    Ok(())
+    crate::control::with_retries_blocking("powersync_init", || {
+        conn.query_row("SELECT powersync_init()", [], |_| Ok(()))?;
+        Ok(())
+    })
 }
 
 /// Check if PowerSync functions are available
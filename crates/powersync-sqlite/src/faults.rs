@@ -0,0 +1,112 @@
+//! Simulated latency and failures, for testing frontends under adverse
+//! conditions
+//!
+//! Faults are injected at the connection's entry points, before any SQL
+//! runs, so a failed statement never has partial effects:
+//!
+//! - added latency per statement, holding the connection like a slow query;
+//! - `SQLITE_BUSY` errors at a given rate, as another process holding the
+//!   database would cause;
+//! - failures feeding sync lines to the extension, which the sync client
+//!   treats as a broken stream: it disconnects and reconnects.
+
+use crate::error::{Error, Result};
+use rusqlite::ffi;
+use serde::Deserialize;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// `powersync_control` operations that carry sync stream data
+const SYNC_LINE_OPS: &[&str] = &["line_text", "line_binary"];
+
+/// Faults to inject into a connection. All off by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct FaultInjection {
+    /// Chance, from 0 to 1, that a statement fails with `SQLITE_BUSY`
+    pub busy_probability: f64,
+    /// Delay added before each statement, in milliseconds
+    pub statement_latency_ms: u64,
+    /// Chance, from 0 to 1, that a sync line fails to apply, forcing the
+    /// sync client to reconnect
+    pub sync_disconnect_probability: f64,
+}
+
+impl FaultInjection {
+    /// Whether any fault is configured
+    pub fn is_enabled(&self) -> bool {
+        *self != Self::default()
+    }
+
+    /// Delay, then possibly fail, a statement about to run
+    pub(crate) fn before_statement(&self) -> Result<()> {
+        if self.statement_latency_ms > 0 {
+            std::thread::sleep(Duration::from_millis(self.statement_latency_ms));
+        }
+        if chance(self.busy_probability) {
+            return Err(Error::Database(rusqlite::Error::SqliteFailure(
+                ffi::Error::new(ffi::SQLITE_BUSY),
+                Some("database is locked (injected fault)".to_string()),
+            )));
+        }
+        Ok(())
+    }
+
+    /// Possibly fail a `powersync_control` call carrying sync data
+    pub(crate) fn before_control(&self, op: &str) -> Result<()> {
+        if SYNC_LINE_OPS.contains(&op) && chance(self.sync_disconnect_probability) {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::ConnectionAborted,
+                "sync stream disconnected (injected fault)",
+            )));
+        }
+        self.before_statement()
+    }
+}
+
+/// True with the given probability. A v4 UUID's leading 48 bits are random,
+/// which is plenty for fault rates.
+fn chance(probability: f64) -> bool {
+    if probability <= 0.0 {
+        return false;
+    }
+    let (high, _) = Uuid::new_v4().as_u64_pair();
+    ((high >> 16) as f64 / (1u64 << 48) as f64) < probability
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fault_rates() {
+        let off = FaultInjection::default();
+        assert!(!off.is_enabled());
+        assert!((0..1000).all(|_| off.before_statement().is_ok()));
+
+        let busy = FaultInjection {
+            busy_probability: 1.0,
+            ..Default::default()
+        };
+        let err = busy.before_statement().unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Database(rusqlite::Error::SqliteFailure(e, _))
+                if e.code == rusqlite::ErrorCode::DatabaseBusy
+        ));
+
+        let disconnect = FaultInjection {
+            sync_disconnect_probability: 1.0,
+            ..Default::default()
+        };
+        assert!(matches!(
+            disconnect.before_control("line_text"),
+            Err(Error::Io(_))
+        ));
+        assert!(disconnect.before_control("start").is_ok());
+
+        let half = (0..10_000).filter(|_| chance(0.5)).count();
+        assert!((4_000..6_000).contains(&half), "{}", half);
+    }
+}
@@ -0,0 +1,266 @@
+//! Optional SQL functions registered on each connection
+//!
+//! Everything here but [`register_now`] is off by default; the Tauri plugin
+//! turns functions on from its config (`plugins.powersync-jf` in
+//! `tauri.conf.json`). Together they cover what the other PowerSync SDKs'
+//! SQLite builds provide.
+
+use crate::clock::Clock;
+use regex::Regex;
+use rusqlite::functions::FunctionFlags;
+use rusqlite::types::ValueRef;
+use rusqlite::Connection;
+use serde::Deserialize;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// Which optional functions to register
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SqlFunctions {
+    /// `X REGEXP Y`, matching `X` against the regular expression `Y`
+    pub regexp: bool,
+    /// SQLite's math functions (`sqrt`, `pow`, `ln`, `floor`, ...), for
+    /// builds of SQLite compiled without them
+    pub math: bool,
+    /// `uuid()`, `unixepoch_ms()` and `lower_unicode(X)`
+    pub helpers: bool,
+}
+
+impl SqlFunctions {
+    /// Register the enabled functions on a connection
+    pub fn register(&self, conn: &Connection) -> rusqlite::Result<()> {
+        if self.regexp {
+            register_regexp(conn)?;
+        }
+        if self.math {
+            register_math(conn)?;
+        }
+        if self.helpers {
+            register_helpers(conn)?;
+        }
+        Ok(())
+    }
+}
+
+/// Register `regexp(pattern, text)`, which SQLite calls for `text REGEXP pattern`.
+///
+/// Patterns use the `regex` crate's syntax and are compiled once per
+/// statement. NULL on either side yields NULL.
+pub fn register_regexp(conn: &Connection) -> rusqlite::Result<()> {
+    conn.create_scalar_function("regexp", 2, deterministic(), |ctx| {
+        let is_null = |i| matches!(ctx.get_raw(i), ValueRef::Null);
+        if is_null(0) || is_null(1) {
+            return Ok(None);
+        }
+        let regex = ctx.get_or_create_aux(0, |pattern| -> Result<Regex, BoxError> {
+            Ok(Regex::new(pattern.as_str()?)?)
+        })?;
+        let text = ctx
+            .get_raw(1)
+            .as_str()
+            .map_err(|e| rusqlite::Error::UserFunctionError(e.into()))?;
+        Ok(Some(regex.is_match(text)))
+    })
+}
+
+/// A unary math function over REAL values
+type FloatFn = fn(f64) -> f64;
+
+/// Register the functions of SQLite's `SQLITE_ENABLE_MATH_FUNCTIONS` set.
+///
+/// As in SQLite, NULL arguments and results outside the domain of a
+/// function (e.g. `sqrt(-1)`) yield NULL.
+pub fn register_math(conn: &Connection) -> rusqlite::Result<()> {
+    let unary: [(&str, FloatFn); 19] = [
+        ("acos", f64::acos),
+        ("acosh", f64::acosh),
+        ("asin", f64::asin),
+        ("asinh", f64::asinh),
+        ("atan", f64::atan),
+        ("atanh", f64::atanh),
+        ("cos", f64::cos),
+        ("cosh", f64::cosh),
+        ("degrees", f64::to_degrees),
+        ("exp", f64::exp),
+        ("ln", f64::ln),
+        ("log10", f64::log10),
+        ("log2", f64::log2),
+        ("radians", f64::to_radians),
+        ("sin", f64::sin),
+        ("sinh", f64::sinh),
+        ("sqrt", f64::sqrt),
+        ("tan", f64::tan),
+        ("tanh", f64::tanh),
+    ];
+    for (name, f) in unary {
+        register_float(conn, name, 1, move |args| f(args[0]))?;
+    }
+    // log(X) is base 10, log(B, X) is base B
+    register_float(conn, "log", 1, |args| args[0].log10())?;
+    register_float(conn, "log", 2, |args| match args[0] {
+        2.0 => args[1].log2(),
+        10.0 => args[1].log10(),
+        base => args[1].log(base),
+    })?;
+    for name in ["pow", "power"] {
+        register_float(conn, name, 2, |args| args[0].powf(args[1]))?;
+    }
+    register_float(conn, "atan2", 2, |args| args[0].atan2(args[1]))?;
+    register_float(conn, "mod", 2, |args| args[0] % args[1])?;
+    register_float(conn, "pi", 0, |_| std::f64::consts::PI)?;
+
+    // Rounding keeps integers as they are
+    let rounding: [(&str, FloatFn); 4] = [
+        ("ceil", f64::ceil),
+        ("ceiling", f64::ceil),
+        ("floor", f64::floor),
+        ("trunc", f64::trunc),
+    ];
+    for (name, f) in rounding {
+        conn.create_scalar_function(name, 1, deterministic(), move |ctx| {
+            Ok(match ctx.get_raw(0) {
+                ValueRef::Null => None,
+                ValueRef::Integer(i) => Some(rusqlite::types::Value::Integer(i)),
+                _ => finite(f(ctx.get::<f64>(0)?)).map(rusqlite::types::Value::Real),
+            })
+        })?;
+    }
+    Ok(())
+}
+
+/// Register `now()`, the current time from [`Clock::global`] as ISO 8601
+/// text. Every connection opened with
+/// [`PowerSyncConnection::open`](crate::database::PowerSyncConnection::open)
+/// has it.
+///
+/// Unlike `strftime(..., 'now')`, which is fixed for the duration of a
+/// statement, each call returns a later timestamp.
+pub fn register_now(conn: &Connection) -> rusqlite::Result<()> {
+    conn.create_scalar_function("now", 0, FunctionFlags::SQLITE_UTF8, |_| {
+        Ok(Clock::global().now().utc)
+    })
+}
+
+/// Register `uuid()` (a random v4 UUID), `unixepoch_ms()` (the current time
+/// in milliseconds) and `lower_unicode(X)` (lowercase beyond ASCII, unlike
+/// `lower()`).
+///
+/// The PowerSync extension defines its own `uuid()`; this one is equivalent
+/// and also works on connections without the extension.
+pub fn register_helpers(conn: &Connection) -> rusqlite::Result<()> {
+    conn.create_scalar_function("uuid", 0, FunctionFlags::SQLITE_UTF8, |_| {
+        Ok(uuid::Uuid::new_v4().to_string())
+    })?;
+    conn.create_scalar_function("unixepoch_ms", 0, FunctionFlags::SQLITE_UTF8, |_| {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(|e| rusqlite::Error::UserFunctionError(e.into()))?;
+        Ok(now.as_millis() as i64)
+    })?;
+    conn.create_scalar_function("lower_unicode", 1, deterministic(), |ctx| {
+        Ok(match ctx.get_raw(0) {
+            ValueRef::Null => None,
+            _ => Some(ctx.get::<String>(0)?.to_lowercase()),
+        })
+    })
+}
+
+fn deterministic() -> FunctionFlags {
+    FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC
+}
+
+fn finite(value: f64) -> Option<f64> {
+    value.is_finite().then_some(value)
+}
+
+/// Register a function of `n_args` floats; NULL in, or NaN/infinity out, gives NULL
+fn register_float<F>(conn: &Connection, name: &str, n_args: usize, f: F) -> rusqlite::Result<()>
+where
+    F: Fn(&[f64]) -> f64 + Send + std::panic::UnwindSafe + 'static,
+{
+    conn.create_scalar_function(name, n_args as i32, deterministic(), move |ctx| {
+        let mut args = Vec::with_capacity(n_args);
+        for i in 0..n_args {
+            match ctx.get::<Option<f64>>(i)? {
+                Some(arg) => args.push(arg),
+                None => return Ok(None),
+            }
+        }
+        Ok(finite(f(&args)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_regexp() {
+        let conn = Connection::open_in_memory().unwrap();
+        let functions = SqlFunctions {
+            regexp: true,
+            ..Default::default()
+        };
+        functions.register(&conn).unwrap();
+
+        let matches =
+            |sql: &str| -> Option<bool> { conn.query_row(sql, [], |row| row.get(0)).unwrap() };
+        assert_eq!(matches("SELECT 'todo-42' REGEXP '^todo-\\d+$'"), Some(true));
+        assert_eq!(matches("SELECT 'done' REGEXP '^todo'"), Some(false));
+        assert_eq!(matches("SELECT NULL REGEXP 'x'"), None);
+        assert!(conn.query_row("SELECT 'a' REGEXP '('", [], |row| row.get::<_, bool>(0)).is_err());
+    }
+
+    #[test]
+    fn test_disabled_by_default() {
+        let conn = Connection::open_in_memory().unwrap();
+        SqlFunctions::default().register(&conn).unwrap();
+        assert!(conn.query_row("SELECT 'a' REGEXP 'a'", [], |row| row.get::<_, bool>(0)).is_err());
+    }
+
+    #[test]
+    fn test_math() {
+        let conn = Connection::open_in_memory().unwrap();
+        register_math(&conn).unwrap();
+
+        let real =
+            |sql: &str| -> Option<f64> { conn.query_row(sql, [], |row| row.get(0)).unwrap() };
+        assert_eq!(real("SELECT sqrt(16)"), Some(4.0));
+        assert_eq!(real("SELECT log(2, 8)"), Some(3.0));
+        assert_eq!(real("SELECT pow(2, 10)"), Some(1024.0));
+        assert_eq!(real("SELECT sqrt(-1)"), None);
+        assert_eq!(real("SELECT sqrt(NULL)"), None);
+
+        let int: i64 = conn.query_row("SELECT floor(7)", [], |row| row.get(0)).unwrap();
+        assert_eq!(int, 7);
+        assert_eq!(real("SELECT ceil(1.2)"), Some(2.0));
+    }
+
+    #[test]
+    fn test_helpers() {
+        let conn = Connection::open_in_memory().unwrap();
+        register_helpers(&conn).unwrap();
+
+        let sql = "SELECT uuid(), unixepoch_ms(), lower_unicode('ÄÖÜ Straße')";
+        let (id, now, lower): (String, i64, String) = conn
+            .query_row(sql, [], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap();
+        assert!(uuid::Uuid::parse_str(&id).is_ok());
+        assert!(now > 1_600_000_000_000);
+        assert_eq!(lower, "äöü straße");
+    }
+
+    #[test]
+    fn test_now() {
+        let conn = Connection::open_in_memory().unwrap();
+        register_now(&conn).unwrap();
+
+        let sql = "SELECT now() AS a, now() AS b, strftime('%Y-%m-%dT%H:%M:%fZ', 'now') AS c";
+        let (a, b, c): (String, String, String) = conn
+            .query_row(sql, [], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap();
+        assert!(a < b);
+        assert_eq!(a.len(), c.len());
+    }
+}
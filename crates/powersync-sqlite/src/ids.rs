@@ -0,0 +1,113 @@
+//! Ids for new rows
+//!
+//! PowerSync tables are keyed by a text `id` the client generates, since
+//! rows are created offline before the server sees them.
+//! [`PowerSyncConnection::insert_with_id`] fills it from the connection's
+//! [`IdGenerator`], so every insert uses the same format instead of each
+//! caller picking its own.
+//!
+//! [`PowerSyncConnection::insert_with_id`]: crate::database::PowerSyncConnection::insert_with_id
+
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::OsRng;
+use serde::Deserialize;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Built-in id formats
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub enum IdFormat {
+    /// Random UUID, as the PowerSync extension's `uuid()` produces
+    #[default]
+    UuidV4,
+    /// Time-ordered UUID, so rows inserted later sort later and indexes
+    /// on `id` stay compact
+    UuidV7,
+    /// 21 random URL-safe characters, shorter than a UUID for the same
+    /// collision resistance
+    Nanoid,
+}
+
+/// Callback generating the id of a new row, given its table
+pub type IdCallback = Arc<dyn Fn(&str) -> String + Send + Sync>;
+
+/// Where the ids of new rows come from
+#[derive(Clone)]
+pub enum IdGenerator {
+    Format(IdFormat),
+    Custom(IdCallback),
+}
+
+impl Default for IdGenerator {
+    fn default() -> Self {
+        Self::Format(IdFormat::default())
+    }
+}
+
+impl From<IdFormat> for IdGenerator {
+    fn from(format: IdFormat) -> Self {
+        Self::Format(format)
+    }
+}
+
+impl IdGenerator {
+    /// Generate ids with `f`, e.g. to prefix them with the table name
+    pub fn custom<F>(f: F) -> Self
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        Self::Custom(Arc::new(f))
+    }
+
+    /// A new id for a row of `table`
+    pub fn generate(&self, table: &str) -> String {
+        match self {
+            Self::Format(IdFormat::UuidV4) => Uuid::new_v4().to_string(),
+            Self::Format(IdFormat::UuidV7) => Uuid::now_v7().to_string(),
+            Self::Format(IdFormat::Nanoid) => nanoid(),
+            Self::Custom(f) => f(table),
+        }
+    }
+}
+
+/// Characters of a nanoid; 64 of them, so each random byte maps to one
+/// without bias
+const NANOID_ALPHABET: &[u8; 64] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz_-";
+
+const NANOID_LEN: usize = 21;
+
+fn nanoid() -> String {
+    let mut random = [0u8; NANOID_LEN];
+    OsRng.fill_bytes(&mut random);
+    random
+        .iter()
+        .map(|byte| NANOID_ALPHABET[(byte & 63) as usize] as char)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formats() {
+        let v4 = IdGenerator::default().generate("todos");
+        assert_eq!(Uuid::parse_str(&v4).unwrap().get_version_num(), 4);
+
+        let v7 = IdGenerator::from(IdFormat::UuidV7);
+        let first = v7.generate("todos");
+        assert_eq!(Uuid::parse_str(&first).unwrap().get_version_num(), 7);
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        assert!(v7.generate("todos") > first);
+
+        let nanoid = IdGenerator::from(IdFormat::Nanoid).generate("todos");
+        assert_eq!(nanoid.len(), NANOID_LEN);
+        assert!(nanoid.bytes().all(|c| NANOID_ALPHABET.contains(&c)));
+
+        let custom = IdGenerator::custom(|table| format!("{}_1", table));
+        assert_eq!(custom.generate("todos"), "todos_1");
+    }
+}
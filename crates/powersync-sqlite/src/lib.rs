@@ -0,0 +1,81 @@
+//! PowerSync-enabled native SQLite connections
+//!
+//! Connection management, PowerSync extension loading and change
+//! notifications, with no Tauri dependency. The Tauri plugin is a thin
+//! command layer over this crate; CLI tools and integration tests can use
+//! it directly.
+
+pub mod bundle;
+pub mod changes;
+pub mod clock;
+pub mod coercion;
+#[cfg(feature = "unicode-collation")]
+pub mod collation;
+pub mod consistency;
+pub mod control;
+pub mod coordinator;
+pub mod correlation;
+pub mod database;
+pub mod devtools;
+pub mod diagnostics;
+pub mod error;
+pub mod export;
+pub mod extension;
+pub mod faults;
+pub mod functions;
+pub mod ids;
+pub mod profiles;
+pub mod queue;
+pub mod reconcile;
+pub mod recovery;
+pub mod retention;
+pub mod schema;
+pub mod soft_delete;
+pub mod sync_rules;
+#[cfg(feature = "test-util")]
+pub mod testing;
+pub mod tuning;
+pub mod undo;
+pub mod uploads;
+pub mod views;
+
+pub use bundle::EncryptedExport;
+pub use changes::{ChangeEvent, ChangeOperation, CheckpointEvent, CommitEvent, TableOpCounts};
+pub use clock::{Clock, Timestamp};
+pub use coercion::ParamCoercion;
+pub use consistency::LocalSyncStatus;
+pub use control::{
+    CheckpointFailure, CheckpointFailureKind, ConnectionEvent, ControlOp, SyncLine,
+};
+pub use coordinator::CrossDatabaseResult;
+pub use database::{
+    AbortIf, ChangeFeedEntry, ConnectionInfo, ConnectionOpener, ConnectionStats,
+    DatabaseLifecycleEvent, DatabaseManager, GuardedTransaction, InvalidUtf8, LifecycleKind,
+    NonFiniteReals, OpenResult, PowerSyncConnection, PowerSyncStatus, SqlParam, Statement,
+    TransactionGuard,
+};
+pub use devtools::{OplogSummary, RedactedCrudEntry, SchemaColumn, SchemaTable, SeedData};
+pub use error::{Error, Result};
+pub use export::{ExportFormat, ExportResult};
+pub use faults::FaultInjection;
+pub use functions::SqlFunctions;
+pub use ids::{IdCallback, IdFormat, IdGenerator};
+pub use queue::{
+    LockParticipant, LockState, LockTimeout, WriteContention, WritePermit, WritePriority,
+    WriteQueue, WriteQueueStats,
+};
+pub use reconcile::{IdChange, LocalDataMigration, LocalDataStrategy, LocalTableStrategy};
+pub use recovery::DatabaseRecovery;
+pub use retention::{RetentionReport, RetentionRule};
+pub use schema::SchemaChange;
+pub use sync_rules::SyncRulesIssue;
+pub use tuning::{ConnectionTuning, Synchronous, TempStore, TuningPreset};
+pub use undo::{UndoState, UndoStep};
+pub use uploads::{
+    ConflictResolution, RetryEntry, UploadConflict, UploadConflictHandler, UploadError,
+    UploadQueueStats,
+};
+pub use views::ViewInfo;
+
+// Re-exported so dependents use the same rusqlite version
+pub use rusqlite;
@@ -0,0 +1,107 @@
+//! Per-user database naming for multi-account apps
+//!
+//! Each profile is a database named `profiles/<user>` (stored as
+//! `profiles/<user>.db` under the app data directory), with an optional
+//! `profiles/<user>/` directory for the profile's other files such as
+//! attachments. User IDs are percent-encoded so any ID maps to a safe,
+//! reversible file name.
+
+use crate::error::{Error, Result};
+use std::path::Path;
+
+/// Directory under the app data directory holding profile databases
+pub const PROFILES_DIR: &str = "profiles";
+
+/// Files SQLite keeps next to a database
+const DATABASE_SUFFIXES: &[&str] = &[".db", ".db-wal", ".db-shm", ".db-journal"];
+
+/// Name of the database holding `user_id`'s profile
+pub fn database_name(user_id: &str) -> Result<String> {
+    if user_id.is_empty() {
+        return Err(Error::InvalidParameter("empty profile user ID".to_string()));
+    }
+    Ok(format!("{}/{}", PROFILES_DIR, encode(user_id)))
+}
+
+/// User IDs of all profiles with a database under `app_data_dir`
+pub fn list(app_data_dir: &Path) -> Result<Vec<String>> {
+    let dir = app_data_dir.join(PROFILES_DIR);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut user_ids = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let file_name = entry?.file_name();
+        let Some(encoded) = file_name.to_str().and_then(|f| f.strip_suffix(".db")) else {
+            continue;
+        };
+        if let Some(user_id) = decode(encoded) {
+            user_ids.push(user_id);
+        }
+    }
+    user_ids.sort();
+    Ok(user_ids)
+}
+
+/// Delete a profile's database files and file directory.
+/// The database must be closed first.
+pub fn remove_files(app_data_dir: &Path, user_id: &str) -> Result<()> {
+    let base = app_data_dir.join(database_name(user_id)?);
+    for suffix in DATABASE_SUFFIXES {
+        let mut path = base.clone().into_os_string();
+        path.push(suffix);
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    if base.is_dir() {
+        std::fs::remove_dir_all(&base)?;
+    }
+    Ok(())
+}
+
+/// Percent-encode everything but ASCII letters, digits, `-` and `_`
+fn encode(user_id: &str) -> String {
+    let mut encoded = String::with_capacity(user_id.len());
+    for byte in user_id.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+fn decode(encoded: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut rest = encoded.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encoding_round_trips() {
+        for user_id in ["alice", "bob@example.com", "../etc/passwd", "ünï cödé", "100%"] {
+            let name = database_name(user_id).unwrap();
+            let encoded = name.strip_prefix("profiles/").unwrap();
+            assert!(!encoded.contains(['/', '.', '\\']), "{}", encoded);
+            assert_eq!(decode(encoded).as_deref(), Some(user_id));
+        }
+        assert!(database_name("").is_err());
+    }
+}
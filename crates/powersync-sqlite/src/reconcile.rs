@@ -0,0 +1,355 @@
+//! Re-homing data created before the user signed in
+//!
+//! Apps that can be used anonymously end up with rows owned by nobody (or by
+//! a placeholder user), possibly in local-only tables, whose ids may collide
+//! with the server's. After the first login, [`migrate`] moves them under the
+//! signed-in user in one transaction: it sets their owner column, optionally
+//! gives them fresh ids, optionally moves them into synced tables, and
+//! rewrites the columns referencing them through a mapping of old to new
+//! ids.
+
+use crate::database::{quote_identifier, quote_literal};
+use crate::error::{Error, Result};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use uuid::Uuid;
+
+/// Old to new ids of the rows being migrated, for the duration of a
+/// migration. The `ps_` prefix keeps it out of change notifications.
+const ID_MAP: &str = "temp.ps_jf_id_map";
+
+/// What [`migrate`] does with the anonymous data
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct LocalDataStrategy {
+    /// The signed-in user, written to each table's `owner_column`
+    pub user_id: Option<String>,
+    /// Owner the rows had before signing in. Rows whose owner column is
+    /// NULL or this are migrated.
+    pub previous_user_id: Option<String>,
+    pub tables: Vec<LocalTableStrategy>,
+}
+
+/// How to migrate the rows of one table
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase", default)]
+pub struct LocalTableStrategy {
+    pub table: String,
+    /// Column holding the row's owner. Without one, every row of the table
+    /// is migrated.
+    pub owner_column: Option<String>,
+    /// Give the rows new UUIDs, e.g. when ids were generated in a way that
+    /// could collide with the server's
+    pub regenerate_ids: bool,
+    /// Synced table to move the rows into, typically from a local-only
+    /// table. Columns are matched by name.
+    pub into: Option<String>,
+    /// Columns holding ids of other migrated tables' rows, by column name,
+    /// rewritten when those ids change
+    pub references: BTreeMap<String, String>,
+}
+
+/// Outcome of [`migrate`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct LocalDataMigration {
+    /// Rows migrated, by table
+    pub rows: BTreeMap<String, i64>,
+    /// Rows that were given new ids
+    pub id_changes: Vec<IdChange>,
+}
+
+/// A row given a new id by [`migrate`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct IdChange {
+    pub table: String,
+    pub old_id: String,
+    pub new_id: String,
+}
+
+/// Migrate the anonymous rows `strategy` describes. Run it in a
+/// transaction: a failure can leave some tables migrated.
+pub(crate) fn migrate(
+    conn: &Connection,
+    strategy: &LocalDataStrategy,
+) -> Result<LocalDataMigration> {
+    let columns = strategy
+        .tables
+        .iter()
+        .map(|table| table_columns(conn, table))
+        .collect::<Result<Vec<_>>>()?;
+
+    conn.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS {0} (
+            table_name TEXT NOT NULL,
+            old_id TEXT NOT NULL,
+            new_id TEXT NOT NULL,
+            PRIMARY KEY (table_name, old_id)
+        );
+        DELETE FROM {0};",
+        ID_MAP
+    ))?;
+    let migration = map_ids(conn, strategy)?;
+    for (table, columns) in strategy.tables.iter().zip(&columns) {
+        rewrite(conn, strategy, table, columns)?;
+    }
+    conn.execute_batch(&format!("DROP TABLE {}", ID_MAP))?;
+    Ok(migration)
+}
+
+/// Columns to copy from `table` (all, or those its `into` table has too),
+/// checking the strategy only names columns that exist
+fn table_columns(conn: &Connection, table: &LocalTableStrategy) -> Result<Vec<String>> {
+    let names = |name: &str| -> Result<BTreeSet<String>> {
+        let mut stmt = conn.prepare("SELECT name FROM pragma_table_info(?)")?;
+        let names = stmt
+            .query_map([name], |row| row.get(0))?
+            .collect::<rusqlite::Result<BTreeSet<String>>>()?;
+        if names.is_empty() {
+            return Err(Error::InvalidParameter(format!("No such table: {}", name)));
+        }
+        Ok(names)
+    };
+    let source = names(&table.table)?;
+    let named = std::iter::once("id")
+        .chain(table.owner_column.as_deref())
+        .chain(table.references.keys().map(String::as_str));
+    for column in named {
+        if !source.contains(column) {
+            return Err(Error::InvalidParameter(format!(
+                "{} has no column {}",
+                table.table, column
+            )));
+        }
+    }
+    match &table.into {
+        Some(into) => Ok(source.intersection(&names(into)?).cloned().collect()),
+        None => Ok(source.into_iter().collect()),
+    }
+}
+
+/// Fill the id map with every row to migrate, returning the row counts and
+/// the ids that change
+fn map_ids(conn: &Connection, strategy: &LocalDataStrategy) -> Result<LocalDataMigration> {
+    let mut migration = LocalDataMigration::default();
+    let mut insert = conn.prepare(&format!(
+        "INSERT INTO {} (table_name, old_id, new_id) VALUES (?, ?, ?)",
+        ID_MAP
+    ))?;
+    for table in &strategy.tables {
+        let mut sql = format!("SELECT id FROM {}", quote_identifier(&table.table));
+        if let Some(owner) = &table.owner_column {
+            sql += &format!(" WHERE {0} IS NULL", quote_identifier(owner));
+            if let Some(previous) = &strategy.previous_user_id {
+                sql += &format!(
+                    " OR {} = {}",
+                    quote_identifier(owner),
+                    quote_literal(previous)
+                );
+            }
+        }
+        let mut stmt = conn.prepare(&sql)?;
+        let ids = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        for old_id in &ids {
+            let new_id = if table.regenerate_ids {
+                Uuid::new_v4().to_string()
+            } else {
+                old_id.clone()
+            };
+            insert.execute([&table.table, old_id, &new_id])?;
+            if new_id != *old_id {
+                migration.id_changes.push(IdChange {
+                    table: table.table.clone(),
+                    old_id: old_id.clone(),
+                    new_id,
+                });
+            }
+        }
+        migration.rows.insert(table.table.clone(), ids.len() as i64);
+    }
+    Ok(migration)
+}
+
+/// Apply the id map, owner and references to the mapped rows of one table,
+/// in place, or by moving them to their new ids or `into` table
+fn rewrite(
+    conn: &Connection,
+    strategy: &LocalDataStrategy,
+    table: &LocalTableStrategy,
+    columns: &[String],
+) -> Result<()> {
+    let mapped = |referenced: &str, column: &str| {
+        format!(
+            "coalesce((SELECT new_id FROM {} WHERE table_name = {} AND old_id = t.{column}), \
+             t.{column})",
+            ID_MAP,
+            quote_literal(referenced),
+            column = quote_identifier(column)
+        )
+    };
+    let owner = match (&table.owner_column, &strategy.user_id) {
+        (Some(column), Some(user_id)) => Some((column.as_str(), quote_literal(user_id))),
+        _ => None,
+    };
+    let source = quote_identifier(&table.table);
+    let migrated = format!(
+        "SELECT old_id FROM {} WHERE table_name = {}",
+        ID_MAP,
+        quote_literal(&table.table)
+    );
+
+    if table.into.is_some() || table.regenerate_ids {
+        let values = columns.iter().map(|column| match owner {
+            Some((owner, ref user_id)) if owner == column => user_id.clone(),
+            _ if column == "id" => mapped(&table.table, column),
+            _ => match table.references.get(column) {
+                Some(referenced) => mapped(referenced, column),
+                None => format!("t.{}", quote_identifier(column)),
+            },
+        });
+        let target = quote_identifier(table.into.as_deref().unwrap_or(&table.table));
+        conn.execute(
+            &format!(
+                "INSERT INTO {} ({}) SELECT {} FROM {} t WHERE t.id IN ({})",
+                target,
+                columns
+                    .iter()
+                    .map(|c| quote_identifier(c))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                values.collect::<Vec<_>>().join(", "),
+                source,
+                migrated
+            ),
+            [],
+        )?;
+        // New ids never appear among the old ones, so this keeps the copies
+        conn.execute(
+            &format!("DELETE FROM {} WHERE id IN ({})", source, migrated),
+            [],
+        )?;
+        return Ok(());
+    }
+
+    let mut assignments: Vec<_> = table
+        .references
+        .iter()
+        .map(|(column, referenced)| {
+            format!(
+                "{} = {}",
+                quote_identifier(column),
+                mapped(referenced, column)
+            )
+        })
+        .collect();
+    if let Some((column, user_id)) = owner {
+        assignments.push(format!("{} = {}", quote_identifier(column), user_id));
+    }
+    if assignments.is_empty() {
+        return Ok(());
+    }
+    conn.execute(
+        &format!(
+            "UPDATE {} AS t SET {} WHERE t.id IN ({})",
+            source,
+            assignments.join(", "),
+            migrated
+        ),
+        [],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(conn: &Connection, sql: &str) -> Vec<Vec<Option<String>>> {
+        let mut stmt = conn.prepare(sql).unwrap();
+        let width = stmt.column_count();
+        stmt.query_map([], |row| (0..width).map(|i| row.get(i)).collect())
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_migrate() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE lists (id TEXT PRIMARY KEY, owner_id TEXT, name TEXT);
+             CREATE TABLE draft_todos (id TEXT PRIMARY KEY, list_id TEXT, note TEXT);
+             CREATE TABLE todos (id TEXT PRIMARY KEY, list_id TEXT);
+             INSERT INTO lists VALUES ('1', NULL, 'Mine'), ('2', 'anon', 'Also mine'),
+                 ('3', 'bob', 'Synced');
+             INSERT INTO draft_todos VALUES ('a', '1', 'x'), ('b', '3', 'y');",
+        )
+        .unwrap();
+        let strategy = LocalDataStrategy {
+            user_id: Some("alice".to_string()),
+            previous_user_id: Some("anon".to_string()),
+            tables: vec![
+                LocalTableStrategy {
+                    table: "lists".to_string(),
+                    owner_column: Some("owner_id".to_string()),
+                    regenerate_ids: true,
+                    ..Default::default()
+                },
+                LocalTableStrategy {
+                    table: "draft_todos".to_string(),
+                    into: Some("todos".to_string()),
+                    references: [("list_id".to_string(), "lists".to_string())].into(),
+                    ..Default::default()
+                },
+            ],
+        };
+
+        let migration = migrate(&conn, &strategy).unwrap();
+        assert_eq!(migration.rows["lists"], 2);
+        assert_eq!(migration.rows["draft_todos"], 2);
+        assert_eq!(migration.id_changes.len(), 2);
+        let new_id = |old: &str| {
+            let change = migration
+                .id_changes
+                .iter()
+                .find(|c| c.old_id == old)
+                .unwrap();
+            Some(change.new_id.clone())
+        };
+
+        let lists = rows(&conn, "SELECT id, owner_id FROM lists ORDER BY name");
+        assert_eq!(lists.len(), 3);
+        assert!(lists.contains(&vec![new_id("1"), Some("alice".to_string())]));
+        assert!(lists.contains(&vec![Some("3".to_string()), Some("bob".to_string())]));
+        let todos = rows(&conn, "SELECT id, list_id FROM todos ORDER BY id");
+        assert_eq!(
+            todos,
+            vec![
+                vec![Some("a".to_string()), new_id("1")],
+                vec![Some("b".to_string()), Some("3".to_string())],
+            ]
+        );
+        assert!(rows(&conn, "SELECT * FROM draft_todos").is_empty());
+
+        let unknown = LocalDataStrategy {
+            tables: vec![LocalTableStrategy {
+                table: "lists".to_string(),
+                owner_column: Some("user_id".to_string()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        assert!(matches!(
+            migrate(&conn, &unknown),
+            Err(Error::InvalidParameter(_))
+        ));
+    }
+}
@@ -0,0 +1,110 @@
+//! Recovering from database files SQLite can't read
+//!
+//! A database whose file is damaged (`SQLITE_CORRUPT`) or isn't a database
+//! at all (`SQLITE_NOTADB`) fails to open every time. Recovery moves its
+//! files aside as `<name>.db.corrupt-<timestamp>` (and the same for the
+//! `-wal`/`-shm`/`-journal` files) so they can be inspected or sent with a
+//! bug report, and opens a fresh database in their place. The fresh
+//! database has no sync state, so the next sync downloads everything again.
+//! Changes that weren't uploaded yet are lost with the damaged file.
+
+use crate::error::{Error, Result};
+use crate::schema;
+use rusqlite::{Connection, ErrorCode, OpenFlags};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Files SQLite keeps next to a database file, by suffix
+const COMPANION_SUFFIXES: &[&str] = &["", "-wal", "-shm", "-journal"];
+
+/// A damaged database replaced by a fresh one
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct DatabaseRecovery {
+    /// Why the database couldn't be opened, or `None` if the app asked for
+    /// the recovery
+    pub error: Option<String>,
+    /// Where the damaged database file was moved
+    pub backup_path: PathBuf,
+    /// Whether the schema last applied to the damaged database could be
+    /// read back and applied to the fresh one. If not, the app has to apply
+    /// it before using the database.
+    pub schema_restored: bool,
+}
+
+/// Whether `error` means the database file is damaged or not a database
+pub fn is_corruption(error: &Error) -> bool {
+    let Error::Database(e) = error else {
+        return false;
+    };
+    matches!(
+        e.sqlite_error_code(),
+        Some(ErrorCode::DatabaseCorrupt | ErrorCode::NotADatabase)
+    )
+}
+
+/// The schema last applied to the database at `db_path`, if it can still be
+/// read. Damage is often confined to a few pages, so this is worth a try.
+pub fn salvage_schema(db_path: &Path) -> Option<String> {
+    let conn = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY).ok()?;
+    match schema::applied(&conn) {
+        Ok(applied) => applied.map(|applied| applied.schema),
+        Err(e) => {
+            log::debug!("Can't read the schema of {:?}: {}", db_path, e);
+            None
+        }
+    }
+}
+
+/// Move the database file at `db_path` and its companion files aside,
+/// returning where the database file went
+pub fn quarantine(db_path: &Path) -> Result<PathBuf> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis());
+    let mut backup_path = None;
+    for suffix in COMPANION_SUFFIXES {
+        let mut from = db_path.as_os_str().to_owned();
+        from.push(suffix);
+        let mut to = from.clone();
+        to.push(format!(".corrupt-{}", timestamp));
+        match std::fs::rename(&from, &to) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        backup_path.get_or_insert_with(|| PathBuf::from(to));
+    }
+    Ok(backup_path.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_and_quarantines_garbage_file() {
+        let dir = std::env::temp_dir().join(format!("powersync-recovery-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("app.db");
+        std::fs::write(&db_path, vec![0x42; 4096]).unwrap();
+
+        let conn = Connection::open(&db_path).unwrap();
+        let error = Error::from(conn.execute_batch("PRAGMA journal_mode=WAL").unwrap_err());
+        drop(conn);
+        assert!(is_corruption(&error), "{}", error);
+        assert!(!is_corruption(&Error::InvalidParameter("x".to_string())));
+        assert_eq!(salvage_schema(&db_path), None);
+
+        let backup_path = quarantine(&db_path).unwrap();
+        assert!(!db_path.exists());
+        assert_eq!(std::fs::read(&backup_path).unwrap(), vec![0x42; 4096]);
+        assert!(backup_path
+            .to_str()
+            .unwrap()
+            .starts_with(dir.join("app.db.corrupt-").to_str().unwrap()));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
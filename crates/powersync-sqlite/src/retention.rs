@@ -0,0 +1,156 @@
+//! Pruning old rows from local-only and raw tables
+//!
+//! Desktop apps keep local telemetry, logs and drafts for as long as they
+//! are installed. Retention rules bound that: each rule deletes the rows of
+//! one table that are older than a number of days, or all but the newest
+//! rows. Synced tables are refused, as deleting their rows would queue the
+//! deletes for upload.
+
+use crate::database::quote_identifier;
+use crate::error::{Error, Result};
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+/// Rows of a table to delete
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionRule {
+    /// A local-only table of the PowerSync schema, or a raw table
+    pub table: String,
+    /// Column holding when each row was created, as ISO 8601 text or Unix
+    /// seconds. Rows where it is NULL are never deleted by age.
+    pub timestamp_column: String,
+    /// Delete rows older than this many days
+    #[serde(default)]
+    pub max_age_days: Option<u32>,
+    /// Delete all but the newest this many rows
+    #[serde(default)]
+    pub keep_last: Option<u32>,
+}
+
+/// Rows a rule deleted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionReport {
+    pub table: String,
+    pub deleted: i64,
+}
+
+/// Apply `rule`, returning the number of rows it deleted
+pub(crate) fn prune(conn: &Connection, rule: &RetentionRule) -> Result<i64> {
+    if rule.max_age_days.is_none() && rule.keep_last.is_none() {
+        return Err(Error::InvalidParameter(format!(
+            "Retention rule for {} needs maxAgeDays or keepLast",
+            rule.table
+        )));
+    }
+    check_prunable(conn, &rule.table)?;
+
+    let table = quote_identifier(&rule.table);
+    let column = quote_identifier(&rule.timestamp_column);
+    let mut deleted = 0;
+    if let Some(days) = rule.max_age_days {
+        let sql = format!(
+            "DELETE FROM {table} WHERE (CASE typeof({column})
+                WHEN 'text' THEN julianday({column})
+                ELSE julianday({column}, 'unixepoch') END) < julianday('now', ?)",
+        );
+        deleted += conn.execute(&sql, [format!("-{} days", days)])?;
+    }
+    if let Some(keep) = rule.keep_last {
+        // Views of local-only tables have no rowid, but always an id
+        let key = if has_column(conn, &rule.table, "id")? {
+            "id"
+        } else {
+            "rowid"
+        };
+        let sql = format!(
+            "DELETE FROM {table} WHERE {key} NOT IN
+                (SELECT {key} FROM {table} ORDER BY {column} DESC LIMIT ?)",
+        );
+        deleted += conn.execute(&sql, [keep])?;
+    }
+    Ok(deleted as i64)
+}
+
+/// Fail unless `table` is a local-only table or a raw table
+fn check_prunable(conn: &Connection, table: &str) -> Result<()> {
+    let kind = |name: &str| -> Result<Option<String>> {
+        let kind = conn
+            .query_row(
+                "SELECT type FROM sqlite_master WHERE name = ?",
+                [name],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(kind)
+    };
+    if kind(&format!("ps_data_local__{}", table))?.is_some() {
+        return Ok(());
+    }
+    match kind(table)?.as_deref() {
+        Some("table") if !table.starts_with("ps_") => Ok(()),
+        Some(_) => Err(Error::InvalidParameter(format!(
+            "{} is not a local-only or raw table",
+            table
+        ))),
+        None => Err(Error::InvalidParameter(format!("No such table: {}", table))),
+    }
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    let exists = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM pragma_table_info(?) WHERE name = ?)",
+        [table, column],
+        |row| row.get(0),
+    )?;
+    Ok(exists)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(table: &str, max_age_days: Option<u32>, keep_last: Option<u32>) -> RetentionRule {
+        RetentionRule {
+            table: table.to_string(),
+            timestamp_column: "created_at".to_string(),
+            max_age_days,
+            keep_last,
+        }
+    }
+
+    #[test]
+    fn test_prune() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE events (created_at);
+             INSERT INTO events VALUES
+                (datetime('now', '-40 days')),
+                (CAST(strftime('%s', 'now', '-35 days') AS INTEGER)),
+                (datetime('now', '-2 days')),
+                (CAST(strftime('%s', 'now', '-1 days') AS INTEGER)),
+                (datetime('now')),
+                (NULL);
+             CREATE TABLE ps_data__todos (id, data);",
+        )
+        .unwrap();
+
+        assert_eq!(prune(&conn, &rule("events", Some(30), None)).unwrap(), 2);
+        assert_eq!(prune(&conn, &rule("events", None, Some(2))).unwrap(), 2);
+        let remaining: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM events WHERE created_at IS NOT NULL",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(remaining, 2);
+
+        assert!(prune(&conn, &rule("events", None, None)).is_err());
+        assert!(prune(&conn, &rule("ps_data__todos", None, Some(1))).is_err());
+        assert!(prune(&conn, &rule("missing", None, Some(1))).is_err());
+    }
+}
@@ -0,0 +1,175 @@
+//! Tracking of the applied PowerSync schema
+//!
+//! Apps call `replace_schema` on every start, usually with the schema they
+//! applied last time. The plugin records a hash of the last applied schema
+//! JSON in the database, so unchanged schemas are skipped instead of having
+//! their views dropped and recreated, and real changes are numbered and
+//! reported with the views they redefined.
+
+use crate::error::Result;
+use rusqlite::{Connection, OptionalExtension};
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use std::collections::BTreeMap;
+
+/// Plugin table holding the last applied schema. The `ps_` prefix keeps it
+/// out of user table listings and change notifications.
+const SCHEMA_TABLE: &str = "ps_jf_schema";
+
+/// A schema change applied by `replace_schema`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaChange {
+    /// Number of schema changes applied to the database, starting at 1
+    pub version: i64,
+    /// Hash of the schema JSON
+    pub hash: String,
+    /// Views created or redefined by the change
+    pub recreated_views: Vec<String>,
+    /// Views the change dropped
+    pub dropped_views: Vec<String>,
+}
+
+/// The schema last applied to a database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppliedSchema {
+    pub version: i64,
+    pub hash: String,
+    /// Schema JSON as it was applied
+    pub schema: String,
+}
+
+/// Hash of a schema's JSON, ignoring formatting. FNV-1a, so hashes stay
+/// comparable across builds.
+pub fn hash(schema_json: &str) -> Result<String> {
+    let canonical = serde_json::from_str::<JsonValue>(schema_json)?.to_string();
+    let hash = canonical
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+    Ok(format!("{:016x}", hash))
+}
+
+/// The schema last applied to the database, if any
+pub fn applied(conn: &Connection) -> Result<Option<AppliedSchema>> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?)",
+        [SCHEMA_TABLE],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        return Ok(None);
+    }
+    let applied = conn
+        .query_row(
+            &format!(
+                "SELECT version, hash, schema FROM {} WHERE id = 1",
+                SCHEMA_TABLE
+            ),
+            [],
+            |row| {
+                Ok(AppliedSchema {
+                    version: row.get(0)?,
+                    hash: row.get(1)?,
+                    schema: row.get(2)?,
+                })
+            },
+        )
+        .optional()?;
+    Ok(applied)
+}
+
+/// Record `schema_json` as applied, returning its version
+pub(crate) fn record(conn: &Connection, schema_json: &str, hash: &str) -> Result<i64> {
+    conn.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            version INTEGER NOT NULL,
+            hash TEXT NOT NULL,
+            schema TEXT NOT NULL
+        )",
+        SCHEMA_TABLE
+    ))?;
+    let version = conn.query_row(
+        &format!(
+            "INSERT INTO {0} (id, version, hash, schema) VALUES (1, 1, ?1, ?2)
+             ON CONFLICT (id) DO UPDATE SET
+                version = {0}.version + 1, hash = excluded.hash, schema = excluded.schema
+             RETURNING version",
+            SCHEMA_TABLE
+        ),
+        [hash, schema_json],
+        |row| row.get(0),
+    )?;
+    Ok(version)
+}
+
+/// Definitions of all views, by name
+pub(crate) fn views(conn: &Connection) -> Result<BTreeMap<String, String>> {
+    let mut stmt = conn.prepare("SELECT name, sql FROM sqlite_master WHERE type = 'view'")?;
+    let views = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(views)
+}
+
+/// Describe the view changes between two [`views`] listings
+pub(crate) fn change(
+    version: i64,
+    hash: String,
+    before: &BTreeMap<String, String>,
+    after: &BTreeMap<String, String>,
+) -> SchemaChange {
+    SchemaChange {
+        version,
+        hash,
+        recreated_views: after
+            .iter()
+            .filter(|(name, sql)| before.get(*name) != Some(*sql))
+            .map(|(name, _)| name.clone())
+            .collect(),
+        dropped_views: before
+            .keys()
+            .filter(|name| !after.contains_key(*name))
+            .cloned()
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_ignores_formatting() {
+        let compact = hash(r#"{"tables":[{"name":"todos"}]}"#).unwrap();
+        let spaced = hash("{ \"tables\": [ { \"name\": \"todos\" } ] }").unwrap();
+        assert_eq!(compact, spaced);
+        assert_ne!(compact, hash(r#"{"tables":[]}"#).unwrap());
+        assert!(hash("not json").is_err());
+    }
+
+    #[test]
+    fn test_record_and_view_changes() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert_eq!(applied(&conn).unwrap(), None);
+        assert_eq!(record(&conn, "{}", "a").unwrap(), 1);
+        assert_eq!(record(&conn, "[]", "b").unwrap(), 2);
+        let last = applied(&conn).unwrap().unwrap();
+        assert_eq!((last.version, last.hash.as_str()), (2, "b"));
+
+        conn.execute_batch("CREATE VIEW kept AS SELECT 1; CREATE VIEW gone AS SELECT 1")
+            .unwrap();
+        let before = views(&conn).unwrap();
+        conn.execute_batch(
+            "DROP VIEW gone; DROP VIEW kept; CREATE VIEW kept AS SELECT 1;
+             CREATE VIEW added AS SELECT 2",
+        )
+        .unwrap();
+        let change = change(3, "c".to_string(), &before, &views(&conn).unwrap());
+        assert_eq!(change.recreated_views, vec!["added"]);
+        assert_eq!(change.dropped_views, vec!["gone"]);
+    }
+}
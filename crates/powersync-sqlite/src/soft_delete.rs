@@ -0,0 +1,368 @@
+//! Soft delete settings
+//!
+//! Which column tombstones the rows of a table is recorded in the database
+//! itself, so soft delete stays enabled across restarts and travels with
+//! snapshots and exports.
+//!
+//! Tombstoned rows are filtered out of the PowerSync-generated view itself.
+//! The generated view is kept under a hidden name and the table's view is
+//! replaced with one selecting the live rows from it, with the generated
+//! `INSTEAD OF` triggers moved across so writes still reach the sync
+//! tables. Replacing the schema recreates the generated views, so the
+//! filters are applied again by [`reapply`] afterwards.
+
+use crate::database::quote_identifier;
+use crate::error::{Error, Result};
+use rusqlite::{Connection, OptionalExtension};
+
+/// Plugin table holding the tombstone column of each table. The `ps_`
+/// prefix keeps it out of user table listings and change notifications.
+const SOFT_DELETE_TABLE: &str = "ps_jf_soft_delete";
+
+/// Single-row table switching the filters off while tombstoned rows are
+/// restored or purged
+const STATE_TABLE: &str = "ps_jf_soft_delete_state";
+
+/// Prefix of the hidden copies of the generated views
+const UNFILTERED_PREFIX: &str = "ps_jf_unfiltered__";
+
+/// Trailing comment the extension marks its views with. Keeping it on both
+/// views lets the extension drop them when the table leaves the schema.
+const GENERATED_MARKER: &str = " -- powersync-auto-generated";
+
+/// Tombstone column of `table`, if soft delete was enabled for it
+pub(crate) fn column(conn: &Connection, table: &str) -> Result<Option<String>> {
+    if !exists(conn, "table", SOFT_DELETE_TABLE)? {
+        return Ok(None);
+    }
+    let column = conn
+        .query_row(
+            &format!("SELECT col FROM {} WHERE tbl = ?", SOFT_DELETE_TABLE),
+            [table],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(column)
+}
+
+/// Filter rows tombstoned in `column` out of the view of `table`, and
+/// record `column` as its tombstone column. Nothing changes if either step
+/// fails.
+pub(crate) fn enable(conn: &Connection, table: &str, column: &str) -> Result<()> {
+    in_savepoint(conn, || {
+        ensure_tables(conn)?;
+        filter(conn, table, column)?;
+        record(conn, table, column)
+    })
+}
+
+/// Filter the views of every table soft delete is enabled for again, after
+/// the extension recreated them. Tables no longer in the schema are skipped.
+pub(crate) fn reapply(conn: &Connection) -> Result<()> {
+    if !exists(conn, "table", SOFT_DELETE_TABLE)? {
+        return Ok(());
+    }
+    let mut stmt = conn.prepare(&format!("SELECT tbl, col FROM {}", SOFT_DELETE_TABLE))?;
+    let tables = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    in_savepoint(conn, || {
+        ensure_tables(conn)?;
+        for (table, column) in &tables {
+            if exists(conn, "view", table)? {
+                filter(conn, table, column)?;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Run `f` with tombstoned rows visible, for writes that must reach them
+pub(crate) fn with_deleted<T>(conn: &Connection, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    if !exists(conn, "table", STATE_TABLE)? {
+        return f();
+    }
+    in_savepoint(conn, || {
+        conn.execute(&format!("UPDATE {} SET show_deleted = 1", STATE_TABLE), [])?;
+        let value = f()?;
+        conn.execute(&format!("UPDATE {} SET show_deleted = 0", STATE_TABLE), [])?;
+        Ok(value)
+    })
+}
+
+/// Replace the view of `table` with one filtering on `column`, keeping the
+/// view it replaces (or the one it filtered already) as the unfiltered copy
+fn filter(conn: &Connection, table: &str, column: &str) -> Result<()> {
+    let sql: Option<String> = conn
+        .query_row(
+            "SELECT sql FROM sqlite_master WHERE type = 'view' AND name = ?",
+            [table],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let Some(sql) = sql else {
+        return Err(Error::InvalidParameter(format!(
+            "{} is not a PowerSync view",
+            table
+        )));
+    };
+
+    let unfiltered = quote_identifier(&format!("{}{}", UNFILTERED_PREFIX, table));
+    let filtered_prefix = format!(
+        "CREATE VIEW {} AS SELECT * FROM {} WHERE ",
+        quote_identifier(table),
+        unfiltered
+    );
+    if !sql.starts_with(&filtered_prefix) {
+        conn.execute(&format!("DROP VIEW IF EXISTS {}", unfiltered), [])?;
+        conn.execute(&rename_view(&sql, &unfiltered)?, [])?;
+    }
+    let known: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM pragma_table_info(?) WHERE name = ? COLLATE NOCASE)",
+        [format!("{}{}", UNFILTERED_PREFIX, table).as_str(), column],
+        |row| row.get(0),
+    )?;
+    if !known {
+        return Err(Error::InvalidParameter(format!(
+            "{} has no column {}",
+            table, column
+        )));
+    }
+
+    // Dropping the view drops its triggers, so they are created again on
+    // the filtered one
+    let mut stmt = conn.prepare(
+        "SELECT sql FROM sqlite_master WHERE type = 'trigger' AND tbl_name = ? ORDER BY rowid",
+    )?;
+    let triggers = stmt
+        .query_map([table], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    conn.execute(&format!("DROP VIEW {}", quote_identifier(table)), [])?;
+    conn.execute(
+        &format!(
+            "{}{} IS NULL OR (SELECT show_deleted FROM {}){}",
+            filtered_prefix,
+            quote_identifier(column),
+            STATE_TABLE,
+            GENERATED_MARKER
+        ),
+        [],
+    )?;
+    for trigger in &triggers {
+        conn.execute(trigger, [])?;
+    }
+    Ok(())
+}
+
+/// `sql` of a view as SQLite stores it, with the view renamed to `name`
+/// (already quoted)
+fn rename_view(sql: &str, name: &str) -> Result<String> {
+    let malformed = || Error::InvalidParameter(format!("Unexpected view definition: {}", sql));
+    let rest = sql.strip_prefix("CREATE VIEW ").ok_or_else(malformed)?;
+    let len = identifier_len(rest).ok_or_else(malformed)?;
+    Ok(format!("CREATE VIEW {}{}", name, &rest[len..]))
+}
+
+/// Length of the identifier `sql` starts with, quoted or not
+fn identifier_len(sql: &str) -> Option<usize> {
+    let close = match sql.chars().next()? {
+        '"' => '"',
+        '`' => '`',
+        '[' => ']',
+        _ => {
+            let len = sql
+                .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+                .unwrap_or(sql.len());
+            return (len > 0).then_some(len);
+        }
+    };
+    let mut chars = sql.char_indices().skip(1).peekable();
+    while let Some((i, c)) = chars.next() {
+        if c != close {
+            continue;
+        }
+        // Quotes are doubled inside a quoted identifier
+        if close != ']' && chars.peek().is_some_and(|&(_, next)| next == close) {
+            chars.next();
+            continue;
+        }
+        return Some(i + c.len_utf8());
+    }
+    None
+}
+
+fn exists(conn: &Connection, kind: &str, name: &str) -> Result<bool> {
+    let exists = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = ? AND name = ?)",
+        [kind, name],
+        |row| row.get(0),
+    )?;
+    Ok(exists)
+}
+
+fn ensure_tables(conn: &Connection) -> Result<()> {
+    conn.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS {soft_delete} (
+            tbl TEXT PRIMARY KEY,
+            col TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS {state} (show_deleted INTEGER NOT NULL);
+        INSERT INTO {state} (show_deleted) SELECT 0 WHERE NOT EXISTS (SELECT 1 FROM {state});",
+        soft_delete = SOFT_DELETE_TABLE,
+        state = STATE_TABLE
+    ))?;
+    Ok(())
+}
+
+fn record(conn: &Connection, table: &str, column: &str) -> Result<()> {
+    conn.execute(
+        &format!(
+            "INSERT INTO {} (tbl, col) VALUES (?1, ?2)
+             ON CONFLICT (tbl) DO UPDATE SET col = excluded.col",
+            SOFT_DELETE_TABLE
+        ),
+        [table, column],
+    )?;
+    Ok(())
+}
+
+/// Run `f` in a savepoint, rolling back everything it did if it fails
+fn in_savepoint<T>(conn: &Connection, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    conn.execute_batch("SAVEPOINT ps_jf_soft_delete")?;
+    match f() {
+        Ok(value) => {
+            conn.execute_batch("RELEASE SAVEPOINT ps_jf_soft_delete")?;
+            Ok(value)
+        }
+        Err(e) => {
+            let _ = conn.execute_batch("ROLLBACK TO SAVEPOINT ps_jf_soft_delete");
+            let _ = conn.execute_batch("RELEASE SAVEPOINT ps_jf_soft_delete");
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A sync table with a view and delete trigger shaped like the
+    /// extension's
+    fn generated_view(conn: &Connection) {
+        conn.execute_batch(
+            "CREATE TABLE ps_data__todos (id TEXT PRIMARY KEY, data TEXT);
+             CREATE VIEW \"todos\"(\"id\", \"deleted_at\", \"archived_at\") AS SELECT id,
+                 json_extract(data, '$.deleted_at'), json_extract(data, '$.archived_at')
+                 FROM \"ps_data__todos\" -- powersync-auto-generated
+             ;
+             CREATE TRIGGER \"ps_view_delete_todos\" INSTEAD OF DELETE ON \"todos\"
+                 FOR EACH ROW BEGIN DELETE FROM ps_data__todos WHERE id = OLD.id; END;
+             INSERT INTO ps_data__todos VALUES
+                 ('a', '{}'), ('b', '{\"deleted_at\": \"2024-01-01\"}');",
+        )
+        .unwrap();
+    }
+
+    fn count(conn: &Connection, sql: &str) -> i64 {
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_enable() {
+        let conn = Connection::open_in_memory().unwrap();
+        generated_view(&conn);
+        assert_eq!(column(&conn, "todos").unwrap(), None);
+        enable(&conn, "todos", "deleted_at").unwrap();
+        assert_eq!(count(&conn, "SELECT count(*) FROM todos"), 1);
+        enable(&conn, "todos", "archived_at").unwrap();
+        assert_eq!(
+            column(&conn, "todos").unwrap().as_deref(),
+            Some("archived_at")
+        );
+        assert_eq!(count(&conn, "SELECT count(*) FROM todos"), 2);
+        assert_eq!(column(&conn, "lists").unwrap(), None);
+
+        // The generated trigger moved to the filtered view
+        conn.execute("DELETE FROM todos WHERE id = 'a'", [])
+            .unwrap();
+        assert_eq!(count(&conn, "SELECT count(*) FROM ps_data__todos"), 1);
+    }
+
+    #[test]
+    fn test_with_deleted() {
+        let conn = Connection::open_in_memory().unwrap();
+        generated_view(&conn);
+        assert_eq!(with_deleted(&conn, || Ok(1)).unwrap(), 1);
+        enable(&conn, "todos", "deleted_at").unwrap();
+        let all = with_deleted(&conn, || Ok(count(&conn, "SELECT count(*) FROM todos"))).unwrap();
+        assert_eq!(all, 2);
+        assert_eq!(count(&conn, "SELECT count(*) FROM todos"), 1);
+    }
+
+    #[test]
+    fn test_reapply() {
+        let conn = Connection::open_in_memory().unwrap();
+        generated_view(&conn);
+        enable(&conn, "todos", "deleted_at").unwrap();
+
+        // Replacing the schema recreates the generated view unfiltered
+        conn.execute_batch(
+            "DROP VIEW todos;
+             CREATE VIEW \"todos\"(\"id\", \"deleted_at\") AS SELECT id,
+                 json_extract(data, '$.deleted_at') FROM \"ps_data__todos\"
+                 -- powersync-auto-generated",
+        )
+        .unwrap();
+        assert_eq!(count(&conn, "SELECT count(*) FROM todos"), 2);
+        reapply(&conn).unwrap();
+        assert_eq!(count(&conn, "SELECT count(*) FROM todos"), 1);
+    }
+
+    #[test]
+    fn test_enable_rejects_unknown() {
+        let conn = Connection::open_in_memory().unwrap();
+        generated_view(&conn);
+        conn.execute_batch("CREATE TABLE notes (id TEXT PRIMARY KEY, deleted_at TEXT)")
+            .unwrap();
+        assert!(matches!(
+            enable(&conn, "notes", "deleted_at"),
+            Err(Error::InvalidParameter(_))
+        ));
+        assert!(matches!(
+            enable(&conn, "todos", "deleted"),
+            Err(Error::InvalidParameter(_))
+        ));
+        assert_eq!(column(&conn, "todos").unwrap(), None);
+        assert_eq!(count(&conn, "SELECT count(*) FROM todos"), 2);
+    }
+
+    #[test]
+    fn test_failed_enable_changes_nothing() {
+        let conn = Connection::open_in_memory().unwrap();
+        generated_view(&conn);
+        conn.execute_batch("CREATE VIEW ps_jf_soft_delete AS SELECT 'todos' AS tbl, NULL AS col;")
+            .unwrap();
+
+        // Recording fails, so the view filtered before it is rolled back
+        assert!(enable(&conn, "todos", "deleted_at").is_err());
+        assert_eq!(count(&conn, "SELECT count(*) FROM todos"), 2);
+        assert_eq!(
+            count(
+                &conn,
+                "SELECT count(*) FROM sqlite_master WHERE name = 'ps_jf_unfiltered__todos'"
+            ),
+            0
+        );
+    }
+
+    #[test]
+    fn test_identifier_len() {
+        assert_eq!(identifier_len("todos AS"), Some(5));
+        assert_eq!(identifier_len("\"to\"\"dos\"(\"id\")"), Some(9));
+        assert_eq!(identifier_len("[todos] AS"), Some(7));
+        assert_eq!(identifier_len("\"todos"), None);
+        assert_eq!(identifier_len("(id)"), None);
+    }
+}
@@ -0,0 +1,310 @@
+//! Cross-checking sync rules against the local schema
+//!
+//! A table in the client schema that no data query outputs to stays empty
+//! forever, which is easy to miss while setting up. [`lint`] reads the data
+//! queries of a sync rules YAML file (`bucket_definitions` and sync
+//! `streams`) and reports the schema tables and columns they never fill.
+//!
+//! Only the parts of YAML sync rules use are understood: mappings, lists,
+//! and plain, quoted or block scalars holding the queries.
+
+use crate::error::Result;
+use regex::Regex;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::OnceLock;
+
+/// Something in the local schema the sync rules don't line up with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum SyncRulesIssue {
+    /// No data query outputs to this schema table
+    #[serde(rename_all = "camelCase")]
+    TableWithoutData { table: String },
+    /// Data queries output to this table, but none selects this column
+    #[serde(rename_all = "camelCase")]
+    ColumnWithoutData { table: String, column: String },
+    /// A data query outputs to a table the local schema doesn't have
+    #[serde(rename_all = "camelCase")]
+    TableNotInSchema { table: String },
+}
+
+/// Columns a data query fills in its output table
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Output {
+    /// `SELECT *`, filling whichever columns the source row has
+    all: bool,
+    columns: BTreeSet<String>,
+}
+
+/// Check the data queries of `sync_rules` against the synced tables of the
+/// applied schema
+pub fn lint(conn: &Connection, sync_rules: &str) -> Result<Vec<SyncRulesIssue>> {
+    let mut outputs: BTreeMap<String, Output> = BTreeMap::new();
+    for query in data_queries(sync_rules) {
+        if let Some((table, output)) = parse_query(&query) {
+            let entry = outputs.entry(table).or_default();
+            entry.all |= output.all;
+            entry.columns.extend(output.columns);
+        }
+    }
+
+    let schema = synced_tables(conn)?;
+    let mut issues = Vec::new();
+    for (table, columns) in &schema {
+        let Some(output) = outputs.get(&table.to_lowercase()) else {
+            issues.push(SyncRulesIssue::TableWithoutData {
+                table: table.clone(),
+            });
+            continue;
+        };
+        if output.all {
+            continue;
+        }
+        for column in columns {
+            // The id comes from the source row whatever the query selects
+            if column != "id" && !output.columns.contains(&column.to_lowercase()) {
+                issues.push(SyncRulesIssue::ColumnWithoutData {
+                    table: table.clone(),
+                    column: column.clone(),
+                });
+            }
+        }
+    }
+    let local: BTreeSet<String> = schema.keys().map(|table| table.to_lowercase()).collect();
+    for table in outputs.keys().filter(|table| !local.contains(*table)) {
+        issues.push(SyncRulesIssue::TableNotInSchema {
+            table: table.clone(),
+        });
+    }
+    Ok(issues)
+}
+
+/// Schema tables that receive synced data, with their columns.
+///
+/// These are the views PowerSync creates over `ps_data__*` tables; local-only
+/// and insert-only tables are left out.
+fn synced_tables(conn: &Connection) -> Result<BTreeMap<String, Vec<String>>> {
+    let mut stmt = conn.prepare(
+        "SELECT name FROM sqlite_master WHERE type = 'view' AND instr(sql, 'ps_data__') > 0",
+    )?;
+    let views = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut columns = conn.prepare("SELECT name FROM pragma_table_info(?)")?;
+    let mut tables = BTreeMap::new();
+    for view in views {
+        let names = columns
+            .query_map([&view], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        tables.insert(view, names);
+    }
+    Ok(tables)
+}
+
+/// The queries listed under `data:` in bucket definitions, and under
+/// `query:`/`queries:` in streams
+fn data_queries(yaml: &str) -> Vec<String> {
+    let lines: Vec<(usize, &str)> = yaml
+        .lines()
+        .map(|line| {
+            let content = line.trim_start();
+            (line.len() - content.len(), content.trim_end())
+        })
+        .filter(|(_, content)| !content.is_empty() && !content.starts_with('#'))
+        .collect();
+
+    let mut queries = Vec::new();
+    // Indentation of the `data:`/`queries:` key whose list is being read
+    let mut list_indent = None;
+    let mut i = 0;
+    while i < lines.len() {
+        let (indent, content) = lines[i];
+        if list_indent.is_some_and(|list_indent| indent <= list_indent) {
+            list_indent = None;
+        }
+
+        let value = if list_indent.is_some() {
+            content.strip_prefix('-').map(str::trim_start)
+        } else if let Some(value) = key_value(content, &["data", "queries"]) {
+            if value.is_empty() {
+                list_indent = Some(indent);
+                None
+            } else {
+                // A single query on the key's own line
+                Some(value)
+            }
+        } else {
+            key_value(content, &["query"]).filter(|value| !value.is_empty())
+        };
+        i += 1;
+
+        if let Some(value) = value {
+            // A scalar continues on the lines indented past its own
+            let mut parts = vec![value];
+            while i < lines.len() && lines[i].0 > indent && !lines[i].1.starts_with("- ") {
+                parts.push(lines[i].1);
+                i += 1;
+            }
+            let query = parts
+                .into_iter()
+                .filter(|part| !matches!(*part, "|" | ">" | "|-" | ">-" | "|+" | ">+"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            queries.push(unquote(&query).to_string());
+        }
+    }
+    queries
+}
+
+/// The value of `key: value` if the line is one of `keys`
+fn key_value<'a>(content: &'a str, keys: &[&str]) -> Option<&'a str> {
+    let (key, value) = content.split_once(':')?;
+    keys.contains(&key.trim()).then_some(value.trim())
+}
+
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(inner) = value.strip_prefix(quote).and_then(|v| v.strip_suffix(quote)) {
+            return inner;
+        }
+    }
+    value
+}
+
+/// Output table (lowercased) and columns of `SELECT ... FROM table [AS output]`
+fn parse_query(sql: &str) -> Option<(String, Output)> {
+    static SELECT: OnceLock<Regex> = OnceLock::new();
+    let select = SELECT.get_or_init(|| {
+        Regex::new(r#"(?is)^\s*SELECT\s+(.*?)\s+FROM\s+"?([\w.]+)"?(?:\s+AS\s+"?(\w+)"?)?"#)
+            .expect("valid SELECT pattern")
+    });
+    let captures = select.captures(sql)?;
+    let table = match captures.get(3) {
+        Some(alias) => alias.as_str(),
+        // Output tables are named after the source table, without its schema
+        None => captures[2].rsplit('.').next().unwrap_or(&captures[2]),
+    };
+
+    let mut output = Output::default();
+    for item in split_top_level(&captures[1]) {
+        let item = item.trim();
+        if item == "*" || item.ends_with(".*") {
+            output.all = true;
+        } else if let Some(name) = column_name(item) {
+            output.columns.insert(name.to_lowercase());
+        }
+    }
+    Some((table.to_lowercase(), output))
+}
+
+/// Output name of one select list item: its alias, or a bare column reference
+fn column_name(item: &str) -> Option<&str> {
+    static ALIAS: OnceLock<Regex> = OnceLock::new();
+    let alias = ALIAS.get_or_init(|| {
+        Regex::new(r#"(?is)\s+AS\s+"?(\w+)"?$"#).expect("valid alias pattern")
+    });
+    if let Some(captures) = alias.captures(item) {
+        return captures.get(1).map(|name| name.as_str());
+    }
+    let name = item.rsplit('.').next()?.trim_matches('"');
+    name.chars()
+        .all(|c| c.is_alphanumeric() || c == '_')
+        .then_some(name)
+}
+
+/// Split a select list on commas outside parentheses and quotes
+fn split_top_level(list: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let (mut depth, mut quote, mut start) = (0, None, 0);
+    for (i, c) in list.char_indices() {
+        match (c, quote) {
+            ('\'' | '"', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            (_, Some(_)) => {}
+            ('(', None) => depth += 1,
+            (')', None) => depth -= 1,
+            (',', None) if depth == 0 => {
+                items.push(&list[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&list[start..]);
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYNC_RULES: &str = r#"
+bucket_definitions:
+  user_lists:
+    # Lists the user owns
+    parameters: SELECT request.user_id() AS user_id
+    data:
+      - SELECT * FROM lists WHERE owner_id = bucket.user_id
+      - "SELECT id, description, upper(name) AS title FROM todos WHERE owner_id = bucket.user_id"
+      - >
+        SELECT id, name
+        FROM public.shared AS shared_lists
+streams:
+  archive:
+    query: SELECT id, note FROM archive
+"#;
+
+    #[test]
+    fn test_data_queries() {
+        assert_eq!(
+            data_queries(SYNC_RULES),
+            vec![
+                "SELECT * FROM lists WHERE owner_id = bucket.user_id",
+                "SELECT id, description, upper(name) AS title FROM todos WHERE owner_id = bucket.user_id",
+                "SELECT id, name FROM public.shared AS shared_lists",
+                "SELECT id, note FROM archive",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lint() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE ps_data__lists (id, data);
+             CREATE TABLE ps_data__todos (id, data);
+             CREATE TABLE ps_data__comments (id, data);
+             CREATE TABLE ps_data_local__drafts (id, data);
+             CREATE VIEW lists (id, name) AS SELECT id, data FROM ps_data__lists;
+             CREATE VIEW todos (id, description, title, done) AS
+                 SELECT id, data, data, data FROM ps_data__todos;
+             CREATE VIEW comments (id, body) AS SELECT id, data FROM ps_data__comments;
+             CREATE VIEW drafts (id, body) AS SELECT id, data FROM ps_data_local__drafts;",
+        )
+        .unwrap();
+
+        let issues = lint(&conn, SYNC_RULES).unwrap();
+        let table = |name: &str| name.to_string();
+        assert_eq!(
+            issues,
+            vec![
+                SyncRulesIssue::TableWithoutData {
+                    table: table("comments")
+                },
+                SyncRulesIssue::ColumnWithoutData {
+                    table: table("todos"),
+                    column: table("done")
+                },
+                SyncRulesIssue::TableNotInSchema {
+                    table: table("archive")
+                },
+                SyncRulesIssue::TableNotInSchema {
+                    table: table("shared_lists")
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! Stand-in PowerSync extension for tests
+//!
+//! Registers Rust implementations of the PowerSync SQL functions and creates
+//! the `ps_crud` and `ps_buckets` tables, so PowerSync code paths can be exercised without
+//! building powersync-sqlite-core. The functions only record their calls;
+//! they don't implement sync.
+
+use crate::database::PowerSyncConnection;
+use crate::error::Result;
+use rusqlite::functions::FunctionFlags;
+use rusqlite::types::ValueRef;
+use rusqlite::Connection;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Value returned by the mock `powersync_rs_version()`
+pub const MOCK_VERSION: &str = "0.0.0-mock";
+
+/// Value returned by the mock `powersync_client_id()`
+pub const MOCK_CLIENT_ID: &str = "00000000-0000-4000-8000-000000000000";
+
+#[derive(Debug, Default)]
+struct MockState {
+    schema: Option<String>,
+    reject_schema: bool,
+    control_calls: Vec<(String, String)>,
+    control_response: Option<String>,
+    last_synced_at: Option<String>,
+}
+
+/// Handle to inspect and steer a mock extension after installation
+#[derive(Clone, Default)]
+pub struct MockExtension {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockExtension {
+    /// Register the mock functions and internal tables on a connection
+    pub fn install(conn: &Connection) -> Result<Self> {
+        let mock = Self::default();
+
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS ps_crud (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                tx_id INTEGER,
+                data TEXT
+            );
+            CREATE TABLE IF NOT EXISTS ps_buckets (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                last_applied_op INTEGER NOT NULL DEFAULT 0,
+                last_op INTEGER NOT NULL DEFAULT 0,
+                target_op INTEGER NOT NULL DEFAULT 0
+            );",
+        )?;
+
+        let flags = FunctionFlags::SQLITE_UTF8;
+
+        conn.create_scalar_function("powersync_init", 0, flags, |_| Ok(None::<String>))?;
+
+        conn.create_scalar_function("powersync_rs_version", 0, flags, |_| {
+            Ok(MOCK_VERSION.to_string())
+        })?;
+
+        conn.create_scalar_function("powersync_client_id", 0, flags, |_| {
+            Ok(MOCK_CLIENT_ID.to_string())
+        })?;
+
+        let state = Arc::clone(&mock.state);
+        conn.create_scalar_function("powersync_replace_schema", 1, flags, move |ctx| {
+            let schema: String = ctx.get(0)?;
+            let mut state = state.lock().unwrap();
+            if state.reject_schema {
+                return Err(rusqlite::Error::UserFunctionError("schema rejected".into()));
+            }
+            state.schema = Some(schema);
+            Ok(None::<String>)
+        })?;
+
+        let state = Arc::clone(&mock.state);
+        conn.create_scalar_function("powersync_control", 2, flags, move |ctx| {
+            let op: String = ctx.get(0)?;
+            let payload = match ctx.get_raw(1) {
+                ValueRef::Text(text) | ValueRef::Blob(text) => {
+                    String::from_utf8_lossy(text).into_owned()
+                }
+                _ => String::new(),
+            };
+            let mut state = state.lock().unwrap();
+            state.control_calls.push((op, payload));
+            Ok(state.control_response.clone().unwrap_or_else(|| "[]".to_string()))
+        })?;
+
+        let state = Arc::clone(&mock.state);
+        conn.create_scalar_function("powersync_last_synced_at", 0, flags, move |_| {
+            Ok(state.lock().unwrap().last_synced_at.clone())
+        })?;
+
+        Ok(mock)
+    }
+
+    /// Open a connection at `path` with the mock extension in place of the real one
+    pub fn open(name: &str, path: &Path) -> Result<(PowerSyncConnection, Self)> {
+        let conn = Connection::open(path)?;
+        let mock = Self::install(&conn)?;
+        let conn = PowerSyncConnection::from_connection(conn, name, path.to_path_buf(), None)?;
+        Ok((conn, mock))
+    }
+
+    /// Schema JSON from the last `powersync_replace_schema` call
+    pub fn schema(&self) -> Option<String> {
+        self.state.lock().unwrap().schema.clone()
+    }
+
+    /// Make `powersync_replace_schema` fail, as it does for an invalid schema
+    pub fn set_reject_schema(&self, reject: bool) {
+        self.state.lock().unwrap().reject_schema = reject;
+    }
+
+    /// All `(op, payload)` pairs passed to `powersync_control`, in order
+    pub fn control_calls(&self) -> Vec<(String, String)> {
+        self.state.lock().unwrap().control_calls.clone()
+    }
+
+    /// Set the instructions returned by `powersync_control` (default `[]`)
+    pub fn set_control_response(&self, instructions: Option<&str>) {
+        self.state.lock().unwrap().control_response = instructions.map(str::to_string);
+    }
+
+    /// Set the value returned by `powersync_last_synced_at`
+    pub fn set_last_synced_at(&self, value: Option<&str>) {
+        self.state.lock().unwrap().last_synced_at = value.map(str::to_string);
+    }
+}
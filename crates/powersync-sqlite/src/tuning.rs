@@ -0,0 +1,205 @@
+//! Performance pragmas applied to each connection
+//!
+//! Unset options keep SQLite's defaults. The Tauri plugin reads these from
+//! its config (`plugins.powersync-jf` in `tauri.conf.json`), so apps don't
+//! have to send PRAGMAs through the validated SQL path.
+//!
+//! A [`TuningPreset`] sets all of them at once for a common situation;
+//! options set explicitly alongside it take precedence.
+
+use rusqlite::Connection;
+use serde::Deserialize;
+
+/// Where SQLite keeps temporary tables and indices
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TempStore {
+    /// As chosen when SQLite was compiled (usually a file)
+    Default,
+    File,
+    Memory,
+}
+
+/// How often SQLite waits for writes to reach the disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Synchronous {
+    Off,
+    /// Sync at checkpoints only. Safe from corruption in WAL mode; a power
+    /// loss may roll back the last commits.
+    Normal,
+    /// Sync on every commit (SQLite's default)
+    Full,
+    Extra,
+}
+
+/// Named sets of tuning options
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum TuningPreset {
+    /// SQLite's defaults, with `synchronous = NORMAL` as recommended for WAL
+    Default,
+    /// Small page cache, no memory mapping and temporary data on disk, with
+    /// frequent checkpoints to keep the WAL small
+    LowMemory,
+    /// Large page cache and memory map, temporary data in memory and rare
+    /// checkpoints, for bulk sync and heavy queries
+    HighThroughput,
+    /// Fewer disk syncs and checkpoints, and a cache large enough to avoid
+    /// re-reading pages
+    BatterySaver,
+}
+
+impl TuningPreset {
+    /// The options this preset sets
+    pub fn tuning(self) -> ConnectionTuning {
+        let tuning = ConnectionTuning {
+            synchronous: Some(Synchronous::Normal),
+            ..Default::default()
+        };
+        match self {
+            TuningPreset::Default => tuning,
+            TuningPreset::LowMemory => ConnectionTuning {
+                mmap_size: Some(0),
+                cache_size: Some(-512),
+                temp_store: Some(TempStore::File),
+                wal_autocheckpoint: Some(250),
+                ..tuning
+            },
+            TuningPreset::HighThroughput => ConnectionTuning {
+                mmap_size: Some(256 * 1024 * 1024),
+                cache_size: Some(-64 * 1024),
+                temp_store: Some(TempStore::Memory),
+                wal_autocheckpoint: Some(10_000),
+                ..tuning
+            },
+            TuningPreset::BatterySaver => ConnectionTuning {
+                mmap_size: Some(64 * 1024 * 1024),
+                cache_size: Some(-16 * 1024),
+                wal_autocheckpoint: Some(4_000),
+                ..tuning
+            },
+        }
+    }
+}
+
+/// Connection tuning options
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ConnectionTuning {
+    /// Starting point for the options not set here
+    pub preset: Option<TuningPreset>,
+    /// `PRAGMA mmap_size`: bytes of the database file to memory-map for reads
+    pub mmap_size: Option<i64>,
+    /// `PRAGMA cache_size`: pages if positive, KiB if negative
+    pub cache_size: Option<i64>,
+    /// `PRAGMA temp_store`
+    pub temp_store: Option<TempStore>,
+    /// `PRAGMA synchronous`
+    pub synchronous: Option<Synchronous>,
+    /// `PRAGMA wal_autocheckpoint`: WAL pages after which a commit
+    /// checkpoints the WAL into the database file, 0 to never
+    pub wal_autocheckpoint: Option<i64>,
+}
+
+impl From<TuningPreset> for ConnectionTuning {
+    fn from(preset: TuningPreset) -> Self {
+        ConnectionTuning {
+            preset: Some(preset),
+            ..Default::default()
+        }
+    }
+}
+
+impl ConnectionTuning {
+    /// The options to apply: those set explicitly, then the preset's
+    pub fn resolved(&self) -> ConnectionTuning {
+        let Some(preset) = self.preset else {
+            return self.clone();
+        };
+        let base = preset.tuning();
+        ConnectionTuning {
+            preset: None,
+            mmap_size: self.mmap_size.or(base.mmap_size),
+            cache_size: self.cache_size.or(base.cache_size),
+            temp_store: self.temp_store.or(base.temp_store),
+            synchronous: self.synchronous.or(base.synchronous),
+            wal_autocheckpoint: self.wal_autocheckpoint.or(base.wal_autocheckpoint),
+        }
+    }
+
+    /// Apply the set options to a connection
+    pub fn apply(&self, conn: &Connection) -> rusqlite::Result<()> {
+        let tuning = self.resolved();
+        if let Some(mmap_size) = tuning.mmap_size {
+            conn.pragma_update(None, "mmap_size", mmap_size)?;
+        }
+        if let Some(cache_size) = tuning.cache_size {
+            conn.pragma_update(None, "cache_size", cache_size)?;
+        }
+        if let Some(temp_store) = tuning.temp_store {
+            let value = match temp_store {
+                TempStore::Default => 0,
+                TempStore::File => 1,
+                TempStore::Memory => 2,
+            };
+            conn.pragma_update(None, "temp_store", value)?;
+        }
+        if let Some(synchronous) = tuning.synchronous {
+            let value = match synchronous {
+                Synchronous::Off => 0,
+                Synchronous::Normal => 1,
+                Synchronous::Full => 2,
+                Synchronous::Extra => 3,
+            };
+            conn.pragma_update(None, "synchronous", value)?;
+        }
+        if let Some(wal_autocheckpoint) = tuning.wal_autocheckpoint {
+            conn.pragma_update(None, "wal_autocheckpoint", wal_autocheckpoint)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pragma(conn: &Connection, name: &str) -> i64 {
+        conn.pragma_query_value(None, name, |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_apply() {
+        let conn = Connection::open_in_memory().unwrap();
+        let defaults = (pragma(&conn, "cache_size"), pragma(&conn, "temp_store"));
+        ConnectionTuning::default().apply(&conn).unwrap();
+        assert_eq!((pragma(&conn, "cache_size"), pragma(&conn, "temp_store")), defaults);
+
+        let tuning: ConnectionTuning =
+            serde_json::from_str(r#"{ "cacheSize": -16000, "tempStore": "memory" }"#).unwrap();
+        tuning.apply(&conn).unwrap();
+        assert_eq!(pragma(&conn, "cache_size"), -16000);
+        assert_eq!(pragma(&conn, "temp_store"), 2);
+    }
+
+    #[test]
+    fn test_preset_with_overrides() {
+        let conn = Connection::open_in_memory().unwrap();
+        let tuning: ConnectionTuning =
+            serde_json::from_str(r#"{ "preset": "low_memory", "walAutocheckpoint": 100 }"#)
+                .unwrap();
+        tuning.apply(&conn).unwrap();
+        assert_eq!(pragma(&conn, "cache_size"), -512);
+        assert_eq!(pragma(&conn, "temp_store"), 1);
+        assert_eq!(pragma(&conn, "synchronous"), 1);
+        assert_eq!(pragma(&conn, "wal_autocheckpoint"), 100);
+
+        ConnectionTuning::from(TuningPreset::HighThroughput)
+            .apply(&conn)
+            .unwrap();
+        assert_eq!(pragma(&conn, "cache_size"), -65536);
+        assert_eq!(pragma(&conn, "wal_autocheckpoint"), 10_000);
+    }
+}
@@ -0,0 +1,581 @@
+//! Undo and redo of local writes
+//!
+//! Document-style apps let users step back through their edits. [`enable`]
+//! installs TEMP triggers on the chosen tables that record the old and new
+//! values of every row a write changes, grouped by transaction: [`undo`]
+//! reverts everything one transaction wrote and [`redo`] writes it again. A
+//! new write after an undo discards the steps it could have redone.
+//!
+//! The history lives in TEMP tables, so it belongs to the connection and is
+//! gone when the database closes. Undo and redo write through the same views
+//! as the app, so PowerSync queues them for upload like any other local
+//! write. Writes applied by sync don't go through the views and aren't
+//! recorded; a row changed by sync since is overwritten with the recorded
+//! values (the last writer wins).
+
+use crate::database::{quote_identifier, quote_literal};
+use crate::error::{Error, Result};
+use rusqlite::functions::FunctionFlags;
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// One entry per row changed: its table and id before and after the write
+/// (NULL before an insert and after a delete)
+const LOG_TABLE: &str = "ps_jf_undo_log";
+/// Column values of each entry. Updates only record the columns they changed.
+const VALUES_TABLE: &str = "ps_jf_undo_values";
+/// Single row switching capture off while undo and redo write
+const STATE_TABLE: &str = "ps_jf_undo_state";
+const TRIGGER_PREFIX: &str = "ps_jf_undo_";
+/// SQL function numbering the open transaction, so its entries share a step
+const STEP_FUNCTION: &str = "ps_jf_undo_step";
+
+/// What [`undo`] or [`redo`] changed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct UndoStep {
+    /// Tables written, by name
+    pub tables: Vec<String>,
+    /// Rows inserted, updated or deleted
+    pub rows: u32,
+}
+
+/// Tables tracked for undo and the steps available
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct UndoState {
+    pub tables: Vec<String>,
+    /// Transactions [`undo`] can revert
+    pub undo_steps: u32,
+    /// Transactions [`redo`] can write again
+    pub redo_steps: u32,
+}
+
+/// Record writes to `tables` from now on, replacing the tables tracked
+/// before and clearing the history. An empty list turns tracking off.
+///
+/// Each table must be a table or view with an `id` column. `commits` is
+/// the connection's commit count, which tells transactions apart.
+pub(crate) fn enable(conn: &Connection, commits: Arc<AtomicU64>, tables: &[String]) -> Result<()> {
+    conn.create_scalar_function(STEP_FUNCTION, 0, FunctionFlags::SQLITE_UTF8, move |_| {
+        Ok(commits.load(Ordering::Relaxed) as i64)
+    })?;
+    in_savepoint(conn, || {
+        ensure_tables(conn)?;
+        drop_triggers(conn)?;
+        conn.execute_batch(&format!(
+            "DELETE FROM {}; DELETE FROM {}; UPDATE {} SET capturing = 1",
+            VALUES_TABLE, LOG_TABLE, STATE_TABLE
+        ))?;
+        for table in tables {
+            create_triggers(conn, table)?;
+        }
+        Ok(())
+    })
+}
+
+/// Install the triggers on `tables` again, keeping the history, e.g. after
+/// a schema change recreated the views. Tables that no longer exist are
+/// skipped.
+pub(crate) fn reinstall(conn: &Connection, tables: &[String]) -> Result<()> {
+    in_savepoint(conn, || {
+        drop_triggers(conn)?;
+        for table in tables {
+            if kind(conn, table)?.is_some() {
+                create_triggers(conn, table)?;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Revert the latest transaction not undone yet, or return `None` if there
+/// is none
+pub(crate) fn undo(conn: &Connection) -> Result<Option<UndoStep>> {
+    if !tables_exist(conn)? {
+        return Ok(None);
+    }
+    let step: Option<i64> = conn.query_row(
+        &format!("SELECT MAX(step) FROM {} WHERE NOT undone", LOG_TABLE),
+        [],
+        |row| row.get(0),
+    )?;
+    step.map(|step| apply(conn, step, true)).transpose()
+}
+
+/// Write the earliest undone transaction again, or return `None` if there
+/// is none
+pub(crate) fn redo(conn: &Connection) -> Result<Option<UndoStep>> {
+    if !tables_exist(conn)? {
+        return Ok(None);
+    }
+    let step: Option<i64> = conn.query_row(
+        &format!("SELECT MIN(step) FROM {} WHERE undone", LOG_TABLE),
+        [],
+        |row| row.get(0),
+    )?;
+    step.map(|step| apply(conn, step, false)).transpose()
+}
+
+/// Steps available for the tracked `tables`
+pub(crate) fn state(conn: &Connection, tables: &[String]) -> Result<UndoState> {
+    let (undo_steps, redo_steps) = if tables_exist(conn)? {
+        conn.query_row(
+            &format!(
+                "SELECT COUNT(DISTINCT CASE WHEN undone THEN NULL ELSE step END),
+                        COUNT(DISTINCT CASE WHEN undone THEN step END)
+                 FROM {}",
+                LOG_TABLE
+            ),
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?
+    } else {
+        (0, 0)
+    };
+    Ok(UndoState {
+        tables: tables.to_vec(),
+        undo_steps,
+        redo_steps,
+    })
+}
+
+/// Undo (or redo) the entries of `step`, latest first (or earliest first)
+fn apply(conn: &Connection, step: i64, undo: bool) -> Result<UndoStep> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT seq, tbl, old_id, new_id FROM {} WHERE step = ? ORDER BY seq {}",
+        LOG_TABLE,
+        if undo { "DESC" } else { "ASC" }
+    ))?;
+    let entries = stmt
+        .query_map([step], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Value>(2)?,
+                row.get::<_, Value>(3)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    in_savepoint(conn, || {
+        conn.execute(&format!("UPDATE {} SET capturing = 0", STATE_TABLE), [])?;
+        let mut tables = BTreeSet::new();
+        for (seq, table, old_id, new_id) in &entries {
+            let (from, to) = if undo {
+                (new_id, old_id)
+            } else {
+                (old_id, new_id)
+            };
+            let values = entry_values(conn, *seq, undo)?;
+            restore(conn, table, from, to, &values)?;
+            tables.insert(table.clone());
+        }
+        conn.execute(
+            &format!("UPDATE {} SET undone = ? WHERE step = ?", LOG_TABLE),
+            params![undo, step],
+        )?;
+        conn.execute(&format!("UPDATE {} SET capturing = 1", STATE_TABLE), [])?;
+        Ok(UndoStep {
+            tables: tables.into_iter().collect(),
+            rows: entries.len() as u32,
+        })
+    })
+}
+
+/// Column values of entry `seq`, before the write if `old` is set
+fn entry_values(conn: &Connection, seq: i64, old: bool) -> Result<Vec<(String, Value)>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT col, {} FROM {} WHERE seq = ? ORDER BY rowid",
+        if old { "old" } else { "new" },
+        VALUES_TABLE
+    ))?;
+    let values = stmt
+        .query_map([seq], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(values)
+}
+
+/// Bring the row with id `from` (NULL if it doesn't exist) to id `to` (NULL
+/// to delete it) and `values`
+fn restore(
+    conn: &Connection,
+    table: &str,
+    from: &Value,
+    to: &Value,
+    values: &[(String, Value)],
+) -> Result<()> {
+    let table = quote_identifier(table);
+    if *to == Value::Null {
+        conn.execute(&format!("DELETE FROM {} WHERE id = ?", table), [from])?;
+        return Ok(());
+    }
+    let current = if *from == Value::Null { to } else { from };
+    let exists: bool = conn.query_row(
+        &format!("SELECT EXISTS (SELECT 1 FROM {} WHERE id = ?)", table),
+        [current],
+        |row| row.get(0),
+    )?;
+    if exists {
+        let set: Vec<_> = values
+            .iter()
+            .filter(|(column, value)| column != "id" || value != current)
+            .collect();
+        if set.is_empty() {
+            return Ok(());
+        }
+        let assignments = set
+            .iter()
+            .map(|(column, _)| format!("{} = ?", quote_identifier(column)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        conn.execute(
+            &format!("UPDATE {} SET {} WHERE id = ?", table, assignments),
+            params_from_iter(set.iter().map(|(_, value)| value).chain([current])),
+        )?;
+    } else if *from == Value::Null {
+        let columns = values
+            .iter()
+            .map(|(column, _)| quote_identifier(column))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let placeholders = vec!["?"; values.len()].join(", ");
+        conn.execute(
+            &format!(
+                "INSERT INTO {} ({}) VALUES ({})",
+                table, columns, placeholders
+            ),
+            params_from_iter(values.iter().map(|(_, value)| value)),
+        )?;
+    }
+    // Otherwise the row was updated and has since been deleted, e.g. by
+    // sync: there is nothing to update
+    Ok(())
+}
+
+/// Whether `table` is a `table` or a `view` in the main database, if it exists
+fn kind(conn: &Connection, table: &str) -> Result<Option<String>> {
+    let kind = conn
+        .query_row(
+            "SELECT type FROM main.sqlite_master
+             WHERE name = ? COLLATE NOCASE AND type IN ('table', 'view')",
+            [table],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(kind)
+}
+
+fn create_triggers(conn: &Connection, table: &str) -> Result<()> {
+    let lower = table.to_ascii_lowercase();
+    if lower.starts_with("ps_") || lower.starts_with("sqlite_") {
+        return Err(Error::InvalidParameter(format!(
+            "Internal tables can't be tracked for undo: {}",
+            table
+        )));
+    }
+    // PowerSync tables are views whose INSTEAD OF triggers write the data
+    let timing = match kind(conn, table)?.as_deref() {
+        Some("view") => "INSTEAD OF",
+        Some(_) => "AFTER",
+        None => {
+            return Err(Error::InvalidParameter(format!(
+                "No table or view named {}",
+                table
+            )))
+        }
+    };
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info(?, 'main') ORDER BY cid")?;
+    let columns = stmt
+        .query_map([table], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    if !columns.iter().any(|column| column == "id") {
+        return Err(Error::InvalidParameter(format!(
+            "{} needs an id column to be tracked for undo",
+            table
+        )));
+    }
+
+    let trigger = |op: &str| quote_identifier(&format!("{}{}_{}", TRIGGER_PREFIX, table, op));
+    let target = format!("main.{}", quote_identifier(table));
+    let capturing = format!("(SELECT capturing FROM {})", STATE_TABLE);
+    // Trigger bodies can't qualify table names; the unqualified names of
+    // the TEMP tables resolve to them first
+    let clear_redo = format!(
+        "DELETE FROM {values} WHERE seq IN (SELECT seq FROM {log} WHERE undone);
+         DELETE FROM {log} WHERE undone;",
+        values = VALUES_TABLE,
+        log = LOG_TABLE
+    );
+    let log = |old_id: &str, new_id: &str| {
+        format!(
+            "INSERT INTO {} (step, tbl, old_id, new_id) VALUES ({}(), {}, {}, {});",
+            LOG_TABLE,
+            STEP_FUNCTION,
+            quote_literal(table),
+            old_id,
+            new_id
+        )
+    };
+    let seq = format!("(SELECT MAX(seq) FROM {})", LOG_TABLE);
+    let all_values = |row: &str, old: bool| {
+        let rows = columns
+            .iter()
+            .map(|column| {
+                let value = format!("{}.{}", row, quote_identifier(column));
+                let (old_value, new_value) = if old {
+                    (value.as_str(), "NULL")
+                } else {
+                    ("NULL", value.as_str())
+                };
+                format!(
+                    "({}, {}, {}, {})",
+                    seq,
+                    quote_literal(column),
+                    old_value,
+                    new_value
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            "INSERT INTO {} (seq, col, old, new) VALUES {};",
+            VALUES_TABLE, rows
+        )
+    };
+    let changed = |column: &str| format!("OLD.{0} IS NOT NEW.{0}", quote_identifier(column));
+    let changed_values = columns
+        .iter()
+        .map(|column| {
+            format!(
+                "INSERT INTO {} (seq, col, old, new)
+                 SELECT {}, {}, OLD.{column}, NEW.{column} WHERE {};",
+                VALUES_TABLE,
+                seq,
+                quote_literal(column),
+                changed(column),
+                column = quote_identifier(column)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let any_changed = columns
+        .iter()
+        .map(|column| changed(column))
+        .collect::<Vec<_>>()
+        .join(" OR ");
+
+    conn.execute_batch(&format!(
+        "CREATE TEMP TRIGGER {insert} {timing} INSERT ON {target}
+         WHEN {capturing}
+         BEGIN
+           {clear_redo}
+           {log_insert}
+           {insert_values}
+         END;
+         CREATE TEMP TRIGGER {update} {timing} UPDATE ON {target}
+         WHEN {capturing} AND ({any_changed})
+         BEGIN
+           {clear_redo}
+           {log_update}
+           {changed_values}
+         END;
+         CREATE TEMP TRIGGER {delete} {timing} DELETE ON {target}
+         WHEN {capturing}
+         BEGIN
+           {clear_redo}
+           {log_delete}
+           {delete_values}
+         END;",
+        insert = trigger("insert"),
+        update = trigger("update"),
+        delete = trigger("delete"),
+        log_insert = log("NULL", "NEW.id"),
+        log_update = log("OLD.id", "NEW.id"),
+        log_delete = log("OLD.id", "NULL"),
+        insert_values = all_values("NEW", false),
+        delete_values = all_values("OLD", true),
+    ))?;
+    Ok(())
+}
+
+fn drop_triggers(conn: &Connection) -> Result<()> {
+    let mut stmt =
+        conn.prepare("SELECT name FROM sqlite_temp_master WHERE type = 'trigger' AND name GLOB ?")?;
+    let triggers = stmt
+        .query_map([format!("{}*", TRIGGER_PREFIX)], |row| {
+            row.get::<_, String>(0)
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for trigger in triggers {
+        conn.execute_batch(&format!("DROP TRIGGER temp.{}", quote_identifier(&trigger)))?;
+    }
+    Ok(())
+}
+
+fn tables_exist(conn: &Connection) -> Result<bool> {
+    let exists = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_temp_master WHERE type = 'table' AND name = ?)",
+        [LOG_TABLE],
+        |row| row.get(0),
+    )?;
+    Ok(exists)
+}
+
+fn ensure_tables(conn: &Connection) -> Result<()> {
+    // Values are untyped so they come back exactly as written
+    conn.execute_batch(&format!(
+        "CREATE TEMP TABLE IF NOT EXISTS {log} (
+            seq INTEGER PRIMARY KEY,
+            step INTEGER NOT NULL,
+            tbl TEXT NOT NULL,
+            old_id,
+            new_id,
+            undone INTEGER NOT NULL DEFAULT 0
+        );
+        CREATE INDEX IF NOT EXISTS temp.{log}_step ON {log} (undone, step);
+        CREATE TEMP TABLE IF NOT EXISTS {values} (
+            seq INTEGER NOT NULL,
+            col TEXT NOT NULL,
+            old,
+            new
+        );
+        CREATE INDEX IF NOT EXISTS temp.{values}_seq ON {values} (seq);
+        CREATE TEMP TABLE IF NOT EXISTS {state} (capturing INTEGER NOT NULL);
+        INSERT INTO {state} (capturing) SELECT 1 WHERE NOT EXISTS (SELECT 1 FROM {state});",
+        log = LOG_TABLE,
+        values = VALUES_TABLE,
+        state = STATE_TABLE
+    ))?;
+    Ok(())
+}
+
+/// Run `f` in a savepoint, rolling back everything it did if it fails
+fn in_savepoint<T>(conn: &Connection, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    conn.execute_batch("SAVEPOINT ps_jf_undo")?;
+    match f() {
+        Ok(value) => {
+            conn.execute_batch("RELEASE SAVEPOINT ps_jf_undo")?;
+            Ok(value)
+        }
+        Err(e) => {
+            let _ = conn.execute_batch("ROLLBACK TO SAVEPOINT ps_jf_undo");
+            let _ = conn.execute_batch("RELEASE SAVEPOINT ps_jf_undo");
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A connection counting commits as the change notifier does
+    fn open() -> (Connection, Arc<AtomicU64>) {
+        let conn = Connection::open_in_memory().unwrap();
+        let commits = Arc::new(AtomicU64::new(0));
+        let counter = Arc::clone(&commits);
+        conn.commit_hook(Some(move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+            false
+        }));
+        conn.execute_batch(
+            "CREATE TABLE todos (id TEXT PRIMARY KEY, title TEXT, done INTEGER, data BLOB);
+             INSERT INTO todos VALUES ('1', 'Buy milk', 0, x'00ff');",
+        )
+        .unwrap();
+        (conn, commits)
+    }
+
+    fn rows(conn: &Connection) -> Vec<(String, String, i64)> {
+        let mut stmt = conn
+            .prepare("SELECT id, title, done FROM todos ORDER BY id")
+            .unwrap();
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .unwrap();
+        rows
+    }
+
+    #[test]
+    fn test_undo_and_redo() {
+        let (conn, commits) = open();
+        enable(&conn, commits, &["todos".to_string()]).unwrap();
+        let before = rows(&conn);
+
+        conn.execute_batch(
+            "BEGIN;
+             INSERT INTO todos VALUES ('2', 'Walk dog', 0, NULL);
+             UPDATE todos SET done = 1 WHERE id = '1';
+             COMMIT;",
+        )
+        .unwrap();
+        conn.execute_batch("DELETE FROM todos WHERE id = '1'")
+            .unwrap();
+        let after = rows(&conn);
+        let state = state(&conn, &["todos".to_string()]).unwrap();
+        assert_eq!((state.undo_steps, state.redo_steps), (2, 0));
+
+        // The delete, then the transaction as a whole
+        assert_eq!(undo(&conn).unwrap().unwrap().rows, 1);
+        let data: Vec<u8> = conn
+            .query_row("SELECT data FROM todos WHERE id = '1'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(data, vec![0x00, 0xff]);
+        let step = undo(&conn).unwrap().unwrap();
+        assert_eq!(step.tables, vec!["todos"]);
+        assert_eq!(step.rows, 2);
+        assert_eq!(rows(&conn), before);
+        assert_eq!(undo(&conn).unwrap(), None);
+
+        redo(&conn).unwrap().unwrap();
+        redo(&conn).unwrap().unwrap();
+        assert_eq!(rows(&conn), after);
+        assert_eq!(redo(&conn).unwrap(), None);
+    }
+
+    #[test]
+    fn test_write_discards_redo() {
+        let (conn, commits) = open();
+        enable(&conn, commits, &["todos".to_string()]).unwrap();
+        conn.execute_batch("UPDATE todos SET title = 'Buy oat milk'")
+            .unwrap();
+        undo(&conn).unwrap().unwrap();
+        conn.execute_batch("UPDATE todos SET done = 1").unwrap();
+
+        let state = state(&conn, &["todos".to_string()]).unwrap();
+        assert_eq!((state.undo_steps, state.redo_steps), (1, 0));
+        undo(&conn).unwrap().unwrap();
+        assert_eq!(
+            rows(&conn),
+            vec![("1".to_string(), "Buy milk".to_string(), 0)]
+        );
+    }
+
+    #[test]
+    fn test_enable_validates_and_disables() {
+        let (conn, commits) = open();
+        conn.execute_batch("CREATE TABLE notes (body TEXT)")
+            .unwrap();
+        for tables in [vec!["missing"], vec!["notes"], vec!["ps_crud"]] {
+            let tables: Vec<String> = tables.into_iter().map(String::from).collect();
+            assert!(enable(&conn, Arc::clone(&commits), &tables).is_err());
+        }
+
+        enable(&conn, Arc::clone(&commits), &["todos".to_string()]).unwrap();
+        conn.execute_batch("UPDATE todos SET done = 1").unwrap();
+        enable(&conn, commits, &[]).unwrap();
+        conn.execute_batch("UPDATE todos SET done = 0").unwrap();
+        assert_eq!(undo(&conn).unwrap(), None);
+    }
+}
@@ -0,0 +1,411 @@
+//! Failed upload tracking
+//!
+//! The app's upload connector reports each failed attempt to upload a
+//! `ps_crud` entry with `report_upload_error`. Failures are kept in the
+//! database, counted per entry, until the entry is removed from the queue,
+//! so "sync issues" screens can show what is stuck and why, across restarts.
+//!
+//! When the backend rejects a transaction outright (e.g. HTTP 409), retrying
+//! it as is would stall the queue forever. A [`ConflictResolution`] settles
+//! it instead: discard it and restore the server's rows, retry it with
+//! transformed data, or drop it from the queue keeping the local rows.
+
+use crate::database::{quote_identifier, CrudEntry};
+use crate::error::{Error, Result};
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value as JsonValue};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Plugin table holding upload failures. The `ps_` prefix keeps it out of
+/// user table listings and change notifications.
+const ERRORS_TABLE: &str = "ps_jf_upload_errors";
+
+/// Failed upload attempts of a pending CRUD entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct UploadError {
+    pub crud_id: i64,
+    pub tx_id: Option<i64>,
+    /// Failed attempts so far, starting at 1
+    pub retries: i64,
+    pub last_error: String,
+    /// Unix time of the first failure, in milliseconds
+    pub first_failed_at: i64,
+    /// Unix time of the latest failure, in milliseconds
+    pub last_failed_at: i64,
+}
+
+/// State of the upload queue
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct UploadQueueStats {
+    /// CRUD entries waiting to be uploaded
+    pub count: i64,
+    /// Pending transactions, counting each entry without a transaction as one
+    pub transactions: i64,
+    /// Pending entries that failed to upload, oldest entry first
+    pub failing: Vec<UploadError>,
+}
+
+/// A CRUD transaction the backend rejected
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct UploadConflict {
+    /// The rejected entries, oldest first
+    pub entries: Vec<CrudEntry>,
+    /// HTTP status of the rejection, if the backend gave one
+    pub status: Option<i64>,
+    pub error: String,
+}
+
+/// How to settle an [`UploadConflict`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(tag = "action", rename_all = "kebab-case")]
+pub enum ConflictResolution {
+    /// Drop the entries and restore the rows they changed to the last
+    /// version synced from the server (deleting rows created locally)
+    Discard,
+    /// Keep the entries queued, replacing the data of those listed, so the
+    /// next upload sends the transformed data
+    Retry {
+        #[serde(default)]
+        entries: Vec<RetryEntry>,
+    },
+    /// Drop the entries but keep the rows as they are locally, until the
+    /// server next changes them
+    KeepLocal,
+}
+
+/// Replacement data for a queued CRUD entry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct RetryEntry {
+    pub crud_id: i64,
+    /// New column values, replacing the entry's `data`
+    pub data: Map<String, JsonValue>,
+}
+
+/// Callback choosing how to settle an upload conflict on a database, or
+/// `None` to leave the entries queued
+pub type UploadConflictHandler =
+    Arc<dyn Fn(&str, &UploadConflict) -> Option<ConflictResolution> + Send + Sync>;
+
+/// Record a failed attempt to upload `crud_id`, returning its updated
+/// failure record
+pub(crate) fn record(conn: &Connection, crud_id: i64, error: &str) -> Result<UploadError> {
+    let tx_id: Option<Option<i64>> = conn
+        .query_row("SELECT tx_id FROM ps_crud WHERE id = ?", [crud_id], |row| {
+            row.get(0)
+        })
+        .optional()?;
+    let Some(tx_id) = tx_id else {
+        return Err(Error::InvalidParameter(format!(
+            "CRUD entry {} is not pending upload",
+            crud_id
+        )));
+    };
+    conn.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+            crud_id INTEGER PRIMARY KEY,
+            retries INTEGER NOT NULL,
+            last_error TEXT NOT NULL,
+            first_failed_at INTEGER NOT NULL,
+            last_failed_at INTEGER NOT NULL
+        )",
+        ERRORS_TABLE
+    ))?;
+    let upload_error = conn.query_row(
+        &format!(
+            "INSERT INTO {0} (crud_id, retries, last_error, first_failed_at, last_failed_at)
+             VALUES (?1, 1, ?2, ?3, ?3)
+             ON CONFLICT (crud_id) DO UPDATE SET
+                retries = {0}.retries + 1,
+                last_error = excluded.last_error,
+                last_failed_at = excluded.last_failed_at
+             RETURNING retries, first_failed_at, last_failed_at",
+            ERRORS_TABLE
+        ),
+        rusqlite::params![crud_id, error, now_ms()],
+        |row| {
+            Ok(UploadError {
+                crud_id,
+                tx_id,
+                retries: row.get(0)?,
+                last_error: error.to_string(),
+                first_failed_at: row.get(1)?,
+                last_failed_at: row.get(2)?,
+            })
+        },
+    )?;
+    Ok(upload_error)
+}
+
+/// Forget the failures of entries up to and including `crud_id`, once they
+/// have left the queue
+pub(crate) fn clear_through(conn: &Connection, crud_id: i64) -> Result<()> {
+    if exists(conn)? {
+        conn.execute(
+            &format!("DELETE FROM {} WHERE crud_id <= ?", ERRORS_TABLE),
+            [crud_id],
+        )?;
+    }
+    Ok(())
+}
+
+/// The queued entries up to and including `crud_id`, rejected with `error`
+pub(crate) fn conflict(
+    conn: &Connection,
+    crud_id: i64,
+    status: Option<i64>,
+    error: &str,
+) -> Result<UploadConflict> {
+    let mut stmt = conn.prepare("SELECT id, tx_id, data FROM ps_crud WHERE id <= ? ORDER BY id")?;
+    let entries: Vec<CrudEntry> = stmt
+        .query_map([crud_id], |row| {
+            Ok(CrudEntry {
+                id: row.get(0)?,
+                tx_id: row.get(1)?,
+                data: row.get(2)?,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    if entries.is_empty() {
+        return Err(Error::InvalidParameter(format!(
+            "CRUD entry {} is not pending upload",
+            crud_id
+        )));
+    }
+    Ok(UploadConflict {
+        entries,
+        status,
+        error: error.to_string(),
+    })
+}
+
+/// Settle `conflict`, all at once or not at all
+pub(crate) fn resolve(
+    conn: &mut Connection,
+    conflict: &UploadConflict,
+    resolution: &ConflictResolution,
+) -> Result<()> {
+    let Some(last) = conflict.entries.last() else {
+        return Ok(());
+    };
+    let tx = conn.savepoint()?;
+    match resolution {
+        ConflictResolution::Discard => {
+            for entry in &conflict.entries {
+                let op: JsonValue = serde_json::from_str(&entry.data)?;
+                if let (Some(row_type), Some(row_id)) = (op["type"].as_str(), op["id"].as_str()) {
+                    restore_row(&tx, row_type, row_id)?;
+                }
+            }
+            tx.execute("DELETE FROM ps_crud WHERE id <= ?", [last.id])?;
+            clear_through(&tx, last.id)?;
+        }
+        ConflictResolution::KeepLocal => {
+            tx.execute("DELETE FROM ps_crud WHERE id <= ?", [last.id])?;
+            clear_through(&tx, last.id)?;
+        }
+        ConflictResolution::Retry { entries } => {
+            for entry in entries {
+                if !conflict.entries.iter().any(|e| e.id == entry.crud_id) {
+                    return Err(Error::InvalidParameter(format!(
+                        "CRUD entry {} is not part of the conflict",
+                        entry.crud_id
+                    )));
+                }
+                tx.execute(
+                    "UPDATE ps_crud SET data = json_set(data, '$.data', json(?)) WHERE id = ?",
+                    rusqlite::params![
+                        JsonValue::Object(entry.data.clone()).to_string(),
+                        entry.crud_id
+                    ],
+                )?;
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Put a row back to its latest version in the oplog, writing the backing
+/// table directly so no new CRUD entry is queued
+fn restore_row(conn: &Connection, row_type: &str, row_id: &str) -> Result<()> {
+    let table = format!("ps_data__{}", row_type);
+    if !table_exists(conn, &table)? {
+        return Ok(());
+    }
+    let synced: Option<Option<String>> = conn
+        .query_row(
+            "SELECT data FROM ps_oplog WHERE row_type = ? AND row_id = ?
+             ORDER BY op_id DESC LIMIT 1",
+            [row_type, row_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let table = quote_identifier(&table);
+    match synced.flatten() {
+        Some(data) => conn.execute(
+            &format!("INSERT OR REPLACE INTO {} (id, data) VALUES (?, ?)", table),
+            [row_id, data.as_str()],
+        )?,
+        None => conn.execute(&format!("DELETE FROM {} WHERE id = ?", table), [row_id])?,
+    };
+    Ok(())
+}
+
+/// Pending entry counts and the failures of entries still pending
+pub(crate) fn stats(conn: &Connection) -> Result<UploadQueueStats> {
+    let (count, transactions) = conn.query_row(
+        "SELECT count(*), count(DISTINCT tx_id) + count(*) - count(tx_id) FROM ps_crud",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    Ok(UploadQueueStats {
+        count,
+        transactions,
+        failing: failing(conn)?,
+    })
+}
+
+fn failing(conn: &Connection) -> Result<Vec<UploadError>> {
+    if !exists(conn)? {
+        return Ok(Vec::new());
+    }
+    let mut stmt = conn.prepare(&format!(
+        "SELECT e.crud_id, c.tx_id, e.retries, e.last_error, e.first_failed_at,
+            e.last_failed_at
+         FROM {} e JOIN ps_crud c ON c.id = e.crud_id
+         ORDER BY e.crud_id",
+        ERRORS_TABLE
+    ))?;
+    let failing = stmt
+        .query_map([], |row| {
+            Ok(UploadError {
+                crud_id: row.get(0)?,
+                tx_id: row.get(1)?,
+                retries: row.get(2)?,
+                last_error: row.get(3)?,
+                first_failed_at: row.get(4)?,
+                last_failed_at: row.get(5)?,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(failing)
+}
+
+fn exists(conn: &Connection) -> Result<bool> {
+    table_exists(conn, ERRORS_TABLE)
+}
+
+fn table_exists(conn: &Connection, table: &str) -> Result<bool> {
+    let exists = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?)",
+        [table],
+        |row| row.get(0),
+    )?;
+    Ok(exists)
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_stats() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE ps_crud (id INTEGER PRIMARY KEY, tx_id INTEGER, data TEXT);
+             INSERT INTO ps_crud (tx_id, data) VALUES (1, '{}'), (1, '{}'), (NULL, '{}')",
+        )
+        .unwrap();
+        let queue = stats(&conn).unwrap();
+        assert_eq!((queue.count, queue.transactions), (3, 2));
+        assert!(queue.failing.is_empty());
+
+        assert_eq!(record(&conn, 2, "timeout").unwrap().retries, 1);
+        let second = record(&conn, 2, "HTTP 500").unwrap();
+        assert_eq!((second.retries, second.tx_id), (2, Some(1)));
+        assert!(record(&conn, 9, "gone").is_err());
+        let failing = stats(&conn).unwrap().failing;
+        assert_eq!(failing, vec![second]);
+
+        conn.execute("DELETE FROM ps_crud WHERE id <= 2", [])
+            .unwrap();
+        clear_through(&conn, 2).unwrap();
+        assert!(stats(&conn).unwrap().failing.is_empty());
+    }
+
+    #[test]
+    fn test_resolve_conflicts() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"CREATE TABLE ps_crud (id INTEGER PRIMARY KEY, tx_id INTEGER, data TEXT);
+               CREATE TABLE ps_oplog (op_id INTEGER, row_type TEXT, row_id TEXT, data TEXT);
+               CREATE TABLE ps_data__todos (id TEXT PRIMARY KEY, data TEXT);
+               INSERT INTO ps_oplog VALUES (1, 'todos', 'a', '{"done":0}');
+               INSERT INTO ps_data__todos VALUES ('a', '{"done":1}'), ('b', '{"done":0}');
+               INSERT INTO ps_crud (tx_id, data) VALUES
+                   (1, '{"op":"PATCH","type":"todos","id":"a","data":{"done":1}}'),
+                   (1, '{"op":"PUT","type":"todos","id":"b","data":{"done":0}}'),
+                   (2, '{"op":"PUT","type":"todos","id":"c","data":{"done":0}}');"#,
+        )
+        .unwrap();
+        let rows = |conn: &Connection| -> Vec<(String, String)> {
+            let mut stmt = conn
+                .prepare("SELECT id, data FROM ps_data__todos ORDER BY id")
+                .unwrap();
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+                .unwrap()
+                .collect::<rusqlite::Result<_>>()
+                .unwrap()
+        };
+
+        let rejected = conflict(&conn, 2, Some(409), "conflict").unwrap();
+        assert_eq!(rejected.entries.len(), 2);
+        let retry = ConflictResolution::Retry {
+            entries: vec![RetryEntry {
+                crud_id: 2,
+                data: serde_json::from_str(r#"{"done":2}"#).unwrap(),
+            }],
+        };
+        resolve(&mut conn, &rejected, &retry).unwrap();
+        let done: i64 = conn
+            .query_row(
+                "SELECT json_extract(data, '$.data.done') FROM ps_crud WHERE id = 2",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(done, 2);
+
+        resolve(&mut conn, &rejected, &ConflictResolution::Discard).unwrap();
+        assert_eq!(
+            rows(&conn),
+            vec![("a".to_string(), r#"{"done":0}"#.to_string())]
+        );
+        assert_eq!(stats(&conn).unwrap().count, 1);
+
+        conn.execute("INSERT INTO ps_data__todos VALUES ('c', '{}')", [])
+            .unwrap();
+        let rejected = conflict(&conn, 3, None, "rejected").unwrap();
+        resolve(&mut conn, &rejected, &ConflictResolution::KeepLocal).unwrap();
+        assert_eq!(rows(&conn).len(), 2);
+        assert_eq!(stats(&conn).unwrap().count, 0);
+        assert!(conflict(&conn, 3, None, "gone").is_err());
+    }
+}
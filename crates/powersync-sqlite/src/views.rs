@@ -0,0 +1,336 @@
+//! App-defined views
+//!
+//! Derived read models (joins, aggregates over synced tables) can be defined
+//! once in the database with [`create`] and queried like tables. Their
+//! definitions are recorded, so [`list`] tells them apart from the views
+//! PowerSync generates for its tables, and [`drop`] only removes views
+//! created this way.
+//!
+//! Watched queries over a view work as over its tables: SQLite reads the
+//! tables under the view, which is what change tracking sees.
+
+use crate::database::quote_identifier;
+use crate::error::{Error, Result};
+use rusqlite::fallible_iterator::FallibleIterator;
+use rusqlite::{Batch, Connection, OptionalExtension};
+use serde::Serialize;
+
+/// Plugin table holding view definitions. The `ps_` prefix keeps it out of
+/// user table listings and change notifications.
+const VIEWS_TABLE: &str = "ps_jf_views";
+
+/// A view created with [`create`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct ViewInfo {
+    pub name: String,
+    /// The SELECT statement defining the view
+    pub select: String,
+    /// Result columns, empty if the view no longer compiles
+    pub columns: Vec<String>,
+    /// Why the view no longer compiles, e.g. a schema change removed a
+    /// table it reads
+    pub error: Option<String>,
+}
+
+/// Create view `name` as `select`, replacing a view of that name created
+/// with this function if `replace` is set. Returns the view with its columns.
+///
+/// `name` must be a plain identifier not starting with `ps_` or `sqlite_`
+/// and not used by a table or another view. `select` must be a single
+/// read-only statement without parameters that compiles against the
+/// current schema. Nothing changes if either check fails.
+pub(crate) fn create(
+    conn: &Connection,
+    name: &str,
+    select: &str,
+    replace: bool,
+) -> Result<ViewInfo> {
+    validate_name(name)?;
+    let select = select.trim().trim_end_matches(';').trim_end();
+    validate_select(conn, select)?;
+
+    let existing: Option<String> = conn
+        .query_row(
+            "SELECT type FROM sqlite_master WHERE name = ? COLLATE NOCASE",
+            [name],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if let Some(kind) = existing {
+        if !replace || !is_managed(conn, name)? {
+            return Err(Error::InvalidParameter(format!(
+                "A {} named {} already exists",
+                kind, name
+            )));
+        }
+    }
+
+    in_savepoint(conn, || {
+        ensure_table(conn)?;
+        let view = quote_identifier(name);
+        conn.execute(&format!("DROP VIEW IF EXISTS {}", view), [])?;
+        conn.execute(&format!("CREATE VIEW {} AS {}", view, select), [])?;
+        let columns = columns(conn, name)?;
+        conn.execute(
+            &format!(
+                "INSERT INTO {} (name, sql) VALUES (?1, ?2)
+                 ON CONFLICT (name) DO UPDATE SET sql = excluded.sql",
+                VIEWS_TABLE
+            ),
+            [name, select],
+        )?;
+        Ok(ViewInfo {
+            name: name.to_string(),
+            select: select.to_string(),
+            columns,
+            error: None,
+        })
+    })
+}
+
+/// Drop view `name`, which must have been created with [`create`]
+pub(crate) fn drop(conn: &Connection, name: &str) -> Result<()> {
+    if !is_managed(conn, name)? {
+        return Err(Error::InvalidParameter(format!(
+            "{} is not a view created with create_view",
+            name
+        )));
+    }
+    in_savepoint(conn, || {
+        conn.execute_batch(&format!("DROP VIEW IF EXISTS {}", quote_identifier(name)))?;
+        conn.execute(
+            &format!("DELETE FROM {} WHERE name = ? COLLATE NOCASE", VIEWS_TABLE),
+            [name],
+        )?;
+        Ok(())
+    })
+}
+
+/// Views created with [`create`], by name
+pub(crate) fn list(conn: &Connection) -> Result<Vec<ViewInfo>> {
+    if !table_exists(conn)? {
+        return Ok(Vec::new());
+    }
+    let mut stmt = conn.prepare(&format!(
+        "SELECT name, sql FROM {} ORDER BY name",
+        VIEWS_TABLE
+    ))?;
+    let views = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(views
+        .into_iter()
+        .map(|(name, select)| {
+            let (columns, error) = match columns(conn, &name) {
+                Ok(columns) => (columns, None),
+                Err(e) => (Vec::new(), Some(e.to_string())),
+            };
+            ViewInfo {
+                name,
+                select,
+                columns,
+                error,
+            }
+        })
+        .collect())
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    let mut chars = name.chars();
+    let plain = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !plain {
+        return Err(Error::InvalidParameter(format!(
+            "View names must be letters, digits and underscores: {:?}",
+            name
+        )));
+    }
+    let lower = name.to_ascii_lowercase();
+    if lower.starts_with("ps_") || lower.starts_with("sqlite_") {
+        return Err(Error::InvalidParameter(format!(
+            "View names starting with ps_ or sqlite_ are reserved: {}",
+            name
+        )));
+    }
+    Ok(())
+}
+
+fn validate_select(conn: &Connection, select: &str) -> Result<()> {
+    let first_word = select
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .next()
+        .unwrap_or_default()
+        .to_ascii_uppercase();
+    if !matches!(first_word.as_str(), "SELECT" | "WITH" | "VALUES") {
+        return Err(Error::InvalidParameter(
+            "A view must be defined by a SELECT statement".to_string(),
+        ));
+    }
+    // Compiling it checks the tables and columns it reads exist. Only the
+    // first statement is compiled, so anything after it other than
+    // whitespace and comments is rejected here.
+    let mut statements = Batch::new(conn, select);
+    let stmt = statements.next()?.ok_or_else(|| {
+        Error::InvalidParameter("A view must be defined by a SELECT statement".to_string())
+    })?;
+    if statements.next()?.is_some() {
+        return Err(Error::InvalidParameter(
+            "A view must be defined by a single statement".to_string(),
+        ));
+    }
+    if !stmt.readonly() {
+        return Err(Error::InvalidParameter(
+            "A view must be defined by a read-only statement".to_string(),
+        ));
+    }
+    if stmt.parameter_count() > 0 {
+        return Err(Error::InvalidParameter(
+            "A view can't take parameters".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn columns(conn: &Connection, name: &str) -> Result<Vec<String>> {
+    let stmt = conn.prepare(&format!("SELECT * FROM {}", quote_identifier(name)))?;
+    Ok(stmt.column_names().into_iter().map(String::from).collect())
+}
+
+fn is_managed(conn: &Connection, name: &str) -> Result<bool> {
+    if !table_exists(conn)? {
+        return Ok(false);
+    }
+    let managed = conn.query_row(
+        &format!(
+            "SELECT EXISTS (SELECT 1 FROM {} WHERE name = ? COLLATE NOCASE)",
+            VIEWS_TABLE
+        ),
+        [name],
+        |row| row.get(0),
+    )?;
+    Ok(managed)
+}
+
+fn table_exists(conn: &Connection) -> Result<bool> {
+    let exists = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?)",
+        [VIEWS_TABLE],
+        |row| row.get(0),
+    )?;
+    Ok(exists)
+}
+
+fn ensure_table(conn: &Connection) -> Result<()> {
+    conn.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+            name TEXT PRIMARY KEY COLLATE NOCASE,
+            sql TEXT NOT NULL
+        )",
+        VIEWS_TABLE
+    ))?;
+    Ok(())
+}
+
+/// Run `f` in a savepoint, rolling back everything it did if it fails
+fn in_savepoint<T>(conn: &Connection, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    conn.execute_batch("SAVEPOINT ps_jf_views")?;
+    match f() {
+        Ok(value) => {
+            conn.execute_batch("RELEASE SAVEPOINT ps_jf_views")?;
+            Ok(value)
+        }
+        Err(e) => {
+            let _ = conn.execute_batch("ROLLBACK TO SAVEPOINT ps_jf_views");
+            let _ = conn.execute_batch("RELEASE SAVEPOINT ps_jf_views");
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE todos (id TEXT PRIMARY KEY, list_id TEXT, done INTEGER);
+             INSERT INTO todos VALUES ('1', 'a', 1), ('2', 'a', 0), ('3', 'b', 0);",
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_create_list_and_drop() {
+        let conn = open();
+        let select = "SELECT list_id, COUNT(*) AS open FROM todos WHERE done = 0 GROUP BY list_id";
+        let view = create(&conn, "open_counts", &format!("{};", select), false).unwrap();
+        assert_eq!(view.columns, vec!["list_id", "open"]);
+        assert_eq!(view.select, select);
+
+        let open: i64 = conn
+            .query_row("SELECT SUM(open) FROM open_counts", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(open, 2);
+
+        // Only replaced when asked
+        assert!(create(&conn, "open_counts", "SELECT 1 AS one", false).is_err());
+        let replaced = create(&conn, "open_counts", "SELECT 1 AS one", true).unwrap();
+        assert_eq!(replaced.columns, vec!["one"]);
+        assert_eq!(list(&conn).unwrap(), vec![replaced]);
+
+        drop(&conn, "open_counts").unwrap();
+        assert!(list(&conn).unwrap().is_empty());
+        assert!(drop(&conn, "open_counts").is_err());
+    }
+
+    #[test]
+    fn test_validation() {
+        let conn = open();
+        conn.execute_batch("CREATE VIEW generated AS SELECT 1")
+            .unwrap();
+        let invalid = [
+            ("todos", "SELECT 1"),
+            ("generated", "SELECT 1"),
+            ("ps_counts", "SELECT 1"),
+            ("bad name", "SELECT 1"),
+            ("counts", "DELETE FROM todos"),
+            ("counts", "SELECT * FROM missing"),
+            ("counts", "SELECT 1; DROP TABLE todos"),
+            ("counts", "SELECT 1; -- trailing\nDROP TABLE todos"),
+            ("counts", "SELECT * FROM todos WHERE id = ?"),
+        ];
+        for (name, select) in invalid {
+            assert!(
+                create(&conn, name, select, true).is_err(),
+                "{} {}",
+                name,
+                select
+            );
+        }
+        // Views it didn't create are left alone
+        assert!(drop(&conn, "generated").is_err());
+        assert!(list(&conn).unwrap().is_empty());
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM todos", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn test_broken_view_reported() {
+        let conn = open();
+        create(&conn, "done", "SELECT id FROM todos WHERE done = 1", false).unwrap();
+        conn.execute_batch("DROP TABLE todos").unwrap();
+        let views = list(&conn).unwrap();
+        assert!(views[0].columns.is_empty());
+        assert!(views[0].error.as_deref().unwrap().contains("todos"));
+    }
+}
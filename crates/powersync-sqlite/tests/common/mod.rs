@@ -0,0 +1,55 @@
+//! Shared helpers for integration tests
+
+// Each test binary uses a different subset of the helpers
+#![allow(dead_code)]
+
+use powersync_sqlite::{DatabaseManager, PowerSyncConnection, SqlParam};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// A DatabaseManager over a throwaway directory, removed on drop
+pub struct TestManager {
+    pub manager: DatabaseManager,
+    dir: PathBuf,
+}
+
+impl TestManager {
+    pub fn new() -> Self {
+        let dir = std::env::temp_dir().join(format!("powersync-sqlite-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        Self {
+            manager: DatabaseManager::new(dir.clone(), None),
+            dir,
+        }
+    }
+
+    /// Path at which the manager would store database `name`
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.db", name))
+    }
+
+    /// Open a database and return its connection
+    pub fn open(&mut self, name: &str) -> Arc<Mutex<PowerSyncConnection>> {
+        self.manager.open(name).unwrap();
+        self.manager.get(name).unwrap()
+    }
+}
+
+impl Drop for TestManager {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Shorthand for a text parameter
+pub fn text(value: &str) -> SqlParam {
+    SqlParam::Text(value.to_string())
+}
+
+/// Count the rows of a table
+pub fn count(conn: &PowerSyncConnection, table: &str) -> i64 {
+    let result = conn
+        .get_all(&format!("SELECT COUNT(*) AS n FROM {}", table), &[])
+        .unwrap();
+    result.rows[0]["n"].as_i64().unwrap()
+}
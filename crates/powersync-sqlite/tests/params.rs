@@ -0,0 +1,169 @@
+//! Value-mapping contract: SqlParam → bind → store → read → JSON
+
+mod common;
+
+use base64::Engine;
+use common::TestManager;
+use powersync_sqlite::{InvalidUtf8, NonFiniteReals, ParamCoercion, PowerSyncConnection, SqlParam};
+use proptest::prelude::*;
+use serde_json::Value as JsonValue;
+
+fn sql_param() -> impl Strategy<Value = SqlParam> {
+    prop_oneof![
+        Just(SqlParam::Null),
+        any::<bool>().prop_map(SqlParam::Bool),
+        any::<i64>().prop_map(SqlParam::Int),
+        any::<f64>().prop_map(SqlParam::Real),
+        any::<String>().prop_map(SqlParam::Text),
+        prop::collection::vec(any::<u8>(), 0..64).prop_map(SqlParam::Blob),
+    ]
+}
+
+/// The JSON value a parameter is expected to read back as
+fn expected_json(param: &SqlParam) -> JsonValue {
+    match param {
+        SqlParam::Null => JsonValue::Null,
+        SqlParam::Bool(b) => JsonValue::from(*b as i64),
+        SqlParam::Int(i) => JsonValue::from(*i),
+        // SQLite stores NaN as NULL, and JSON has no representation for ±Infinity
+        SqlParam::Real(f) if !f.is_finite() => JsonValue::Null,
+        SqlParam::Real(f) => JsonValue::from(*f),
+        SqlParam::Text(s) => JsonValue::from(s.as_str()),
+        SqlParam::Blob(b) => JsonValue::from(base64::engine::general_purpose::STANDARD.encode(b)),
+    }
+}
+
+/// Store a value in an affinity-less column and read it back
+fn round_trip(conn: &mut PowerSyncConnection, param: SqlParam) -> JsonValue {
+    let result = conn
+        .execute("INSERT INTO vals (v) VALUES (?)", &[param])
+        .unwrap();
+    let rows = conn
+        .get_all(
+            "SELECT v FROM vals WHERE rowid = ?",
+            &[SqlParam::Int(result.last_insert_rowid)],
+        )
+        .unwrap();
+    rows.rows[0]["v"].clone()
+}
+
+fn open(harness: &mut TestManager) -> std::sync::Arc<std::sync::Mutex<PowerSyncConnection>> {
+    let conn = harness.open("params");
+    conn.lock()
+        .unwrap()
+        .execute("CREATE TABLE vals (v)", &[])
+        .unwrap();
+    conn
+}
+
+#[test]
+fn test_params_round_trip() {
+    let mut harness = TestManager::new();
+    let conn = open(&mut harness);
+
+    proptest!(|(param in sql_param())| {
+        let expected = expected_json(&param);
+        let mut conn = conn.lock().unwrap();
+        prop_assert_eq!(round_trip(&mut conn, param), expected);
+    });
+}
+
+#[test]
+fn test_param_edge_cases() {
+    let mut harness = TestManager::new();
+    let conn = open(&mut harness);
+    let mut conn = conn.lock().unwrap();
+
+    for param in [
+        SqlParam::Int(i64::MIN),
+        SqlParam::Int(i64::MAX),
+        SqlParam::Real(f64::NAN),
+        SqlParam::Real(f64::INFINITY),
+        SqlParam::Real(f64::NEG_INFINITY),
+        SqlParam::Real(-0.0),
+        SqlParam::Text(String::new()),
+        SqlParam::Text("nul\0inside".to_string()),
+        SqlParam::Blob(Vec::new()),
+    ] {
+        let expected = expected_json(&param);
+        assert_eq!(round_trip(&mut conn, param.clone()), expected, "{:?}", param);
+    }
+}
+
+#[test]
+fn test_invalid_utf8_text_is_replaced() {
+    let mut harness = TestManager::new();
+    let conn = open(&mut harness);
+    let conn = conn.lock().unwrap();
+
+    let rows = conn
+        .get_all("SELECT CAST(x'61ff62' AS TEXT) AS v", &[])
+        .unwrap();
+    assert_eq!(rows.rows[0]["v"], "a\u{FFFD}b");
+}
+
+#[test]
+fn test_invalid_utf8_strategies() {
+    let mut harness = TestManager::new();
+    let conn = open(&mut harness);
+    let mut conn = conn.lock().unwrap();
+    let sql = "SELECT 'ok' AS v UNION ALL SELECT CAST(x'61ff62' AS TEXT)";
+
+    conn.set_invalid_utf8(InvalidUtf8::Blob);
+    let rows = conn.get_all(sql, &[]).unwrap().rows;
+    assert_eq!(rows[0]["v"], "ok");
+    assert_eq!(rows[1]["v"], "Yf9i");
+
+    conn.set_invalid_utf8(InvalidUtf8::Error);
+    let err = conn.get_all(sql, &[]).unwrap_err().to_string();
+    assert!(err.contains("row 1 column v"), "{}", err);
+    assert!(conn.get_optional("SELECT 'ok' AS v", &[]).is_ok());
+}
+
+#[test]
+fn test_non_finite_reals() {
+    let mut harness = TestManager::new();
+    let conn = open(&mut harness);
+    let mut conn = conn.lock().unwrap();
+    let sql = "SELECT CAST(v AS REAL) AS v FROM vals ORDER BY rowid";
+    conn.execute("INSERT INTO vals (v) VALUES (1e999), (-1e999)", &[]).unwrap();
+
+    let rows = conn.get_all(sql, &[]).unwrap().rows;
+    assert_eq!((&rows[0]["v"], &rows[1]["v"]), (&JsonValue::Null, &JsonValue::Null));
+
+    conn.set_non_finite_reals(NonFiniteReals::String);
+    let rows = conn.get_all(sql, &[]).unwrap().rows;
+    assert_eq!(rows[0]["v"], "Infinity");
+    assert_eq!(rows[1]["v"], "-Infinity");
+
+    conn.set_non_finite_reals(NonFiniteReals::Error);
+    assert!(conn.get_all(sql, &[]).is_err());
+    assert!(conn.get_optional(sql, &[]).is_err());
+}
+
+#[test]
+fn test_param_coercion() {
+    let mut harness = TestManager::new();
+    let conn = open(&mut harness);
+    let mut conn = conn.lock().unwrap();
+    conn.execute("CREATE TABLE nums (id TEXT, n INTEGER)", &[]).unwrap();
+    let insert = "INSERT INTO nums (id, n) VALUES (?, ?)";
+    let row = |id: &str, n: &str| vec![SqlParam::Text(id.into()), SqlParam::Text(n.into())];
+
+    conn.set_param_coercion(ParamCoercion::Coerce);
+    conn.execute(insert, &row("1", "5")).unwrap();
+    conn.execute_batch(insert, &[row("2", "6")]).unwrap();
+    conn.insert_many("nums", &["id".into(), "n".into()], &[row("3", "7")]).unwrap();
+    assert!(conn.execute(insert, &row("4", "five")).is_err());
+
+    let rows = conn
+        .get_all("SELECT id, typeof(n) AS t FROM nums ORDER BY id", &[])
+        .unwrap()
+        .rows;
+    assert_eq!(rows.len(), 3);
+    assert!(rows.iter().all(|row| row["id"].is_string() && row["t"] == "integer"));
+
+    conn.set_param_coercion(ParamCoercion::Reject);
+    assert!(conn.execute(insert, &row("5", "8")).is_err());
+    assert!(conn.insert_many("nums", &["id".into(), "n".into()], &[row("5", "8")]).is_err());
+}
@@ -0,0 +1,312 @@
+mod common;
+
+use common::{count, text, TestManager};
+use powersync_sqlite::testing::{MockExtension, MOCK_CLIENT_ID, MOCK_VERSION};
+use powersync_sqlite::{CheckpointFailureKind, ControlOp, Error, PowerSyncConnection, SyncLine};
+
+fn open_mock(harness: &TestManager, name: &str) -> (PowerSyncConnection, MockExtension) {
+    MockExtension::open(name, &harness.path(name)).unwrap()
+}
+
+fn empty_line() -> ControlOp {
+    ControlOp::LineText(SyncLine::new("{}").unwrap())
+}
+
+#[test]
+fn test_extension_functions_reach_mock() {
+    let harness = TestManager::new();
+    let (mut conn, mock) = open_mock(&harness, "functions");
+
+    assert!(conn.is_powersync_loaded());
+    assert_eq!(conn.get_powersync_version().unwrap(), MOCK_VERSION);
+    assert_eq!(conn.get_client_id().unwrap(), MOCK_CLIENT_ID);
+    let status = conn.powersync_status();
+    assert!(status.loaded);
+    assert_eq!(status.version.as_deref(), Some(MOCK_VERSION));
+    assert_eq!(status.load_error, None);
+
+    conn.replace_schema(r#"{"tables":[]}"#).unwrap();
+    assert_eq!(mock.schema().as_deref(), Some(r#"{"tables":[]}"#));
+
+    let start = ControlOp::Start(Some(Default::default()));
+    assert_eq!(conn.powersync_control(&start).unwrap(), "[]");
+    conn.powersync_control(&ControlOp::CompletedUpload).unwrap();
+    assert_eq!(
+        mock.control_calls(),
+        vec![
+            ("start".to_string(), "{}".to_string()),
+            ("completed_upload".to_string(), String::new()),
+        ]
+    );
+
+}
+
+#[test]
+fn test_replace_schema_skips_unchanged() {
+    let harness = TestManager::new();
+    let (mut conn, mock) = open_mock(&harness, "schema_version");
+    let mut schema_changes = conn.subscribe_schema_changes();
+
+    let first = conn.replace_schema(r#"{"tables":[]}"#).unwrap().unwrap();
+    assert_eq!(first.version, 1);
+    assert_eq!(schema_changes.try_recv().unwrap(), first);
+
+    // Same schema, formatted differently
+    assert_eq!(conn.replace_schema(r#"{ "tables": [] }"#).unwrap(), None);
+    assert!(schema_changes.try_recv().is_err());
+    assert_eq!(mock.schema().as_deref(), Some(r#"{"tables":[]}"#));
+
+    let schema = r#"{"tables":[{"name":"todos","columns":[]}]}"#;
+    let second = conn.replace_schema(schema).unwrap().unwrap();
+    assert_eq!(second.version, 2);
+    assert_ne!(second.hash, first.hash);
+    assert_eq!(mock.schema().as_deref(), Some(schema));
+
+    // The version survives reopening
+    drop(conn);
+    let (mut conn, _mock) = open_mock(&harness, "schema_version");
+    assert_eq!(conn.replace_schema(schema).unwrap(), None);
+}
+
+#[test]
+fn test_schema_reapplied_after_restore() {
+    let harness = TestManager::new();
+    let (mut conn, _mock) = open_mock(&harness, "schema_restore");
+    let before_schema = conn.snapshot().unwrap();
+    let schema = r#"{"tables":[{"name":"todos","columns":[]}]}"#;
+    let applied = conn.replace_schema(schema).unwrap().unwrap();
+    let mut schema_changes = conn.subscribe_schema_changes();
+
+    conn.restore(&before_schema).unwrap();
+    let reapplied = schema_changes.try_recv().unwrap();
+    assert_eq!(reapplied.hash, applied.hash);
+    // Recorded again, so the next start skips it
+    assert_eq!(conn.replace_schema(schema).unwrap(), None);
+}
+
+#[test]
+fn test_rejected_schema_not_reapplied() {
+    let harness = TestManager::new();
+    let (mut conn, mock) = open_mock(&harness, "schema_rejected");
+    let before_schema = conn.snapshot().unwrap();
+    let schema = r#"{"tables":[{"name":"todos","columns":[]}]}"#;
+    let applied = conn.replace_schema(schema).unwrap().unwrap();
+
+    mock.set_reject_schema(true);
+    assert!(conn
+        .replace_schema(r#"{"tables":[{"name":"lists","columns":[]}]}"#)
+        .is_err());
+    mock.set_reject_schema(false);
+
+    // Restoring re-applies the last schema that was accepted
+    let mut schema_changes = conn.subscribe_schema_changes();
+    conn.restore(&before_schema).unwrap();
+    assert_eq!(schema_changes.try_recv().unwrap().hash, applied.hash);
+    assert_eq!(mock.schema().as_deref(), Some(schema));
+}
+
+#[test]
+fn test_sync_status() {
+    let harness = TestManager::new();
+    let (mut conn, mock) = open_mock(&harness, "sync_status");
+
+    assert_eq!(conn.get_last_synced_at().unwrap(), None);
+    assert!(!conn.has_synced().unwrap());
+    mock.set_last_synced_at(Some("2024-01-01 00:00:00"));
+    assert_eq!(conn.get_last_synced_at().unwrap(), Some(1_704_067_200_000));
+    assert!(conn.has_synced().unwrap());
+
+    assert_eq!(conn.get_write_checkpoint().unwrap(), None);
+    conn.execute(
+        "INSERT INTO ps_buckets (name, target_op) VALUES ('$local', 42)",
+        &[],
+    )
+    .unwrap();
+    assert_eq!(conn.get_write_checkpoint().unwrap().as_deref(), Some("42"));
+}
+
+#[test]
+fn test_refresh_schema_sees_external_tables() {
+    let harness = TestManager::new();
+    let (mut conn, _mock) = open_mock(&harness, "refresh");
+
+    // Another connection changes the schema behind this one's back
+    let other = powersync_sqlite::rusqlite::Connection::open(harness.path("refresh")).unwrap();
+    other
+        .execute_batch("CREATE TABLE external (id TEXT PRIMARY KEY)")
+        .unwrap();
+
+    conn.refresh_schema().unwrap();
+    let rows = conn.get_all("SELECT * FROM external", &[]).unwrap();
+    assert!(rows.rows.is_empty());
+}
+
+#[test]
+fn test_checkpoint_event_on_completed_sync() {
+    let harness = TestManager::new();
+    let (conn, mock) = open_mock(&harness, "checkpoint");
+    let mut checkpoints = conn.subscribe_checkpoints();
+
+    conn.powersync_control(&empty_line()).unwrap();
+    assert!(checkpoints.try_recv().is_err());
+
+    mock.set_control_response(Some(r#"[{"UpdateSyncStatus":{}},{"DidCompleteSync":{}}]"#));
+    conn.powersync_control(&empty_line()).unwrap();
+    assert!(checkpoints.try_recv().is_ok());
+}
+
+#[test]
+fn test_wait_for_checkpoint() {
+    use powersync_sqlite::consistency::wait_for_checkpoint;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    let harness = TestManager::new();
+    let (conn, mock) = open_mock(&harness, "consistency");
+    let conn = Arc::new(Mutex::new(conn));
+    let token = conn.lock().unwrap().sync_status().unwrap().checkpoint_token;
+    assert_eq!(token, "0");
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let timeout = Some(Duration::from_millis(10));
+        let result = wait_for_checkpoint(&conn, &token, timeout).await;
+        assert!(matches!(result, Err(Error::CheckpointTimeout(_))), "{:?}", result);
+
+        let waiter = {
+            let conn = Arc::clone(&conn);
+            let token = token.clone();
+            tokio::spawn(async move { wait_for_checkpoint(&conn, &token, None).await })
+        };
+        tokio::task::yield_now().await;
+
+        {
+            let mut conn = conn.lock().unwrap();
+            conn.execute(
+                "INSERT INTO ps_buckets (name, last_applied_op) VALUES ('todos', 7)",
+                &[],
+            )
+            .unwrap();
+            mock.set_control_response(Some(r#"[{"DidCompleteSync":{}}]"#));
+            conn.powersync_control(&empty_line()).unwrap();
+        }
+        assert_eq!(waiter.await.unwrap().unwrap(), "7");
+
+        // Already past the token
+        assert_eq!(wait_for_checkpoint(&conn, &token, None).await.unwrap(), "7");
+    });
+
+    let result = runtime.block_on(wait_for_checkpoint(&conn, "seven", None));
+    assert!(matches!(result, Err(Error::InvalidParameter(_))), "{:?}", result);
+}
+
+#[test]
+fn test_checkpoint_failure_reported() {
+    let harness = TestManager::new();
+    let (conn, mock) = open_mock(&harness, "checkpoint_failure");
+    let mut failures = conn.subscribe_checkpoint_failures();
+
+    mock.set_control_response(Some(
+        r#"[{"LogLine":{"severity":"WARNING","line":"Checksums didn't match, failed for: b1"}}]"#,
+    ));
+    // The raw instructions are still returned for the sync client
+    assert!(conn
+        .powersync_control(&empty_line())
+        .unwrap()
+        .contains("LogLine"));
+
+    let failure = failures.try_recv().unwrap();
+    assert_eq!(failure.kind, CheckpointFailureKind::ChecksumMismatch);
+    assert!(conn.subscribe_checkpoints().try_recv().is_err());
+}
+
+#[test]
+fn test_crud_queue() {
+    let harness = TestManager::new();
+    let (mut conn, _mock) = open_mock(&harness, "crud");
+
+    assert!(!conn.has_pending_crud().unwrap());
+    for data in ["a", "b", "c"] {
+        conn.execute("INSERT INTO ps_crud (tx_id, data) VALUES (1, ?)", &[text(data)])
+            .unwrap();
+    }
+    assert!(conn.has_pending_crud().unwrap());
+
+    let batch = conn.get_crud_batch(2).unwrap();
+    assert_eq!(batch.len(), 2);
+    assert_eq!(batch[0].data, "a");
+
+    conn.remove_crud(batch[1].id).unwrap();
+    let remaining = conn.get_crud_batch(10).unwrap();
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].data, "c");
+}
+
+#[test]
+fn test_migrate_local_table() {
+    let harness = TestManager::new();
+    let (mut conn, _mock) = open_mock(&harness, "migrate");
+
+    // Views and triggers shaped like the ones the extension creates
+    let other = powersync_sqlite::rusqlite::Connection::open(harness.path("migrate")).unwrap();
+    other
+        .execute_batch(
+            "CREATE TABLE ps_data_local__drafts (id TEXT PRIMARY KEY, data TEXT);
+             CREATE VIEW drafts AS SELECT id, json_extract(data, '$.title') AS title,
+                 json_extract(data, '$.device') AS device FROM ps_data_local__drafts;
+             CREATE TRIGGER drafts_delete INSTEAD OF DELETE ON drafts BEGIN
+                 DELETE FROM ps_data_local__drafts WHERE id = OLD.id;
+             END;
+             CREATE TABLE ps_data__todos (id TEXT PRIMARY KEY, data TEXT);
+             CREATE VIEW todos AS
+                 SELECT id, json_extract(data, '$.title') AS title FROM ps_data__todos;
+             CREATE TRIGGER todos_insert INSTEAD OF INSERT ON todos BEGIN
+                 INSERT INTO ps_data__todos VALUES (NEW.id, json_object('title', NEW.title));
+                 INSERT INTO ps_crud (tx_id, data)
+                     VALUES (1, json_object('op', 'PUT', 'type', 'todos', 'id', NEW.id));
+             END;
+             INSERT INTO ps_data_local__drafts VALUES
+                 ('a', json_object('title', 'Buy milk', 'device', 'phone')),
+                 ('b', json_object('title', 'Call mum'));",
+        )
+        .unwrap();
+    conn.refresh_schema().unwrap();
+
+    assert!(matches!(
+        conn.migrate_local_table("todos", "drafts", false),
+        Err(Error::InvalidParameter(_))
+    ));
+    assert_eq!(
+        conn.migrate_local_table("drafts", "todos", true).unwrap(),
+        2
+    );
+    assert_eq!(count(&conn, "todos"), 2);
+    assert_eq!(count(&conn, "ps_crud"), 2);
+    assert_eq!(count(&conn, "drafts"), 0);
+
+    // A failed copy leaves both tables as they were
+    other
+        .execute("INSERT INTO ps_data_local__drafts VALUES ('a', '{}')", [])
+        .unwrap();
+    assert!(conn.migrate_local_table("drafts", "todos", true).is_err());
+    assert_eq!(count(&conn, "drafts"), 1);
+    assert_eq!(count(&conn, "ps_crud"), 2);
+}
+
+#[test]
+fn test_powersync_commands_require_extension() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("plain");
+    let conn = conn.lock().unwrap();
+
+    // Only meaningful when the real extension isn't available to the test build
+    if !conn.is_powersync_loaded() {
+        assert!(matches!(
+            conn.get_crud_batch(10),
+            Err(Error::PowerSyncNotInitialized)
+        ));
+    }
+}
@@ -0,0 +1,1263 @@
+mod common;
+
+use common::{count, text, TestManager};
+use powersync_sqlite::coordinator::execute_across;
+use powersync_sqlite::database::{PipelineOp, PipelineResult};
+use powersync_sqlite::{
+    AbortIf, Error, ExportFormat, IdGenerator, LifecycleKind, RetentionRule, SqlParam, Statement,
+    TransactionGuard, WritePriority, WriteQueue,
+};
+use std::time::Duration;
+
+const CREATE_TODOS: &str = "CREATE TABLE todos (id TEXT PRIMARY KEY, description TEXT)";
+const INSERT_TODO: &str = "INSERT INTO todos (id, description) VALUES (?, ?)";
+
+#[test]
+fn test_execute_and_get_all() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("execute");
+    let mut conn = conn.lock().unwrap();
+
+    conn.execute(CREATE_TODOS, &[]).unwrap();
+    let result = conn.execute(INSERT_TODO, &[text("1"), text("Buy milk")]).unwrap();
+    assert_eq!(result.changes, 1);
+    assert_eq!(result.last_insert_rowid, 1);
+
+    let rows = conn.get_all("SELECT * FROM todos", &[]).unwrap();
+    assert_eq!(rows.columns, vec!["id", "description"]);
+    assert_eq!(rows.rows[0]["description"], "Buy milk");
+
+    // SELECT through execute returns rows as well
+    let result = conn.execute("SELECT id FROM todos", &[]).unwrap();
+    assert_eq!(result.rows.unwrap().len(), 1);
+}
+
+#[test]
+fn test_commit_and_rollback() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("commit");
+    let mut conn = conn.lock().unwrap();
+    conn.execute(CREATE_TODOS, &[]).unwrap();
+
+    let tx = conn.begin_transaction(true).unwrap();
+    conn.execute(INSERT_TODO, &[text("1"), text("kept")]).unwrap();
+    conn.commit_transaction(&tx).unwrap();
+
+    let tx = conn.begin_transaction(true).unwrap();
+    conn.execute(INSERT_TODO, &[text("2"), text("discarded")]).unwrap();
+    conn.rollback_transaction(&tx).unwrap();
+
+    assert_eq!(count(&conn, "todos"), 1);
+    assert!(matches!(
+        conn.commit_transaction(&tx),
+        Err(Error::TransactionNotFound(_))
+    ));
+}
+
+#[test]
+fn test_nested_transaction_uses_savepoint() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("savepoint");
+    let mut conn = conn.lock().unwrap();
+    conn.execute(CREATE_TODOS, &[]).unwrap();
+
+    let outer = conn.begin_transaction(true).unwrap();
+    conn.execute(INSERT_TODO, &[text("1"), text("outer")]).unwrap();
+
+    let inner = conn.begin_transaction(true).unwrap();
+    conn.execute(INSERT_TODO, &[text("2"), text("inner")]).unwrap();
+    conn.rollback_transaction(&inner).unwrap();
+
+    conn.commit_transaction(&outer).unwrap();
+
+    let rows = conn.get_all("SELECT id FROM todos", &[]).unwrap();
+    assert_eq!(rows.rows.len(), 1);
+    assert_eq!(rows.rows[0]["id"], "1");
+}
+
+#[test]
+fn test_outer_commit_deferred_until_savepoint_released() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("deferred");
+    let mut conn = conn.lock().unwrap();
+    conn.execute(CREATE_TODOS, &[]).unwrap();
+
+    let outer = conn.begin_transaction(true).unwrap();
+    let inner = conn.begin_transaction(true).unwrap();
+    conn.execute(INSERT_TODO, &[text("1"), text("inner")]).unwrap();
+
+    // Committing the outer transaction first only marks it as pending
+    conn.commit_transaction(&outer).unwrap();
+    assert!(matches!(
+        conn.commit_transaction(&outer),
+        Err(Error::TransactionCompleted(_))
+    ));
+
+    // Releasing the savepoint performs the deferred commit
+    conn.commit_transaction(&inner).unwrap();
+    let tx = conn.begin_transaction(true).unwrap();
+    conn.rollback_transaction(&tx).unwrap();
+    assert_eq!(count(&conn, "todos"), 1);
+}
+
+#[test]
+fn test_rollback_with_active_nested_transaction_fails() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("order");
+    let mut conn = conn.lock().unwrap();
+    conn.execute(CREATE_TODOS, &[]).unwrap();
+
+    let outer = conn.begin_transaction(true).unwrap();
+    conn.execute(INSERT_TODO, &[text("1"), text("outer")]).unwrap();
+    let inner = conn.begin_transaction(true).unwrap();
+
+    assert!(matches!(
+        conn.rollback_transaction(&outer),
+        Err(Error::TransactionOrder(_))
+    ));
+
+    // Both transactions are still usable afterwards
+    conn.commit_transaction(&inner).unwrap();
+    conn.commit_transaction(&outer).unwrap();
+    assert_eq!(count(&conn, "todos"), 1);
+}
+
+#[test]
+fn test_stale_transactions_discarded_after_raw_commit() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("stale");
+    let mut conn = conn.lock().unwrap();
+    conn.execute(CREATE_TODOS, &[]).unwrap();
+
+    let stale = conn.begin_transaction(true).unwrap();
+    conn.execute("COMMIT", &[]).unwrap();
+
+    // The next begin starts a fresh transaction rather than a savepoint
+    let tx = conn.begin_transaction(true).unwrap();
+    conn.execute(INSERT_TODO, &[text("1"), text("fresh")]).unwrap();
+    conn.commit_transaction(&tx).unwrap();
+
+    assert_eq!(count(&conn, "todos"), 1);
+    assert!(matches!(
+        conn.commit_transaction(&stale),
+        Err(Error::TransactionNotFound(_))
+    ));
+}
+
+#[test]
+fn test_transaction_owner_is_enforced() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("owner");
+    let mut conn = conn.lock().unwrap();
+
+    let tx = conn.begin_owned_transaction(true, Some("main")).unwrap();
+    assert!(conn.check_transaction_owner(&tx, "main").is_ok());
+    assert!(matches!(
+        conn.check_transaction_owner(&tx, "settings"),
+        Err(Error::TransactionOwner(_))
+    ));
+    conn.commit_transaction(&tx).unwrap();
+
+    // Unowned transactions can be resolved by anyone
+    let tx = conn.begin_transaction(true).unwrap();
+    assert!(conn.check_transaction_owner(&tx, "settings").is_ok());
+    conn.rollback_transaction(&tx).unwrap();
+}
+
+#[test]
+fn test_writes_reenter_their_transaction_permit() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("reenter");
+    let mut conn = conn.lock().unwrap();
+    let queue = WriteQueue::new();
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+
+    let outer = conn.begin_owned_transaction(true, Some("main")).unwrap();
+    let permit = runtime.block_on(queue.acquire(WritePriority::Normal, Some("main")));
+    conn.attach_write_permit(&outer, permit).unwrap();
+    let inner = conn.begin_owned_transaction(false, Some("main")).unwrap();
+
+    // A write in the nested transaction nests under the outer one's permit
+    let nested = conn.reenter_write_permit(&inner).unwrap().unwrap();
+    conn.commit_transaction(&inner).unwrap();
+    conn.commit_transaction(&outer).unwrap();
+    assert_eq!(queue.stats().holder.as_deref(), Some("main"));
+    drop(nested);
+    assert_eq!(queue.stats().holder, None);
+
+    // Read transactions hold no permit to nest under
+    let read = conn.begin_owned_transaction(false, Some("main")).unwrap();
+    assert!(conn.reenter_write_permit(&read).unwrap().is_none());
+    conn.rollback_transaction(&read).unwrap();
+}
+
+#[test]
+fn test_batch_inside_write_transaction() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("batch_in_transaction");
+    let mut conn = conn.lock().unwrap();
+    conn.execute(CREATE_TODOS, &[]).unwrap();
+    let queue = WriteQueue::new();
+    queue.set_timeout(Some(Duration::from_millis(50)));
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .unwrap();
+
+    let tx = conn.begin_owned_transaction(true, Some("main")).unwrap();
+    let permit = runtime.block_on(queue.acquire(WritePriority::Normal, Some("main")));
+    conn.attach_write_permit(&tx, permit).unwrap();
+
+    // Queuing behind the transaction's own permit never gets a turn
+    assert!(matches!(
+        runtime.block_on(queue.acquire_timed(WritePriority::Normal, Some("main"))),
+        Err(Error::LockTimeout(_))
+    ));
+
+    // Batches and bulk inserts given the transaction nest under its permit
+    let nested = conn.reenter_write_permit(&tx).unwrap().unwrap();
+    let batch = [
+        vec![text("1"), text("Buy milk")],
+        vec![text("2"), text("Walk dog")],
+    ];
+    assert_eq!(conn.execute_batch(INSERT_TODO, &batch).unwrap().changes, 2);
+    let columns = ["id".to_string(), "description".to_string()];
+    let rows = [vec![text("3"), text("Feed cat")]];
+    assert_eq!(conn.insert_many("todos", &columns, &rows).unwrap().changes, 1);
+    drop(nested);
+    assert_eq!(count(&conn, "todos"), 3);
+
+    // They are part of the transaction, so they roll back with it
+    conn.rollback_transaction(&tx).unwrap();
+    assert_eq!(count(&conn, "todos"), 0);
+    assert_eq!(queue.stats().holder, None);
+}
+
+#[test]
+fn test_rollback_owned_transactions() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("owner_rollback");
+    let mut conn = conn.lock().unwrap();
+    conn.execute(CREATE_TODOS, &[]).unwrap();
+
+    let outer = conn.begin_owned_transaction(true, Some("main")).unwrap();
+    conn.execute(INSERT_TODO, &[text("1"), text("kept")]).unwrap();
+    conn.begin_owned_transaction(true, Some("settings")).unwrap();
+    conn.execute(INSERT_TODO, &[text("2"), text("abandoned")]).unwrap();
+    conn.begin_owned_transaction(true, Some("settings")).unwrap();
+
+    assert_eq!(conn.rollback_owned_transactions("settings").unwrap(), 2);
+    assert_eq!(conn.rollback_owned_transactions("settings").unwrap(), 0);
+
+    conn.commit_transaction(&outer).unwrap();
+    assert_eq!(count(&conn, "todos"), 1);
+}
+
+#[test]
+fn test_named_savepoints() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("named_savepoints");
+    let mut conn = conn.lock().unwrap();
+    conn.execute(CREATE_TODOS, &[]).unwrap();
+
+    let tx = conn.begin_transaction(true).unwrap();
+    conn.execute(INSERT_TODO, &[text("1"), text("kept")]).unwrap();
+    conn.create_savepoint(&tx, "before_import").unwrap();
+    conn.execute(INSERT_TODO, &[text("2"), text("undone")]).unwrap();
+    conn.create_savepoint(&tx, "later").unwrap();
+
+    // Rolling back discards the later savepoint but keeps this one
+    conn.rollback_to_savepoint(&tx, "before_import").unwrap();
+    assert!(matches!(
+        conn.release_savepoint(&tx, "later"),
+        Err(Error::SavepointNotFound(_))
+    ));
+    conn.execute(INSERT_TODO, &[text("3"), text("retried")]).unwrap();
+    conn.release_savepoint(&tx, "before_import").unwrap();
+
+    conn.commit_transaction(&tx).unwrap();
+    assert_eq!(count(&conn, "todos"), 2);
+}
+
+#[test]
+fn test_named_savepoint_validation() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("savepoint_validation");
+    let mut conn = conn.lock().unwrap();
+
+    let outer = conn.begin_transaction(true).unwrap();
+    assert!(matches!(
+        conn.create_savepoint(&outer, "bad name; DROP TABLE x"),
+        Err(Error::InvalidParameter(_))
+    ));
+    conn.create_savepoint(&outer, "sp").unwrap();
+    assert!(matches!(
+        conn.create_savepoint(&outer, "sp"),
+        Err(Error::InvalidParameter(_))
+    ));
+
+    // Only the innermost transaction can take savepoints
+    let inner = conn.begin_transaction(true).unwrap();
+    assert!(matches!(
+        conn.rollback_to_savepoint(&outer, "sp"),
+        Err(Error::TransactionOrder(_))
+    ));
+    conn.rollback_transaction(&inner).unwrap();
+    conn.rollback_to_savepoint(&outer, "sp").unwrap();
+    conn.rollback_transaction(&outer).unwrap();
+}
+
+#[test]
+fn test_execute_batch_is_atomic() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("batch");
+    let mut conn = conn.lock().unwrap();
+    conn.execute(CREATE_TODOS, &[]).unwrap();
+
+    let result = conn
+        .execute_batch(
+            INSERT_TODO,
+            &[vec![text("1"), text("a")], vec![text("2"), SqlParam::Null]],
+        )
+        .unwrap();
+    assert_eq!(result.changes, 2);
+
+    // The duplicate key fails the whole batch
+    let result = conn.execute_batch(
+        INSERT_TODO,
+        &[vec![text("3"), text("b")], vec![text("1"), text("dup")]],
+    );
+    assert!(result.is_err());
+    assert_eq!(count(&conn, "todos"), 2);
+}
+
+#[test]
+fn test_execute_batch_detailed() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("batch_detailed");
+    let mut conn = conn.lock().unwrap();
+    conn.execute(CREATE_TODOS, &[]).unwrap();
+
+    let result = conn
+        .execute_batch_detailed(
+            INSERT_TODO,
+            &[vec![text("1"), text("a")], vec![text("2"), text("b")]],
+        )
+        .unwrap();
+    assert_eq!(result.changes, 2);
+    let entries = result.entries.unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[1].last_insert_rowid, result.last_insert_rowid);
+    assert!(result.failure.is_none());
+
+    // The failing parameter set is reported instead of raised
+    let result = conn
+        .execute_batch_detailed(
+            INSERT_TODO,
+            &[
+                vec![text("3"), text("c")],
+                vec![text("1"), text("dup")],
+                vec![text("4"), text("d")],
+            ],
+        )
+        .unwrap();
+    let failure = result.failure.unwrap();
+    assert_eq!(failure.index, 1);
+    assert!(failure.error.contains("UNIQUE"), "{}", failure.error);
+    assert_eq!(result.entries.unwrap().len(), 1);
+    assert_eq!(result.changes, 0);
+    assert_eq!(count(&conn, "todos"), 2);
+}
+
+#[test]
+fn test_execute_transaction() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("one_shot");
+    let mut conn = conn.lock().unwrap();
+    conn.execute(CREATE_TODOS, &[]).unwrap();
+
+    let statement = |sql: &str, params: Vec<SqlParam>| Statement {
+        sql: sql.to_string(),
+        params,
+    };
+
+    let results = conn
+        .execute_transaction(&[
+            statement(INSERT_TODO, vec![text("1"), text("a")]),
+            statement(INSERT_TODO, vec![text("2"), text("b")]),
+            statement("SELECT COUNT(*) AS n FROM todos", vec![]),
+        ])
+        .unwrap();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[1].changes, 1);
+    assert_eq!(results[2].rows.as_ref().unwrap()[0]["n"], 2);
+
+    // A failing statement rolls back the ones before it
+    let result = conn.execute_transaction(&[
+        statement(INSERT_TODO, vec![text("3"), text("c")]),
+        statement(INSERT_TODO, vec![text("1"), text("dup")]),
+    ]);
+    assert!(result.is_err());
+    assert_eq!(count(&conn, "todos"), 2);
+}
+
+#[test]
+fn test_with_transaction() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("guarded");
+    let mut conn = conn.lock().unwrap();
+    conn.execute(CREATE_TODOS, &[]).unwrap();
+
+    let insert = |id: &str| Statement {
+        sql: INSERT_TODO.to_string(),
+        params: vec![text(id), text("todo")],
+    };
+    let guard = |sql: &str, abort_if, after| TransactionGuard {
+        sql: sql.to_string(),
+        params: vec![],
+        abort_if,
+        after,
+    };
+
+    // A guard before the statements aborts without running them
+    let outcome = conn
+        .with_transaction(
+            &[insert("1")],
+            &[guard("SELECT 1 FROM todos", AbortIf::Empty, false)],
+        )
+        .unwrap();
+    assert!(!outcome.committed);
+    assert_eq!(outcome.aborted_by, Some(0));
+    assert_eq!(count(&conn, "todos"), 0);
+
+    let outcome = conn
+        .with_transaction(
+            &[insert("1"), insert("2")],
+            &[guard("SELECT 1 FROM todos", AbortIf::NotEmpty, false)],
+        )
+        .unwrap();
+    assert!(outcome.committed);
+    assert_eq!(outcome.results.len(), 2);
+    assert_eq!(count(&conn, "todos"), 2);
+
+    // A guard after the statements sees their writes and rolls them back
+    let too_many = "SELECT COUNT(*) FROM todos HAVING COUNT(*) > 2";
+    let outcome = conn
+        .with_transaction(
+            &[insert("3")],
+            &[
+                guard("SELECT 1 FROM todos", AbortIf::Empty, false),
+                guard(too_many, AbortIf::NotEmpty, true),
+            ],
+        )
+        .unwrap();
+    assert!(!outcome.committed);
+    assert_eq!(outcome.aborted_by, Some(1));
+    assert_eq!(count(&conn, "todos"), 2);
+
+    // Guards can't write
+    let result = conn.with_transaction(
+        &[insert("3")],
+        &[guard("DELETE FROM todos", AbortIf::Empty, true)],
+    );
+    assert!(matches!(result, Err(Error::InvalidParameter(_))));
+    assert_eq!(count(&conn, "todos"), 2);
+}
+
+#[test]
+fn test_execute_across() {
+    let mut harness = TestManager::new();
+    let local = harness.open("across_local");
+    let synced = harness.open("across_synced");
+    let mut local = local.lock().unwrap();
+    let mut synced = synced.lock().unwrap();
+    local.execute(CREATE_TODOS, &[]).unwrap();
+    local.execute(INSERT_TODO, &[text("1"), text("Buy milk")]).unwrap();
+    // A deferred foreign key only fails the COMMIT
+    synced.execute("PRAGMA foreign_keys = ON", &[]).unwrap();
+    synced.execute("CREATE TABLE lists (id TEXT PRIMARY KEY)", &[]).unwrap();
+    synced
+        .execute(
+            "CREATE TABLE todos (id TEXT PRIMARY KEY, description TEXT,
+                list_id TEXT REFERENCES lists (id) DEFERRABLE INITIALLY DEFERRED)",
+            &[],
+        )
+        .unwrap();
+    synced.execute("INSERT INTO lists (id) VALUES ('inbox')", &[]).unwrap();
+
+    let statement = |sql: &str, params: Vec<SqlParam>| Statement {
+        sql: sql.to_string(),
+        params,
+    };
+    let delete = statement("DELETE FROM todos WHERE id = ?", vec![text("1")]);
+    let undo = statement(INSERT_TODO, vec![text("1"), text("Buy milk")]);
+    let insert = |list: &str| {
+        statement(
+            "INSERT INTO todos (id, description, list_id) VALUES (?, ?, ?)",
+            vec![text("1"), text("Buy milk"), text(list)],
+        )
+    };
+
+    // The second database fails to commit, so the first is compensated
+    let result = execute_across(
+        &mut local,
+        std::slice::from_ref(&delete),
+        std::slice::from_ref(&undo),
+        &mut synced,
+        &[insert("missing")],
+    );
+    assert!(matches!(result, Err(Error::Database(_))), "{:?}", result);
+    assert_eq!(count(&local, "todos"), 1);
+    assert_eq!(count(&synced, "todos"), 0);
+
+    // Without compensation the databases are left out of step
+    let result = execute_across(
+        &mut local,
+        std::slice::from_ref(&delete),
+        &[],
+        &mut synced,
+        &[insert("missing")],
+    );
+    assert!(matches!(result, Err(Error::PartialCommit(_))), "{:?}", result);
+    assert_eq!(count(&local, "todos"), 0);
+    local.execute(INSERT_TODO, &[text("1"), text("Buy milk")]).unwrap();
+
+    // A failing statement rolls both back before either commits
+    let result = execute_across(
+        &mut local,
+        std::slice::from_ref(&delete),
+        std::slice::from_ref(&undo),
+        &mut synced,
+        &[statement("INSERT INTO missing VALUES (1)", vec![])],
+    );
+    assert!(result.is_err());
+    assert_eq!(count(&local, "todos"), 1);
+
+    let result =
+        execute_across(&mut local, &[delete], &[undo], &mut synced, &[insert("inbox")]).unwrap();
+    assert_eq!(result.first[0].changes, 1);
+    assert_eq!(count(&local, "todos"), 0);
+    assert_eq!(count(&synced, "todos"), 1);
+
+    // Not inside a transaction of its own
+    let tx = local.begin_transaction(true).unwrap();
+    let result = execute_across(&mut local, &[], &[], &mut synced, &[]);
+    assert!(matches!(result, Err(Error::TransactionOrder(_))), "{:?}", result);
+    local.rollback_transaction(&tx).unwrap();
+}
+
+#[test]
+fn test_prepared_statements() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("prepared");
+    let mut conn = conn.lock().unwrap();
+    conn.execute(CREATE_TODOS, &[]).unwrap();
+
+    let insert = conn.prepare(INSERT_TODO).unwrap();
+    for i in 0..100 {
+        let result = conn
+            .bind_and_step(&insert, &[text(&i.to_string()), text("todo")])
+            .unwrap();
+        assert_eq!(result.changes, 1);
+    }
+    assert_eq!(count(&conn, "todos"), 100);
+
+    let select = conn
+        .prepare_owned("SELECT description FROM todos WHERE id = ?", Some("main"))
+        .unwrap();
+    let result = conn.bind_and_step(&select, &[text("42")]).unwrap();
+    assert_eq!(result.rows.unwrap()[0]["description"], "todo");
+
+    conn.finalize(&insert).unwrap();
+    assert!(matches!(
+        conn.bind_and_step(&insert, &[text("100"), text("todo")]),
+        Err(Error::StatementNotFound(_))
+    ));
+    assert!(matches!(
+        conn.finalize(&insert),
+        Err(Error::StatementNotFound(_))
+    ));
+
+    // Statements of a window that went away are finalized with it
+    assert_eq!(conn.finalize_owned("settings"), 0);
+    assert_eq!(conn.finalize_owned("main"), 1);
+    assert!(conn.bind_and_step(&select, &[text("42")]).is_err());
+}
+
+#[test]
+fn test_apply_retention_is_atomic() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("retention");
+    let mut conn = conn.lock().unwrap();
+    conn.execute(
+        "CREATE TABLE logs (id INTEGER PRIMARY KEY, logged_at TEXT)",
+        &[],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO logs (logged_at) VALUES
+            (datetime('now', '-3 days')), (datetime('now', '-2 days')), (datetime('now'))",
+        &[],
+    )
+    .unwrap();
+
+    let rule = |table: &str| RetentionRule {
+        table: table.to_string(),
+        timestamp_column: "logged_at".to_string(),
+        max_age_days: None,
+        keep_last: Some(1),
+    };
+    assert!(conn
+        .apply_retention(&[rule("logs"), rule("missing")])
+        .is_err());
+    assert_eq!(count(&conn, "logs"), 3);
+
+    let reports = conn.apply_retention(&[rule("logs")]).unwrap();
+    assert_eq!(reports[0].deleted, 2);
+    assert_eq!(count(&conn, "logs"), 1);
+}
+
+#[test]
+fn test_patch_json() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("patch_json");
+    let mut conn = conn.lock().unwrap();
+    conn.execute("CREATE TABLE docs (id TEXT PRIMARY KEY, data TEXT)", &[])
+        .unwrap();
+    conn.execute(
+        "INSERT INTO docs (id, data) VALUES ('1', '{\"a\":1,\"b\":2}'), ('2', NULL)",
+        &[],
+    )
+    .unwrap();
+
+    let result = conn.patch_json("docs", "data", "1", r#"{"b":null,"c":3}"#).unwrap();
+    assert_eq!(result.changes, 1);
+    conn.patch_json("docs", "data", "2", r#"{"a":1}"#).unwrap();
+
+    let rows = conn.get_all("SELECT data FROM docs ORDER BY id", &[]).unwrap();
+    assert_eq!(rows.rows[0]["data"], r#"{"a":1,"c":3}"#);
+    assert_eq!(rows.rows[1]["data"], r#"{"a":1}"#);
+
+    assert_eq!(conn.patch_json("docs", "data", "missing", "{}").unwrap().changes, 0);
+}
+
+#[test]
+fn test_insert_with_id() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("insert_with_id");
+    let mut conn = conn.lock().unwrap();
+    conn.execute(CREATE_TODOS, &[]).unwrap();
+
+    let columns = ["description".to_string()];
+    let id = conn
+        .insert_with_id("todos", &columns, &[text("Buy milk")])
+        .unwrap();
+    let row = conn
+        .get_optional("SELECT description FROM todos WHERE id = ?", &[text(&id)])
+        .unwrap()
+        .unwrap();
+    assert_eq!(row["description"], "Buy milk");
+
+    conn.set_id_generator(IdGenerator::custom(|table| format!("{}-1", table)));
+    let id = conn
+        .insert_with_id("todos", &columns, &[text("Walk dog")])
+        .unwrap();
+    assert_eq!(id, "todos-1");
+    assert_eq!(count(&conn, "todos"), 2);
+
+    let with_id = ["ID".to_string()];
+    assert!(matches!(
+        conn.insert_with_id("todos", &with_id, &[text("2")]),
+        Err(Error::InvalidParameter(_))
+    ));
+    assert!(conn.insert_with_id("todos", &columns, &[]).is_err());
+}
+
+#[test]
+fn test_soft_delete() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("soft_delete");
+    let mut conn = conn.lock().unwrap();
+    // A view writing through INSTEAD OF triggers, as PowerSync's views do
+    let setup = [
+        "CREATE TABLE data_todos (id TEXT PRIMARY KEY, description TEXT, deleted_at TEXT)",
+        "CREATE VIEW todos AS SELECT id, description, deleted_at FROM data_todos",
+        "CREATE TRIGGER todos_insert INSTEAD OF INSERT ON todos BEGIN
+           INSERT INTO data_todos VALUES (NEW.id, NEW.description, NEW.deleted_at);
+         END",
+        "CREATE TRIGGER todos_update INSTEAD OF UPDATE ON todos BEGIN
+           UPDATE data_todos SET description = NEW.description, deleted_at = NEW.deleted_at
+             WHERE id = OLD.id;
+         END",
+        "CREATE TRIGGER todos_delete INSTEAD OF DELETE ON todos BEGIN
+           DELETE FROM data_todos WHERE id = OLD.id;
+         END",
+    ];
+    for sql in setup {
+        conn.execute(sql, &[]).unwrap();
+    }
+    conn.execute(INSERT_TODO, &[text("1"), text("Buy milk")]).unwrap();
+    conn.execute(INSERT_TODO, &[text("2"), text("Walk dog")]).unwrap();
+
+    assert!(matches!(
+        conn.soft_delete_row("todos", "1"),
+        Err(Error::InvalidParameter(_))
+    ));
+    assert!(matches!(
+        conn.enable_soft_delete("data_todos", "deleted_at"),
+        Err(Error::InvalidParameter(_))
+    ));
+    conn.enable_undo(&["todos".to_string()]).unwrap();
+    conn.enable_soft_delete("todos", "deleted_at").unwrap();
+
+    // The tombstoned row is gone from the view, but not from the data
+    conn.soft_delete_row("todos", "1").unwrap();
+    assert_eq!(count(&conn, "todos"), 1);
+    assert_eq!(count(&conn, "data_todos"), 2);
+
+    conn.restore_row("todos", "1").unwrap();
+    assert_eq!(count(&conn, "todos"), 2);
+
+    // Undo still reaches rows hidden by the filter
+    conn.soft_delete_row("todos", "1").unwrap();
+    assert_eq!(conn.undo().unwrap().unwrap().rows, 1);
+    assert_eq!(count(&conn, "todos"), 2);
+
+    conn.soft_delete_row("todos", "2").unwrap();
+    conn.purge_deleted("todos", Some("2000-01-01")).unwrap();
+    assert_eq!(count(&conn, "data_todos"), 2);
+    conn.purge_deleted("todos", None).unwrap();
+    assert_eq!(count(&conn, "data_todos"), 1);
+    assert_eq!(count(&conn, "todos"), 1);
+}
+
+#[test]
+fn test_undo_and_redo() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("undo");
+    let mut conn = conn.lock().unwrap();
+    // A view writing through INSTEAD OF triggers that queue CRUD entries, as
+    // PowerSync's views do
+    let setup = [
+        "CREATE TABLE data_todos (id TEXT PRIMARY KEY, description TEXT)",
+        "CREATE TABLE crud (op TEXT NOT NULL, id TEXT NOT NULL, description TEXT)",
+        "CREATE VIEW todos AS SELECT id, description FROM data_todos",
+        "CREATE TRIGGER todos_insert INSTEAD OF INSERT ON todos BEGIN
+           INSERT INTO data_todos VALUES (NEW.id, NEW.description);
+           INSERT INTO crud VALUES ('PUT', NEW.id, NEW.description);
+         END",
+        "CREATE TRIGGER todos_update INSTEAD OF UPDATE ON todos BEGIN
+           UPDATE data_todos SET description = NEW.description WHERE id = OLD.id;
+           INSERT INTO crud VALUES ('PATCH', OLD.id, NEW.description);
+         END",
+        "CREATE TRIGGER todos_delete INSTEAD OF DELETE ON todos BEGIN
+           DELETE FROM data_todos WHERE id = OLD.id;
+           INSERT INTO crud VALUES ('DELETE', OLD.id, NULL);
+         END",
+    ];
+    for sql in setup {
+        conn.execute(sql, &[]).unwrap();
+    }
+    conn.enable_undo(&["todos".to_string()]).unwrap();
+
+    conn.execute(INSERT_TODO, &[text("1"), text("Buy milk")]).unwrap();
+    conn.execute(
+        "UPDATE todos SET description = ? WHERE id = ?",
+        &[text("Buy oat milk"), text("1")],
+    )
+    .unwrap();
+    assert_eq!(conn.undo_state().unwrap().undo_steps, 2);
+
+    let step = conn.undo().unwrap().unwrap();
+    assert_eq!(step.tables, vec!["todos"]);
+    let row = conn
+        .get_optional("SELECT description FROM todos WHERE id = '1'", &[])
+        .unwrap()
+        .unwrap();
+    assert_eq!(row["description"], "Buy milk");
+    conn.undo().unwrap().unwrap();
+    assert_eq!(count(&conn, "todos"), 0);
+    conn.redo().unwrap().unwrap();
+    assert_eq!(count(&conn, "todos"), 1);
+
+    // Undo and redo were queued for upload like the writes they reverted
+    let crud = conn
+        .get_all("SELECT op FROM crud ORDER BY rowid", &[])
+        .unwrap();
+    let ops: Vec<_> = crud
+        .rows
+        .iter()
+        .map(|row| row["op"].as_str().unwrap())
+        .collect();
+    assert_eq!(ops, ["PUT", "PATCH", "PATCH", "DELETE", "PUT"]);
+    let state = conn.undo_state().unwrap();
+    assert_eq!((state.undo_steps, state.redo_steps), (1, 1));
+    assert_eq!(state.tables, vec!["todos"]);
+}
+
+#[test]
+fn test_change_feed() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("change_feed");
+    let mut conn = conn.lock().unwrap();
+    conn.execute(CREATE_TODOS, &[]).unwrap();
+    assert!(conn.read_change_feed(10).unwrap().is_empty());
+
+    conn.enable_change_feed(&["todos"]).unwrap();
+    conn.enable_change_feed(&["todos"]).unwrap();
+    assert!(matches!(
+        conn.enable_change_feed(&["missing"]),
+        Err(Error::InvalidParameter(_))
+    ));
+
+    conn.execute(INSERT_TODO, &[text("1"), text("Buy milk")]).unwrap();
+    conn.execute("UPDATE todos SET description = 'Buy oat milk' WHERE id = '1'", &[])
+        .unwrap();
+    conn.execute("DELETE FROM todos WHERE id = '1'", &[]).unwrap();
+
+    let feed = conn.read_change_feed(10).unwrap();
+    let ops: Vec<(&str, &str, &str)> = feed
+        .iter()
+        .map(|e| (e.table.as_str(), e.pk.as_str(), e.op.as_str()))
+        .collect();
+    assert_eq!(
+        ops,
+        [("todos", "1", "INSERT"), ("todos", "1", "UPDATE"), ("todos", "1", "DELETE")]
+    );
+    assert!(feed.windows(2).all(|w| w[0].seq < w[1].seq));
+
+    conn.ack_change_feed(feed[1].seq).unwrap();
+    let remaining = conn.read_change_feed(10).unwrap();
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].op, "DELETE");
+}
+
+#[test]
+fn test_closed_database_is_not_found() {
+    let mut harness = TestManager::new();
+    harness.open("closed");
+    harness.manager.close("closed").unwrap();
+    assert!(matches!(
+        harness.manager.get("closed"),
+        Err(Error::DatabaseNotFound(_))
+    ));
+}
+
+#[test]
+fn test_profiles() {
+    let mut harness = TestManager::new();
+    assert!(harness.manager.list_profiles().unwrap().is_empty());
+
+    let alice = harness.manager.open_profile("alice@example.com").unwrap();
+    let bob = harness.manager.open_profile("bob").unwrap();
+    assert_ne!(alice, bob);
+    {
+        let conn = harness.manager.get(&alice).unwrap();
+        let mut conn = conn.lock().unwrap();
+        conn.execute(CREATE_TODOS, &[]).unwrap();
+    }
+    assert_eq!(harness.manager.list_profiles().unwrap(), ["alice@example.com", "bob"]);
+
+    harness.manager.delete_profile("alice@example.com").unwrap();
+    assert!(matches!(harness.manager.get(&alice), Err(Error::DatabaseNotFound(_))));
+    assert!(!harness.path(&alice).exists());
+    assert_eq!(harness.manager.list_profiles().unwrap(), ["bob"]);
+
+    // Reopening starts from an empty database
+    let alice = harness.manager.open_profile("alice@example.com").unwrap();
+    let conn = harness.manager.get(&alice).unwrap();
+    let conn = conn.lock().unwrap();
+    assert!(conn.get_all("SELECT * FROM todos", &[]).is_err());
+}
+
+#[test]
+fn test_lifecycle_events() {
+    let mut harness = TestManager::new();
+    let mut lifecycle = harness.manager.subscribe_lifecycle();
+
+    harness.open("lifecycle");
+    harness.open("lifecycle");
+    harness.manager.close("lifecycle").unwrap();
+    harness.manager.close("lifecycle").unwrap();
+
+    let opened = lifecycle.try_recv().unwrap();
+    assert_eq!(opened.kind, LifecycleKind::Opened);
+    assert_eq!(opened.name, "lifecycle");
+    assert_eq!(opened.path, harness.path("lifecycle"));
+    assert!(!opened.powersync_loaded);
+    assert_eq!(lifecycle.try_recv().unwrap().kind, LifecycleKind::Closed);
+    assert!(lifecycle.try_recv().is_err());
+}
+
+#[test]
+fn test_open_is_idempotent() {
+    let mut harness = TestManager::new();
+    let result = harness.manager.open("idempotent").unwrap();
+    assert_eq!(result.info.path, harness.path("idempotent"));
+    assert!(!result.info.powersync_loaded);
+    assert_eq!(result.info.powersync_version, None);
+    assert!(result.created);
+
+    let conn = harness.manager.get("idempotent").unwrap();
+    assert_eq!(harness.manager.open("idempotent").unwrap(), result);
+    assert!(std::sync::Arc::ptr_eq(&conn, &harness.manager.get("idempotent").unwrap()));
+
+    let lock = harness.manager.open_lock("idempotent");
+    assert!(std::sync::Arc::ptr_eq(&lock, &harness.manager.open_lock("idempotent")));
+    assert_eq!(harness.manager.info("other").unwrap(), None);
+
+    // Reopening an existing file doesn't create it
+    drop(conn);
+    harness.manager.close("idempotent").unwrap();
+    assert!(!harness.manager.open("idempotent").unwrap().created);
+}
+
+#[test]
+fn test_open_locks_released() {
+    use std::sync::Arc;
+
+    let mut harness = TestManager::new();
+    harness.open("released");
+    let lock = harness.manager.open_lock("released");
+    let released = Arc::downgrade(&lock);
+    drop(lock);
+    harness.manager.close("released").unwrap();
+    assert!(released.upgrade().is_none());
+
+    // An open that never finished is dropped by the next one
+    let lock = harness.manager.open_lock("failed");
+    let failed = Arc::downgrade(&lock);
+    drop(lock);
+    let held = harness.manager.open_lock("held");
+    assert!(failed.upgrade().is_none());
+
+    // A lock still being waited on survives closing
+    harness.manager.close("held").unwrap();
+    assert!(Arc::ptr_eq(&held, &harness.manager.open_lock("held")));
+}
+
+#[test]
+fn test_recover_corrupt_database() {
+    let mut harness = TestManager::new();
+    let mut lifecycle = harness.manager.subscribe_lifecycle();
+    std::fs::write(harness.path("corrupt"), vec![0x42; 4096]).unwrap();
+
+    assert!(matches!(
+        harness.manager.open("corrupt"),
+        Err(Error::CorruptDatabase(_))
+    ));
+    assert!(harness.path("corrupt").exists());
+
+    let result = harness.manager.recover("corrupt").unwrap();
+    assert!(result.created);
+    let recovery = result.recovery.unwrap();
+    assert_eq!(recovery.error, None);
+    assert!(!recovery.schema_restored);
+    assert_eq!(std::fs::read(&recovery.backup_path).unwrap(), vec![0x42; 4096]);
+
+    let opened = lifecycle.try_recv().unwrap();
+    assert_eq!(opened.kind, LifecycleKind::Opened);
+    assert_eq!(opened.recovery.unwrap().backup_path, recovery.backup_path);
+
+    let conn = harness.manager.get("corrupt").unwrap();
+    let mut conn = conn.lock().unwrap();
+    conn.execute(CREATE_TODOS, &[]).unwrap();
+    assert_eq!(count(&conn, "todos"), 0);
+}
+
+#[test]
+fn test_pipeline() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("pipeline");
+    let mut conn = conn.lock().unwrap();
+    conn.execute(CREATE_TODOS, &[]).unwrap();
+
+    let results = conn
+        .pipeline(&[
+            PipelineOp::Execute {
+                sql: INSERT_TODO.to_string(),
+                params: vec![text("1"), text("Buy milk")],
+            },
+            PipelineOp::GetAll {
+                sql: "SELECT * FROM todos".to_string(),
+                params: vec![],
+            },
+            PipelineOp::GetOptional {
+                sql: "SELECT * FROM todos WHERE id = ?".to_string(),
+                params: vec![text("2")],
+            },
+        ])
+        .unwrap();
+
+    assert!(matches!(&results[0], PipelineResult::Execute(result) if result.changes == 1));
+    assert!(matches!(&results[1], PipelineResult::GetAll(rows) if rows.rows.len() == 1));
+    assert!(matches!(&results[2], PipelineResult::GetOptional(None)));
+
+    // Not atomic: the insert before the failing operation stays applied
+    let failed = conn.pipeline(&[
+        PipelineOp::Execute {
+            sql: INSERT_TODO.to_string(),
+            params: vec![text("2"), text("Eggs")],
+        },
+        PipelineOp::GetAll {
+            sql: "SELECT * FROM missing".to_string(),
+            params: vec![],
+        },
+    ]);
+    assert!(failed.is_err());
+    assert_eq!(count(&conn, "todos"), 2);
+}
+
+#[test]
+fn test_integrity_check_and_names() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("intact");
+    harness.open("another");
+    assert_eq!(harness.manager.names(), ["another", "intact"]);
+    let mut conn = conn.lock().unwrap();
+    conn.execute(CREATE_TODOS, &[]).unwrap();
+    assert!(conn.integrity_check(10).unwrap().is_empty());
+}
+
+#[test]
+fn test_diagnostics_consent_persists() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("consent");
+    {
+        let mut conn = conn.lock().unwrap();
+        assert!(conn.diagnostics_enabled().unwrap());
+        conn.set_diagnostics_default(false);
+        assert!(!conn.diagnostics_enabled().unwrap());
+        conn.set_diagnostics_enabled(true).unwrap();
+        assert!(conn.diagnostics_enabled().unwrap());
+    }
+    drop(conn);
+    harness.manager.close("consent").unwrap();
+
+    let conn = harness.open("consent");
+    let mut conn = conn.lock().unwrap();
+    conn.set_diagnostics_default(false);
+    assert!(conn.diagnostics_enabled().unwrap());
+    conn.set_diagnostics_enabled(false).unwrap();
+    conn.set_diagnostics_default(true);
+    assert!(!conn.diagnostics_enabled().unwrap());
+}
+
+#[test]
+fn test_connection_stats() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("stats");
+    let mut conn = conn.lock().unwrap();
+    conn.execute(CREATE_TODOS, &[]).unwrap();
+    conn.execute(INSERT_TODO, &[text("1"), text("Buy milk")]).unwrap();
+    conn.get_all("SELECT * FROM todos", &[]).unwrap();
+
+    let stats = conn.stats().unwrap();
+    assert!(stats.cache_used > 0);
+    assert!(stats.schema_used > 0);
+    assert!(stats.cache_hits + stats.cache_misses > 0);
+}
+
+#[test]
+fn test_analyze() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("analyze");
+    let mut conn = conn.lock().unwrap();
+    conn.execute(CREATE_TODOS, &[]).unwrap();
+    let batch: Vec<_> = (0..20).map(|i| vec![text(&i.to_string()), text("todo")]).collect();
+    conn.execute_batch(INSERT_TODO, &batch).unwrap();
+
+    conn.analyze(Some("todos")).unwrap();
+    let stats = conn.get_all("SELECT * FROM sqlite_stat1 WHERE tbl = 'todos'", &[]).unwrap();
+    assert!(!stats.rows.is_empty());
+
+    // Automatic runs wait for the transaction and don't disturb its writes
+    conn.set_auto_analyze(Some(5));
+    let tx = conn.begin_transaction(true).unwrap();
+    let more: Vec<_> = (20..30).map(|i| vec![text(&i.to_string()), text("todo")]).collect();
+    conn.execute_batch(INSERT_TODO, &more).unwrap();
+    conn.commit_transaction(&tx).unwrap();
+    assert_eq!(count(&conn, "todos"), 30);
+}
+
+#[test]
+fn test_insert_many() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("insert_many");
+    let mut conn = conn.lock().unwrap();
+    conn.execute(CREATE_TODOS, &[]).unwrap();
+    let columns = vec!["id".to_string(), "description".to_string()];
+
+    // More variables than fit in one statement
+    let rows: Vec<_> = (0..20_000).map(|i| vec![text(&i.to_string()), text("todo")]).collect();
+    let result = conn.insert_many("todos", &columns, &rows).unwrap();
+    assert_eq!(result.changes, 20_000);
+    assert_eq!(count(&conn, "todos"), 20_000);
+
+    // A failing statement rolls back every row
+    let rows = vec![vec![text("new"), text("a")], vec![text("0"), text("duplicate")]];
+    assert!(conn.insert_many("todos", &columns, &rows).is_err());
+    assert_eq!(count(&conn, "todos"), 20_000);
+
+    let short_row = vec![vec![text("x")]];
+    assert!(matches!(
+        conn.insert_many("todos", &columns, &short_row),
+        Err(Error::InvalidParameter(_))
+    ));
+}
+
+#[test]
+fn test_get_optional_returns_first_row() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("get_optional");
+    let mut conn = conn.lock().unwrap();
+    conn.execute(CREATE_TODOS, &[]).unwrap();
+    let rows: Vec<_> = (0..100).map(|i| vec![text(&format!("{:03}", i)), text("todo")]).collect();
+    conn.execute_batch(INSERT_TODO, &rows).unwrap();
+
+    let row = conn.get_optional("SELECT * FROM todos ORDER BY id", &[]).unwrap().unwrap();
+    assert_eq!(row["id"], "000");
+    assert_eq!(row["description"], "todo");
+    assert!(conn
+        .get_optional("SELECT * FROM todos WHERE id = ?", &[text("missing")])
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn test_rows_keep_column_order() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("column_order");
+    let conn = conn.lock().unwrap();
+
+    let sql = "SELECT 1 AS zeta, 2 AS alpha, 3 AS mid, 4 AS beta";
+    let rows = conn.get_all(sql, &[]).unwrap();
+    let keys: Vec<&str> = rows.rows[0].keys().map(|key| &**key).collect();
+    assert_eq!(keys, rows.columns);
+    assert_eq!(keys, vec!["zeta", "alpha", "mid", "beta"]);
+
+    let json = serde_json::to_string(&rows.rows[0]).unwrap();
+    assert_eq!(json, r#"{"zeta":1,"alpha":2,"mid":3,"beta":4}"#);
+}
+
+#[test]
+fn test_query_to_file() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("export");
+    let mut conn = conn.lock().unwrap();
+    conn.execute(CREATE_TODOS, &[]).unwrap();
+    conn.execute(INSERT_TODO, &[text("1"), text("Buy milk")]).unwrap();
+    conn.execute(INSERT_TODO, &[text("2"), text("Eggs, bread")]).unwrap();
+
+    let dest = harness.path("export").with_extension("csv");
+    let sql = "SELECT id, description FROM todos WHERE id >= ? ORDER BY id";
+    let result = conn
+        .query_to_file(sql, &[text("1")], &dest, ExportFormat::Csv)
+        .unwrap();
+    let csv = std::fs::read_to_string(&dest).unwrap();
+    assert_eq!(csv, "id,description\n1,Buy milk\n2,\"Eggs, bread\"\n");
+    assert_eq!(result.rows, 2);
+    assert_eq!(result.bytes, csv.len() as i64);
+
+    // Writes are refused without touching the destination
+    let err = conn
+        .query_to_file("DELETE FROM todos", &[], &dest, ExportFormat::Ndjson)
+        .unwrap_err();
+    assert!(matches!(err, Error::InvalidParameter(_)));
+    assert_eq!(std::fs::read_to_string(&dest).unwrap(), csv);
+    assert_eq!(count(&conn, "todos"), 2);
+}
+
+#[test]
+fn test_get_all_snapshot() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("snapshot");
+    let mut conn = conn.lock().unwrap();
+    conn.execute(CREATE_TODOS, &[]).unwrap();
+    conn.execute(INSERT_TODO, &[text("1"), text("first")]).unwrap();
+
+    let query = |sql: &str| Statement {
+        sql: sql.to_string(),
+        params: vec![],
+    };
+    let results = conn
+        .get_all_snapshot(&[
+            query("SELECT * FROM todos"),
+            query("SELECT count(*) AS n FROM todos"),
+        ])
+        .unwrap();
+    assert_eq!(results[0].rows.len(), 1);
+    assert_eq!(results[1].rows[0]["n"], 1);
+    // The snapshot's transaction is over
+    let tx_id = conn.begin_transaction(true).unwrap();
+    conn.commit_transaction(&tx_id).unwrap();
+
+    let err = conn.get_all_snapshot(&[query("DELETE FROM todos")]).unwrap_err();
+    assert!(matches!(err, Error::InvalidParameter(_)));
+    assert_eq!(count(&conn, "todos"), 1);
+}
+
+#[test]
+fn test_snapshot_and_restore() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("restore");
+    let mut conn = conn.lock().unwrap();
+    conn.execute(CREATE_TODOS, &[]).unwrap();
+    conn.execute(INSERT_TODO, &[text("1"), text("seeded")]).unwrap();
+
+    let snapshot_id = conn.snapshot().unwrap();
+    conn.execute(INSERT_TODO, &[text("2"), text("scenario")]).unwrap();
+    conn.execute("CREATE TABLE extra (id TEXT)", &[]).unwrap();
+    assert_eq!(count(&conn, "todos"), 2);
+
+    let mut commits = conn.subscribe_commits();
+    conn.restore(&snapshot_id).unwrap();
+    assert_eq!(count(&conn, "todos"), 1);
+    let tables = commits.try_recv().unwrap().tables;
+    assert!(tables.contains("todos") && tables.contains("extra"));
+
+    // A snapshot can be restored repeatedly, but not inside a transaction
+    conn.execute("DELETE FROM todos", &[]).unwrap();
+    conn.restore(&snapshot_id).unwrap();
+    assert_eq!(count(&conn, "todos"), 1);
+    let tx_id = conn.begin_transaction(true).unwrap();
+    assert!(matches!(
+        conn.restore(&snapshot_id),
+        Err(Error::TransactionOrder(_))
+    ));
+    conn.rollback_transaction(&tx_id).unwrap();
+    assert!(matches!(
+        conn.restore("unknown"),
+        Err(Error::InvalidParameter(_))
+    ));
+}
+
+#[test]
+fn test_encrypted_export_round_trip() {
+    let mut harness = TestManager::new();
+    let conn = harness.open("bundle");
+    let mut conn = conn.lock().unwrap();
+    conn.execute(CREATE_TODOS, &[]).unwrap();
+    conn.execute(INSERT_TODO, &[text("1"), text("secret plans")]).unwrap();
+
+    let bundle = harness.path("bundle").with_extension("bundle");
+    let export = conn.export_encrypted(&bundle, "hunter2").unwrap();
+    assert!(export.bytes > 0 && export.bytes < export.database_bytes);
+    let sealed = std::fs::read(&bundle).unwrap();
+    assert!(!sealed.windows(12).any(|w| w == b"secret plans"));
+
+    conn.execute(INSERT_TODO, &[text("2"), text("later")]).unwrap();
+    assert!(matches!(
+        conn.import_encrypted(&bundle, "wrong"),
+        Err(Error::InvalidBundle(_))
+    ));
+    assert_eq!(count(&conn, "todos"), 2);
+
+    let mut commits = conn.subscribe_commits();
+    conn.import_encrypted(&bundle, "hunter2").unwrap();
+    assert_eq!(count(&conn, "todos"), 1);
+    assert!(commits.try_recv().unwrap().tables.contains("todos"));
+    // Imported through an in-memory copy, without losing WAL mode
+    let mode = conn.get_all("PRAGMA journal_mode", &[]).unwrap();
+    assert_eq!(mode.rows[0]["journal_mode"], "wal");
+}
@@ -0,0 +1,64 @@
+/**
+ * Minimal FIFO async mutex.
+ *
+ * The Rust side nests any begin_transaction issued while another transaction
+ * is open as a savepoint, so concurrent JS callers must be serialized here to
+ * keep their write transactions isolated from each other.
+ */
+export class Mutex {
+  private locked = false;
+  private waiters: Array<() => void> = [];
+
+  /**
+   * Run a callback while holding the lock.
+   * @param timeoutMs Reject if the lock isn't acquired within this many milliseconds
+   */
+  async runExclusive<T>(callback: () => Promise<T>, timeoutMs?: number): Promise<T> {
+    await this.acquire(timeoutMs);
+    try {
+      return await callback();
+    } finally {
+      this.release();
+    }
+  }
+
+  /**
+   * Acquire the lock. Every successful acquire must be paired with release().
+   */
+  acquire(timeoutMs?: number): Promise<void> {
+    if (!this.locked) {
+      this.locked = true;
+      return Promise.resolve();
+    }
+
+    return new Promise<void>((resolve, reject) => {
+      let timer: ReturnType<typeof setTimeout> | null = null;
+
+      const waiter = () => {
+        if (timer) clearTimeout(timer);
+        resolve();
+      };
+      this.waiters.push(waiter);
+
+      if (timeoutMs != null) {
+        timer = setTimeout(() => {
+          const index = this.waiters.indexOf(waiter);
+          if (index >= 0) {
+            this.waiters.splice(index, 1);
+            reject(new Error(`Timed out waiting for lock after ${timeoutMs}ms`));
+          }
+        }, timeoutMs);
+      }
+    });
+  }
+
+  release(): void {
+    const next = this.waiters.shift();
+    if (next) {
+      // Hand the lock directly to the next waiter
+      next();
+    } else {
+      this.locked = false;
+    }
+  }
+}
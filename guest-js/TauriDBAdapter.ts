@@ -1,18 +1,3 @@
-import { invoke as rawInvoke } from '@tauri-apps/api/core';
-
-/**
- * Tauri's invoke() rejects with a plain string on Rust errors.
- * PowerSync expects Error objects (reads .name/.message/.stack).
- * This wrapper ensures rejections are always proper Error instances.
- */
-async function invoke<T>(cmd: string, args?: Record<string, unknown>): Promise<T> {
-  try {
-    return await rawInvoke<T>(cmd, args);
-  } catch (e) {
-    if (e instanceof Error) throw e;
-    throw new Error(typeof e === 'string' ? e : JSON.stringify(e));
-  }
-}
 import type {
   DBAdapter,
   DBAdapterListener,
@@ -24,67 +9,44 @@ import type {
   UpdateNotification,
 } from '@powersync/common';
 import { BaseObserver, RowUpdateType } from '@powersync/common';
-import type { ExecuteResult, QueryResult as TauriQueryResult, CrudEntry } from './types';
+import type { UnlistenFn } from '@tauri-apps/api/event';
+import type { CrudEntry, ExecuteResult, PowerSyncStatus } from './types';
+import * as commands from './commands';
+import { onTablesChanged } from './events';
+import { Mutex } from './Mutex';
 
 // PowerSync internal table name for CRUD entries
 const PS_CRUD_TABLE = 'ps_crud';
 
-/**
- * Tagged union type for SQL parameters.
- * This matches the Rust SqlParam enum for proper serialization.
- */
-type SqlParam =
-  | { type: 'null' }
-  | { type: 'bool'; value: boolean }
-  | { type: 'int'; value: number }
-  | { type: 'real'; value: number }
-  | { type: 'text'; value: string }
-  | { type: 'blob'; value: number[] };
+// PowerSync internal table for sync operations
+const PS_OPERATIONS_TABLE = 'powersync_operations';
 
 /**
- * Convert a JavaScript value to a typed SqlParam for Rust.
- * This ensures blobs and other types are properly serialized.
+ * Times a read is re-run because writes were acknowledged while it ran
  */
-function toSqlParam(p: unknown): SqlParam {
-  if (p === null || p === undefined) {
-    return { type: 'null' };
-  }
-  if (typeof p === 'boolean') {
-    return { type: 'bool', value: p };
-  }
-  if (typeof p === 'number') {
-    // Distinguish integers from floats
-    return Number.isInteger(p)
-      ? { type: 'int', value: p }
-      : { type: 'real', value: p };
-  }
-  if (typeof p === 'string') {
-    return { type: 'text', value: p };
-  }
-  if (p instanceof Uint8Array) {
-    return { type: 'blob', value: Array.from(p) };
-  }
-  if (ArrayBuffer.isView(p)) {
-    // Handle other typed arrays
-    return { type: 'blob', value: Array.from(new Uint8Array(p.buffer, p.byteOffset, p.byteLength)) };
-  }
-  if (p instanceof ArrayBuffer) {
-    return { type: 'blob', value: Array.from(new Uint8Array(p)) };
-  }
-  // Fallback: serialize as JSON text
-  return { type: 'text', value: JSON.stringify(p) };
-}
+const MAX_READ_RETRIES = 3;
 
 /**
- * Convert an array of parameters to typed SqlParams.
+ * Writes a transaction or write lock can run inside itself besides
+ * execute(). Passing `txId` to the adapter's other write methods runs them
+ * inside it too; without it they would wait for the transaction to end.
  */
-function toSqlParams(params: unknown[] | undefined): SqlParam[] {
-  if (!params) return [];
-  return params.map(toSqlParam);
+export interface TransactionWrites {
+  /** Id of the transaction, as taken by the adapter's write methods */
+  txId: string;
+  executeBatch(
+    sql: string,
+    paramsBatch?: any[][],
+    options?: commands.BatchOptions
+  ): Promise<QueryResult>;
+  insertMany(table: string, columns: string[], rows: unknown[][]): Promise<ExecuteResult>;
 }
 
-// PowerSync internal table for sync operations
-const PS_OPERATIONS_TABLE = 'powersync_operations';
+/** The `tx` passed to readTransaction() and writeTransaction() callbacks */
+export type TauriTransaction = Transaction & TransactionWrites;
+
+/** The context passed to writeLock() callbacks */
+export type TauriLockContext = LockContext & TransactionWrites;
 
 /**
  * Extract table names from SQL statement for change notifications.
@@ -130,16 +92,6 @@ function extractTablesFromSql(sql: string): string[] {
   return tables;
 }
 
-/**
- * Check if a table name is a PowerSync internal table.
- * When these tables are modified, user tables may have been updated by the extension.
- */
-function isPowerSyncInternalTable(tableName: string): boolean {
-  return tableName === 'powersync_operations' ||
-         tableName.startsWith('ps_') ||
-         tableName.startsWith('ps_data_');
-}
-
 /**
  * Determine the operation type from SQL statement.
  */
@@ -168,7 +120,12 @@ export class TauriDBAdapter extends BaseObserver<DBAdapterListener> implements D
   private closed = false;
   private pendingUpdates: Set<string> = new Set();
   private updateTimer: ReturnType<typeof setTimeout> | null = null;
-  private userTables: Set<string> = new Set();
+  /** Stops listening for the tables changed by each commit */
+  private unlistenCommits: UnlistenFn | null = null;
+  /** Serializes write transactions/locks from concurrent callers */
+  private writeMutex = new Mutex();
+  /** Bumped each time a write through this adapter is acknowledged */
+  private writeEpoch = 0;
 
   constructor(name: string) {
     super();
@@ -176,14 +133,10 @@ export class TauriDBAdapter extends BaseObserver<DBAdapterListener> implements D
   }
 
   /**
-   * Register user table names for sync notifications.
-   * Called during schema initialization.
+   * @deprecated Tables changed by sync are now reported by the plugin after
+   * each commit, so they no longer need registering.
    */
-  registerUserTables(tableNames: string[]): void {
-    for (const name of tableNames) {
-      this.userTables.add(name);
-    }
-  }
+  registerUserTables(_tableNames: string[]): void {}
 
   /**
    * Queue table updates for batched notification.
@@ -203,6 +156,48 @@ export class TauriDBAdapter extends BaseObserver<DBAdapterListener> implements D
     }
   }
 
+  /**
+   * Record that a write was acknowledged, and notify listeners about the
+   * tables it changed.
+   */
+  private acknowledgeWrite(tables: string[]): void {
+    this.writeEpoch++;
+    if (tables.length > 0) {
+      this.queueTableUpdate(tables);
+    }
+  }
+
+  /**
+   * Run a write after the write transactions/locks queued before it, or
+   * right away inside transaction `txId`, which already holds the mutex
+   */
+  private write<T>(txId: string | undefined, run: () => Promise<T>): Promise<T> {
+    return txId ? run() : this.writeMutex.runExclusive(run);
+  }
+
+  /**
+   * Run a read, re-running it if a write through this adapter was
+   * acknowledged while it was in flight.
+   *
+   * Such a read may have run before the write on the Rust side. A watch
+   * emitting its result after the write resolved would show the write
+   * undone until the next emission; re-running guarantees any read
+   * resolving after a write reflects it (read-your-writes).
+   *
+   * Under continuous writes it gives up after MAX_READ_RETRIES re-runs and
+   * returns the last result. The writes it raced already queued table
+   * updates, so watches re-run once more and catch up.
+   */
+  private async read<T>(query: () => Promise<T>): Promise<T> {
+    for (let retry = 0; ; retry++) {
+      const epoch = this.writeEpoch;
+      const result = await query();
+      if (epoch === this.writeEpoch || retry === MAX_READ_RETRIES) {
+        return result;
+      }
+    }
+  }
+
   /**
    * Fire pending table update notifications to listeners.
    */
@@ -228,7 +223,14 @@ export class TauriDBAdapter extends BaseObserver<DBAdapterListener> implements D
    * Open the database connection
    */
   async open(): Promise<void> {
-    await invoke('plugin:powersync-jf|open', { name: this.name });
+    await commands.open(this.name);
+    // Covers writes the SQL can't reveal: sync applied by powersync_control,
+    // triggers, and other windows or Rust code writing to the database
+    if (!this.unlistenCommits) {
+      this.unlistenCommits = await onTablesChanged(this.name, ({ tables }) =>
+        this.queueTableUpdate(tables)
+      );
+    }
   }
 
   /**
@@ -237,57 +239,46 @@ export class TauriDBAdapter extends BaseObserver<DBAdapterListener> implements D
   async close(): Promise<void> {
     if (this.closed) return;
     this.closed = true;
-    await invoke('plugin:powersync-jf|close', { name: this.name });
+    this.unlistenCommits?.();
+    this.unlistenCommits = null;
+    await commands.close(this.name);
   }
 
   /**
    * Execute a SQL statement
    */
   async execute(sql: string, params?: any[]): Promise<QueryResult> {
-    const result = await invoke<ExecuteResult>('plugin:powersync-jf|execute', {
-      name: this.name,
-      sql,
-      params: toSqlParams(params),
-    });
+    return this.executeInTransaction(undefined, sql, params);
+  }
 
-    // Detect table changes and notify listeners
+  /**
+   * Execute a SQL statement inside transaction `txId` (from
+   * commands.beginTransaction), taking the transaction's place in the write
+   * queue rather than waiting for it to end
+   */
+  async executeInTransaction(
+    txId: string | undefined,
+    sql: string,
+    params?: any[]
+  ): Promise<QueryResult> {
+    const result = await commands.executePowerSync(this.name, sql, params, txId);
+
+    // Notify listeners right away about tables the statement names; tables
+    // changed indirectly arrive with the commit's tables-changed event
     const detectedTables = extractTablesFromSql(sql);
-
-    // Check if any PowerSync internal tables were modified
-    // If so, user tables may have been updated by the extension - notify all of them
-    const modifiedInternalTable = detectedTables.some(isPowerSyncInternalTable);
-
-    if (modifiedInternalTable && this.userTables.size > 0) {
-      // PowerSync internal operation - notify all user tables
-      this.queueTableUpdate(Array.from(this.userTables));
-    } else if (detectedTables.length > 0) {
-      // Regular write operation - notify detected tables
-      this.queueTableUpdate(detectedTables);
+    if (detectedTables.length > 0) {
+      this.acknowledgeWrite(detectedTables);
     }
 
     // For SELECT statements, the Rust side returns the actual rows
-    const rowsArray = result.rows ?? [];
-
-    return {
-      insertId: result.lastInsertRowid,
-      rowsAffected: result.changes,
-      rows: {
-        _array: rowsArray,
-        length: rowsArray.length,
-        item: (i: number) => rowsArray[i] ?? null,
-      },
-    };
+    return result;
   }
 
   /**
    * Execute a SQL statement and return raw results (array of arrays)
    */
   async executeRaw(sql: string, params?: any[]): Promise<any[][]> {
-    const result = await invoke<TauriQueryResult>('plugin:powersync-jf|get_all', {
-      name: this.name,
-      sql,
-      params: toSqlParams(params),
-    });
+    const result = await this.read(() => commands.getAll(this.name, sql, params));
 
     // Convert row objects to arrays of values
     return result.rows.map((row) => result.columns.map((col) => row[col]));
@@ -297,24 +288,24 @@ export class TauriDBAdapter extends BaseObserver<DBAdapterListener> implements D
    * Execute a read-only query and return all results
    */
   async getAll<T>(sql: string, params?: any[]): Promise<T[]> {
-    const result = await invoke<TauriQueryResult>('plugin:powersync-jf|get_all', {
-      name: this.name,
-      sql,
-      params: toSqlParams(params),
-    });
+    const result = await this.read(() => commands.getAll(this.name, sql, params));
 
     return result.rows as T[];
   }
 
+  /**
+   * Run read-only queries against one snapshot; returns each query's rows
+   */
+  async getAllSnapshot(queries: commands.Statement[]): Promise<unknown[][]> {
+    const results = await this.read(() => commands.getAllSnapshot(this.name, queries));
+    return results.map((result) => result.rows);
+  }
+
   /**
    * Execute a read-only query and return a single optional result
    */
   async getOptional<T>(sql: string, params?: any[]): Promise<T | null> {
-    const result = await invoke<Record<string, unknown> | null>('plugin:powersync-jf|get_optional', {
-      name: this.name,
-      sql,
-      params: toSqlParams(params),
-    });
+    const result = await this.read(() => commands.getOptional(this.name, sql, params));
 
     return result as T | null;
   }
@@ -331,20 +322,28 @@ export class TauriDBAdapter extends BaseObserver<DBAdapterListener> implements D
   }
 
   /**
-   * Execute a batch of SQL statements with the same SQL but different parameters
+   * Execute a batch of SQL statements with the same SQL but different
+   * parameters, inside transaction `txId` if given
    */
-  async executeBatch(sql: string, paramsBatch?: any[][]): Promise<QueryResult> {
-    const result = await invoke<ExecuteResult>('plugin:powersync-jf|execute_batch', {
-      name: this.name,
-      sql,
-      paramsBatch: (paramsBatch ?? []).map(toSqlParams),
-    });
+  async executeBatch(
+    sql: string,
+    paramsBatch?: any[][],
+    options?: commands.BatchOptions,
+    txId?: string
+  ): Promise<QueryResult> {
+    const result = await commands.executeBatch(this.name, sql, paramsBatch, options, txId);
 
     // Notify listeners about table changes
     // Always notify for batch operations
-    const tables = extractTablesFromSql(sql);
-    if (tables.length > 0) {
-      this.queueTableUpdate(tables);
+    this.acknowledgeWrite(extractTablesFromSql(sql));
+
+    // Chunked batches report failures instead of rejecting, since earlier
+    // chunks stay committed
+    if (result.failure && !options?.details) {
+      throw new commands.PowerSyncPluginError(
+        'execute_batch',
+        `parameter set ${result.failure.index} failed after ${result.changes} rows were committed: ${result.failure.error}`
+      );
     }
 
     return {
@@ -358,6 +357,176 @@ export class TauriDBAdapter extends BaseObserver<DBAdapterListener> implements D
     };
   }
 
+  /**
+   * Run statements atomically in a single write transaction with one IPC call.
+   *
+   * Cheaper than writeTransaction() when the statements are known up front,
+   * e.g. inserting a parent row together with its children.
+   */
+  async executeTransaction(
+    statements: commands.Statement[],
+    txId?: string
+  ): Promise<ExecuteResult[]> {
+    const results = await this.write(txId, () =>
+      commands.executeTransaction(this.name, statements, txId)
+    );
+
+    this.acknowledgeWrite(statements.flatMap(({ sql }) => extractTablesFromSql(sql)));
+
+    return results;
+  }
+
+  /**
+   * Run statements atomically with one IPC call, committing only if none of
+   * the guard queries aborts, e.g. to update a row only while it still
+   * matches what the user saw.
+   */
+  async withTransaction(
+    statements: commands.Statement[],
+    guards: commands.TransactionGuard[],
+    txId?: string
+  ): Promise<commands.GuardedTransaction> {
+    const outcome = await this.write(txId, () =>
+      commands.withTransaction(this.name, statements, guards, txId)
+    );
+
+    if (outcome.committed) {
+      this.acknowledgeWrite(statements.flatMap(({ sql }) => extractTablesFromSql(sql)));
+    }
+
+    return outcome;
+  }
+
+  /**
+   * Insert `rows` (one value per entry of `columns`) into `table` atomically.
+   * Much faster than executeBatch() for initial data loads.
+   */
+  async insertMany(
+    table: string,
+    columns: string[],
+    rows: unknown[][],
+    txId?: string
+  ): Promise<ExecuteResult> {
+    const result = await this.write(txId, () =>
+      commands.insertMany(this.name, table, columns, rows, txId)
+    );
+    this.acknowledgeWrite([table]);
+    return result;
+  }
+
+  /**
+   * Insert a row into `table` with a natively generated `id`, returning the id
+   */
+  async insertWithId(
+    table: string,
+    values: Record<string, unknown>,
+    txId?: string
+  ): Promise<string> {
+    const id = await this.write(txId, () =>
+      commands.insertWithId(this.name, table, values, txId)
+    );
+    this.acknowledgeWrite([table]);
+    return id;
+  }
+
+  /**
+   * Revert the latest transaction written to the tables tracked with
+   * commands.enableUndo(), or resolve to null if there is nothing to undo
+   */
+  async undo(txId?: string): Promise<commands.UndoStep | null> {
+    const step = await this.write(txId, () => commands.undo(this.name, txId));
+    if (step) {
+      this.acknowledgeWrite(step.tables);
+    }
+    return step;
+  }
+
+  /**
+   * Write the transaction last undone again, or resolve to null if there is
+   * nothing to redo
+   */
+  async redo(txId?: string): Promise<commands.UndoStep | null> {
+    const step = await this.write(txId, () => commands.redo(this.name, txId));
+    if (step) {
+      this.acknowledgeWrite(step.tables);
+    }
+    return step;
+  }
+
+  /**
+   * Run execute/getAll/getOptional operations back to back with one IPC call.
+   * Not atomic; use executeTransaction() for writes that must apply together.
+   */
+  async pipeline(
+    ops: commands.PipelineOp[],
+    txId?: string
+  ): Promise<commands.PipelineResult[]> {
+    const run = () => commands.pipeline(this.name, ops, txId);
+    const writes = ops.filter(({ op }) => op === 'execute');
+    const results = await (writes.length > 0 ? this.write(txId, run) : run());
+
+    if (writes.length > 0) {
+      this.acknowledgeWrite(writes.flatMap(({ sql }) => extractTablesFromSql(sql)));
+    }
+
+    return results;
+  }
+
+  /**
+   * Apply a JSON merge patch to a JSON column of the row with `id`
+   */
+  async patchJson(
+    table: string,
+    column: string,
+    id: string,
+    patch: Record<string, unknown>,
+    txId?: string
+  ): Promise<ExecuteResult> {
+    const result = await this.write(txId, () =>
+      commands.patchJson(this.name, table, column, id, patch, txId)
+    );
+    this.acknowledgeWrite([table]);
+    return result;
+  }
+
+  /**
+   * Enable soft delete for a table; see `commands.enableSoftDelete`
+   */
+  async enableSoftDelete(table: string, column: string, txId?: string): Promise<void> {
+    await this.write(txId, () => commands.enableSoftDelete(this.name, table, column, txId));
+  }
+
+  /**
+   * Tombstone the row with `id` instead of deleting it
+   */
+  async softDelete(table: string, id: string, txId?: string): Promise<ExecuteResult> {
+    const result = await this.write(txId, () =>
+      commands.softDeleteRow(this.name, table, id, txId)
+    );
+    this.acknowledgeWrite([table]);
+    return result;
+  }
+
+  /**
+   * Clear the tombstone of a soft-deleted row
+   */
+  async restore(table: string, id: string, txId?: string): Promise<ExecuteResult> {
+    const result = await this.write(txId, () => commands.restoreRow(this.name, table, id, txId));
+    this.acknowledgeWrite([table]);
+    return result;
+  }
+
+  /**
+   * Permanently delete tombstoned rows, optionally only those deleted before `before`
+   */
+  async purgeDeleted(table: string, before?: Date, txId?: string): Promise<ExecuteResult> {
+    const result = await this.write(txId, () =>
+      commands.purgeDeleted(this.name, table, before?.toISOString(), txId)
+    );
+    this.acknowledgeWrite([table]);
+    return result;
+  }
+
   /**
    * Run a callback within a read transaction
    */
@@ -369,13 +538,14 @@ export class TauriDBAdapter extends BaseObserver<DBAdapterListener> implements D
   }
 
   /**
-   * Run a callback within a write transaction
+   * Run a callback within a write transaction. `tx` is a TauriTransaction,
+   * which also runs batches and bulk inserts inside the transaction.
    */
   async writeTransaction<T>(
     callback: (tx: Transaction) => Promise<T>,
     options?: DBLockOptions
   ): Promise<T> {
-    return this.transaction(callback, true);
+    return this.writeMutex.runExclusive(() => this.transaction(callback, true), options?.timeoutMs);
   }
 
   /**
@@ -383,14 +553,11 @@ export class TauriDBAdapter extends BaseObserver<DBAdapterListener> implements D
    * The Rust side handles nesting automatically via savepoints.
    */
   private async transaction<T>(
-    callback: (tx: Transaction) => Promise<T>,
+    callback: (tx: TauriTransaction) => Promise<T>,
     isWrite: boolean
   ): Promise<T> {
     // Always use begin_transaction - Rust side handles nesting with savepoints
-    const txId = await invoke<string>('plugin:powersync-jf|begin_transaction', {
-      name: this.name,
-      isWrite,
-    });
+    const txId = await commands.beginTransaction(this.name, isWrite);
 
     const self = this;
     // Track tables modified during this transaction
@@ -398,13 +565,11 @@ export class TauriDBAdapter extends BaseObserver<DBAdapterListener> implements D
     // Track if transaction was already finalized by callback
     let finalized = false;
 
-    const tx: Transaction = {
+    const tx: TauriTransaction = {
+      txId,
+
       execute: async (sql: string, params?: any[]): Promise<QueryResult> => {
-        const result = await invoke<ExecuteResult>('plugin:powersync-jf|execute', {
-          name: self.name,
-          sql,
-          params: toSqlParams(params),
-        });
+        const result = await commands.executePowerSync(self.name, sql, params, txId);
 
         // Track table changes for notification after commit
         // Always track for INSERT/UPDATE/DELETE (views with triggers may not report changes)
@@ -414,53 +579,44 @@ export class TauriDBAdapter extends BaseObserver<DBAdapterListener> implements D
         }
 
         // For SELECT statements, the Rust side returns the actual rows
-        const rowsArray = result.rows ?? [];
-
-        return {
-          insertId: result.lastInsertRowid,
-          rowsAffected: result.changes,
-          rows: {
-            _array: rowsArray,
-            length: rowsArray.length,
-            item: (i: number) => rowsArray[i] ?? null,
-          },
-        };
+        return result;
       },
 
-      executeRaw: async (sql: string, params?: any[]): Promise<any[][]> => {
-        const result = await invoke<TauriQueryResult>('plugin:powersync-jf|get_all', {
-          name: self.name,
-          sql,
-          params: toSqlParams(params),
-        });
-        // powersync_control() is a SELECT that internally modifies user tables.
-        // extractTablesFromSql() can't detect this, so notify all user tables.
-        if (sql.includes('powersync_control') && self.userTables.size > 0) {
-          for (const table of self.userTables) {
-            modifiedTables.add(table);
-          }
+      executeBatch: async (
+        sql: string,
+        paramsBatch?: any[][],
+        options?: commands.BatchOptions
+      ): Promise<QueryResult> => {
+        const result = await self.executeBatch(sql, paramsBatch, options, txId);
+        for (const table of extractTablesFromSql(sql)) {
+          modifiedTables.add(table);
         }
+        return result;
+      },
+
+      insertMany: async (
+        table: string,
+        columns: string[],
+        rows: unknown[][]
+      ): Promise<ExecuteResult> => {
+        const result = await self.insertMany(table, columns, rows, txId);
+        modifiedTables.add(table);
+        return result;
+      },
+
+      executeRaw: async (sql: string, params?: any[]): Promise<any[][]> => {
+        // Tables changed by powersync_control() are reported on commit
+        const result = await commands.getAll(self.name, sql, params);
         return result.rows.map((row) => result.columns.map((col) => row[col]));
       },
 
       getAll: async <R>(sql: string, params?: any[]): Promise<R[]> => {
-        const result = await invoke<TauriQueryResult>('plugin:powersync-jf|get_all', {
-          name: self.name,
-          sql,
-          params: toSqlParams(params),
-        });
+        const result = await commands.getAll(self.name, sql, params);
         return result.rows as R[];
       },
 
       getOptional: async <R>(sql: string, params?: any[]): Promise<R | null> => {
-        const result = await invoke<Record<string, unknown> | null>(
-          'plugin:powersync-jf|get_optional',
-          {
-            name: self.name,
-            sql,
-            params: toSqlParams(params),
-          }
-        );
+        const result = await commands.getOptional(self.name, sql, params);
         return result as R | null;
       },
 
@@ -474,22 +630,16 @@ export class TauriDBAdapter extends BaseObserver<DBAdapterListener> implements D
 
       rollback: async (): Promise<QueryResult> => {
         finalized = true;
-        await invoke('plugin:powersync-jf|rollback_transaction', {
-          name: self.name,
-          txId,
-        });
+        await commands.rollbackTransaction(self.name, txId);
         return { rowsAffected: 0, rows: { _array: [], length: 0, item: () => null } };
       },
 
       commit: async (): Promise<QueryResult> => {
         finalized = true;
-        await invoke('plugin:powersync-jf|commit_transaction', {
-          name: self.name,
-          txId,
-        });
+        await commands.commitTransaction(self.name, txId);
         // Notify listeners about tables modified in the transaction
-        if (modifiedTables.size > 0) {
-          self.queueTableUpdate(Array.from(modifiedTables));
+        if (isWrite) {
+          self.acknowledgeWrite(Array.from(modifiedTables));
         }
         return { rowsAffected: 0, rows: { _array: [], length: 0, item: () => null } };
       },
@@ -500,14 +650,11 @@ export class TauriDBAdapter extends BaseObserver<DBAdapterListener> implements D
 
       // Only commit if not already finalized by the callback
       if (!finalized) {
-        await invoke('plugin:powersync-jf|commit_transaction', {
-          name: this.name,
-          txId,
-        });
+        await commands.commitTransaction(this.name, txId);
 
         // Notify listeners about tables modified in the transaction
-        if (modifiedTables.size > 0) {
-          this.queueTableUpdate(Array.from(modifiedTables));
+        if (isWrite) {
+          this.acknowledgeWrite(Array.from(modifiedTables));
         }
       }
 
@@ -516,10 +663,7 @@ export class TauriDBAdapter extends BaseObserver<DBAdapterListener> implements D
       // Only rollback if not already finalized
       if (!finalized) {
         try {
-          await invoke('plugin:powersync-jf|rollback_transaction', {
-            name: this.name,
-            txId,
-          });
+          await commands.rollbackTransaction(this.name, txId);
         } catch {
           // Ignore rollback errors
         }
@@ -533,7 +677,12 @@ export class TauriDBAdapter extends BaseObserver<DBAdapterListener> implements D
    */
   async readLock<T>(callback: (ctx: LockContext) => Promise<T>, options?: DBLockOptions): Promise<T> {
     const ctx: LockContext = {
-      execute: (sql, params) => this.execute(sql, params),
+      // Watches run their queries through here. Writes aren't re-run, and
+      // execute() only acknowledges statements it recognizes as writes.
+      execute: (sql, params) =>
+        extractTablesFromSql(sql).length > 0
+          ? this.execute(sql, params)
+          : this.read(() => this.execute(sql, params)),
       executeRaw: (sql, params) => this.executeRaw(sql, params),
       getAll: (sql, params) => this.getAll(sql, params),
       getOptional: (sql, params) => this.getOptional(sql, params),
@@ -543,26 +692,35 @@ export class TauriDBAdapter extends BaseObserver<DBAdapterListener> implements D
   }
 
   /**
-   * Acquire a write lock and run a callback
+   * Acquire a write lock and run a callback. `ctx` is a TauriLockContext.
    */
   async writeLock<T>(callback: (ctx: LockContext) => Promise<T>, options?: DBLockOptions): Promise<T> {
-    return this.writeTransaction(async (tx) => {
-      const ctx: LockContext = {
-        execute: (sql, params) => tx.execute(sql, params),
-        executeRaw: (sql, params) => tx.executeRaw(sql, params),
-        getAll: (sql, params) => tx.getAll(sql, params),
-        getOptional: (sql, params) => tx.getOptional(sql, params),
-        get: (sql, params) => tx.get(sql, params),
-      };
-      return callback(ctx);
-    });
+    const run = () =>
+      this.transaction(async (tx) => {
+        const ctx: TauriLockContext = {
+          txId: tx.txId,
+          execute: (sql, params) => tx.execute(sql, params),
+          executeBatch: (sql: string, paramsBatch?: any[][], options?: commands.BatchOptions) =>
+            tx.executeBatch(sql, paramsBatch, options),
+          insertMany: (table: string, columns: string[], rows: unknown[][]) =>
+            tx.insertMany(table, columns, rows),
+          executeRaw: (sql, params) => tx.executeRaw(sql, params),
+          getAll: (sql, params) => tx.getAll(sql, params),
+          getOptional: (sql, params) => tx.getOptional(sql, params),
+          get: (sql, params) => tx.get(sql, params),
+        };
+        return callback(ctx);
+      }, true);
+    return this.writeMutex.runExclusive(run, options?.timeoutMs);
   }
 
   /**
-   * Refresh the schema - no-op for now
+   * Refresh the schema.
+   * Re-runs PowerSync initialization and reloads SQLite's schema cache, so
+   * views regenerated by replaceSchema() or external changes are picked up.
    */
   async refreshSchema(): Promise<void> {
-    // No-op - schema is managed by PowerSync extension
+    await commands.refreshSchema(this.name);
   }
 
   // =====================================================
@@ -573,18 +731,28 @@ export class TauriDBAdapter extends BaseObserver<DBAdapterListener> implements D
    * Check if the PowerSync extension is loaded
    */
   async isPowerSyncLoaded(): Promise<boolean> {
-    return invoke<boolean>('plugin:powersync-jf|is_powersync_loaded', {
-      name: this.name,
-    });
+    return commands.isPowerSyncLoaded(this.name);
   }
 
   /**
    * Get the PowerSync extension version
    */
   async getPowerSyncVersion(): Promise<string> {
-    return invoke<string>('plugin:powersync-jf|get_powersync_version', {
-      name: this.name,
-    });
+    return commands.getPowerSyncVersion(this.name);
+  }
+
+  /**
+   * Whether the PowerSync extension is loaded, and why loading failed if not
+   */
+  async getPowerSyncStatus(): Promise<PowerSyncStatus> {
+    return commands.getPowerSyncStatus(this.name);
+  }
+
+  /**
+   * Get the client ID sent with sync stream requests
+   */
+  async getClientId(): Promise<string> {
+    return commands.getClientId(this.name);
   }
 
   /**
@@ -592,24 +760,16 @@ export class TauriDBAdapter extends BaseObserver<DBAdapterListener> implements D
    * @param schemaJson JSON-encoded schema definition
    */
   async replaceSchema(schemaJson: string): Promise<void> {
-    await invoke('plugin:powersync-jf|replace_schema', {
-      name: this.name,
-      schemaJson,
-    });
+    await commands.replaceSchema(this.name, schemaJson);
   }
 
   /**
    * Execute a PowerSync control operation
-   * @param op Operation name
-   * @param payload JSON-encoded operation payload
+   * @param control Operation and its payload, e.g. `{ op: 'line_text', payload: line }`
    * @returns JSON-encoded result
    */
-  async powerSyncControl(op: string, payload: string): Promise<string> {
-    return invoke<string>('plugin:powersync-jf|powersync_control', {
-      name: this.name,
-      op,
-      payload,
-    });
+  async powerSyncControl(control: commands.ControlOp): Promise<string> {
+    return commands.powerSyncControl(this.name, control);
   }
 
   /**
@@ -617,10 +777,7 @@ export class TauriDBAdapter extends BaseObserver<DBAdapterListener> implements D
    * @param limit Maximum number of entries to return (default: 100)
    */
   async getCrudBatch(limit?: number): Promise<CrudEntry[]> {
-    return invoke<CrudEntry[]>('plugin:powersync-jf|get_crud_batch', {
-      name: this.name,
-      limit,
-    });
+    return commands.getCrudBatch(this.name, limit);
   }
 
   /**
@@ -628,27 +785,34 @@ export class TauriDBAdapter extends BaseObserver<DBAdapterListener> implements D
    * @param crudId Maximum CRUD entry ID to remove
    */
   async removeCrud(crudId: number): Promise<void> {
-    await invoke('plugin:powersync-jf|remove_crud', {
-      name: this.name,
-      crudId,
-    });
+    await commands.removeCrud(this.name, crudId);
   }
 
   /**
    * Check if there are pending CRUD entries
    */
   async hasPendingCrud(): Promise<boolean> {
-    return invoke<boolean>('plugin:powersync-jf|has_pending_crud', {
-      name: this.name,
-    });
+    return commands.hasPendingCrud(this.name);
   }
 
   /**
-   * Get the current write checkpoint
+   * Get the write checkpoint local changes are waiting on
    */
   async getWriteCheckpoint(): Promise<string | null> {
-    return invoke<string | null>('plugin:powersync-jf|get_write_checkpoint', {
-      name: this.name,
-    });
+    return commands.getWriteCheckpoint(this.name);
+  }
+
+  /**
+   * Get the time the last sync completed
+   */
+  async getLastSyncedAt(): Promise<Date | null> {
+    return commands.getLastSyncedAt(this.name);
+  }
+
+  /**
+   * Check whether a full sync has completed
+   */
+  async hasSynced(): Promise<boolean> {
+    return commands.hasSynced(this.name);
   }
 }
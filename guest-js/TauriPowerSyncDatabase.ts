@@ -1,5 +1,6 @@
 import { TauriDBAdapter } from './TauriDBAdapter';
 import type { QueryResult } from '@powersync/common';
+import type { BatchOptions } from './commands';
 
 /**
  * Options for creating a TauriPowerSyncDatabase
@@ -130,9 +131,13 @@ export class TauriPowerSyncDatabase {
   /**
    * Execute a batch of SQL statements
    */
-  async executeBatch(sql: string, paramsBatch?: any[][]): Promise<QueryResult> {
+  async executeBatch(
+    sql: string,
+    paramsBatch?: any[][],
+    options?: BatchOptions
+  ): Promise<QueryResult> {
     this.ensureInitialized();
-    return this.adapter.executeBatch(sql, paramsBatch);
+    return this.adapter.executeBatch(sql, paramsBatch, options);
   }
 
   /**
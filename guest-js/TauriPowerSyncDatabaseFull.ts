@@ -6,14 +6,18 @@ import {
   type RequiredAdditionalConnectionOptions,
   type StreamingSyncImplementation,
   type BucketStorageAdapter,
-  type Schema,
+  Schema,
   SqliteBucketStorage,
   AbstractRemote,
   type CreateSyncImplementationOptions,
   type RemoteConnector,
+  type CrudTransaction,
 } from '@powersync/common';
 import { fetch as tauriFetch } from '@tauri-apps/plugin-http';
 import { TauriDBAdapter } from './TauriDBAdapter';
+import * as commands from './commands';
+import type { Statement } from './commands';
+import type { ConflictResolution, PowerSyncStatus } from './types';
 import { TauriStreamingSyncImplementation } from './TauriStreamingSyncImplementation';
 import { TauriWebSocket } from './TauriWebSocket';
 
@@ -27,14 +31,73 @@ export interface TauriPowerSyncDatabaseFullOptions {
   database: string;
 
   /**
-   * PowerSync schema definition
+   * PowerSync schema definition.
+   * May be omitted here and passed to `init(schema)` instead.
    */
-  schema: Schema;
+  schema?: Schema;
 
   /**
    * Optional logger
    */
   logger?: any;
+
+  /**
+   * Tables to soft delete, mapped to their tombstone column
+   * (e.g. `{ todos: 'deleted_at' }`). Tombstoned rows are filtered out of
+   * their views.
+   */
+  softDelete?: Record<string, string>;
+
+  /**
+   * Settle a transaction the backend rejected instead of retrying it
+   * forever. Called when the connector's `uploadData` throws an error whose
+   * `status` is one of `uploadConflictStatuses`. Resolve to null to defer to
+   * the Rust handler registered with `Builder::on_upload_conflict`, if any.
+   */
+  onUploadConflict?: (
+    conflict: UploadConflict
+  ) => ConflictResolution | null | Promise<ConflictResolution | null>;
+
+  /**
+   * HTTP statuses of upload errors treated as conflicts. Defaults to `[409]`.
+   */
+  uploadConflictStatuses?: number[];
+}
+
+/**
+ * A transaction the backend rejected while uploading
+ */
+export interface UploadConflict {
+  transaction: CrudTransaction;
+  status: number;
+  /** What `uploadData` threw */
+  error: unknown;
+}
+
+/**
+ * Callbacks for watchSnapshot()
+ */
+export interface WatchSnapshotHandler {
+  /**
+   * Rows of each query, in order, all read from the same snapshot
+   */
+  onResult: (results: unknown[][]) => void;
+  onError?: (error: Error) => void;
+}
+
+/**
+ * Options for watchSnapshot()
+ */
+export interface WatchSnapshotOptions {
+  /**
+   * Tables to watch. Resolved from the queries if omitted.
+   */
+  tables?: string[];
+  /**
+   * Minimum delay between re-runs in milliseconds
+   */
+  throttleMs?: number;
+  signal?: AbortSignal;
 }
 
 /**
@@ -101,20 +164,24 @@ class TauriRemote extends AbstractRemote {
  * ```
  */
 export class TauriPowerSyncDatabaseFull extends AbstractPowerSyncDatabase {
+  private readonly softDelete: Record<string, string>;
+  private readonly onUploadConflict?: TauriPowerSyncDatabaseFullOptions['onUploadConflict'];
+  private readonly uploadConflictStatuses: number[];
+
   constructor(options: TauriPowerSyncDatabaseFullOptions) {
     const adapter = new TauriDBAdapter(options.database);
-
-    // Register user table names for sync notifications
-    const tableNames = options.schema.tables.map((t) => t.name);
-    adapter.registerUserTables(tableNames);
+    const schema = options.schema ?? new Schema({});
 
     const dbOptions: PowerSyncDatabaseOptionsWithDBAdapter = {
       database: adapter,
-      schema: options.schema,
+      schema,
       logger: options.logger,
     };
 
     super(dbOptions);
+    this.softDelete = options.softDelete ?? {};
+    this.onUploadConflict = options.onUploadConflict;
+    this.uploadConflictStatuses = options.uploadConflictStatuses ?? [409];
   }
 
   /**
@@ -124,6 +191,30 @@ export class TauriPowerSyncDatabaseFull extends AbstractPowerSyncDatabase {
     return this.database as TauriDBAdapter;
   }
 
+  /**
+   * Wait for the database to be ready, optionally applying a schema.
+   * Passing a schema here is equivalent to passing it to the constructor.
+   */
+  async init(schema?: Schema): Promise<void> {
+    await super.init();
+    if (schema) {
+      await this.updateSchema(schema);
+    }
+  }
+
+  /**
+   * Apply a new schema, enabling soft delete for the tables listed in
+   * `softDelete`.
+   */
+  async updateSchema(schema: Schema): Promise<void> {
+    await super.updateSchema(schema);
+    // Tables already enabled are filtered again by the schema change itself;
+    // this enables the ones that weren't in the schema before
+    for (const [table, column] of Object.entries(this.softDelete)) {
+      await this.tauriAdapter.enableSoftDelete(table, column);
+    }
+  }
+
   /**
    * Initialize the database.
    * Opens the underlying SQLite connection and sets up PowerSync.
@@ -158,7 +249,13 @@ export class TauriPowerSyncDatabaseFull extends AbstractPowerSyncDatabase {
       adapter: this.bucketStorageAdapter,
       remote,
       uploadCrud: async () => {
-        await connector.uploadData(this);
+        try {
+          await connector.uploadData(this);
+        } catch (error) {
+          if (!(await this.resolveUploadConflict(error))) {
+            throw error;
+          }
+        }
       },
       identifier: this.database.name,
       logger: this.logger,
@@ -166,6 +263,33 @@ export class TauriPowerSyncDatabaseFull extends AbstractPowerSyncDatabase {
     });
   }
 
+  /**
+   * Settle the next CRUD transaction if `error` rejected it as a conflict.
+   *
+   * @returns Whether it was settled; if not, the upload is retried as usual
+   */
+  private async resolveUploadConflict(error: unknown): Promise<boolean> {
+    const status = errorStatus(error);
+    if (status == null || !this.uploadConflictStatuses.includes(status)) {
+      return false;
+    }
+    const transaction = await this.getNextCrudTransaction();
+    if (!transaction) {
+      return false;
+    }
+    const resolution = (await this.onUploadConflict?.({ transaction, status, error })) ?? undefined;
+    const applied = await commands.resolveUploadConflict(
+      this.database.name,
+      transaction.crud[transaction.crud.length - 1].clientId,
+      { status, error: error instanceof Error ? error.message : String(error) },
+      resolution
+    );
+    if (applied) {
+      this.logger.warn(`Upload rejected with status ${status}, resolved with ${applied.action}`);
+    }
+    return applied != null;
+  }
+
   /**
    * Get the underlying Tauri adapter for direct access to PowerSync extension methods.
    */
@@ -173,6 +297,58 @@ export class TauriPowerSyncDatabaseFull extends AbstractPowerSyncDatabase {
     return this.tauriAdapter;
   }
 
+  /**
+   * Watch several queries that must agree with each other, e.g. a list and
+   * the count shown in its badge. Unlike separate watch() calls, every
+   * emission reads all queries from one snapshot, so a commit can never land
+   * between them.
+   *
+   * @returns A function that stops watching
+   */
+  watchSnapshot(
+    queries: Statement[],
+    handler: WatchSnapshotHandler,
+    options: WatchSnapshotOptions = {}
+  ): () => void {
+    let stopped = false;
+    let unsubscribe = () => {};
+    const stop = () => {
+      stopped = true;
+      unsubscribe();
+    };
+    options.signal?.addEventListener('abort', stop);
+
+    const run = async () => {
+      try {
+        const results = await this.tauriAdapter.getAllSnapshot(queries);
+        if (!stopped) {
+          handler.onResult(results);
+        }
+      } catch (error) {
+        handler.onError?.(error as Error);
+      }
+    };
+
+    const start = async () => {
+      const tables =
+        options.tables ??
+        (
+          await Promise.all(queries.map(({ sql, params }) => this.resolveTables(sql, params)))
+        ).flat();
+      if (stopped) {
+        return;
+      }
+      unsubscribe = this.onChange(
+        { onChange: run },
+        { tables, throttleMs: options.throttleMs, signal: options.signal }
+      );
+      await run();
+    };
+    start().catch((error) => handler.onError?.(error as Error));
+
+    return stop;
+  }
+
   /**
    * Check if the PowerSync extension is loaded.
    */
@@ -186,6 +362,23 @@ export class TauriPowerSyncDatabaseFull extends AbstractPowerSyncDatabase {
   async getPowerSyncVersion(): Promise<string> {
     return this.tauriAdapter.getPowerSyncVersion();
   }
+
+  /**
+   * Whether the PowerSync extension is loaded, and why loading failed if not.
+   */
+  async getPowerSyncStatus(): Promise<PowerSyncStatus> {
+    return this.tauriAdapter.getPowerSyncStatus();
+  }
+}
+
+/**
+ * HTTP status of an upload error, as fetch responses and common HTTP client
+ * errors carry it
+ */
+function errorStatus(error: unknown): number | undefined {
+  const e = error as { status?: unknown; statusCode?: unknown; response?: { status?: unknown } };
+  const status = e?.status ?? e?.statusCode ?? e?.response?.status;
+  return typeof status === 'number' ? status : undefined;
 }
 
 // Re-export as a more user-friendly name
@@ -0,0 +1,1447 @@
+import { invoke as rawInvoke } from '@tauri-apps/api/core';
+import type { QueryResult as PowerSyncQueryResult } from '@powersync/common';
+import * as commands from './commands';
+import type {
+  ExecuteResult,
+  OpenResult,
+  PowerSyncStatus,
+  QueryResult,
+  CrudEntry,
+  OplogSummary,
+  RedactedCrudEntry,
+  RetentionReport,
+  RetentionRule,
+  SchemaChange,
+  SchemaTable,
+  SeedData,
+  SyncRulesIssue,
+  ConflictResolution,
+  LocalDataMigration,
+  LocalDataStrategy,
+  UploadError,
+  UploadQueueStats,
+} from './types';
+
+/**
+ * Typed wrappers around the plugin's Tauri commands.
+ *
+ * These are the lowest-level bindings: one function per Rust command, with
+ * parameters tagged for the Rust `SqlParam` enum and errors normalized to
+ * `PowerSyncPluginError`. Higher-level classes (TauriDBAdapter etc.) are
+ * built on top of these.
+ */
+
+const PLUGIN_PREFIX = 'plugin:powersync-jf|';
+
+/**
+ * Error raised when a plugin command fails on the Rust side.
+ */
+export class PowerSyncPluginError extends Error {
+  /**
+   * Name of the command that failed (without the plugin prefix)
+   */
+  readonly command: string;
+
+  /**
+   * Correlation id the command was invoked with, if it was invoked through
+   * `correlated()`
+   */
+  correlationId: string | null = null;
+
+  constructor(command: string, message: string) {
+    super(message);
+    this.name = 'PowerSyncPluginError';
+    this.command = command;
+  }
+}
+
+/**
+ * A write gave up waiting for its turn in the write queue (see
+ * `setLockTimeout`). The message names the holder and how long it has held
+ * the writer; `getLockState` has the full picture.
+ */
+export class LockTimeoutError extends PowerSyncPluginError {
+  constructor(command: string, message: string) {
+    super(command, message);
+    this.name = 'LockTimeoutError';
+  }
+}
+
+/**
+ * A database file is damaged or isn't a database, so it can't be opened.
+ * recoverDatabase() (or the `recoverCorruptDatabases` setting) moves it
+ * aside and starts over with a fresh database.
+ */
+export class CorruptDatabaseError extends PowerSyncPluginError {
+  constructor(command: string, message: string) {
+    super(command, message);
+    this.name = 'CorruptDatabaseError';
+  }
+}
+
+/**
+ * importEncrypted() was given a file that isn't an export bundle, the wrong
+ * passphrase, or a bundle that was truncated or modified.
+ */
+export class InvalidBundleError extends PowerSyncPluginError {
+  constructor(command: string, message: string) {
+    super(command, message);
+    this.name = 'InvalidBundleError';
+  }
+}
+
+/**
+ * executeAcross() committed the first database but not the second, and
+ * couldn't undo the first (no compensation given, or it failed). The
+ * databases are out of step until the app reconciles them.
+ */
+export class PartialCommitError extends PowerSyncPluginError {
+  constructor(command: string, message: string) {
+    super(command, message);
+    this.name = 'PartialCommitError';
+  }
+}
+
+/**
+ * waitForCheckpoint() gave up before sync applied a checkpoint past the
+ * token, e.g. because the app is offline or the change isn't in any of this
+ * user's buckets.
+ */
+export class CheckpointTimeoutError extends PowerSyncPluginError {
+  constructor(command: string, message: string) {
+    super(command, message);
+    this.name = 'CheckpointTimeoutError';
+  }
+}
+
+/**
+ * Correlation id sent with the commands invoked while a `correlated()`
+ * binding runs
+ */
+let activeCorrelationId: string | null = null;
+
+/**
+ * Tauri's invoke() rejects with a plain string on Rust errors.
+ * PowerSync expects Error objects (reads .name/.message/.stack).
+ * This wrapper ensures rejections are always proper Error instances.
+ */
+export async function invoke<T>(command: string, args?: Record<string, unknown>): Promise<T> {
+  const correlationId = activeCorrelationId;
+  try {
+    const payload = correlationId === null ? args : { ...args, correlationId };
+    return await rawInvoke<T>(PLUGIN_PREFIX + command, payload);
+  } catch (e) {
+    if (e instanceof Error) throw e;
+    const message = typeof e === 'string' ? e : JSON.stringify(e);
+    const error = pluginError(command, message);
+    error.correlationId = correlationId;
+    throw error;
+  }
+}
+
+function pluginError(command: string, message: string): PowerSyncPluginError {
+  if (message.startsWith('Lock timeout:')) return new LockTimeoutError(command, message);
+  if (message.startsWith('Database corrupt:')) return new CorruptDatabaseError(command, message);
+  if (message.startsWith('Invalid bundle:')) return new InvalidBundleError(command, message);
+  if (message.startsWith('Partial commit:')) return new PartialCommitError(command, message);
+  if (message.startsWith('Checkpoint timeout:')) {
+    return new CheckpointTimeoutError(command, message);
+  }
+  return new PowerSyncPluginError(command, message);
+}
+
+/**
+ * These command bindings, sending `correlationId` with every command they
+ * invoke. The plugin tags the log records, `tables-changed` and
+ * `checkpoint` events the command causes with the id. Its errors are plain
+ * messages, so the `PowerSyncPluginError` these bindings reject with gets
+ * the id set here as `correlationId`. One user action can thus be followed
+ * across IPC, SQL and sync.
+ *
+ * @example
+ * ```typescript
+ * const traced = commands.correlated(crypto.randomUUID());
+ * await traced.execute('myapp', 'UPDATE todos SET done = 1 WHERE id = ?', [id]);
+ * ```
+ */
+export function correlated(correlationId: string): typeof commands {
+  return new Proxy(commands, {
+    get(target, property) {
+      const value: unknown = Reflect.get(target, property);
+      // Error classes are constructed, not called
+      if (typeof value !== 'function' || value.prototype instanceof Error) return value;
+      return (...args: unknown[]) => {
+        const previous = activeCorrelationId;
+        activeCorrelationId = correlationId;
+        try {
+          return value(...args);
+        } finally {
+          activeCorrelationId = previous;
+        }
+      };
+    },
+  });
+}
+
+/**
+ * Tagged union type for SQL parameters.
+ * This matches the Rust SqlParam enum for proper serialization.
+ */
+export type SqlParam =
+  | { type: 'null' }
+  | { type: 'bool'; value: boolean }
+  | { type: 'int'; value: number }
+  | { type: 'real'; value: number }
+  | { type: 'text'; value: string }
+  | { type: 'blob'; value: number[] };
+
+/**
+ * Convert a JavaScript value to a typed SqlParam for Rust.
+ * This ensures blobs and other types are properly serialized.
+ */
+export function toSqlParam(p: unknown): SqlParam {
+  if (p === null || p === undefined) {
+    return { type: 'null' };
+  }
+  if (typeof p === 'boolean') {
+    return { type: 'bool', value: p };
+  }
+  if (typeof p === 'number') {
+    // Distinguish integers from floats
+    return Number.isInteger(p)
+      ? { type: 'int', value: p }
+      : { type: 'real', value: p };
+  }
+  if (typeof p === 'string') {
+    return { type: 'text', value: p };
+  }
+  if (p instanceof Uint8Array) {
+    return { type: 'blob', value: Array.from(p) };
+  }
+  if (ArrayBuffer.isView(p)) {
+    // Handle other typed arrays
+    return { type: 'blob', value: Array.from(new Uint8Array(p.buffer, p.byteOffset, p.byteLength)) };
+  }
+  if (p instanceof ArrayBuffer) {
+    return { type: 'blob', value: Array.from(new Uint8Array(p)) };
+  }
+  // Fallback: serialize as JSON text
+  return { type: 'text', value: JSON.stringify(p) };
+}
+
+/**
+ * Convert an array of parameters to typed SqlParams.
+ */
+export function toSqlParams(params: unknown[] | undefined): SqlParam[] {
+  if (!params) return [];
+  return params.map(toSqlParam);
+}
+
+/**
+ * A result as returned by the Rust side in the `powersync` format.
+ * Identical to @powersync/common's QueryResult except for `rows.item()`.
+ */
+interface PowerSyncQueryResultWire {
+  insertId?: number;
+  rowsAffected: number;
+  rows: { _array: Record<string, unknown>[]; length: number };
+}
+
+/**
+ * Attach `rows.item()`, which can't be sent over IPC.
+ */
+function withItem(result: PowerSyncQueryResultWire): PowerSyncQueryResult {
+  const rows = result.rows._array;
+  return {
+    ...result,
+    rows: { ...result.rows, item: (i: number) => rows[i] ?? null },
+  };
+}
+
+// =====================================================
+// Database operations
+// =====================================================
+
+/**
+ * Named sets of connection pragmas (cache size, synchronous mode, WAL
+ * autocheckpoint, memory map)
+ */
+export type TuningPreset = 'default' | 'low_memory' | 'high_throughput' | 'battery_saver';
+
+/**
+ * Open a database. Safe to call concurrently and repeatedly: the database
+ * is initialized once and later calls resolve to the same result.
+ *
+ * `preset` tunes the connection for the situation, overriding the
+ * configured tuning. The connection is shared by all windows, so the last
+ * preset given wins.
+ */
+export function open(name: string, options?: { preset?: TuningPreset }): Promise<OpenResult> {
+  return invoke('open', { name, preset: options?.preset ?? null });
+}
+
+export function close(name: string): Promise<void> {
+  return invoke('close', { name });
+}
+
+/**
+ * Close a database if it is open, move its files aside and open a fresh
+ * database in its place, e.g. after a CorruptDatabaseError. The fresh
+ * database syncs everything again; changes that weren't uploaded are lost.
+ */
+export function recoverDatabase(name: string): Promise<OpenResult> {
+  return invoke('recover_database', { name });
+}
+
+/**
+ * Open a user's profile database. Resolves to the database name to pass to
+ * the other commands (and as `database` to TauriPowerSyncDatabaseFull).
+ */
+export function openProfile(userId: string): Promise<string> {
+  return invoke('open_profile', { userId });
+}
+
+/**
+ * User IDs of all profiles on disk.
+ */
+export function listProfiles(): Promise<string[]> {
+  return invoke('list_profiles');
+}
+
+/**
+ * Close a user's profile database and delete its files (database, WAL,
+ * shared memory and the profile's file directory).
+ */
+export function deleteProfile(userId: string): Promise<void> {
+  return invoke('delete_profile', { userId });
+}
+
+/**
+ * @param txId Write transaction of this window the statement runs in, so it
+ * doesn't queue behind that transaction's own place in the write queue.
+ * Every command that writes takes it as its last argument.
+ */
+export function execute(
+  name: string,
+  sql: string,
+  params?: unknown[],
+  txId?: string
+): Promise<ExecuteResult> {
+  return invoke('execute', { name, sql, params: toSqlParams(params), txId });
+}
+
+/**
+ * Like execute(), but without the SQL policy of the default commands
+ * (no ATTACH, load_extension, VACUUM INTO, ...). Needs the `raw-sql`
+ * permission set, meant for trusted admin windows.
+ */
+export function executeRaw(
+  name: string,
+  sql: string,
+  params?: unknown[],
+  txId?: string
+): Promise<ExecuteResult> {
+  return invoke('execute_raw', { name, sql, params: toSqlParams(params), txId });
+}
+
+/**
+ * Like execute(), but returns the @powersync/common QueryResult shape.
+ */
+export async function executePowerSync(
+  name: string,
+  sql: string,
+  params?: unknown[],
+  txId?: string
+): Promise<PowerSyncQueryResult> {
+  const result = await invoke<PowerSyncQueryResultWire>('execute', {
+    name,
+    sql,
+    params: toSqlParams(params),
+    format: 'powersync',
+    txId,
+  });
+  return withItem(result);
+}
+
+/**
+ * Options for executeBatch()
+ */
+export interface BatchOptions {
+  /**
+   * Commit every `chunkSize` parameter sets, releasing the write lock in
+   * between and emitting `powersync-jf://batch-progress` after each chunk.
+   * Earlier chunks stay committed if a later one fails, so a failure is
+   * then always reported in `failure` instead of rejecting.
+   */
+  chunkSize?: number;
+  /**
+   * Report each parameter set's result in `entries`, and a failing
+   * parameter set in `failure` instead of rejecting
+   */
+  details?: boolean;
+}
+
+/** Result of one parameter set of a batch */
+export interface BatchEntry {
+  changes: number;
+  lastInsertRowid: number;
+}
+
+export interface BatchResult {
+  /**
+   * Rows changed, 0 if the batch failed and was rolled back. With
+   * `chunkSize`, the rows of the chunks committed before a failure.
+   */
+  changes: number;
+  lastInsertRowid: number;
+  /**
+   * With `details`, results of the parameter sets that ran, in order. Those
+   * before a failure were rolled back with it (or, with `chunkSize`, those
+   * in the failing chunk).
+   */
+  entries?: BatchEntry[];
+  /**
+   * With `details` or `chunkSize`, the parameter set that failed, by
+   * position in the batch
+   */
+  failure?: { index: number; error: string };
+}
+
+export function executeBatch(
+  name: string,
+  sql: string,
+  paramsBatch?: unknown[][],
+  options?: BatchOptions,
+  txId?: string
+): Promise<BatchResult> {
+  return invoke('execute_batch', {
+    name,
+    sql,
+    paramsBatch: (paramsBatch ?? []).map(toSqlParams),
+    chunkSize: options?.chunkSize ?? null,
+    details: options?.details ?? null,
+    txId,
+  });
+}
+
+/**
+ * Insert `rows` into `table` atomically, using multi-row VALUES statements.
+ * Each row holds one value per entry of `columns`. Much faster than
+ * executeBatch() for initial data loads.
+ */
+export function insertMany(
+  name: string,
+  table: string,
+  columns: string[],
+  rows: unknown[][],
+  txId?: string
+): Promise<ExecuteResult> {
+  return invoke('insert_many', { name, table, columns, rows: rows.map(toSqlParams), txId });
+}
+
+/**
+ * Insert a row into `table` with an `id` generated natively, in the format
+ * configured with `idFormat`, and return the id. `values` must not include `id`.
+ */
+export function insertWithId(
+  name: string,
+  table: string,
+  values: Record<string, unknown>,
+  txId?: string
+): Promise<string> {
+  return invoke('insert_with_id', {
+    name,
+    table,
+    columns: Object.keys(values),
+    values: toSqlParams(Object.values(values)),
+    txId,
+  });
+}
+
+/**
+ * A reading of the plugin's clock
+ */
+export interface Timestamp {
+  /** ISO 8601 in UTC with milliseconds, e.g. `2024-05-01T12:30:00.000Z` */
+  utc: string;
+  /** Milliseconds since the Unix epoch */
+  unixMs: number;
+  /** Correction applied to the device clock (from `ntpServer`), in milliseconds */
+  offsetMs: number;
+}
+
+/**
+ * Current UTC time from the clock behind the `now()` SQL function. Readings
+ * never repeat or go backwards, even if the device clock does, so they suit
+ * `created_at`/`updated_at` columns.
+ */
+export function now(): Promise<Timestamp> {
+  return invoke('now');
+}
+
+/**
+ * A statement for executeTransaction()
+ */
+export interface Statement {
+  sql: string;
+  params?: unknown[];
+}
+
+/**
+ * Run statements atomically in a single write transaction with one IPC call.
+ * Results are returned per statement; SELECTs include their rows.
+ */
+export function executeTransaction(
+  name: string,
+  statements: Statement[],
+  txId?: string
+): Promise<ExecuteResult[]> {
+  return invoke('execute_transaction', {
+    name,
+    statements: statements.map(({ sql, params }) => ({ sql, params: toSqlParams(params) })),
+    txId,
+  });
+}
+
+/**
+ * A read-only query deciding whether withTransaction() commits: it aborts
+ * the transaction if it returns no rows (`'empty'`) or any row (`'notEmpty'`).
+ * Guards run before the statements, or after them with `after`.
+ */
+export interface TransactionGuard {
+  sql: string;
+  params?: unknown[];
+  abortIf: 'empty' | 'notEmpty';
+  after?: boolean;
+}
+
+/**
+ * Outcome of withTransaction()
+ */
+export interface GuardedTransaction {
+  committed: boolean;
+  /** One result per statement, empty if a guard aborted the transaction */
+  results: ExecuteResult[];
+  /** Index of the guard that aborted the transaction */
+  abortedBy: number | null;
+}
+
+/**
+ * Run statements atomically in one IPC call, committing only if no guard
+ * aborts. The transaction is committed or rolled back on the Rust side, so
+ * it is never left open. A failing statement or guard rejects as with
+ * executeTransaction().
+ */
+export function withTransaction(
+  name: string,
+  statements: Statement[],
+  guards: TransactionGuard[],
+  txId?: string
+): Promise<GuardedTransaction> {
+  return invoke('with_transaction', {
+    name,
+    statements: statements.map(({ sql, params }) => ({ sql, params: toSqlParams(params) })),
+    guards: guards.map(({ sql, params, abortIf, after }) => ({
+      sql,
+      params: toSqlParams(params),
+      abortIf,
+      after: after ?? false,
+    })),
+    txId,
+  });
+}
+
+/**
+ * Results of executeAcross(), one per statement
+ */
+export interface CrossDatabaseResult {
+  first: ExecuteResult[];
+  second: ExecuteResult[];
+}
+
+/**
+ * Run statements on two databases, committing both or, on a best-effort
+ * basis, neither. Each database's statements run in a transaction of its
+ * own, then the first commits and the second commits. If the second fails
+ * to commit, `compensation` runs on the first to undo its statements and
+ * the call rejects with the commit error.
+ *
+ * Not a true atomic commit: readers can see the first database's changes
+ * before the second commits, a crash between the two commits leaves only
+ * the first committed, and compensation is a new write (uploaded like any
+ * other on a synced database). Put the database cheapest to undo first.
+ * Rejects with a PartialCommitError if the first stays committed alone.
+ */
+export function executeAcross(
+  first: { name: string; statements: Statement[]; compensation?: Statement[] },
+  second: { name: string; statements: Statement[] }
+): Promise<CrossDatabaseResult> {
+  const tagged = (statements: Statement[]) =>
+    statements.map(({ sql, params }) => ({ sql, params: toSqlParams(params) }));
+  return invoke('execute_across', {
+    first: first.name,
+    firstStatements: tagged(first.statements),
+    compensation: tagged(first.compensation ?? []),
+    second: second.name,
+    secondStatements: tagged(second.statements),
+  });
+}
+
+/**
+ * An operation for pipeline()
+ */
+export interface PipelineOp {
+  op: 'execute' | 'getAll' | 'getOptional';
+  sql: string;
+  params?: unknown[];
+}
+
+/**
+ * Result of a pipeline() operation, as the matching single command returns it
+ */
+export type PipelineResult = ExecuteResult | QueryResult | Record<string, unknown> | null;
+
+/**
+ * Run execute/getAll/getOptional operations back to back with one IPC call,
+ * e.g. for chatty startup sequences. Results are returned per operation.
+ * Not atomic: writes before a failing operation stay applied.
+ */
+export function pipeline(
+  name: string,
+  ops: PipelineOp[],
+  txId?: string
+): Promise<PipelineResult[]> {
+  return invoke('pipeline', {
+    name,
+    ops: ops.map(({ op, sql, params }) => ({ op, sql, params: toSqlParams(params) })),
+    txId,
+  });
+}
+
+/**
+ * Prepare `sql` once for hot loops that run it many times, resolving to a
+ * statement handle for bindAndStep(). Release it with finalize(); handles
+ * are also released when the window reloads or closes.
+ */
+export function prepare(name: string, sql: string): Promise<string> {
+  return invoke('prepare', { name, sql });
+}
+
+/**
+ * Run a prepared statement with `params`, as execute() would run its SQL,
+ * without sending or preparing the SQL again. `txId` is as for execute().
+ */
+export function bindAndStep(
+  name: string,
+  stmtId: string,
+  params?: unknown[],
+  txId?: string
+): Promise<ExecuteResult> {
+  return invoke('bind_and_step', { name, stmtId, params: toSqlParams(params), txId });
+}
+
+/**
+ * Release a statement handle from prepare()
+ */
+export function finalize(name: string, stmtId: string): Promise<void> {
+  return invoke('finalize', { name, stmtId });
+}
+
+/**
+ * Apply a JSON merge patch (RFC 7396) to a JSON column of the row with `id`,
+ * atomically: `UPDATE table SET column = json_patch(column, patch) WHERE id = ?`.
+ * Keys set to null in the patch are removed.
+ */
+export function patchJson(
+  name: string,
+  table: string,
+  column: string,
+  id: string,
+  patch: Record<string, unknown>,
+  txId?: string
+): Promise<ExecuteResult> {
+  return invoke('patch_json', { name, table, column, id, patch, txId });
+}
+
+/**
+ * Enable soft delete for a table, tombstoning rows through `column`.
+ * Tombstoned rows are filtered out of the table's view, also after the
+ * schema is replaced.
+ */
+export function enableSoftDelete(
+  name: string,
+  table: string,
+  column: string,
+  txId?: string
+): Promise<void> {
+  return invoke('enable_soft_delete', { name, table, column, txId });
+}
+
+/**
+ * Tombstone a row instead of deleting it. Soft delete must be enabled for the table.
+ */
+export function softDeleteRow(
+  name: string,
+  table: string,
+  id: string,
+  txId?: string
+): Promise<ExecuteResult> {
+  return invoke('soft_delete_row', { name, table, id, txId });
+}
+
+/**
+ * Clear the tombstone of a soft-deleted row.
+ */
+export function restoreRow(
+  name: string,
+  table: string,
+  id: string,
+  txId?: string
+): Promise<ExecuteResult> {
+  return invoke('restore_row', { name, table, id, txId });
+}
+
+/**
+ * Permanently delete tombstoned rows, optionally only those deleted before
+ * `before` (an ISO 8601 timestamp).
+ */
+export function purgeDeleted(
+  name: string,
+  table: string,
+  before?: string,
+  txId?: string
+): Promise<ExecuteResult> {
+  return invoke('purge_deleted', { name, table, before, txId });
+}
+
+/**
+ * A view created with createView()
+ */
+export interface ViewInfo {
+  name: string;
+  /** The SELECT statement defining the view */
+  select: string;
+  /** Result columns, empty if the view no longer compiles */
+  columns: string[];
+  /** Why the view no longer compiles, e.g. a schema change removed a table it reads */
+  error: string | null;
+}
+
+/**
+ * Create view `view` defined by a SELECT statement, e.g. a join or aggregate
+ * to query and watch like a table. Fails if the statement doesn't compile or
+ * the name is taken, unless `replace` is set and the view was created by
+ * this function.
+ */
+export function createView(
+  name: string,
+  view: string,
+  select: string,
+  options?: { replace?: boolean },
+  txId?: string
+): Promise<ViewInfo> {
+  return invoke('create_view', { name, view, select, replace: options?.replace ?? null, txId });
+}
+
+/**
+ * Drop a view created with createView()
+ */
+export function dropView(name: string, view: string, txId?: string): Promise<void> {
+  return invoke('drop_view', { name, view, txId });
+}
+
+/**
+ * The views created with createView(), with their columns
+ */
+export function listViews(name: string): Promise<ViewInfo[]> {
+  return invoke('list_views', { name });
+}
+
+/**
+ * What undo() or redo() changed
+ */
+export interface UndoStep {
+  /** Tables written */
+  tables: string[];
+  /** Rows inserted, updated or deleted */
+  rows: number;
+}
+
+/**
+ * Tables tracked for undo and the steps available
+ */
+export interface UndoState {
+  tables: string[];
+  /** Transactions undo() can revert */
+  undoSteps: number;
+  /** Transactions redo() can write again */
+  redoSteps: number;
+}
+
+/**
+ * Record writes to `tables` for undo() and redo(), replacing the tables
+ * tracked before and clearing the history. Each table needs an `id` column.
+ * An empty list turns tracking off.
+ */
+export function enableUndo(name: string, tables: string[], txId?: string): Promise<void> {
+  return invoke('enable_undo', { name, tables, txId });
+}
+
+/**
+ * Revert the latest transaction written to the tracked tables, or resolve to
+ * null if there is nothing to undo
+ */
+export function undo(name: string, txId?: string): Promise<UndoStep | null> {
+  return invoke('undo', { name, txId });
+}
+
+/**
+ * Write the transaction last undone again, or resolve to null if there is
+ * nothing to redo
+ */
+export function redo(name: string, txId?: string): Promise<UndoStep | null> {
+  return invoke('redo', { name, txId });
+}
+
+/**
+ * Tables tracked for undo and how many steps can be undone and redone
+ */
+export function getUndoState(name: string): Promise<UndoState> {
+  return invoke('get_undo_state', { name });
+}
+
+/**
+ * Delete old rows of local-only and raw tables as `rules` describe, in one
+ * transaction. Rules configured under `retention` in the plugin config run
+ * in the background; this applies rules on demand, e.g. from a settings
+ * screen. Synced tables are refused.
+ */
+export function applyRetention(
+  name: string,
+  rules: RetentionRule[],
+  txId?: string
+): Promise<RetentionReport[]> {
+  return invoke('apply_retention', { name, rules, txId });
+}
+
+/**
+ * An entry of the `_changes` feed
+ */
+export interface ChangeFeedEntry {
+  /** Increasing position in the feed; pass to ackChangeFeed() once consumed */
+  seq: number;
+  table: string;
+  /** Primary key (`id`, or rowid for tables without one) as text */
+  pk: string;
+  op: 'INSERT' | 'UPDATE' | 'DELETE';
+}
+
+/**
+ * Start recording inserts, updates and deletes of `tables` (local or synced)
+ * in the `_changes` feed. Idempotent.
+ */
+export function enableChangeFeed(name: string, tables: string[], txId?: string): Promise<void> {
+  return invoke('enable_change_feed', { name, tables, txId });
+}
+
+/**
+ * Read the oldest unacknowledged change feed entries, in order.
+ */
+export function readChangeFeed(name: string, limit?: number): Promise<ChangeFeedEntry[]> {
+  return invoke('read_change_feed', { name, limit });
+}
+
+/**
+ * Acknowledge (remove) change feed entries up to and including `seq`.
+ */
+export function ackChangeFeed(name: string, seq: number, txId?: string): Promise<void> {
+  return invoke('ack_change_feed', { name, seq, txId });
+}
+
+export function getAll(name: string, sql: string, params?: unknown[]): Promise<QueryResult> {
+  return invoke('get_all', { name, sql, params: toSqlParams(params) });
+}
+
+/**
+ * Run read-only queries against one snapshot of the database, so a list and
+ * its count can't disagree when a commit lands in between.
+ */
+export function getAllSnapshot(name: string, queries: Statement[]): Promise<QueryResult[]> {
+  return invoke('get_all_snapshot', {
+    name,
+    queries: queries.map(({ sql, params }) => ({ sql, params: toSqlParams(params) })),
+  });
+}
+
+/**
+ * Like getAll(), but returns the @powersync/common QueryResult shape.
+ */
+export async function getAllPowerSync(
+  name: string,
+  sql: string,
+  params?: unknown[]
+): Promise<PowerSyncQueryResult> {
+  const result = await invoke<PowerSyncQueryResultWire>('get_all', {
+    name,
+    sql,
+    params: toSqlParams(params),
+    format: 'powersync',
+  });
+  return withItem(result);
+}
+
+export function getOptional(
+  name: string,
+  sql: string,
+  params?: unknown[]
+): Promise<Record<string, unknown> | null> {
+  return invoke('get_optional', { name, sql, params: toSqlParams(params) });
+}
+
+/**
+ * File format of queryToFile(): one JSON object per line, or CSV with a
+ * header row
+ */
+export type ExportFormat = 'ndjson' | 'csv';
+
+/**
+ * What queryToFile() wrote
+ */
+export interface ExportResult {
+  rows: number;
+  /** Size of the written file */
+  bytes: number;
+}
+
+/**
+ * Run a read-only query and write its rows straight to a file on the Rust
+ * side, for reports too large to load into the webview. `dest` must be an
+ * absolute path (e.g. from a save dialog); an existing file is replaced.
+ * Needs the `export` permission set.
+ */
+export function queryToFile(
+  name: string,
+  sql: string,
+  params: unknown[] | undefined,
+  dest: string,
+  format: ExportFormat
+): Promise<ExportResult> {
+  return invoke('query_to_file', { name, sql, params: toSqlParams(params), dest, format });
+}
+
+/**
+ * What exportEncrypted() wrote
+ */
+export interface EncryptedExport {
+  /** Size of the bundle */
+  bytes: number;
+  /** Size of the database it holds, uncompressed */
+  databaseBytes: number;
+}
+
+/**
+ * Write a compressed copy of the database to `dest`, an absolute path,
+ * encrypted with a key derived from `passphrase`. Needs the `export`
+ * permission set.
+ */
+export function exportEncrypted(
+  name: string,
+  dest: string,
+  passphrase: string
+): Promise<EncryptedExport> {
+  return invoke('export_encrypted', { name, dest, passphrase });
+}
+
+/**
+ * Replace the database contents with a bundle written by exportEncrypted(),
+ * e.g. on a new machine. Rejects with an InvalidBundleError if the
+ * passphrase is wrong or the bundle was modified, leaving the database as
+ * it was. Needs the `export` permission set.
+ */
+export function importEncrypted(
+  name: string,
+  src: string,
+  passphrase: string,
+  txId?: string
+): Promise<void> {
+  return invoke('import_encrypted', { name, src, passphrase, txId });
+}
+
+/**
+ * @param txId Transaction of this window to nest the new one in, so a write
+ * transaction doesn't queue behind its own enclosing one
+ * @returns Transaction ID to pass to commitTransaction/rollbackTransaction
+ */
+export function beginTransaction(
+  name: string,
+  isWrite: boolean,
+  priority?: WritePriority,
+  txId?: string
+): Promise<string> {
+  return invoke('begin_transaction', { name, isWrite, priority, txId });
+}
+
+export function commitTransaction(name: string, txId: string): Promise<void> {
+  return invoke('commit_transaction', { name, txId });
+}
+
+export function rollbackTransaction(name: string, txId: string): Promise<void> {
+  return invoke('rollback_transaction', { name, txId });
+}
+
+/**
+ * Create a named savepoint inside an active transaction.
+ * The transaction must not have nested transactions open.
+ */
+export function createSavepoint(name: string, txId: string, savepoint: string): Promise<void> {
+  return invoke('create_savepoint', { name, txId, savepoint });
+}
+
+/**
+ * Release a savepoint (and any created after it), keeping its changes.
+ */
+export function releaseSavepoint(name: string, txId: string, savepoint: string): Promise<void> {
+  return invoke('release_savepoint', { name, txId, savepoint });
+}
+
+/**
+ * Undo changes made since the savepoint was created. The savepoint stays in place.
+ */
+export function rollbackToSavepoint(name: string, txId: string, savepoint: string): Promise<void> {
+  return invoke('rollback_to_savepoint', { name, txId, savepoint });
+}
+
+/**
+ * Order in which waiting writers get the connection: `sync` first, then
+ * `normal`, then `background`; arrival order within a priority.
+ */
+export type WritePriority = 'background' | 'normal' | 'sync';
+
+export interface WriteQueueStats {
+  /** Writers waiting for their turn */
+  waiting: number;
+  waitingBackground: number;
+  waitingNormal: number;
+  waitingSync: number;
+  /** Window label (or "" for Rust callers) currently holding the writer, if any */
+  holder: string | null;
+}
+
+export function getWriteQueueStats(name: string): Promise<WriteQueueStats> {
+  return invoke('get_write_queue_stats', { name });
+}
+
+/** A writer holding the write queue's turn or waiting for it */
+export interface LockParticipant {
+  /** Window label, or null for Rust callers */
+  owner: string | null;
+  priority: WritePriority;
+  /** Milliseconds since it took the turn, or started waiting */
+  elapsedMs: number;
+}
+
+export interface LockState {
+  holder: LockParticipant | null;
+  /** Waiting writers in the order they will be served */
+  waiters: LockParticipant[];
+  /** How long writers wait before failing, or null to wait indefinitely */
+  timeoutMs: number | null;
+}
+
+/** Who holds the database's writer, and who is waiting for it */
+export function getLockState(name: string): Promise<LockState> {
+  return invoke('get_lock_state', { name });
+}
+
+/**
+ * Fail writes that waited `timeoutMs` for their turn with a
+ * `LockTimeoutError`, or wait indefinitely with `null`. Overrides the
+ * `lockTimeoutMs` config until the database is closed.
+ */
+export function setLockTimeout(name: string, timeoutMs: number | null): Promise<void> {
+  return invoke('set_lock_timeout', { name, timeoutMs });
+}
+
+/**
+ * Gather query planner statistics (`ANALYZE`) for one table, or the whole
+ * database if `table` is omitted. See also the `autoAnalyzeAfter` config.
+ */
+export function analyze(name: string, table?: string, txId?: string): Promise<void> {
+  return invoke('analyze', { name, table: table ?? null, txId });
+}
+
+/**
+ * Connection counters from `sqlite3_db_status`. Memory figures are in bytes.
+ */
+export interface ConnectionStats {
+  /** Heap used by the page cache */
+  cacheUsed: number;
+  /** Heap used by the parsed schema */
+  schemaUsed: number;
+  /** Heap used by prepared statements */
+  statementsUsed: number;
+  cacheHits: number;
+  cacheMisses: number;
+  /** Dirty pages written out of the cache */
+  cacheWrites: number;
+  /** Prepared statements alive on the connection, including cached ones */
+  preparedStatements: number;
+}
+
+export function getConnectionStats(name: string): Promise<ConnectionStats> {
+  return invoke('get_connection_stats', { name });
+}
+
+/**
+ * A database event as it was emitted
+ */
+export interface JournalEntry {
+  seq: number;
+  /** Event name, e.g. `powersync-jf://tables-changed` */
+  event: string;
+  /** Payload as emitted, including `name` and `seq` */
+  payload: unknown;
+}
+
+/**
+ * Events emitted for a database after a given `seq`
+ */
+export interface EventsSince {
+  /** Oldest first */
+  events: JournalEntry[];
+  /** `seq` of the latest event emitted for the database, 0 if none */
+  latestSeq: number;
+  /** False if events after the requested `seq` are no longer kept; refetch state then */
+  complete: boolean;
+}
+
+/**
+ * Events emitted for database `name` after `seq`, e.g. to catch up after a
+ * window was hidden or reloaded. Pass 0 for everything still kept.
+ */
+export function getEventsSince(name: string, seq: number): Promise<EventsSince> {
+  return invoke('get_events_since', { name, seq });
+}
+
+/**
+ * Zip up diagnostics for a bug report (schema, integrity check, metrics,
+ * recent events and failing uploads per open database, plus recent logs),
+ * with quoted literals and the home directory redacted. Resolves to the
+ * path of the zip in the temporary directory.
+ */
+export function createSupportBundle(): Promise<string> {
+  return invoke('create_support_bundle');
+}
+
+/**
+ * Whether diagnostics may be collected for database `name`: the choice
+ * recorded with `setDiagnosticsEnabled`, else the `diagnostics` config
+ */
+export function getDiagnosticsEnabled(name: string): Promise<boolean> {
+  return invoke('get_diagnostics_enabled', { name });
+}
+
+/**
+ * Record whether diagnostics may be collected for database `name`, e.g. once
+ * the user answered a consent prompt. Stored in the database, so it persists.
+ */
+export function setDiagnosticsEnabled(
+  name: string,
+  enabled: boolean,
+  txId?: string
+): Promise<void> {
+  return invoke('set_diagnostics_enabled', { name, enabled, txId });
+}
+
+// =====================================================
+// PowerSync extension operations
+// =====================================================
+
+export function getPowerSyncVersion(name: string): Promise<string> {
+  return invoke('get_powersync_version', { name });
+}
+
+/**
+ * Client ID the sync protocol identifies this database by.
+ */
+export function getClientId(name: string): Promise<string> {
+  return invoke('get_client_id', { name });
+}
+
+export function isPowerSyncLoaded(name: string): Promise<boolean> {
+  return invoke('is_powersync_loaded', { name });
+}
+
+/**
+ * Whether the PowerSync extension is loaded, its version, and why loading it
+ * failed if it isn't.
+ */
+export function getPowerSyncStatus(name: string): Promise<PowerSyncStatus> {
+  return invoke('get_powersync_status', { name });
+}
+
+/**
+ * Apply a PowerSync schema. Skipped, resolving to `null`, if it is the
+ * schema applied last; otherwise resolves to the change, which is also
+ * emitted as `powersync-jf://schema-changed`.
+ */
+export function replaceSchema(
+  name: string,
+  schemaJson: string,
+  txId?: string
+): Promise<SchemaChange | null> {
+  return invoke('replace_schema', { name, schemaJson, txId });
+}
+
+/**
+ * Re-run PowerSync initialization and reload the schema, so new tables and
+ * regenerated views are picked up without reopening the database.
+ */
+export function refreshSchema(name: string, txId?: string): Promise<void> {
+  return invoke('refresh_schema', { name, txId });
+}
+
+/**
+ * Check sync rules YAML against the applied schema, reporting synced tables
+ * and columns that no data query will ever fill. A development aid for
+ * "why is my table empty".
+ */
+export function lintSyncRules(name: string, syncRules: string): Promise<SyncRulesIssue[]> {
+  return invoke('lint_sync_rules', { name, syncRules });
+}
+
+/**
+ * A `powersync_control` operation. Sync lines are checked to be JSON
+ * objects, and unknown operations rejected, before reaching the extension.
+ */
+export type ControlOp =
+  | { op: 'start'; payload: Record<string, unknown> | null }
+  | { op: 'stop' }
+  | { op: 'line_text'; payload: string }
+  | { op: 'line_binary'; payload: Uint8Array | number[] }
+  | { op: 'refreshed_token' }
+  | { op: 'completed_upload' }
+  | { op: 'connection'; payload: 'established' | 'end' };
+
+/**
+ * Execute a PowerSync control operation, returning the extension's
+ * instructions as JSON
+ */
+export function powerSyncControl(name: string, control: ControlOp, txId?: string): Promise<string> {
+  if (control.op === 'line_binary') {
+    // IPC arguments are JSON: send bytes as a plain array
+    control = { op: 'line_binary', payload: Array.from(control.payload) };
+  }
+  return invoke('powersync_control', { name, control, txId });
+}
+
+export function getCrudBatch(name: string, limit?: number): Promise<CrudEntry[]> {
+  return invoke('get_crud_batch', { name, limit });
+}
+
+export function removeCrud(name: string, crudId: number, txId?: string): Promise<void> {
+  return invoke('remove_crud', { name, crudId, txId });
+}
+
+export function hasPendingCrud(name: string): Promise<boolean> {
+  return invoke('has_pending_crud', { name });
+}
+
+/**
+ * Record a failed attempt to upload CRUD entry `crudId`. Failures are
+ * counted until removeCrud() removes the entry, and emitted as
+ * `powersync-jf://upload-error`.
+ */
+export function reportUploadError(
+  name: string,
+  crudId: number,
+  error: string,
+  txId?: string
+): Promise<UploadError> {
+  return invoke('report_upload_error', { name, crudId, error, txId });
+}
+
+/**
+ * Settle a CRUD transaction the backend rejected: the entries up to and
+ * including `crudId`. Without a `resolution`, the Rust handler registered
+ * with `Builder::on_upload_conflict` chooses one.
+ *
+ * @returns The resolution applied, or null if the entries stay queued
+ */
+export function resolveUploadConflict(
+  name: string,
+  crudId: number,
+  rejection: { status?: number; error: string },
+  resolution?: ConflictResolution,
+  txId?: string
+): Promise<ConflictResolution | null> {
+  return invoke('resolve_upload_conflict', {
+    name,
+    crudId,
+    status: rejection.status ?? null,
+    error: rejection.error,
+    resolution: resolution ?? null,
+    txId,
+  });
+}
+
+/**
+ * Copy local-only table `from` into synced table `to` in one transaction,
+ * queueing every row for upload, e.g. once a user who started out
+ * anonymously signs in. Columns are matched by name. With `deleteSource`,
+ * `from` is emptied in the same transaction.
+ *
+ * @returns The number of rows copied
+ */
+export function migrateLocalTable(
+  name: string,
+  from: string,
+  to: string,
+  options?: { deleteSource?: boolean },
+  txId?: string
+): Promise<number> {
+  return invoke('migrate_local_table', {
+    name,
+    from,
+    to,
+    deleteSource: options?.deleteSource ?? null,
+    txId,
+  });
+}
+
+/**
+ * Re-home the rows created before the user signed in, in one transaction:
+ * set their owner column, optionally give them new ids or move them into
+ * synced tables, and rewrite the columns referencing their old ids.
+ */
+export function migrateLocalData(
+  name: string,
+  strategy: LocalDataStrategy,
+  txId?: string
+): Promise<LocalDataMigration> {
+  return invoke('migrate_local_data', { name, strategy, txId });
+}
+
+/**
+ * Pending upload counts, with the entries that failed to upload
+ */
+export function getUploadQueueStats(name: string): Promise<UploadQueueStats> {
+  return invoke('get_upload_queue_stats', { name });
+}
+
+/**
+ * Write checkpoint (op id) local changes are waiting on, or null if none.
+ */
+export function getWriteCheckpoint(name: string): Promise<string | null> {
+  return invoke('get_write_checkpoint', { name });
+}
+
+/**
+ * Time the last sync completed, or null if the database has never synced.
+ */
+export async function getLastSyncedAt(name: string): Promise<Date | null> {
+  const millis = await invoke<number | null>('get_last_synced_at', { name });
+  return millis === null ? null : new Date(millis);
+}
+
+/**
+ * Whether a full sync has completed, e.g. to leave a "waiting for first sync" screen.
+ */
+export function hasSynced(name: string): Promise<boolean> {
+  return invoke('has_synced', { name });
+}
+
+/**
+ * Where a database's sync stands
+ */
+export interface LocalSyncStatus {
+  /** Highest op id applied to the local tables, "0" before the first checkpoint */
+  checkpointToken: string;
+  /** When the last full sync completed, in milliseconds since the Unix epoch */
+  lastSyncedAt: number | null;
+  /** Write checkpoint local changes are waiting on, if any */
+  writeCheckpoint: string | null;
+}
+
+/**
+ * Checkpoint token, last sync time and write checkpoint in one call.
+ */
+export function getSyncStatus(name: string): Promise<LocalSyncStatus> {
+  return invoke('get_sync_status', { name });
+}
+
+/**
+ * Wait until sync has applied a checkpoint past `token` (a `checkpointToken`
+ * taken before changing data through the app's own API), so queries see data
+ * at least that new. Resolves with the new token; rejects with a
+ * CheckpointTimeoutError after `timeoutMs` if given, carrying the
+ * `correlationId` of a `correlated()` binding like other errors.
+ */
+export function waitForCheckpoint(
+  name: string,
+  token: string,
+  timeoutMs?: number
+): Promise<string> {
+  return invoke('wait_for_checkpoint', { name, token, timeoutMs: timeoutMs ?? null });
+}
+
+// =====================================================
+// Dev-tools operations (outside the default permissions: the
+// `powersync-jf:devtools` set, or allow-seed, allow-snapshot and allow-restore)
+// =====================================================
+
+/**
+ * Rows of `ps_buckets`: one per sync bucket, with its op progress.
+ */
+export function inspectBuckets(name: string): Promise<QueryResult> {
+  return invoke('inspect_buckets', { name });
+}
+
+/**
+ * Operation counts in `ps_oplog` per bucket and row type.
+ */
+export function inspectOplog(name: string): Promise<OplogSummary[]> {
+  return invoke('inspect_oplog', { name });
+}
+
+/**
+ * Pending uploads from `ps_crud`, listing the columns each sets but not their values.
+ */
+export function inspectCrud(name: string, limit?: number): Promise<RedactedCrudEntry[]> {
+  return invoke('inspect_crud', { name, limit: limit ?? null });
+}
+
+/**
+ * The applied schema, in the JSON shape replaceSchema() takes, read back
+ * from the views PowerSync created.
+ */
+export function inspectSchema(name: string): Promise<SchemaTable[]> {
+  return invoke('inspect_schema', { name });
+}
+
+/**
+ * Load fixture data in one transaction: an SQL script, or rows by table
+ * (inserted in key order; arrays and objects are stored as JSON text).
+ * Returns the number of rows inserted from tables.
+ *
+ * Rows seeded into synced tables are queued for upload like any write.
+ */
+export function seed(name: string, data: SeedData, txId?: string): Promise<number> {
+  return invoke('seed', { name, data, txId });
+}
+
+/**
+ * Copy the database to a temporary file for restore(), returning its id.
+ * Snapshot files are deleted when the database is closed.
+ */
+export function snapshot(name: string, txId?: string): Promise<string> {
+  return invoke('snapshot', { name, txId });
+}
+
+/**
+ * Reset the database to a snapshot taken since it was opened. Watches
+ * re-run as if every table changed. Fails inside a transaction.
+ */
+export function restore(name: string, snapshotId: string, txId?: string): Promise<void> {
+  return invoke('restore', { name, snapshotId, txId });
+}
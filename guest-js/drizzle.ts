@@ -0,0 +1,89 @@
+import { drizzle as drizzleProxy, type SqliteRemoteDatabase } from 'drizzle-orm/sqlite-proxy';
+import type { DrizzleConfig } from 'drizzle-orm';
+import type { TauriDBAdapter } from './TauriDBAdapter';
+import * as commands from './commands';
+
+/**
+ * Drizzle ORM driver backed by the plugin's commands.
+ *
+ * Built on drizzle's `sqlite-proxy` driver, so only the async query API is
+ * available (every query is an IPC round trip). Queries go through the given
+ * adapter so table change notifications (and therefore `watch()`) keep
+ * working. Drizzle's BEGIN/COMMIT/ROLLBACK statements are mapped onto the
+ * plugin's transaction commands; nested transactions use savepoints.
+ *
+ * @example
+ * ```typescript
+ * import { drizzle } from '@jfairbairn/tauri-plugin-powersync-jf/drizzle';
+ *
+ * const db = drizzle(powerSyncDb.database as TauriDBAdapter, { schema });
+ * const todos = await db.select().from(schema.todos);
+ * ```
+ */
+export function drizzle<TSchema extends Record<string, unknown> = Record<string, never>>(
+  adapter: TauriDBAdapter,
+  config?: DrizzleConfig<TSchema>
+): SqliteRemoteDatabase<TSchema> {
+  // Open transaction IDs, innermost last
+  const transactions: string[] = [];
+
+  const run = async (
+    sql: string,
+    params: unknown[],
+    method: 'run' | 'all' | 'values' | 'get'
+  ): Promise<{ rows: any[] }> => {
+    const statement = sql.trim().toLowerCase();
+
+    if (statement.startsWith('begin')) {
+      const isWrite = !statement.startsWith('begin deferred');
+      const parent = transactions[transactions.length - 1];
+      transactions.push(await commands.beginTransaction(adapter.name, isWrite, undefined, parent));
+      return { rows: [] };
+    }
+    if (statement === 'commit' || statement === 'end') {
+      const txId = transactions.pop();
+      if (txId) await commands.commitTransaction(adapter.name, txId);
+      return { rows: [] };
+    }
+    if (statement === 'rollback') {
+      const txId = transactions.pop();
+      if (txId) await commands.rollbackTransaction(adapter.name, txId);
+      return { rows: [] };
+    }
+
+    if (method === 'run') {
+      await adapter.executeInTransaction(transactions[transactions.length - 1], sql, params);
+      return { rows: [] };
+    }
+
+    const rows = await adapter.executeRaw(sql, params);
+    return { rows: method === 'get' ? rows[0] : rows };
+  };
+
+  const batch = async (
+    queries: { sql: string; params: unknown[]; method: 'run' | 'all' | 'values' | 'get' }[]
+  ): Promise<{ rows: any[] }[]> => {
+    // Batches are atomic
+    const parent = transactions[transactions.length - 1];
+    const txId = await commands.beginTransaction(adapter.name, true, undefined, parent);
+    try {
+      const results: { rows: any[] }[] = [];
+      // The batch's statements run in its transaction
+      transactions.push(txId);
+      try {
+        for (const query of queries) {
+          results.push(await run(query.sql, query.params, query.method));
+        }
+      } finally {
+        transactions.pop();
+      }
+      await commands.commitTransaction(adapter.name, txId);
+      return results;
+    } catch (error) {
+      await commands.rollbackTransaction(adapter.name, txId).catch(() => {});
+      throw error;
+    }
+  };
+
+  return drizzleProxy<TSchema>(run, batch, config);
+}
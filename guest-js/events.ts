@@ -0,0 +1,409 @@
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import type { LockParticipant } from './commands';
+import type { DatabaseRecovery, RetentionReport, SchemaChange, UploadError } from './types';
+
+/**
+ * Name of the event emitted once per commit with the user tables it changed.
+ */
+export const TABLES_CHANGED_EVENT = 'powersync-jf://tables-changed';
+
+/**
+ * Name of the event emitted once a sync checkpoint is fully applied locally.
+ */
+export const CHECKPOINT_EVENT = 'powersync-jf://checkpoint';
+
+/**
+ * Name of the event emitted when a checkpoint fails validation or can't be applied.
+ */
+export const CHECKPOINT_FAILED_EVENT = 'powersync-jf://checkpoint-failed';
+
+/**
+ * Name of the event emitted when replaceSchema() applies a changed schema.
+ */
+export const SCHEMA_CHANGED_EVENT = 'powersync-jf://schema-changed';
+
+/**
+ * Name of the event emitted when reportUploadError() records a failed upload.
+ */
+export const UPLOAD_ERROR_EVENT = 'powersync-jf://upload-error';
+
+/**
+ * Name of the event emitted when a write has waited past `writeContentionMs` for its turn.
+ */
+export const WRITE_CONTENTION_EVENT = 'powersync-jf://write-contention';
+
+/**
+ * Name of the event emitted when a database is opened, by any window or by Rust code.
+ */
+export const DB_OPENED_EVENT = 'powersync-jf://db-opened';
+
+/**
+ * Name of the event emitted when a database is closed.
+ */
+export const DB_CLOSED_EVENT = 'powersync-jf://db-closed';
+
+/**
+ * Name of the event emitted when a database is opened but the PowerSync extension failed to load.
+ */
+export const EXTENSION_LOAD_FAILED_EVENT = 'powersync-jf://extension-load-failed';
+
+/**
+ * Name of the event emitted when a damaged database was moved aside and a fresh one opened.
+ */
+export const DB_RECOVERED_EVENT = 'powersync-jf://db-recovered';
+
+/**
+ * Name of the event emitted to the calling window after each chunk of a chunked executeBatch().
+ */
+export const BATCH_PROGRESS_EVENT = 'powersync-jf://batch-progress';
+
+/**
+ * Name of the event emitted when configured retention rules deleted rows.
+ */
+export const RETENTION_APPLIED_EVENT = 'powersync-jf://retention-applied';
+
+/**
+ * Name of the event emitted for each plugin log record when `logEvents` is configured.
+ */
+export const LOG_EVENT = 'powersync-jf://log';
+
+/**
+ * The user tables one commit changed
+ */
+export interface TablesChangedEvent {
+  /** Database name */
+  name: string;
+  /** Position among the database's events; see `commands.getEventsSince` */
+  seq: number;
+  /** View names of the changed tables, without PowerSync's internal `ps_*` tables */
+  tables: string[];
+  /** Correlation id of the command that committed, if it had one */
+  correlationId: string | null;
+}
+
+/**
+ * Run a callback once per commit to database `name` that changed user
+ * tables, including commits applying sync data. TauriDBAdapter uses this
+ * to notify watched queries.
+ *
+ * @returns A function that stops listening
+ */
+export function onTablesChanged(
+  name: string,
+  callback: (event: TablesChangedEvent) => void
+): Promise<UnlistenFn> {
+  return listen<TablesChangedEvent>(TABLES_CHANGED_EVENT, (event) => {
+    if (event.payload.name === name) {
+      callback(event.payload);
+    }
+  });
+}
+
+/**
+ * Row operations a checkpoint applied to one table
+ */
+export interface TableOpCounts {
+  inserts: number;
+  updates: number;
+  deletes: number;
+}
+
+/**
+ * A sync checkpoint that has been fully applied to the local database
+ */
+export interface CheckpointEvent {
+  /** Database name */
+  name: string;
+  /** Position among the database's events; see `commands.getEventsSince` */
+  seq: number;
+  /** Operations per changed table since the previous checkpoint */
+  tables: Record<string, TableOpCounts>;
+  /** Correlation id of the `powerSyncControl` call that completed it, if it had one */
+  correlationId: string | null;
+}
+
+/**
+ * Run a callback once per applied checkpoint of database `name`, e.g. to
+ * refresh caches once instead of per changed row.
+ *
+ * @returns A function that stops listening
+ */
+export function onCheckpoint(
+  name: string,
+  callback: (event: CheckpointEvent) => void
+): Promise<UnlistenFn> {
+  return listen<CheckpointEvent>(CHECKPOINT_EVENT, (event) => {
+    if (event.payload.name === name) {
+      callback(event.payload);
+    }
+  });
+}
+
+/**
+ * A checkpoint that failed validation or couldn't be applied
+ */
+export interface CheckpointFailureEvent {
+  /** Database name */
+  name: string;
+  /** Position among the database's events; see `commands.getEventsSince` */
+  seq: number;
+  kind: 'checksumMismatch' | 'invalidDiff';
+  /** Message reported by the PowerSync extension */
+  message: string;
+}
+
+/**
+ * Run a callback whenever a checkpoint of database `name` fails validation.
+ * Checksum mismatches are recovered from by re-syncing; this is for diagnostics.
+ *
+ * @returns A function that stops listening
+ */
+export function onCheckpointFailure(
+  name: string,
+  callback: (event: CheckpointFailureEvent) => void
+): Promise<UnlistenFn> {
+  return listen<CheckpointFailureEvent>(CHECKPOINT_FAILED_EVENT, (event) => {
+    if (event.payload.name === name) {
+      callback(event.payload);
+    }
+  });
+}
+
+/**
+ * A changed schema applied to a database
+ */
+export interface SchemaChangedEvent extends SchemaChange {
+  /** Database name */
+  name: string;
+  /** Position among the database's events; see `commands.getEventsSince` */
+  seq: number;
+}
+
+/**
+ * Run a callback whenever a changed schema is applied to database `name`,
+ * e.g. to rebuild caches of prepared queries on the recreated views.
+ * Re-applying an unchanged schema doesn't fire.
+ *
+ * @returns A function that stops listening
+ */
+export function onSchemaChanged(
+  name: string,
+  callback: (event: SchemaChangedEvent) => void
+): Promise<UnlistenFn> {
+  return listen<SchemaChangedEvent>(SCHEMA_CHANGED_EVENT, (event) => {
+    if (event.payload.name === name) {
+      callback(event.payload);
+    }
+  });
+}
+
+/**
+ * A failed upload reported for a database
+ */
+export interface UploadErrorEvent extends UploadError {
+  /** Database name */
+  name: string;
+  /** Position among the database's events; see `commands.getEventsSince` */
+  seq: number;
+}
+
+/**
+ * Run a callback whenever a failed upload is reported for database `name`,
+ * e.g. to refresh a sync issues screen.
+ *
+ * @returns A function that stops listening
+ */
+export function onUploadError(
+  name: string,
+  callback: (event: UploadErrorEvent) => void
+): Promise<UnlistenFn> {
+  return listen<UploadErrorEvent>(UPLOAD_ERROR_EVENT, (event) => {
+    if (event.payload.name === name) {
+      callback(event.payload);
+    }
+  });
+}
+
+/**
+ * A write that has waited past `writeContentionMs` for its turn to write
+ */
+export interface WriteContentionEvent {
+  /** Database name */
+  name: string;
+  /** Position among the database's events; see `commands.getEventsSince` */
+  seq: number;
+  /** The waiting writer; `elapsedMs` is how long it has waited so far */
+  waiter: LockParticipant;
+  /**
+   * The writer it waits for: `priority: 'sync'` for sync applying a
+   * checkpoint, a window label as `owner` for that window's transaction
+   */
+  holder: LockParticipant | null;
+  /** Writers waiting, including this one */
+  waiting: number;
+}
+
+/**
+ * Run a callback whenever a write to database `name` waits past
+ * `writeContentionMs` for its turn, e.g. to show that a save is held up by
+ * sync. Each waiting write is reported once; it keeps waiting.
+ *
+ * @returns A function that stops listening
+ */
+export function onWriteContention(
+  name: string,
+  callback: (event: WriteContentionEvent) => void
+): Promise<UnlistenFn> {
+  return listen<WriteContentionEvent>(WRITE_CONTENTION_EVENT, (event) => {
+    if (event.payload.name === name) {
+      callback(event.payload);
+    }
+  });
+}
+
+/**
+ * Rows the `retention` rules configured for a database deleted in the background
+ */
+export interface RetentionAppliedEvent {
+  /** Database name */
+  name: string;
+  /** One report per rule */
+  pruned: RetentionReport[];
+}
+
+/**
+ * Run a callback whenever the configured retention rules deleted rows from
+ * database `name`.
+ *
+ * @returns A function that stops listening
+ */
+export function onRetentionApplied(
+  name: string,
+  callback: (event: RetentionAppliedEvent) => void
+): Promise<UnlistenFn> {
+  return listen<RetentionAppliedEvent>(RETENTION_APPLIED_EVENT, (event) => {
+    if (event.payload.name === name) {
+      callback(event.payload);
+    }
+  });
+}
+
+/**
+ * A database that was opened or closed
+ */
+export interface DatabaseLifecycleEvent {
+  kind: 'opened' | 'closed';
+  /** Database name */
+  name: string;
+  /** Path of the database file */
+  path: string;
+  /** Whether the PowerSync extension is loaded on the connection */
+  powersyncLoaded: boolean;
+  /** Why the PowerSync extension failed to load, for `opened` events */
+  powersyncLoadError: string | null;
+  /** The damaged database replaced by a fresh one, for `opened` events */
+  recovery: DatabaseRecovery | null;
+}
+
+/**
+ * Run a callback whenever any database is opened.
+ *
+ * @returns A function that stops listening
+ */
+export function onDatabaseOpened(
+  callback: (event: DatabaseLifecycleEvent) => void
+): Promise<UnlistenFn> {
+  return listen<DatabaseLifecycleEvent>(DB_OPENED_EVENT, (event) => callback(event.payload));
+}
+
+/**
+ * Run a callback whenever any database is closed.
+ *
+ * @returns A function that stops listening
+ */
+export function onDatabaseClosed(
+  callback: (event: DatabaseLifecycleEvent) => void
+): Promise<UnlistenFn> {
+  return listen<DatabaseLifecycleEvent>(DB_CLOSED_EVENT, (event) => callback(event.payload));
+}
+
+/**
+ * Run a callback whenever a database is opened without the PowerSync
+ * extension because loading it failed. `powersyncLoadError` says why.
+ *
+ * @returns A function that stops listening
+ */
+export function onExtensionLoadFailed(
+  callback: (event: DatabaseLifecycleEvent) => void
+): Promise<UnlistenFn> {
+  return listen<DatabaseLifecycleEvent>(EXTENSION_LOAD_FAILED_EVENT, (event) =>
+    callback(event.payload)
+  );
+}
+
+/**
+ * Run a callback whenever a damaged database was moved aside and a fresh
+ * one opened in its place. `recovery` says where the old file went. The
+ * fresh database syncs everything again once connected; changes that
+ * weren't uploaded are lost.
+ *
+ * @returns A function that stops listening
+ */
+export function onDatabaseRecovered(
+  callback: (event: DatabaseLifecycleEvent) => void
+): Promise<UnlistenFn> {
+  return listen<DatabaseLifecycleEvent>(DB_RECOVERED_EVENT, (event) => callback(event.payload));
+}
+
+/**
+ * Progress of a chunked executeBatch()
+ */
+export interface BatchProgressEvent {
+  /** Database name */
+  name: string;
+  /** Parameter sets executed so far */
+  processed: number;
+  /** Parameter sets in the whole batch */
+  total: number;
+  /** Rows changed so far */
+  changes: number;
+}
+
+/**
+ * Run a callback after each committed chunk of a chunked executeBatch() on
+ * database `name`, e.g. to drive an import progress bar.
+ *
+ * @returns A function that stops listening
+ */
+export function onBatchProgress(
+  name: string,
+  callback: (event: BatchProgressEvent) => void
+): Promise<UnlistenFn> {
+  return listen<BatchProgressEvent>(BATCH_PROGRESS_EVENT, (event) => {
+    if (event.payload.name === name) {
+      callback(event.payload);
+    }
+  });
+}
+
+/**
+ * A log record from the plugin's Rust side
+ */
+export interface LogEvent {
+  level: 'error' | 'warn' | 'info' | 'debug' | 'trace';
+  /** Rust module path the record came from */
+  target: string;
+  message: string;
+  /** Correlation id of the command being run when it was logged */
+  correlationId: string | null;
+}
+
+/**
+ * Run a callback for each plugin log record. Records are only emitted when
+ * `logEvents` is set in the plugin configuration.
+ *
+ * @returns A function that stops listening
+ */
+export function onLog(callback: (event: LogEvent) => void): Promise<UnlistenFn> {
+  return listen<LogEvent>(LOG_EVENT, (event) => callback(event.payload));
+}
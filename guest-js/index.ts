@@ -80,25 +80,123 @@ export {
   TauriPowerSyncDatabaseFull,
   PowerSyncDatabase,
   type TauriPowerSyncDatabaseFullOptions,
+  type UploadConflict,
+  type WatchSnapshotHandler,
+  type WatchSnapshotOptions,
 } from './TauriPowerSyncDatabaseFull';
 
 // Simple database (no sync, for offline-only or simple use cases)
 export { TauriPowerSyncDatabase, type TauriPowerSyncDatabaseOptions } from './TauriPowerSyncDatabase';
 
 // Low-level adapter
-export { TauriDBAdapter } from './TauriDBAdapter';
+export {
+  TauriDBAdapter,
+  type TauriLockContext,
+  type TauriTransaction,
+  type TransactionWrites,
+} from './TauriDBAdapter';
+
+// Typed command bindings (one function per Rust command)
+export * as commands from './commands';
+export {
+  CheckpointTimeoutError,
+  CorruptDatabaseError,
+  InvalidBundleError,
+  LockTimeoutError,
+  PartialCommitError,
+  PowerSyncPluginError,
+  toSqlParam,
+  toSqlParams,
+  type BatchOptions,
+  type BatchResult,
+  type ControlOp,
+  type CrossDatabaseResult,
+  type EventsSince,
+  type GuardedTransaction,
+  type TransactionGuard,
+  type ExportFormat,
+  type EncryptedExport,
+  type ExportResult,
+  type JournalEntry,
+  type LocalSyncStatus,
+  type LockState,
+  type SqlParam,
+  type Timestamp,
+  type TuningPreset,
+  type UndoState,
+  type UndoStep,
+  type ViewInfo,
+} from './commands';
+
+// Events emitted by the Rust side
+export {
+  CHECKPOINT_EVENT,
+  CHECKPOINT_FAILED_EVENT,
+  SCHEMA_CHANGED_EVENT,
+  UPLOAD_ERROR_EVENT,
+  WRITE_CONTENTION_EVENT,
+  DB_OPENED_EVENT,
+  DB_CLOSED_EVENT,
+  EXTENSION_LOAD_FAILED_EVENT,
+  DB_RECOVERED_EVENT,
+  BATCH_PROGRESS_EVENT,
+  RETENTION_APPLIED_EVENT,
+  TABLES_CHANGED_EVENT,
+  LOG_EVENT,
+  onCheckpoint,
+  onCheckpointFailure,
+  onSchemaChanged,
+  onUploadError,
+  onWriteContention,
+  onDatabaseOpened,
+  onDatabaseClosed,
+  onExtensionLoadFailed,
+  onDatabaseRecovered,
+  onBatchProgress,
+  onRetentionApplied,
+  onTablesChanged,
+  onLog,
+  type BatchProgressEvent,
+  type CheckpointEvent,
+  type CheckpointFailureEvent,
+  type DatabaseLifecycleEvent,
+  type LogEvent,
+  type RetentionAppliedEvent,
+  type SchemaChangedEvent,
+  type TableOpCounts,
+  type TablesChangedEvent,
+  type UploadErrorEvent,
+  type WriteContentionEvent,
+} from './events';
 
 // Streaming sync implementation (for advanced use cases)
 export { TauriStreamingSyncImplementation } from './TauriStreamingSyncImplementation';
 
 // Types
 export type {
+  ConnectionInfo,
+  DatabaseRecovery,
+  OpenResult,
+  OplogSummary,
+  PowerSyncStatus,
+  RedactedCrudEntry,
+  RetentionReport,
+  RetentionRule,
+  SchemaChange,
+  SchemaTable,
+  SeedData,
+  SyncRulesIssue,
   ExecuteResult,
   QueryResult,
   TransactionContext,
   CrudEntry,
   CrudOperationData,
   SyncStatus,
+  ConflictResolution,
+  LocalDataMigration,
+  LocalDataStrategy,
+  LocalTableStrategy,
+  UploadError,
   UploadQueueStats,
   DownloadProgress,
 } from './types';
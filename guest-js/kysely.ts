@@ -0,0 +1,158 @@
+import {
+  SqliteAdapter,
+  SqliteIntrospector,
+  SqliteQueryCompiler,
+  type CompiledQuery,
+  type DatabaseConnection,
+  type DatabaseIntrospector,
+  type Dialect,
+  type DialectAdapter,
+  type Driver,
+  type Kysely,
+  type QueryCompiler,
+  type QueryResult,
+} from 'kysely';
+import type { TauriDBAdapter } from './TauriDBAdapter';
+import { Mutex } from './Mutex';
+import * as commands from './commands';
+
+/**
+ * Kysely dialect backed by the plugin's commands.
+ *
+ * Reuses Kysely's SQLite adapter, query compiler and introspector; only the
+ * driver is Tauri-specific. Queries go through the given adapter so table
+ * change notifications (and therefore `watch()`) keep working.
+ *
+ * @example
+ * ```typescript
+ * import { Kysely } from 'kysely';
+ * import { TauriDialect } from '@jfairbairn/tauri-plugin-powersync-jf/kysely';
+ *
+ * const db = new Kysely<Database>({
+ *   dialect: new TauriDialect(powerSyncDb.database as TauriDBAdapter),
+ * });
+ * ```
+ */
+export class TauriDialect implements Dialect {
+  constructor(private readonly adapter: TauriDBAdapter) {}
+
+  createAdapter(): DialectAdapter {
+    return new SqliteAdapter();
+  }
+
+  createDriver(): Driver {
+    return new TauriDriver(this.adapter);
+  }
+
+  createQueryCompiler(): QueryCompiler {
+    return new SqliteQueryCompiler();
+  }
+
+  createIntrospector(db: Kysely<any>): DatabaseIntrospector {
+    return new SqliteIntrospector(db);
+  }
+}
+
+/**
+ * There is a single native connection per database, so connections are
+ * handed out one at a time.
+ */
+class TauriDriver implements Driver {
+  private readonly mutex = new Mutex();
+  private readonly connection: TauriConnection;
+
+  constructor(adapter: TauriDBAdapter) {
+    this.connection = new TauriConnection(adapter);
+  }
+
+  async init(): Promise<void> {}
+
+  async acquireConnection(): Promise<DatabaseConnection> {
+    await this.mutex.acquire();
+    return this.connection;
+  }
+
+  async beginTransaction(connection: TauriConnection): Promise<void> {
+    await connection.begin();
+  }
+
+  async commitTransaction(connection: TauriConnection): Promise<void> {
+    await connection.commit();
+  }
+
+  async rollbackTransaction(connection: TauriConnection): Promise<void> {
+    await connection.rollback();
+  }
+
+  async releaseConnection(): Promise<void> {
+    this.mutex.release();
+  }
+
+  async destroy(): Promise<void> {}
+}
+
+/**
+ * Number of rows per chunk when streaming without an explicit chunk size
+ */
+const DEFAULT_STREAM_CHUNK_SIZE = 100;
+
+class TauriConnection implements DatabaseConnection {
+  private txId: string | null = null;
+
+  constructor(private readonly adapter: TauriDBAdapter) {}
+
+  async begin(): Promise<void> {
+    this.txId = await commands.beginTransaction(this.adapter.name, true);
+  }
+
+  async commit(): Promise<void> {
+    const txId = this.takeTransaction();
+    await commands.commitTransaction(this.adapter.name, txId);
+  }
+
+  async rollback(): Promise<void> {
+    const txId = this.takeTransaction();
+    await commands.rollbackTransaction(this.adapter.name, txId);
+  }
+
+  async executeQuery<R>(compiledQuery: CompiledQuery): Promise<QueryResult<R>> {
+    const { sql, parameters } = compiledQuery;
+
+    // RETURNING clauses produce rows, which the execute command only returns for SELECT/PRAGMA
+    if (/\breturning\b/i.test(sql)) {
+      const rows = await this.adapter.getAll<R>(sql, [...parameters]);
+      return { rows, numAffectedRows: BigInt(rows.length) };
+    }
+
+    const result = await this.adapter.executeInTransaction(this.txId ?? undefined, sql, [
+      ...parameters,
+    ]);
+    return {
+      rows: (result.rows?._array ?? []) as R[],
+      numAffectedRows: BigInt(result.rowsAffected),
+      insertId: result.insertId != null ? BigInt(result.insertId) : undefined,
+    };
+  }
+
+  /**
+   * The query runs to completion natively; rows are then yielded in chunks.
+   */
+  async *streamQuery<R>(
+    compiledQuery: CompiledQuery,
+    chunkSize: number = DEFAULT_STREAM_CHUNK_SIZE
+  ): AsyncIterableIterator<QueryResult<R>> {
+    const { rows } = await this.executeQuery<R>(compiledQuery);
+    for (let i = 0; i < rows.length; i += chunkSize) {
+      yield { rows: rows.slice(i, i + chunkSize) };
+    }
+  }
+
+  private takeTransaction(): string {
+    if (this.txId === null) {
+      throw new Error('No transaction in progress');
+    }
+    const txId = this.txId;
+    this.txId = null;
+    return txId;
+  }
+}
@@ -0,0 +1,70 @@
+import type { AbstractPowerSyncDatabase } from '@powersync/common';
+import type { QueryClient, QueryKey } from '@tanstack/query-core';
+
+/**
+ * Maps changed tables to the TanStack query keys that depend on them.
+ * Either a static table → keys map, or a function of the changed tables.
+ */
+export type TableQueryKeys = Record<string, QueryKey[]> | ((tables: string[]) => QueryKey[]);
+
+/**
+ * Options for invalidateQueriesOnChange
+ */
+export interface InvalidateOnChangeOptions {
+  /**
+   * Minimum delay between invalidations in milliseconds (default: 30)
+   */
+  throttleMs?: number;
+}
+
+/**
+ * Invalidate TanStack queries whenever the tables they read from change.
+ *
+ * For live query hooks, `@powersync/tanstack-react-query` works unmodified
+ * with this plugin's `PowerSyncDatabase`; this helper covers plain
+ * `useQuery` setups that fetch with `db.getAll()` and only need to refetch
+ * on change.
+ *
+ * @example
+ * ```typescript
+ * const stop = invalidateQueriesOnChange(db, queryClient, {
+ *   todos: [['todos']],
+ *   lists: [['lists'], ['todos']],
+ * });
+ * ```
+ *
+ * @returns A function that stops listening for changes
+ */
+export function invalidateQueriesOnChange(
+  db: AbstractPowerSyncDatabase,
+  queryClient: QueryClient,
+  queryKeys: TableQueryKeys,
+  options: InvalidateOnChangeOptions = {}
+): () => void {
+  // With a key function, watch every table in the schema
+  const watchedTables =
+    typeof queryKeys === 'function'
+      ? db.schema.tables.map((table) => table.name)
+      : Object.keys(queryKeys);
+
+  const keysFor = (tables: string[]): QueryKey[] => {
+    if (typeof queryKeys === 'function') {
+      return queryKeys(tables);
+    }
+    return tables.flatMap((table) => queryKeys[table] ?? []);
+  };
+
+  return db.onChange(
+    {
+      onChange: async (event) => {
+        for (const queryKey of keysFor(event.changedTables)) {
+          await queryClient.invalidateQueries({ queryKey });
+        }
+      },
+    },
+    {
+      tables: watchedTables,
+      throttleMs: options.throttleMs ?? 30,
+    }
+  );
+}
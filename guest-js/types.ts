@@ -17,6 +17,10 @@ export interface ExecuteResult {
  */
 export interface QueryResult {
   columns: string[];
+  /**
+   * Rows keyed by column name, in SELECT column order (except that
+   * JavaScript lists integer-like keys first)
+   */
   rows: Record<string, unknown>[];
 }
 
@@ -65,6 +69,164 @@ export interface TransactionContext {
   rollback(): Promise<void>;
 }
 
+/**
+ * What an open database is backed by
+ */
+export interface ConnectionInfo {
+  /**
+   * Path of the database file
+   */
+  path: string;
+
+  /**
+   * Whether the PowerSync extension is loaded
+   */
+  powersyncLoaded: boolean;
+
+  /**
+   * Version of the loaded PowerSync extension
+   */
+  powersyncVersion: string | null;
+}
+
+/**
+ * Outcome of opening a database
+ */
+export interface OpenResult extends ConnectionInfo {
+  /**
+   * Whether the database file didn't exist before it was opened.
+   * Stays the same for repeated opens.
+   */
+  created: boolean;
+  /** The damaged database moved aside to open this one, if it was recovered */
+  recovery: DatabaseRecovery | null;
+}
+
+/**
+ * A damaged database file replaced by a fresh database, on open with
+ * `recoverCorruptDatabases` or by recoverDatabase()
+ */
+export interface DatabaseRecovery {
+  /** Why the database couldn't be opened, or null for recoverDatabase() */
+  error: string | null;
+  /** Where the damaged database file was moved */
+  backupPath: string;
+  /**
+   * Whether the schema of the damaged database was applied to the fresh
+   * one. If not, apply it with replaceSchema() before using the database.
+   */
+  schemaRestored: boolean;
+}
+
+/**
+ * Rows of a local-only or raw table to delete, for applyRetention() or the
+ * `retention` plugin config
+ */
+export interface RetentionRule {
+  table: string;
+  /**
+   * Column holding when each row was created, as ISO 8601 text or Unix
+   * seconds. Rows where it is null are never deleted by age.
+   */
+  timestampColumn: string;
+  /** Delete rows older than this many days */
+  maxAgeDays?: number;
+  /** Delete all but the newest this many rows */
+  keepLast?: number;
+}
+
+/**
+ * Rows a retention rule deleted
+ */
+export interface RetentionReport {
+  table: string;
+  deleted: number;
+}
+
+/**
+ * A schema table or column the sync rules don't line up with, from lintSyncRules()
+ */
+export type SyncRulesIssue =
+  /** No data query outputs to this schema table, so it stays empty */
+  | { kind: 'tableWithoutData'; table: string }
+  /** Data queries output to the table, but none selects this column */
+  | { kind: 'columnWithoutData'; table: string; column: string }
+  /** A data query outputs to a table the local schema doesn't have */
+  | { kind: 'tableNotInSchema'; table: string };
+
+/**
+ * Operations in `ps_oplog` for one bucket and row type
+ */
+export interface OplogSummary {
+  /** Bucket name, or its id if `ps_buckets` has no such bucket */
+  bucket: string;
+  rowType: string | null;
+  ops: number;
+  lastOpId: number;
+}
+
+/**
+ * A pending upload from `ps_crud`, without its values
+ */
+export interface RedactedCrudEntry {
+  id: number;
+  txId: number | null;
+  /** `PUT`, `PATCH` or `DELETE` */
+  op: string | null;
+  table: string | null;
+  rowId: string | null;
+  /** Columns the operation sets */
+  columns: string[];
+}
+
+/**
+ * A table of the applied schema, in the JSON shape replaceSchema() takes
+ */
+export interface SchemaTable {
+  name: string;
+  columns: { name: string; type: string }[];
+  local_only: boolean;
+}
+
+/**
+ * A schema change applied by replaceSchema()
+ */
+export interface SchemaChange {
+  /** Number of schema changes applied to the database, starting at 1 */
+  version: number;
+  /** Hash of the schema JSON */
+  hash: string;
+  /** Views created or redefined by the change */
+  recreatedViews: string[];
+  /** Views the change dropped */
+  droppedViews: string[];
+}
+
+/**
+ * Fixture data for seed(): an SQL script, or rows by table
+ */
+export type SeedData = string | Record<string, Record<string, unknown>[]>;
+
+/**
+ * State of the PowerSync extension on a connection
+ */
+export interface PowerSyncStatus {
+  /**
+   * Whether the extension is loaded
+   */
+  loaded: boolean;
+
+  /**
+   * Version of the loaded extension
+   */
+  version: string | null;
+
+  /**
+   * Why the extension couldn't be loaded when the database was opened
+   */
+  loadError: string | null;
+}
+
 /**
  * A CRUD entry from the ps_crud table
  */
@@ -158,6 +320,81 @@ export interface UploadQueueStats {
    * Total size of pending operations in bytes
    */
   size?: number;
+
+  /**
+   * Number of pending transactions, counting each operation without a
+   * transaction as one (from getUploadQueueStats())
+   */
+  transactions?: number;
+
+  /**
+   * Pending operations reported with reportUploadError(), oldest first
+   * (from getUploadQueueStats())
+   */
+  failing?: UploadError[];
+}
+
+/**
+ * How migrateLocalData() re-homes the rows created before signing in
+ */
+export interface LocalDataStrategy {
+  /** The signed-in user, written to each table's `ownerColumn` */
+  userId?: string;
+  /** Owner the rows had before signing in; rows owned by NULL or this are migrated */
+  previousUserId?: string;
+  /** Tables to migrate */
+  tables: LocalTableStrategy[];
+}
+
+export interface LocalTableStrategy {
+  table: string;
+  /** Column holding the row's owner. Without one, every row is migrated. */
+  ownerColumn?: string;
+  /** Give the rows new UUIDs */
+  regenerateIds?: boolean;
+  /** Synced table to move the rows into, matching columns by name */
+  into?: string;
+  /** Columns holding ids of other migrated tables' rows, mapped to those tables */
+  references?: Record<string, string>;
+}
+
+/**
+ * Outcome of migrateLocalData()
+ */
+export interface LocalDataMigration {
+  /** Rows migrated, by table */
+  rows: Record<string, number>;
+  /** Rows given new ids */
+  idChanges: { table: string; oldId: string; newId: string }[];
+}
+
+/**
+ * How to settle a CRUD transaction the backend rejected
+ *
+ * - `discard`: drop it, restoring the rows it changed to their last synced
+ *   version (rows created locally are deleted)
+ * - `retry`: keep it queued, replacing the data of the listed entries
+ * - `keep-local`: drop it, keeping the rows as they are locally until the
+ *   server next changes them
+ */
+export type ConflictResolution =
+  | { action: 'discard' }
+  | { action: 'retry'; entries?: { crudId: number; data: Record<string, unknown> }[] }
+  | { action: 'keep-local' };
+
+/**
+ * Failed upload attempts of a pending CRUD operation
+ */
+export interface UploadError {
+  crudId: number;
+  txId: number | null;
+  /** Failed attempts so far, starting at 1 */
+  retries: number;
+  lastError: string;
+  /** Unix time of the first failure, in milliseconds */
+  firstFailedAt: number;
+  /** Unix time of the latest failure, in milliseconds */
+  lastFailedAt: number;
 }
 
 /**
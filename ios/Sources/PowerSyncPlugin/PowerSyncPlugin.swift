@@ -0,0 +1,14 @@
+import Tauri
+import UIKit
+import WebKit
+
+// All database work happens in Rust; the native side only exists so the
+// plugin is registered with the iOS runtime. The PowerSync extension is
+// statically linked and registered via sqlite3_auto_extension.
+class PowerSyncPlugin: Plugin {
+}
+
+@_cdecl("init_plugin_powersync")
+func initPlugin() -> Plugin {
+    return PowerSyncPlugin()
+}
@@ -0,0 +1,40 @@
+//! TypeScript bindings generated from the Rust command signatures
+//!
+//! Run `cargo test --features specta export_bindings` to write them to
+//! `guest-js/bindings.ts`. The file is not checked in and nothing in guest-js
+//! imports it; the published API is still the hand-written `commands.ts`, so
+//! the generated file is a reference to diff that against.
+
+use crate::commands;
+use tauri::Runtime;
+
+/// Path of the generated bindings, relative to the crate root
+pub const BINDINGS_PATH: &str = "guest-js/bindings.ts";
+
+/// Collect every plugin command for binding generation and, with this
+/// feature on, for the plugin's invoke handler
+pub fn builder<R: Runtime>() -> tauri_specta::Builder<R> {
+    // Generic commands need a concrete runtime for their signatures; the
+    // handler itself is still built for `R`
+    macro_rules! collect {
+        ($($command:ident),* $(,)?) => {
+            tauri_specta::collect_commands![$(commands::$command::<tauri::Wry>),*]
+        };
+    }
+    tauri_specta::Builder::<R>::new()
+        .plugin_name("powersync-jf")
+        .commands(plugin_commands!(collect))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_bindings() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(BINDINGS_PATH);
+        builder::<tauri::Wry>()
+            .export(specta_typescript::Typescript::default(), path)
+            .expect("Failed to export TypeScript bindings");
+    }
+}
@@ -1,145 +1,1634 @@
-use crate::database::{CrudEntry, ExecuteResult, QueryResult, RowResult, SqlParam};
+use crate::bundle::EncryptedExport;
+use crate::clock::{Clock, Timestamp};
+use crate::consistency::{self, LocalSyncStatus};
+use crate::control::ControlOp;
+use crate::coordinator::{self, CrossDatabaseResult};
+use crate::correlation;
+use crate::database::{
+    BatchFailure, BatchProgress, BatchResult, ChangeFeedEntry, ConnectionStats, CrudEntry, ExecuteResult,
+    FormattedResult, GuardedTransaction, OpenResult, PipelineOp, PipelineResult,
+    PowerSyncConnection, PowerSyncStatus, QueryResult, ResultFormat, RowResult, SqlParam,
+    Statement, TransactionGuard,
+};
+use crate::devtools::{OplogSummary, RedactedCrudEntry, SchemaTable, SeedData};
 use crate::error::{Error, Result};
-use crate::PowerSyncState;
-use tauri::{command, AppHandle, Runtime, State};
-
-/// Reject SQL statements that reference powersync_core internals.
-/// Checked against the prepared statement template only, not bound parameter values.
-fn validate_sql(sql: &str) -> Result<()> {
-    if sql.contains("powersync_core") {
-        return Err(Error::ForbiddenSql(
-            "SQL must not reference powersync_core".to_string(),
+use crate::events;
+use crate::export::{ExportFormat, ExportResult};
+use crate::isolation;
+use crate::journal::{EventJournals, EventsSince};
+use crate::profiles;
+use crate::queue::{LockState, WritePermit, WritePriority, WriteQueueStats};
+use crate::reconcile::{LocalDataMigration, LocalDataStrategy};
+use crate::schema::SchemaChange;
+use crate::sql_policy;
+use crate::support;
+use crate::sync_rules::SyncRulesIssue;
+use crate::tuning::{ConnectionTuning, TuningPreset};
+use crate::undo::{UndoState, UndoStep};
+use crate::uploads::{ConflictResolution, UploadError, UploadQueueStats};
+use crate::views::ViewInfo;
+use crate::{PowerSyncState, RetentionReport, RetentionRule, UploadConflicts};
+use std::time::Duration;
+use tauri::{command, AppHandle, Runtime, State, Webview, Window};
+
+/// Wait for a turn in the database's write queue, failing with
+/// `Error::LockTimeout` once the queue's timeout passes.
+/// Must be called before locking the connection, which the current writer needs.
+async fn queue_write(
+    state: &PowerSyncState,
+    name: &str,
+    priority: WritePriority,
+    owner: &str,
+) -> Result<WritePermit> {
+    let queue = state.write_queue(name)?;
+    queue.acquire_timed(priority, Some(owner)).await
+}
+
+/// Like `queue_write`, but a write inside `tx_id`, a write transaction of
+/// the caller, nests under the transaction's permit instead of queuing
+/// behind it. Every command that writes takes an optional `tx_id` for this,
+/// as queuing behind the caller's own transaction would never get a turn.
+async fn queue_write_in(
+    state: &PowerSyncState,
+    name: &str,
+    priority: WritePriority,
+    owner: &str,
+    tx_id: Option<&str>,
+) -> Result<WritePermit> {
+    if let Some(tx_id) = tx_id {
+        if let Some(permit) = reenter_transaction(state, name, owner, tx_id)? {
+            return Ok(permit);
+        }
+    }
+    queue_write(state, name, priority, owner).await
+}
+
+/// The permit of the caller's transaction `tx_id`, nested, if it holds one
+fn reenter_transaction(
+    state: &PowerSyncState,
+    name: &str,
+    owner: &str,
+    tx_id: &str,
+) -> Result<Option<WritePermit>> {
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(name)?;
+    let conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.check_transaction_owner(tx_id, owner)?;
+    conn.reenter_write_permit(tx_id)
+}
+
+/// Open a database connection, reporting whether the extension loaded and
+/// whether the file is new. Repeated opens report the same.
+///
+/// `preset` tunes the connection's pragmas, overriding the configured tuning.
+/// The connection is shared, so the last preset given wins.
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn open<R: Runtime>(
+    app: AppHandle<R>,
+    webview: Webview<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    preset: Option<TuningPreset>,
+    correlation_id: Option<String>,
+) -> Result<OpenResult> {
+    let result = open_database(&app, &webview, &state, &name, correlation_id.clone()).await?;
+    if let Some(preset) = preset {
+        let _correlation = correlation::enter(correlation_id);
+        let name = isolation::database_name(&webview, name)?;
+        let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+        let conn = manager.get(&name)?;
+        let conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+        conn.apply_tuning(&ConnectionTuning::from(preset))?;
+    }
+    Ok(result)
+}
+
+/// Open a database for `webview` unless already open
+async fn open_database<R: Runtime>(
+    app: &AppHandle<R>,
+    webview: &Webview<R>,
+    state: &PowerSyncState,
+    name: &str,
+    correlation_id: Option<String>,
+) -> Result<OpenResult> {
+    let managed_name = isolation::database_name(webview, name.to_string())?;
+    // Events of an isolated webview's databases only go to that webview
+    let target = isolation::namespace(webview).map(|_| webview.label().to_string());
+    open_managed(app, state, &managed_name, name, target, correlation_id).await
+}
+
+/// Open database `managed_name` unless already open, forwarding its events
+/// under `name` to `target` (or every non-isolated webview). The opening
+/// runs under `correlation_id`.
+///
+/// The manager is only locked briefly: the slow part runs under a per-name
+/// lock, so concurrent opens of one database initialize it once and opens
+/// of other databases don't wait.
+pub(crate) async fn open_managed<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &PowerSyncState,
+    managed_name: &str,
+    name: &str,
+    target: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<OpenResult> {
+    let (open_lock, opener) = {
+        let mut manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+        if let Some(result) = manager.open_result(managed_name)? {
+            return Ok(result);
+        }
+        (manager.open_lock(managed_name), manager.opener())
+    };
+
+    let _opening = open_lock.lock().await;
+    let _correlation = correlation::enter(correlation_id);
+    // Someone else may have opened it while we waited
+    let opened = {
+        let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+        manager.open_result(managed_name)?
+    };
+    if let Some(result) = opened {
+        return Ok(result);
+    }
+    let conn = opener.open(managed_name)?;
+    register(app, state, managed_name, name, target, conn)
+}
+
+/// Forward the events of connection `conn` under `name` to `target` (or
+/// every non-isolated webview) and register it as database `managed_name`
+fn register<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &PowerSyncState,
+    managed_name: &str,
+    name: &str,
+    target: Option<String>,
+    conn: PowerSyncConnection,
+) -> Result<OpenResult> {
+    let result = conn.open_result();
+
+    events::forward(
+        app.clone(),
+        managed_name.to_string(),
+        name.to_string(),
+        target.clone(),
+        events::TABLES_CHANGED_EVENT,
+        conn.subscribe_commits(),
+    );
+    events::forward(
+        app.clone(),
+        managed_name.to_string(),
+        name.to_string(),
+        target.clone(),
+        events::CHECKPOINT_EVENT,
+        conn.subscribe_checkpoints(),
+    );
+    events::forward(
+        app.clone(),
+        managed_name.to_string(),
+        name.to_string(),
+        target.clone(),
+        events::CHECKPOINT_FAILED_EVENT,
+        conn.subscribe_checkpoint_failures(),
+    );
+    events::forward(
+        app.clone(),
+        managed_name.to_string(),
+        name.to_string(),
+        target.clone(),
+        events::SCHEMA_CHANGED_EVENT,
+        conn.subscribe_schema_changes(),
+    );
+    events::forward(
+        app.clone(),
+        managed_name.to_string(),
+        name.to_string(),
+        target.clone(),
+        events::UPLOAD_ERROR_EVENT,
+        conn.subscribe_upload_errors(),
+    );
+
+    let mut manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    manager.insert(managed_name, conn);
+    // The write queue is created with the database's entry
+    events::forward(
+        app.clone(),
+        managed_name.to_string(),
+        name.to_string(),
+        target,
+        events::WRITE_CONTENTION_EVENT,
+        manager.write_queue(managed_name)?.subscribe_contention(),
+    );
+    Ok(result)
+}
+
+/// Open a user's profile database, returning the database name to use
+/// with the other commands
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn open_profile<R: Runtime>(
+    app: AppHandle<R>,
+    webview: Webview<R>,
+    state: State<'_, PowerSyncState>,
+    user_id: String,
+    correlation_id: Option<String>,
+) -> Result<String> {
+    let name = profiles::database_name(&user_id)?;
+    open_database(&app, &webview, &state, &name, correlation_id).await?;
+    Ok(name)
+}
+
+/// List the user IDs of all profiles on disk
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn list_profiles<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    state: State<'_, PowerSyncState>,
+    correlation_id: Option<String>,
+) -> Result<Vec<String>> {
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    match isolation::namespace(&webview) {
+        Some(namespace) => profiles::list(&manager.app_data_dir().join(namespace)),
+        None => manager.list_profiles(),
+    }
+}
+
+/// Close a user's profile database and delete its files
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn delete_profile<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    state: State<'_, PowerSyncState>,
+    user_id: String,
+    correlation_id: Option<String>,
+) -> Result<()> {
+    let _correlation = correlation::enter(correlation_id);
+    let mut manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    match isolation::namespace(&webview) {
+        Some(namespace) => {
+            let name = isolation::database_name(&webview, profiles::database_name(&user_id)?)?;
+            manager.close(&name)?;
+            profiles::remove_files(&manager.app_data_dir().join(namespace), &user_id)
+        }
+        None => manager.delete_profile(&user_id),
+    }
+}
+
+/// Close a database if it is open, move its files aside and open a fresh
+/// database in their place, e.g. when queries start failing because the file
+/// is damaged. The fresh database syncs everything again; changes that
+/// weren't uploaded are lost.
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn recover_database<R: Runtime>(
+    app: AppHandle<R>,
+    webview: Webview<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    correlation_id: Option<String>,
+) -> Result<OpenResult> {
+    let managed_name = isolation::database_name(&webview, name.clone())?;
+    let target = isolation::namespace(&webview).map(|_| webview.label().to_string());
+    let (open_lock, opener) = {
+        let mut manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+        (manager.open_lock(&managed_name), manager.opener())
+    };
+
+    let _opening = open_lock.lock().await;
+    let _correlation = correlation::enter(correlation_id);
+    {
+        let mut manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+        manager.close(&managed_name)?;
+    }
+    let conn = opener.recover(&managed_name, None)?;
+    register(&app, &state, &managed_name, &name, target, conn)
+}
+
+/// Close a database connection
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn close<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    correlation_id: Option<String>,
+) -> Result<()> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
+    let mut manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    manager.close(&name)
+}
+
+/// Execute a SQL statement, inside write transaction `tx_id` of the
+/// calling window if given
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn execute<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    sql: String,
+    params: Vec<SqlParam>,
+    format: Option<ResultFormat>,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<FormattedResult<ExecuteResult>> {
+    let name = isolation::database_name(&webview, name)?;
+    sql_policy::validate(&sql)?;
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let result = conn.execute(&sql, &params)?;
+    Ok(format.unwrap_or_default().apply(result))
+}
+
+/// Execute a SQL statement without the SQL policy of `execute`, for trusted
+/// admin surfaces. Outside the default permission set.
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn execute_raw<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    sql: String,
+    params: Vec<SqlParam>,
+    format: Option<ResultFormat>,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<FormattedResult<ExecuteResult>> {
+    let name = isolation::database_name(&webview, name)?;
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let result = conn.execute(&sql, &params)?;
+    Ok(format.unwrap_or_default().apply(result))
+}
+
+/// Execute a batch of SQL statements.
+///
+/// With `chunk_size`, each chunk of parameter sets commits on its own and the
+/// write lock is released in between, so other writers aren't held up by a
+/// large import. Progress is emitted to the calling webview after each chunk.
+/// A failing chunk is rolled back, but earlier chunks stay committed, so
+/// failures are always reported in `failure` rather than by rejecting:
+/// `changes` and `last_insert_rowid` then cover the committed chunks.
+///
+/// With `details`, the result lists each parameter set's changes and rowid,
+/// and a failing parameter set is reported by index in `failure` instead of
+/// rejecting the call.
+///
+/// Inside write transaction `tx_id` of the calling window, chunks commit
+/// with the transaction rather than on their own.
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn execute_batch<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    sql: String,
+    params_batch: Vec<Vec<SqlParam>>,
+    chunk_size: Option<usize>,
+    details: Option<bool>,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<BatchResult> {
+    let requested_name = name.clone();
+    let name = isolation::database_name(&webview, name)?;
+    sql_policy::validate(&sql)?;
+    let details = details.unwrap_or(false);
+    let run = |conn: &mut PowerSyncConnection, params_batch: &[Vec<SqlParam>]| {
+        if details {
+            conn.execute_batch_detailed(&sql, params_batch)
+        } else {
+            conn.execute_batch(&sql, params_batch).map(BatchResult::from)
+        }
+    };
+
+    let total = params_batch.len();
+    let owner = window.label();
+    let tx_id = tx_id.as_deref();
+    let Some(chunk_size) = chunk_size.filter(|&size| size > 0) else {
+        let _permit = queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id).await?;
+        let _correlation = correlation::enter(correlation_id);
+        let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+        let conn = manager.get(&name)?;
+        let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+        return run(&mut *conn, &params_batch);
+    };
+
+    let mut result = BatchResult {
+        changes: 0,
+        last_insert_rowid: 0,
+        entries: details.then(Vec::new),
+        failure: None,
+    };
+    let mut processed = 0;
+    for chunk in params_batch.chunks(chunk_size) {
+        let chunk_result = async {
+            let _permit =
+                queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id).await?;
+            let _correlation = correlation::enter(correlation_id.clone());
+            let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+            let conn = manager.get(&name)?;
+            let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+            // Detailed even without `details`, to learn which parameter set failed
+            conn.execute_batch_detailed(&sql, chunk)
+        }
+        .await;
+        let chunk_result = match chunk_result {
+            Ok(chunk_result) => chunk_result,
+            Err(e) => {
+                result.failure = Some(BatchFailure {
+                    index: processed,
+                    error: e.to_string(),
+                });
+                return Ok(result);
+            }
+        };
+
+        if let (Some(entries), Some(chunk_entries)) = (&mut result.entries, chunk_result.entries) {
+            entries.extend(chunk_entries);
+        }
+        if let Some(mut failure) = chunk_result.failure {
+            // Earlier chunks stay committed
+            failure.index += processed;
+            result.failure = Some(failure);
+            return Ok(result);
+        }
+        processed += chunk.len();
+        result.changes += chunk_result.changes;
+        result.last_insert_rowid = chunk_result.last_insert_rowid;
+        let progress = BatchProgress {
+            processed,
+            total,
+            changes: result.changes,
+        };
+        events::emit_batch_progress(&webview, &requested_name, progress);
+    }
+    Ok(result)
+}
+
+/// Insert many rows into a table with multi-row VALUES statements, atomically
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn insert_many<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    table: String,
+    columns: Vec<String>,
+    rows: Vec<Vec<SqlParam>>,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<ExecuteResult> {
+    let name = isolation::database_name(&webview, name)?;
+    sql_policy::validate(&table)?;
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.insert_many(&table, &columns, &rows)
+}
+
+/// Insert a row with a generated `id`, returning the id
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn insert_with_id<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    table: String,
+    columns: Vec<String>,
+    values: Vec<SqlParam>,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<String> {
+    let name = isolation::database_name(&webview, name)?;
+    sql_policy::validate(&table)?;
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.insert_with_id(&table, &columns, &values)
+}
+
+/// Current UTC time from the clock behind the `now()` SQL function, which
+/// never goes backwards and is corrected against `ntpServer` if configured
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn now<R: Runtime>(
+    _app: AppHandle<R>,
+    correlation_id: Option<String>,
+) -> Result<Timestamp> {
+    let _correlation = correlation::enter(correlation_id);
+    Ok(Clock::global().now())
+}
+
+/// Execute statements atomically in a single write transaction
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn execute_transaction<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    statements: Vec<Statement>,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<Vec<ExecuteResult>> {
+    let name = isolation::database_name(&webview, name)?;
+    for statement in &statements {
+        sql_policy::validate(&statement.sql)?;
+    }
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.execute_transaction(&statements)
+}
+
+/// Execute statements atomically, committing only if none of the guard
+/// queries aborts, so a conditional write takes one round trip and never
+/// leaves a transaction open
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn with_transaction<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    statements: Vec<Statement>,
+    guards: Vec<TransactionGuard>,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<GuardedTransaction> {
+    let name = isolation::database_name(&webview, name)?;
+    for statement in &statements {
+        sql_policy::validate(&statement.sql)?;
+    }
+    for guard in &guards {
+        sql_policy::validate(&guard.sql)?;
+    }
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.with_transaction(&statements, &guards)
+}
+
+/// Execute statements on two databases, committing both or, on a
+/// best-effort basis, neither; `compensation` undoes the first database's
+/// statements if the second fails to commit. See
+/// [`powersync_sqlite::coordinator`] for the limitations.
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn execute_across<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    first: String,
+    first_statements: Vec<Statement>,
+    compensation: Vec<Statement>,
+    second: String,
+    second_statements: Vec<Statement>,
+    correlation_id: Option<String>,
+) -> Result<CrossDatabaseResult> {
+    let first = isolation::database_name(&webview, first)?;
+    let second = isolation::database_name(&webview, second)?;
+    if first == second {
+        return Err(Error::InvalidParameter(
+            "execute_across needs two different databases".to_string(),
         ));
     }
-    Ok(())
+    for statement in first_statements.iter().chain(&compensation).chain(&second_statements) {
+        sql_policy::validate(&statement.sql)?;
+    }
+    // Queue for both writers in name order, so concurrent calls can't each
+    // hold the turn the other waits for
+    let (a, b) = if first < second {
+        (&first, &second)
+    } else {
+        (&second, &first)
+    };
+    let _permit_a = queue_write(&state, a, WritePriority::Normal, window.label()).await?;
+    let _permit_b = queue_write(&state, b, WritePriority::Normal, window.label()).await?;
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let first_conn = manager.get(&first)?;
+    let second_conn = manager.get(&second)?;
+    let mut first_conn = first_conn.lock().map_err(|e| Error::Lock(e.to_string()))?;
+    let mut second_conn = second_conn.lock().map_err(|e| Error::Lock(e.to_string()))?;
+    coordinator::execute_across(
+        &mut first_conn,
+        &first_statements,
+        &compensation,
+        &mut second_conn,
+        &second_statements,
+    )
+}
+
+/// Run execute/get_all/get_optional operations back to back in one round trip.
+/// Not atomic; takes a turn in the write queue only if an operation may write.
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn pipeline<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    ops: Vec<PipelineOp>,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<Vec<PipelineResult>> {
+    let name = isolation::database_name(&webview, name)?;
+    for op in &ops {
+        sql_policy::validate(op.sql())?;
+    }
+    let _permit = if ops.iter().any(PipelineOp::is_write) {
+        let owner = window.label();
+        Some(queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?)
+    } else {
+        None
+    };
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.pipeline(&ops)
+}
+
+/// Prepare a statement for repeated `bind_and_step` calls, returning its
+/// handle. Finalized with `finalize`, or when the calling window reloads
+/// or closes.
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn prepare<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    sql: String,
+    correlation_id: Option<String>,
+) -> Result<String> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
+    sql_policy::validate(&sql)?;
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.prepare_owned(&sql, Some(window.label()))
+}
+
+/// Bind parameters to a prepared statement and run it, without sending or
+/// preparing its SQL again, inside write transaction `tx_id` of the calling
+/// window if given
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn bind_and_step<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    stmt_id: String,
+    params: Vec<SqlParam>,
+    format: Option<ResultFormat>,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<FormattedResult<ExecuteResult>> {
+    let name = isolation::database_name(&webview, name)?;
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let result = conn.bind_and_step(&stmt_id, &params)?;
+    Ok(format.unwrap_or_default().apply(result))
+}
+
+/// Release a prepared statement
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn finalize<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    stmt_id: String,
+    correlation_id: Option<String>,
+) -> Result<()> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.finalize(&stmt_id)
+}
+
+/// Apply a JSON merge patch to a JSON column of one row
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn patch_json<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    table: String,
+    column: String,
+    id: String,
+    patch: serde_json::Value,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<ExecuteResult> {
+    let name = isolation::database_name(&webview, name)?;
+    sql_policy::validate(&table)?;
+    sql_policy::validate(&column)?;
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.patch_json(&table, &column, &id, &patch.to_string())
+}
+
+/// Enable soft delete for a table, filtering tombstoned rows out of its view
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn enable_soft_delete<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    table: String,
+    column: String,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<()> {
+    let name = isolation::database_name(&webview, name)?;
+    sql_policy::validate(&table)?;
+    sql_policy::validate(&column)?;
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.enable_soft_delete(&table, &column)
+}
+
+/// Tombstone a row of a soft-delete table
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn soft_delete_row<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    table: String,
+    id: String,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<ExecuteResult> {
+    let name = isolation::database_name(&webview, name)?;
+    sql_policy::validate(&table)?;
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.soft_delete_row(&table, &id)
+}
+
+/// Restore a soft-deleted row
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn restore_row<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    table: String,
+    id: String,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<ExecuteResult> {
+    let name = isolation::database_name(&webview, name)?;
+    sql_policy::validate(&table)?;
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.restore_row(&table, &id)
+}
+
+/// Permanently delete tombstoned rows, optionally only those deleted before `before`
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn purge_deleted<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    table: String,
+    before: Option<String>,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<ExecuteResult> {
+    let name = isolation::database_name(&webview, name)?;
+    sql_policy::validate(&table)?;
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.purge_deleted(&table, before.as_deref())
+}
+
+/// Create a view defined by a SELECT statement, replacing one created with
+/// this command if `replace` is set, and return it with its columns
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn create_view<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    view: String,
+    select: String,
+    replace: Option<bool>,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<ViewInfo> {
+    let name = isolation::database_name(&webview, name)?;
+    sql_policy::validate(&select)?;
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.create_view(&view, &select, replace.unwrap_or(false))
+}
+
+/// Drop a view created with `create_view`
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn drop_view<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    view: String,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<()> {
+    let name = isolation::database_name(&webview, name)?;
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.drop_view(&view)
+}
+
+/// List the views created with `create_view`, with their columns
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn list_views<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    correlation_id: Option<String>,
+) -> Result<Vec<ViewInfo>> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.list_views()
+}
+
+/// Record writes to `tables` for `undo` and `redo`, replacing the tables
+/// tracked before and clearing the history. An empty list turns tracking off.
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn enable_undo<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    tables: Vec<String>,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<()> {
+    let name = isolation::database_name(&webview, name)?;
+    for table in &tables {
+        sql_policy::validate(table)?;
+    }
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.enable_undo(&tables)
+}
+
+/// Revert the latest transaction written to the tables tracked for undo,
+/// or return `null` if there is nothing to undo
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn undo<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<Option<UndoStep>> {
+    let name = isolation::database_name(&webview, name)?;
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.undo()
+}
+
+/// Write the transaction last undone again, or return `null` if there is
+/// nothing to redo
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn redo<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<Option<UndoStep>> {
+    let name = isolation::database_name(&webview, name)?;
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.redo()
+}
+
+/// Tables tracked for undo and how many steps can be undone and redone
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn get_undo_state<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    correlation_id: Option<String>,
+) -> Result<UndoState> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.undo_state()
+}
+
+/// Delete old rows of local-only and raw tables as `rules` describe
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn apply_retention<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    rules: Vec<RetentionRule>,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<Vec<RetentionReport>> {
+    let name = isolation::database_name(&webview, name)?;
+    for rule in &rules {
+        sql_policy::validate(&rule.table)?;
+    }
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.apply_retention(&rules)
+}
+
+/// Start recording row changes of `tables` in the `_changes` feed
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn enable_change_feed<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    tables: Vec<String>,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<()> {
+    let name = isolation::database_name(&webview, name)?;
+    for table in &tables {
+        sql_policy::validate(table)?;
+    }
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let tables: Vec<&str> = tables.iter().map(String::as_str).collect();
+    conn.enable_change_feed(&tables)
+}
+
+/// Read the oldest unacknowledged change feed entries
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn read_change_feed<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    limit: Option<i64>,
+    correlation_id: Option<String>,
+) -> Result<Vec<ChangeFeedEntry>> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.read_change_feed(limit.unwrap_or(100))
+}
+
+/// Acknowledge change feed entries up to and including `seq`
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn ack_change_feed<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    seq: i64,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<()> {
+    let name = isolation::database_name(&webview, name)?;
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.ack_change_feed(seq)
+}
+
+/// Query and return all matching rows
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn get_all<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    sql: String,
+    params: Vec<SqlParam>,
+    format: Option<ResultFormat>,
+    correlation_id: Option<String>,
+) -> Result<FormattedResult<QueryResult>> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
+    sql_policy::validate(&sql)?;
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let result = conn.get_all(&sql, &params)?;
+    Ok(format.unwrap_or_default().apply(result))
+}
+
+/// Run read-only queries against one snapshot, e.g. a watched list and its count
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn get_all_snapshot<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    queries: Vec<Statement>,
+    correlation_id: Option<String>,
+) -> Result<Vec<QueryResult>> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
+    for query in &queries {
+        sql_policy::validate(&query.sql)?;
+    }
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.get_all_snapshot(&queries)
+}
+
+/// Query and return a single optional row
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn get_optional<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    sql: String,
+    params: Vec<SqlParam>,
+    correlation_id: Option<String>,
+) -> Result<Option<RowResult>> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
+    sql_policy::validate(&sql)?;
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.get_optional(&sql, &params)
+}
+
+/// Stream a read-only query's rows to a file as NDJSON or CSV, for reports
+/// too large to send to the webview. `dest` must be an absolute path, e.g.
+/// from a save dialog. Outside the default permission set.
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn query_to_file<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    sql: String,
+    params: Vec<SqlParam>,
+    dest: String,
+    format: ExportFormat,
+    correlation_id: Option<String>,
+) -> Result<ExportResult> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
+    sql_policy::validate(&sql)?;
+    let dest = absolute_path(dest)?;
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.query_to_file(&sql, &params, &dest, format)
+}
+
+/// Write an encrypted, compressed copy of the database to `dest`, an
+/// absolute path. Outside the default permission set.
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn export_encrypted<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    dest: String,
+    passphrase: String,
+    correlation_id: Option<String>,
+) -> Result<EncryptedExport> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
+    let dest = absolute_path(dest)?;
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.export_encrypted(&dest, &passphrase)
+}
+
+/// Replace the database contents with a bundle written by
+/// `export_encrypted`. Outside the default permission set.
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn import_encrypted<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    src: String,
+    passphrase: String,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<()> {
+    let name = isolation::database_name(&webview, name)?;
+    let src = absolute_path(src)?;
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.import_encrypted(&src, &passphrase)
+}
+
+/// Paths from the webview must not resolve against the app's working directory
+fn absolute_path(path: String) -> Result<std::path::PathBuf> {
+    let path = std::path::PathBuf::from(path);
+    if !path.is_absolute() {
+        return Err(Error::InvalidParameter(format!(
+            "path must be absolute: {:?}",
+            path
+        )));
+    }
+    Ok(path)
+}
+
+/// Begin a new transaction owned by the calling window, nested inside its
+/// transaction `tx_id` if given
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn begin_transaction<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    is_write: bool,
+    priority: Option<WritePriority>,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<String> {
+    let name = isolation::database_name(&webview, name)?;
+    // Write transactions keep their place in the queue until they end
+    let permit = if is_write {
+        let priority = priority.unwrap_or_default();
+        let owner = window.label();
+        Some(queue_write_in(&state, &name, priority, owner, tx_id.as_deref()).await?)
+    } else {
+        None
+    };
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let tx_id = conn.begin_owned_transaction(is_write, Some(window.label()))?;
+    if let Some(permit) = permit {
+        conn.attach_write_permit(&tx_id, permit)?;
+    }
+    Ok(tx_id)
+}
+
+/// Commit a transaction begun by the calling window
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn commit_transaction<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    tx_id: String,
+    correlation_id: Option<String>,
+) -> Result<()> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.check_transaction_owner(&tx_id, window.label())?;
+    conn.commit_transaction(&tx_id)
+}
+
+/// Rollback a transaction begun by the calling window
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn rollback_transaction<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    tx_id: String,
+    correlation_id: Option<String>,
+) -> Result<()> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.check_transaction_owner(&tx_id, window.label())?;
+    conn.rollback_transaction(&tx_id)
+}
+
+/// Create a named savepoint inside a transaction
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn create_savepoint<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    tx_id: String,
+    savepoint: String,
+    correlation_id: Option<String>,
+) -> Result<()> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.check_transaction_owner(&tx_id, window.label())?;
+    conn.create_savepoint(&tx_id, &savepoint)
+}
+
+/// Release a named savepoint, keeping its changes
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn release_savepoint<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    tx_id: String,
+    savepoint: String,
+    correlation_id: Option<String>,
+) -> Result<()> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.check_transaction_owner(&tx_id, window.label())?;
+    conn.release_savepoint(&tx_id, &savepoint)
 }
 
-/// Open a database connection
+/// Undo changes made since a named savepoint
 #[command]
-pub async fn open<R: Runtime>(
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn rollback_to_savepoint<R: Runtime>(
     _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
     state: State<'_, PowerSyncState>,
     name: String,
+    tx_id: String,
+    savepoint: String,
+    correlation_id: Option<String>,
 ) -> Result<()> {
-    let mut manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
-    manager.open(&name)
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.check_transaction_owner(&tx_id, window.label())?;
+    conn.rollback_to_savepoint(&tx_id, &savepoint)
 }
 
-/// Close a database connection
+/// Get the length and holder of a database's write queue
 #[command]
-pub async fn close<R: Runtime>(
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn get_write_queue_stats<R: Runtime>(
     _app: AppHandle<R>,
+    webview: Webview<R>,
     state: State<'_, PowerSyncState>,
     name: String,
-) -> Result<()> {
-    let mut manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
-    manager.close(&name)
+    correlation_id: Option<String>,
+) -> Result<WriteQueueStats> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
+    state.write_queue_stats(&name)
 }
 
-/// Execute a SQL statement
+/// Get the holder and waiters of a database's write queue, with how long
+/// each has held or waited
 #[command]
-pub async fn execute<R: Runtime>(
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn get_lock_state<R: Runtime>(
     _app: AppHandle<R>,
+    webview: Webview<R>,
     state: State<'_, PowerSyncState>,
     name: String,
-    sql: String,
-    params: Vec<SqlParam>,
-) -> Result<ExecuteResult> {
-    validate_sql(&sql)?;
-    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
-    let conn = manager.get(&name)?;
-    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
-    conn.execute(&sql, &params)
+    correlation_id: Option<String>,
+) -> Result<LockState> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
+    state.lock_state(&name)
 }
 
-/// Execute a batch of SQL statements
+/// Set how long writers wait for their turn before failing with a lock
+/// timeout; `null` waits indefinitely. Lasts until the database is closed.
 #[command]
-pub async fn execute_batch<R: Runtime>(
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn set_lock_timeout<R: Runtime>(
     _app: AppHandle<R>,
+    webview: Webview<R>,
     state: State<'_, PowerSyncState>,
     name: String,
-    sql: String,
-    params_batch: Vec<Vec<SqlParam>>,
-) -> Result<ExecuteResult> {
-    validate_sql(&sql)?;
-    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
-    let conn = manager.get(&name)?;
-    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
-    conn.execute_batch(&sql, &params_batch)
+    timeout_ms: Option<i64>,
+    correlation_id: Option<String>,
+) -> Result<()> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
+    let timeout = timeout_ms
+        .map(|ms| {
+            u64::try_from(ms).map(Duration::from_millis).map_err(|_| {
+                Error::InvalidParameter(format!("Lock timeout must not be negative: {}", ms))
+            })
+        })
+        .transpose()?;
+    state.write_queue(&name)?.set_timeout(timeout);
+    Ok(())
 }
 
-/// Query and return all matching rows
+/// Gather query planner statistics for one table, or the whole database
 #[command]
-pub async fn get_all<R: Runtime>(
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn analyze<R: Runtime>(
     _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
     state: State<'_, PowerSyncState>,
     name: String,
-    sql: String,
-    params: Vec<SqlParam>,
-) -> Result<QueryResult> {
-    validate_sql(&sql)?;
+    table: Option<String>,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<()> {
+    let name = isolation::database_name(&webview, name)?;
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
     let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
     let conn = manager.get(&name)?;
-    let conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
-    conn.get_all(&sql, &params)
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.analyze(table.as_deref())
 }
 
-/// Query and return a single optional row
+/// Get memory use and page cache counters of a database connection
 #[command]
-pub async fn get_optional<R: Runtime>(
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn get_connection_stats<R: Runtime>(
     _app: AppHandle<R>,
+    webview: Webview<R>,
     state: State<'_, PowerSyncState>,
     name: String,
-    sql: String,
-    params: Vec<SqlParam>,
-) -> Result<Option<RowResult>> {
-    validate_sql(&sql)?;
+    correlation_id: Option<String>,
+) -> Result<ConnectionStats> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
     let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
     let conn = manager.get(&name)?;
     let conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
-    conn.get_optional(&sql, &params)
+    conn.stats()
 }
 
-/// Begin a new transaction
+/// Events emitted for a database after `seq`, for frontends catching up
+/// after they stopped listening for a while
 #[command]
-pub async fn begin_transaction<R: Runtime>(
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn get_events_since<R: Runtime>(
     _app: AppHandle<R>,
-    state: State<'_, PowerSyncState>,
+    webview: Webview<R>,
+    journals: State<'_, EventJournals>,
     name: String,
-    is_write: bool,
+    seq: i64,
+    correlation_id: Option<String>,
+) -> Result<EventsSince> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
+    Ok(journals.since(&name, seq))
+}
+
+/// Zip up diagnostics of the databases the calling webview can open, for
+/// attaching to a bug report, and return the path of the zip file. See
+/// [`crate::support`].
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn create_support_bundle<R: Runtime>(
+    app: AppHandle<R>,
+    webview: Webview<R>,
+    correlation_id: Option<String>,
 ) -> Result<String> {
-    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
-    let conn = manager.get(&name)?;
-    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
-    conn.begin_transaction(is_write)
+    let _correlation = correlation::enter(correlation_id);
+    let path = support::create(&app, webview.label())?;
+    Ok(path.to_string_lossy().into_owned())
 }
 
-/// Commit a transaction
+/// Whether diagnostics may be collected for a database
 #[command]
-pub async fn commit_transaction<R: Runtime>(
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn get_diagnostics_enabled<R: Runtime>(
     _app: AppHandle<R>,
+    webview: Webview<R>,
     state: State<'_, PowerSyncState>,
     name: String,
-    tx_id: String,
-) -> Result<()> {
+    correlation_id: Option<String>,
+) -> Result<bool> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
     let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
     let conn = manager.get(&name)?;
-    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
-    conn.commit_transaction(&tx_id)
+    let conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.diagnostics_enabled()
 }
 
-/// Rollback a transaction
+/// Record whether diagnostics may be collected for a database, persisted
+/// in the database
 #[command]
-pub async fn rollback_transaction<R: Runtime>(
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn set_diagnostics_enabled<R: Runtime>(
     _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
     state: State<'_, PowerSyncState>,
     name: String,
-    tx_id: String,
+    enabled: bool,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
 ) -> Result<()> {
+    let name = isolation::database_name(&webview, name)?;
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
     let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
     let conn = manager.get(&name)?;
     let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
-    conn.rollback_transaction(&tx_id)
+    conn.set_diagnostics_enabled(enabled)
 }
 
 // =====================================================
@@ -148,67 +1637,187 @@ pub async fn rollback_transaction<R: Runtime>(
 
 /// Get the PowerSync extension version
 #[command]
+#[cfg_attr(feature = "specta", specta::specta)]
 pub async fn get_powersync_version<R: Runtime>(
     _app: AppHandle<R>,
+    webview: Webview<R>,
     state: State<'_, PowerSyncState>,
     name: String,
+    correlation_id: Option<String>,
 ) -> Result<String> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
     let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
     let conn = manager.get(&name)?;
     let conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
     conn.get_powersync_version()
 }
 
+/// Get the client ID used in sync stream requests
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn get_client_id<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    correlation_id: Option<String>,
+) -> Result<String> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.get_client_id()
+}
+
+/// Whether the PowerSync extension is loaded, its version, and why loading
+/// it failed if it isn't
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn get_powersync_status<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    correlation_id: Option<String>,
+) -> Result<PowerSyncStatus> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    Ok(conn.powersync_status())
+}
+
 /// Check if PowerSync extension is loaded
 #[command]
+#[cfg_attr(feature = "specta", specta::specta)]
 pub async fn is_powersync_loaded<R: Runtime>(
     _app: AppHandle<R>,
+    webview: Webview<R>,
     state: State<'_, PowerSyncState>,
     name: String,
+    correlation_id: Option<String>,
 ) -> Result<bool> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
     let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
     let conn = manager.get(&name)?;
     let conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
     Ok(conn.is_powersync_loaded())
 }
 
-/// Replace the PowerSync schema
+/// Replace the PowerSync schema, returning the change, or `null` if the
+/// schema was already applied
 #[command]
+#[cfg_attr(feature = "specta", specta::specta)]
 pub async fn replace_schema<R: Runtime>(
     _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
     state: State<'_, PowerSyncState>,
     name: String,
     schema_json: String,
-) -> Result<()> {
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<Option<SchemaChange>> {
+    let name = isolation::database_name(&webview, name)?;
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
     let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
     let conn = manager.get(&name)?;
-    let conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
     conn.replace_schema(&schema_json)
 }
 
-/// Execute a PowerSync control operation
+/// Re-run PowerSync initialization and reload the schema
 #[command]
-pub async fn powersync_control<R: Runtime>(
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn refresh_schema<R: Runtime>(
     _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
     state: State<'_, PowerSyncState>,
     name: String,
-    op: String,
-    payload: String,
-) -> Result<String> {
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<()> {
+    let name = isolation::database_name(&webview, name)?;
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.refresh_schema()
+}
+
+/// Report schema tables and columns the given sync rules YAML never fills,
+/// for "why is my table empty" debugging during development
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn lint_sync_rules<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    sync_rules: String,
+    correlation_id: Option<String>,
+) -> Result<Vec<SyncRulesIssue>> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
     let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
     let conn = manager.get(&name)?;
     let conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
-    conn.powersync_control(&op, &payload)
+    conn.lint_sync_rules(&sync_rules)
+}
+
+/// Execute a PowerSync control operation. Malformed operations are rejected
+/// when the arguments are deserialized, before reaching the extension.
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn powersync_control<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    control: ControlOp,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<String> {
+    let name = isolation::database_name(&webview, name)?;
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Sync, owner, tx_id.as_deref()).await?;
+    // Each attempt locks afresh, so other databases aren't held up while it backs off
+    crate::control::with_retries(control.name(), || {
+        let _correlation = correlation::enter(correlation_id.clone());
+        let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+        let conn = manager.get(&name)?;
+        let conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+        conn.powersync_control_once(&control)
+    })
+    .await
 }
 
 /// Get a batch of pending CRUD entries
 #[command]
+#[cfg_attr(feature = "specta", specta::specta)]
 pub async fn get_crud_batch<R: Runtime>(
     _app: AppHandle<R>,
+    webview: Webview<R>,
     state: State<'_, PowerSyncState>,
     name: String,
     limit: Option<i64>,
+    correlation_id: Option<String>,
 ) -> Result<Vec<CrudEntry>> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
     let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
     let conn = manager.get(&name)?;
     let conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
@@ -217,40 +1826,437 @@ pub async fn get_crud_batch<R: Runtime>(
 
 /// Remove CRUD entries up to and including the given ID
 #[command]
+#[cfg_attr(feature = "specta", specta::specta)]
 pub async fn remove_crud<R: Runtime>(
     _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
     state: State<'_, PowerSyncState>,
     name: String,
     crud_id: i64,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
 ) -> Result<()> {
+    let name = isolation::database_name(&webview, name)?;
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
     let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
     let conn = manager.get(&name)?;
     let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
     conn.remove_crud(crud_id)
 }
 
+/// Record a failed attempt to upload a CRUD entry, returning its retry
+/// count and first and latest failure
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn report_upload_error<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    crud_id: i64,
+    error: String,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<UploadError> {
+    let name = isolation::database_name(&webview, name)?;
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.report_upload_error(crud_id, &error)
+}
+
+/// Settle a CRUD transaction the backend rejected: the entries up to and
+/// including `crud_id`. Without a `resolution`, the handler registered with
+/// `Builder::on_upload_conflict` chooses one. Returns the resolution
+/// applied, or None if the entries stay queued.
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn resolve_upload_conflict<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    conflicts: State<'_, UploadConflicts>,
+    name: String,
+    crud_id: i64,
+    status: Option<i64>,
+    error: String,
+    resolution: Option<ConflictResolution>,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<Option<ConflictResolution>> {
+    let requested_name = name.clone();
+    let name = isolation::database_name(&webview, name)?;
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conflict = conn.upload_conflict(crud_id, status, &error)?;
+    let resolution = resolution.or_else(|| {
+        let handler = conflicts.0.as_ref()?;
+        handler(&requested_name, &conflict)
+    });
+    if let Some(resolution) = &resolution {
+        conn.resolve_upload_conflict(&conflict, resolution)?;
+    }
+    Ok(resolution)
+}
+
+/// Copy a local-only table into a synced table in one transaction, queueing
+/// the rows for upload, and optionally empty the local-only table. Returns
+/// the number of rows copied.
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn migrate_local_table<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    from: String,
+    to: String,
+    delete_source: Option<bool>,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<usize> {
+    let name = isolation::database_name(&webview, name)?;
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.migrate_local_table(&from, &to, delete_source.unwrap_or(false))
+}
+
+/// Re-home the rows created before the user signed in: set their owner,
+/// optionally give them new ids or move them into synced tables, and
+/// rewrite the references to them, in one transaction
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn migrate_local_data<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    strategy: LocalDataStrategy,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<LocalDataMigration> {
+    let name = isolation::database_name(&webview, name)?;
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.migrate_local_data(&strategy)
+}
+
+/// Get pending upload counts and the entries that failed to upload
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn get_upload_queue_stats<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    correlation_id: Option<String>,
+) -> Result<UploadQueueStats> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.get_upload_queue_stats()
+}
+
 /// Check if there are pending CRUD entries
 #[command]
+#[cfg_attr(feature = "specta", specta::specta)]
 pub async fn has_pending_crud<R: Runtime>(
     _app: AppHandle<R>,
+    webview: Webview<R>,
     state: State<'_, PowerSyncState>,
     name: String,
+    correlation_id: Option<String>,
 ) -> Result<bool> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
     let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
     let conn = manager.get(&name)?;
     let conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
     conn.has_pending_crud()
 }
 
-/// Get the current write checkpoint
+/// Get the write checkpoint local changes are waiting on
 #[command]
+#[cfg_attr(feature = "specta", specta::specta)]
 pub async fn get_write_checkpoint<R: Runtime>(
     _app: AppHandle<R>,
+    webview: Webview<R>,
     state: State<'_, PowerSyncState>,
     name: String,
+    correlation_id: Option<String>,
 ) -> Result<Option<String>> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
     let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
     let conn = manager.get(&name)?;
     let conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
     conn.get_write_checkpoint()
 }
+
+/// Get the time the last sync completed, in milliseconds since the Unix epoch
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn get_last_synced_at<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    correlation_id: Option<String>,
+) -> Result<Option<i64>> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.get_last_synced_at()
+}
+
+/// Check whether a full sync has completed
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn has_synced<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    correlation_id: Option<String>,
+) -> Result<bool> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.has_synced()
+}
+
+/// Get the checkpoint token, last sync time and write checkpoint
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn get_sync_status<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    correlation_id: Option<String>,
+) -> Result<LocalSyncStatus> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.sync_status()
+}
+
+/// Wait until sync has applied a checkpoint past `token` (from
+/// `get_sync_status`), for at most `timeout_ms` if given, and return the new
+/// checkpoint token
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn wait_for_checkpoint<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    token: String,
+    timeout_ms: Option<i64>,
+    correlation_id: Option<String>,
+) -> Result<String> {
+    let name = isolation::database_name(&webview, name)?;
+    let timeout = timeout_ms
+        .map(|ms| {
+            u64::try_from(ms).map(Duration::from_millis).map_err(|_| {
+                Error::InvalidParameter(format!("Checkpoint timeout must not be negative: {}", ms))
+            })
+        })
+        .transpose()?;
+    // The scope is per thread, so it covers the lookup but not the wait
+    let conn = {
+        let _correlation = correlation::enter(correlation_id);
+        state.connection(&name)?
+    };
+    consistency::wait_for_checkpoint(&conn, &token, timeout).await
+}
+
+// =====================================================
+// Dev-tools Commands (outside the default permission set)
+// =====================================================
+
+/// Rows of `ps_buckets`
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn inspect_buckets<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    correlation_id: Option<String>,
+) -> Result<QueryResult> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.inspect_buckets()
+}
+
+/// Operation counts in `ps_oplog` per bucket and row type
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn inspect_oplog<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    correlation_id: Option<String>,
+) -> Result<Vec<OplogSummary>> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.inspect_oplog()
+}
+
+/// Pending uploads with their values redacted
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn inspect_crud<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    limit: Option<i64>,
+    correlation_id: Option<String>,
+) -> Result<Vec<RedactedCrudEntry>> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.inspect_crud(limit.unwrap_or(100))
+}
+
+/// The applied schema, read back from the views PowerSync created
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn inspect_schema<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    correlation_id: Option<String>,
+) -> Result<Vec<SchemaTable>> {
+    let _correlation = correlation::enter(correlation_id);
+    let name = isolation::database_name(&webview, name)?;
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.inspect_schema()
+}
+
+/// Load fixture data (an SQL script, or rows by table) in one transaction.
+/// Gated by `allow-seed`, which no permission set includes.
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn seed<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    data: SeedData,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<usize> {
+    let name = isolation::database_name(&webview, name)?;
+    if let SeedData::Script(script) = &data {
+        sql_policy::validate(script)?;
+    }
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.seed(&data)
+}
+
+/// Copy the database to a temporary file, returning a snapshot id.
+/// Gated by `allow-snapshot`, which no permission set includes.
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn snapshot<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<String> {
+    let name = isolation::database_name(&webview, name)?;
+    // Waiting for the writer keeps another window's open transaction out of the copy
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.snapshot()
+}
+
+/// Replace the database contents with a snapshot.
+/// Gated by `allow-restore`, which no permission set includes.
+#[command]
+#[cfg_attr(feature = "specta", specta::specta)]
+pub async fn restore<R: Runtime>(
+    _app: AppHandle<R>,
+    webview: Webview<R>,
+    window: Window<R>,
+    state: State<'_, PowerSyncState>,
+    name: String,
+    snapshot_id: String,
+    tx_id: Option<String>,
+    correlation_id: Option<String>,
+) -> Result<()> {
+    let name = isolation::database_name(&webview, name)?;
+    let owner = window.label();
+    let _permit =
+        queue_write_in(&state, &name, WritePriority::Normal, owner, tx_id.as_deref()).await?;
+    let _correlation = correlation::enter(correlation_id);
+    let manager = state.0.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    let conn = manager.get(&name)?;
+    let mut conn = conn.lock().map_err(|e| crate::error::Error::Lock(e.to_string()))?;
+    conn.restore(&snapshot_id)
+}
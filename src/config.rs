@@ -0,0 +1,149 @@
+//! Plugin configuration, read from `plugins.powersync-jf` in `tauri.conf.json`
+
+use powersync_sqlite::{
+    ConnectionTuning, FaultInjection, IdFormat, InvalidUtf8, NonFiniteReals, ParamCoercion,
+    RetentionRule, SqlFunctions,
+};
+use serde::Deserialize;
+
+/// ```json
+/// { "plugins": { "powersync-jf": { "regexp": true, "math": true, "helpers": true } } }
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Config {
+    /// Optional SQL functions registered on every connection
+    #[serde(flatten)]
+    pub functions: SqlFunctions,
+    /// `mmapSize`, `cacheSize` and `tempStore` pragmas applied to every connection
+    #[serde(flatten)]
+    pub tuning: ConnectionTuning,
+    /// Run `PRAGMA optimize` once this many rows have changed since the
+    /// last run, e.g. after a large import or the first sync
+    #[serde(default)]
+    pub auto_analyze_after: Option<u64>,
+    /// How results represent REAL values JSON can't (NaN, ±Infinity)
+    #[serde(default)]
+    pub non_finite_reals: NonFiniteReals,
+    /// How results represent TEXT values that aren't valid UTF-8
+    #[serde(default)]
+    pub invalid_utf8: InvalidUtf8,
+    /// Convert or reject text parameters bound to INTEGER and REAL columns
+    #[serde(default)]
+    pub param_coercion: ParamCoercion,
+    /// Format of the ids `insert_with_id` generates, unless the app sets a
+    /// generator with [`Builder::id_generator`](crate::Builder::id_generator)
+    #[serde(default)]
+    pub id_format: IdFormat,
+    /// Simulated latency and failures, for testing the frontend under
+    /// adverse conditions. Ignored in release builds.
+    #[serde(default)]
+    pub fault_injection: FaultInjection,
+    /// NTP server (`host` or `host:port`) to correct the clock behind
+    /// `now()` against at startup
+    #[serde(default)]
+    pub ntp_server: Option<String>,
+    /// Fail writes that waited this long for their turn in the write queue,
+    /// instead of waiting indefinitely
+    #[serde(default)]
+    pub lock_timeout_ms: Option<u64>,
+    /// Sync writes (`powersync_control` calls) served in a row while other
+    /// writers wait, before one of those gets a turn. `null` always serves
+    /// sync first.
+    #[serde(default = "default_sync_batch_size")]
+    pub sync_batch_size: Option<usize>,
+    /// Report writes that waited this long for their turn in the write queue
+    /// with a write-contention event. `null` reports none.
+    #[serde(default = "default_write_contention_ms")]
+    pub write_contention_ms: Option<u64>,
+    /// Move damaged database files aside and open fresh databases in their
+    /// place, instead of failing to open them
+    #[serde(default)]
+    pub recover_corrupt_databases: bool,
+    /// Emit plugin log records at or above this level as events
+    #[serde(default)]
+    pub log_events: Option<log::LevelFilter>,
+    /// Give each webview its own namespace of database names, so embedded
+    /// webviews can't open (or collide with) the app's databases
+    #[serde(default)]
+    pub isolate_webviews: bool,
+    /// Webviews sharing the app's namespace when `isolate_webviews` is on
+    #[serde(default = "default_trusted_webviews")]
+    pub trusted_webviews: Vec<String>,
+    /// Databases opened in the background during plugin setup
+    #[serde(default)]
+    pub preopen: Vec<PreopenDatabase>,
+    /// Rules pruning old rows of local-only and raw tables in the background
+    #[serde(default)]
+    pub retention: Vec<RetentionPolicy>,
+    /// Collect diagnostics for databases that never recorded a choice with
+    /// `set_diagnostics_enabled`. Off ships them disabled until users opt in.
+    #[serde(default = "default_diagnostics")]
+    pub diagnostics: bool,
+}
+
+/// A database to open at startup
+#[derive(Debug, Clone, Deserialize)]
+pub struct PreopenDatabase {
+    pub name: String,
+    /// PowerSync schema JSON to apply once opened, as `replace_schema` takes it
+    #[serde(default)]
+    pub schema: Option<serde_json::Value>,
+}
+
+/// Retention rules of one database
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionPolicy {
+    pub database: String,
+    /// How often the rules are applied while the database is open
+    #[serde(default = "default_retention_interval")]
+    pub interval_minutes: u64,
+    pub rules: Vec<RetentionRule>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            functions: SqlFunctions::default(),
+            tuning: ConnectionTuning::default(),
+            auto_analyze_after: None,
+            non_finite_reals: NonFiniteReals::default(),
+            invalid_utf8: InvalidUtf8::default(),
+            param_coercion: ParamCoercion::default(),
+            id_format: IdFormat::default(),
+            fault_injection: FaultInjection::default(),
+            ntp_server: None,
+            lock_timeout_ms: None,
+            sync_batch_size: default_sync_batch_size(),
+            write_contention_ms: default_write_contention_ms(),
+            recover_corrupt_databases: false,
+            log_events: None,
+            isolate_webviews: false,
+            trusted_webviews: default_trusted_webviews(),
+            preopen: Vec::new(),
+            retention: Vec::new(),
+            diagnostics: default_diagnostics(),
+        }
+    }
+}
+
+fn default_sync_batch_size() -> Option<usize> {
+    Some(100)
+}
+
+fn default_write_contention_ms() -> Option<u64> {
+    Some(500)
+}
+
+fn default_retention_interval() -> u64 {
+    60
+}
+
+fn default_diagnostics() -> bool {
+    true
+}
+
+fn default_trusted_webviews() -> Vec<String> {
+    vec!["main".to_string()]
+}
@@ -0,0 +1,32 @@
+//! Correlation ids on commands
+//!
+//! Commands take an optional `correlationId`, which the JS bindings send
+//! for calls made through `commands.correlated(id)`. Commands do their
+//! database work in a correlation scope, so the commit and checkpoint
+//! events it raises and the plugin log records it causes carry the id, and
+//! commands carrying an id are logged at debug level as they are invoked.
+
+use serde_json::Value as JsonValue;
+use tauri::ipc::{Invoke, InvokeBody};
+use tauri::Runtime;
+
+pub(crate) use powersync_sqlite::correlation::{current, enter};
+
+/// Wrap a command handler to log the commands invoked with a correlation id
+pub(crate) fn traced<R: Runtime>(
+    handler: impl Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static {
+    move |invoke| {
+        if let InvokeBody::Json(args) = invoke.message.payload() {
+            if let Some(id) = args.get("correlationId").and_then(JsonValue::as_str) {
+                let _correlation = enter(Some(id.to_string()));
+                log::debug!(
+                    "{} invoked by {}",
+                    invoke.message.command(),
+                    invoke.message.webview().label()
+                );
+            }
+        }
+        handler(invoke)
+    }
+}
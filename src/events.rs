@@ -0,0 +1,215 @@
+//! Events emitted to the webview
+//!
+//! Payloads carry the database name so windows can ignore databases they
+//! don't use.
+
+use crate::isolation::WebviewIsolation;
+use crate::journal::EventJournals;
+use powersync_sqlite::database::BatchProgress;
+use powersync_sqlite::{DatabaseLifecycleEvent, LifecycleKind, RetentionReport};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, EventTarget, Manager, Runtime, Webview};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// Emitted once per commit with the user tables it changed
+pub const TABLES_CHANGED_EVENT: &str = "powersync-jf://tables-changed";
+
+/// Emitted once a sync checkpoint has been fully applied to local tables
+pub const CHECKPOINT_EVENT: &str = "powersync-jf://checkpoint";
+
+/// Emitted when a checkpoint fails validation or can't be applied
+pub const CHECKPOINT_FAILED_EVENT: &str = "powersync-jf://checkpoint-failed";
+
+/// Emitted when `replace_schema` applies a changed schema
+pub const SCHEMA_CHANGED_EVENT: &str = "powersync-jf://schema-changed";
+
+/// Emitted when the app reports a failed upload with `report_upload_error`
+pub const UPLOAD_ERROR_EVENT: &str = "powersync-jf://upload-error";
+
+/// Emitted when a write has waited past `write_contention_ms` for its turn
+pub const WRITE_CONTENTION_EVENT: &str = "powersync-jf://write-contention";
+
+/// Emitted when a database is opened, by any window or by Rust code
+pub const DB_OPENED_EVENT: &str = "powersync-jf://db-opened";
+
+/// Emitted when a database is closed
+pub const DB_CLOSED_EVENT: &str = "powersync-jf://db-closed";
+
+/// Emitted when a database is opened but the PowerSync extension failed to load
+pub const EXTENSION_LOAD_FAILED_EVENT: &str = "powersync-jf://extension-load-failed";
+
+/// Emitted when a damaged database was moved aside and a fresh one opened
+pub const DB_RECOVERED_EVENT: &str = "powersync-jf://db-recovered";
+
+/// Emitted to the calling webview after each chunk of a chunked `execute_batch`
+pub const BATCH_PROGRESS_EVENT: &str = "powersync-jf://batch-progress";
+
+/// Emitted when configured retention rules deleted rows
+pub const RETENTION_APPLIED_EVENT: &str = "powersync-jf://retention-applied";
+
+/// Emitted for each plugin log record when `logEvents` is set
+pub const LOG_EVENT: &str = "powersync-jf://log";
+
+#[derive(Clone, Serialize)]
+struct Payload<T> {
+    name: String,
+    #[serde(flatten)]
+    event: T,
+}
+
+/// Payload of a database's forwarded events, numbered per database
+#[derive(Clone, Serialize)]
+struct SequencedPayload<T> {
+    name: String,
+    seq: i64,
+    #[serde(flatten)]
+    event: T,
+}
+
+/// Forward a database's broadcast events to the webview until it is closed,
+/// numbering them in the database's journal (see [`crate::journal`]).
+///
+/// Events go to the webview labelled `target` only, or with no target to
+/// every webview not isolated in its own namespace.
+pub fn forward<R, T>(
+    app: AppHandle<R>,
+    managed_name: String,
+    name: String,
+    target: Option<String>,
+    event_name: &'static str,
+    mut receiver: broadcast::Receiver<T>,
+) where
+    R: Runtime,
+    T: Clone + Serialize + Send + 'static,
+{
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    let journals = app.state::<EventJournals>();
+                    let Some(payload) =
+                        journals.record(&managed_name, event_name, |seq| SequencedPayload {
+                            name: name.clone(),
+                            seq,
+                            event,
+                        })
+                    else {
+                        continue;
+                    };
+                    let emitted = match &target {
+                        Some(label) => app.emit_to(label.as_str(), event_name, payload),
+                        None => {
+                            let isolation = app.state::<WebviewIsolation>();
+                            app.emit_filter(event_name, payload, |target| {
+                                !is_isolated_target(&isolation, target)
+                            })
+                        }
+                    };
+                    if let Err(e) = emitted {
+                        log::warn!("Failed to emit {} for {}: {}", event_name, name, e);
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    log::warn!("Dropped {} {} event(s) for {}", skipped, event_name, name);
+                }
+                // The connection was closed
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+/// Report a chunked batch's progress to the webview that started it
+pub fn emit_batch_progress<R: Runtime>(webview: &Webview<R>, name: &str, progress: BatchProgress) {
+    let payload = Payload {
+        name: name.to_string(),
+        event: progress,
+    };
+    if let Err(e) = webview.emit_to(webview.label(), BATCH_PROGRESS_EVENT, payload) {
+        log::warn!("Failed to emit {} for {}: {}", BATCH_PROGRESS_EVENT, name, e);
+    }
+}
+
+#[derive(Clone, Serialize)]
+struct RetentionPayload {
+    name: String,
+    pruned: Vec<RetentionReport>,
+}
+
+/// Report the rows a background run of retention rules deleted to the
+/// webviews sharing the app's namespace
+pub fn emit_retention_applied<R: Runtime>(
+    app: &AppHandle<R>,
+    name: &str,
+    pruned: Vec<RetentionReport>,
+) {
+    let payload = RetentionPayload {
+        name: name.to_string(),
+        pruned,
+    };
+    let isolation = app.state::<WebviewIsolation>();
+    let emitted = app.emit_filter(RETENTION_APPLIED_EVENT, payload, |target| {
+        !is_isolated_target(&isolation, target)
+    });
+    if let Err(e) = emitted {
+        log::warn!("Failed to emit {} for {}: {}", RETENTION_APPLIED_EVENT, name, e);
+    }
+}
+
+/// Forward database open/close events to the webviews for the app's lifetime.
+///
+/// Databases in an isolated webview's namespace are announced to that
+/// webview only, under the name it opened them with.
+pub fn forward_lifecycle<R: Runtime>(
+    app: AppHandle<R>,
+    mut receiver: broadcast::Receiver<DatabaseLifecycleEvent>,
+) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let mut event = match receiver.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(skipped)) => {
+                    log::warn!("Dropped {} database lifecycle event(s)", skipped);
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            let mut event_names = vec![match event.kind {
+                LifecycleKind::Opened => DB_OPENED_EVENT,
+                LifecycleKind::Closed => DB_CLOSED_EVENT,
+            }];
+            if event.powersync_load_error.is_some() {
+                event_names.push(EXTENSION_LOAD_FAILED_EVENT);
+            }
+            if event.recovery.is_some() {
+                event_names.push(DB_RECOVERED_EVENT);
+            }
+            let isolation = app.state::<WebviewIsolation>();
+            let owner = isolation.owner(&event.name);
+            if let Some((_, name)) = &owner {
+                event.name = name.clone();
+            }
+            for event_name in event_names {
+                let emitted = match &owner {
+                    Some((label, _)) => app.emit_to(label.as_str(), event_name, event.clone()),
+                    None => app.emit_filter(event_name, event.clone(), |target| {
+                        !is_isolated_target(&isolation, target)
+                    }),
+                };
+                if let Err(e) = emitted {
+                    log::warn!("Failed to emit {}: {}", event_name, e);
+                }
+            }
+        }
+    });
+}
+
+pub(crate) fn is_isolated_target(isolation: &WebviewIsolation, target: &EventTarget) -> bool {
+    match target {
+        EventTarget::AnyLabel { label }
+        | EventTarget::Window { label }
+        | EventTarget::Webview { label }
+        | EventTarget::WebviewWindow { label } => isolation.is_isolated(label),
+        _ => false,
+    }
+}
@@ -0,0 +1,122 @@
+//! Per-webview database namespaces
+//!
+//! With `isolateWebviews` on, database names a webview passes to commands
+//! resolve inside its own namespace, `webviews/<label>/<name>`, unless the
+//! webview is listed in `trustedWebviews`. An embedded page opening "app"
+//! then gets its own file rather than the app's database.
+
+use crate::config::Config;
+use crate::error::{Error, Result};
+use std::path::{Component, Path};
+use tauri::{Manager, Runtime, Webview};
+
+/// Directory under the app data directory holding webview namespaces
+const WEBVIEWS_DIR: &str = "webviews";
+
+/// Which webviews get their own namespace
+#[derive(Debug, Clone, Default)]
+pub struct WebviewIsolation {
+    enabled: bool,
+    trusted: Vec<String>,
+}
+
+impl WebviewIsolation {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            enabled: config.isolate_webviews,
+            trusted: config.trusted_webviews.clone(),
+        }
+    }
+
+    /// Whether databases of the webview `label` are namespaced
+    pub fn is_isolated(&self, label: &str) -> bool {
+        self.enabled && !self.trusted.iter().any(|trusted| trusted == label)
+    }
+
+    /// Namespace of an isolated webview, relative to the app data directory
+    pub fn namespace(&self, label: &str) -> Option<String> {
+        if !self.is_isolated(label) {
+            return None;
+        }
+        // Labels may contain '/' and ':', neither of which is safe in a file name
+        let label = label.replace('/', "%2F").replace(':', "%3A");
+        Some(format!("{}/{}", WEBVIEWS_DIR, label))
+    }
+
+    /// The isolated webview whose namespace holds the database `name`, and
+    /// the name that webview knows it by
+    pub fn owner(&self, name: &str) -> Option<(String, String)> {
+        let rest = name.strip_prefix(WEBVIEWS_DIR)?.strip_prefix('/')?;
+        let (label, name) = rest.split_once('/')?;
+        let label = label.replace("%2F", "/").replace("%3A", ":");
+        self.is_isolated(&label).then(|| (label, name.to_string()))
+    }
+
+    /// Resolve a database name passed by the webview `label`
+    pub fn database_name(&self, label: &str, name: &str) -> Result<String> {
+        let Some(namespace) = self.namespace(label) else {
+            return Ok(name.to_string());
+        };
+        // Names become file paths, so they must stay inside the namespace
+        let escapes = Path::new(name)
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_)));
+        if name.is_empty() || escapes {
+            return Err(Error::InvalidParameter(format!(
+                "invalid database name: {:?}",
+                name
+            )));
+        }
+        Ok(format!("{}/{}", namespace, name))
+    }
+}
+
+/// Resolve a database name passed by `webview` to the name it is managed under
+pub fn database_name<R: Runtime>(webview: &Webview<R>, name: String) -> Result<String> {
+    let isolation = webview.state::<WebviewIsolation>();
+    if !isolation.is_isolated(webview.label()) {
+        return Ok(name);
+    }
+    isolation.database_name(webview.label(), &name)
+}
+
+/// Namespace of `webview`, relative to the app data directory, if it is isolated
+pub fn namespace<R: Runtime>(webview: &Webview<R>) -> Option<String> {
+    webview.state::<WebviewIsolation>().namespace(webview.label())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_isolated_names() {
+        let config = Config {
+            isolate_webviews: true,
+            ..Config::default()
+        };
+        let isolation = WebviewIsolation::new(&config);
+
+        assert_eq!(isolation.database_name("main", "app").unwrap(), "app");
+        assert_eq!(
+            isolation.database_name("embed", "app").unwrap(),
+            "webviews/embed/app"
+        );
+        assert_eq!(
+            isolation.database_name("a/b:c", "app").unwrap(),
+            "webviews/a%2Fb%3Ac/app"
+        );
+        for name in ["", "../app", "/tmp/app", "nested/../../app"] {
+            assert!(isolation.database_name("embed", name).is_err(), "{}", name);
+        }
+
+        assert_eq!(
+            isolation.owner("webviews/a%2Fb%3Ac/app"),
+            Some(("a/b:c".to_string(), "app".to_string()))
+        );
+        assert_eq!(isolation.owner("app"), None);
+
+        let disabled = WebviewIsolation::new(&Config::default());
+        assert_eq!(disabled.database_name("embed", "app").unwrap(), "app");
+    }
+}
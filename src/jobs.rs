@@ -0,0 +1,127 @@
+//! Periodic background jobs bound to a database
+//!
+//! Each job runs on its own thread and locks the named connection for the
+//! duration of a tick, so it is serialized with commands against the same
+//! database. Ticks wait in the write queue at background priority. Jobs stop
+//! when cancelled or when the app exits.
+
+use crate::database::PowerSyncConnection;
+use crate::error::{Error, Result};
+use crate::queue::WritePriority;
+use crate::PowerSyncState;
+use std::collections::HashMap;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, Runtime};
+
+/// A job body, run with exclusive access to the connection
+pub type JobFn = dyn Fn(&mut PowerSyncConnection) -> Result<()> + Send + 'static;
+
+struct JobHandle {
+    stop: mpsc::Sender<()>,
+    thread: JoinHandle<()>,
+}
+
+impl JobHandle {
+    fn stop(self) {
+        // Either a send or a dropped sender wakes the worker; ignore both errors
+        let _ = self.stop.send(());
+        let _ = self.thread.join();
+    }
+}
+
+/// Scheduler for periodic jobs, managed as plugin state
+#[derive(Default)]
+pub struct JobScheduler {
+    jobs: Mutex<HashMap<String, JobHandle>>,
+}
+
+impl JobScheduler {
+    /// Register a job that runs every `interval` against database `db`.
+    ///
+    /// Ticks where the database isn't open are skipped. Registering a job
+    /// under an existing name replaces the previous job.
+    pub fn register<R, F>(
+        &self,
+        app: &AppHandle<R>,
+        name: &str,
+        db: &str,
+        interval: Duration,
+        job: F,
+    ) -> Result<()>
+    where
+        R: Runtime,
+        F: Fn(&mut PowerSyncConnection) -> Result<()> + Send + 'static,
+    {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let app = app.clone();
+        let job_name = name.to_string();
+        let db = db.to_string();
+
+        let thread = std::thread::Builder::new()
+            .name(format!("powersync-job-{}", job_name))
+            .spawn(move || loop {
+                match stopped.recv_timeout(interval) {
+                    Err(RecvTimeoutError::Timeout) => {
+                        if let Err(e) = run_job(&app, &db, &job) {
+                            log::warn!("PowerSync job '{}' failed: {}", job_name, e);
+                        }
+                    }
+                    // Explicit stop or scheduler dropped
+                    Ok(()) | Err(RecvTimeoutError::Disconnected) => break,
+                }
+            })?;
+
+        let previous = self
+            .jobs
+            .lock()
+            .map_err(|e| Error::Lock(e.to_string()))?
+            .insert(name.to_string(), JobHandle { stop, thread });
+        if let Some(previous) = previous {
+            previous.stop();
+        }
+        Ok(())
+    }
+
+    /// Cancel a job by name, waiting for any in-flight tick to finish.
+    /// Returns false if no job was registered under that name.
+    pub fn cancel(&self, name: &str) -> Result<bool> {
+        let handle = self
+            .jobs
+            .lock()
+            .map_err(|e| Error::Lock(e.to_string()))?
+            .remove(name);
+        Ok(handle.map(JobHandle::stop).is_some())
+    }
+
+    /// Stop all jobs
+    pub fn shutdown(&self) {
+        let jobs = match self.jobs.lock() {
+            Ok(mut jobs) => std::mem::take(&mut *jobs),
+            Err(_) => return,
+        };
+        for (_, handle) in jobs {
+            handle.stop();
+        }
+    }
+}
+
+fn run_job<R: Runtime>(app: &AppHandle<R>, db: &str, job: &JobFn) -> Result<()> {
+    let state = app.state::<PowerSyncState>();
+    let conn = match state.connection(db) {
+        Ok(conn) => conn,
+        Err(Error::DatabaseNotFound(_)) => {
+            log::debug!("Skipping job tick, database '{}' is not open", db);
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    };
+    // Jobs yield to sync and user writes waiting in the queue
+    let queue = state.write_queue(db)?;
+    let _permit =
+        tauri::async_runtime::block_on(queue.acquire(WritePriority::Background, None));
+    let mut conn = conn.lock().map_err(|e| Error::Lock(e.to_string()))?;
+    job(&mut conn)
+}
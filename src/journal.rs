@@ -0,0 +1,135 @@
+//! Per-database journal of recently emitted events
+//!
+//! Every event forwarded for a database carries a `seq` that increases per
+//! database. A frontend that stopped listening for a while (hidden window,
+//! reload) asks for everything after the last `seq` it saw with
+//! `get_events_since` and replays it, instead of refetching all its state.
+//! If the journal no longer reaches back that far, the answer says so and
+//! the frontend refetches after all.
+
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+/// Events kept per database
+const CAPACITY: usize = 1024;
+
+/// An event as it was emitted
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct JournalEntry {
+    pub seq: i64,
+    /// Event name, e.g. `powersync-jf://tables-changed`
+    pub event: String,
+    /// Payload as emitted, including `name` and `seq`
+    pub payload: JsonValue,
+}
+
+/// Answer to `get_events_since`
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "camelCase")]
+pub struct EventsSince {
+    /// Events after the requested `seq`, oldest first
+    pub events: Vec<JournalEntry>,
+    /// `seq` of the latest event emitted for the database, 0 if none
+    pub latest_seq: i64,
+    /// False if some events after the requested `seq` are no longer kept,
+    /// or the `seq` is from before the app started; refetch state then
+    pub complete: bool,
+}
+
+#[derive(Default)]
+struct Journal {
+    latest_seq: i64,
+    entries: VecDeque<JournalEntry>,
+}
+
+/// Journals of all databases, by managed database name. They outlive
+/// closing a database, so sequence numbers keep increasing if it reopens.
+#[derive(Default)]
+pub struct EventJournals(Mutex<HashMap<String, Journal>>);
+
+impl EventJournals {
+    /// Assign the next `seq` of database `db`, and record the event with the
+    /// payload `payload` builds from it
+    pub(crate) fn record<T: Serialize>(
+        &self,
+        db: &str,
+        event: &str,
+        payload: impl FnOnce(i64) -> T,
+    ) -> Option<T> {
+        let mut journals = self.0.lock().ok()?;
+        let journal = journals.entry(db.to_string()).or_default();
+        journal.latest_seq += 1;
+        let seq = journal.latest_seq;
+        let payload = payload(seq);
+        if journal.entries.len() == CAPACITY {
+            journal.entries.pop_front();
+        }
+        journal.entries.push_back(JournalEntry {
+            seq,
+            event: event.to_string(),
+            payload: serde_json::to_value(&payload).unwrap_or_default(),
+        });
+        Some(payload)
+    }
+
+    /// Events of database `db` after `seq`
+    pub fn since(&self, db: &str, seq: i64) -> EventsSince {
+        let journals = self.0.lock().ok();
+        let Some(journal) = journals.as_ref().and_then(|journals| journals.get(db)) else {
+            return EventsSince {
+                events: Vec::new(),
+                latest_seq: 0,
+                complete: seq == 0,
+            };
+        };
+        let oldest = journal
+            .entries
+            .front()
+            .map_or(journal.latest_seq + 1, |e| e.seq);
+        EventsSince {
+            events: journal
+                .entries
+                .iter()
+                .filter(|e| e.seq > seq)
+                .cloned()
+                .collect(),
+            latest_seq: journal.latest_seq,
+            complete: seq <= journal.latest_seq && seq + 1 >= oldest,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_since() {
+        let journals = EventJournals::default();
+        for _ in 0..CAPACITY + 10 {
+            journals.record("app", "tables-changed", |seq| seq);
+        }
+        journals.record("other", "checkpoint", |seq| seq);
+
+        let latest = (CAPACITY + 10) as i64;
+        let recent = journals.since("app", latest - 2);
+        assert_eq!(recent.latest_seq, latest);
+        assert!(recent.complete);
+        let seqs: Vec<_> = recent.events.iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, vec![latest - 1, latest]);
+        assert_eq!(recent.events[1].payload, latest);
+
+        assert!(journals.since("app", latest).complete);
+        assert!(journals.since("app", 10).complete);
+        assert!(!journals.since("app", 9).complete);
+        // A seq from before a restart
+        assert!(!journals.since("app", latest + 5).complete);
+        assert_eq!(journals.since("other", 0).events.len(), 1);
+        assert!(journals.since("unknown", 0).complete);
+    }
+}
@@ -1,58 +1,499 @@
-use std::sync::Mutex;
+use serde::de::DeserializeOwned;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
 use tauri::{
-    plugin::{Builder, TauriPlugin},
-    Manager, Runtime,
+    plugin::{Builder as PluginBuilder, TauriPlugin},
+    webview::PageLoadEvent,
+    Manager, RunEvent, Runtime, WindowEvent,
 };
 
+/// Every plugin command, listed once for both the invoke handler and the
+/// generated bindings. Expands to `$collect!(name, ...)` with the bare names
+/// of the functions in `commands`.
+macro_rules! plugin_commands {
+    ($collect:ident) => {
+        $collect!(
+            // Database operations
+            open,
+            close,
+            open_profile,
+            list_profiles,
+            delete_profile,
+            recover_database,
+            execute,
+            execute_raw,
+            execute_batch,
+            insert_many,
+            insert_with_id,
+            now,
+            execute_transaction,
+            with_transaction,
+            execute_across,
+            pipeline,
+            prepare,
+            bind_and_step,
+            finalize,
+            patch_json,
+            enable_soft_delete,
+            soft_delete_row,
+            restore_row,
+            purge_deleted,
+            create_view,
+            drop_view,
+            list_views,
+            enable_undo,
+            undo,
+            redo,
+            get_undo_state,
+            apply_retention,
+            enable_change_feed,
+            read_change_feed,
+            ack_change_feed,
+            get_all,
+            get_all_snapshot,
+            get_optional,
+            query_to_file,
+            export_encrypted,
+            import_encrypted,
+            begin_transaction,
+            commit_transaction,
+            rollback_transaction,
+            create_savepoint,
+            release_savepoint,
+            rollback_to_savepoint,
+            get_write_queue_stats,
+            get_lock_state,
+            set_lock_timeout,
+            analyze,
+            get_connection_stats,
+            get_events_since,
+            create_support_bundle,
+            get_diagnostics_enabled,
+            set_diagnostics_enabled,
+            // PowerSync extension operations
+            get_powersync_version,
+            get_powersync_status,
+            get_client_id,
+            is_powersync_loaded,
+            replace_schema,
+            refresh_schema,
+            lint_sync_rules,
+            powersync_control,
+            get_crud_batch,
+            remove_crud,
+            report_upload_error,
+            resolve_upload_conflict,
+            migrate_local_table,
+            migrate_local_data,
+            get_upload_queue_stats,
+            has_pending_crud,
+            get_write_checkpoint,
+            get_last_synced_at,
+            has_synced,
+            get_sync_status,
+            wait_for_checkpoint,
+            // Dev-only commands, outside the default permission set
+            inspect_buckets,
+            inspect_oplog,
+            inspect_crud,
+            inspect_schema,
+            seed,
+            snapshot,
+            restore,
+        )
+    };
+}
+
+#[cfg(feature = "specta")]
+pub mod bindings;
 mod commands;
-mod database;
-mod error;
-mod extension;
+mod config;
+mod correlation;
+mod events;
+mod isolation;
+mod jobs;
+mod journal;
+mod log_bridge;
+#[cfg(mobile)]
+mod mobile;
+mod preopen;
+mod retention;
+mod sql_policy;
+mod support;
 
-use database::DatabaseManager;
+// Database logic lives in the Tauri-independent powersync-sqlite crate
+use powersync_sqlite::rusqlite;
+use powersync_sqlite::{
+    bundle, clock, consistency, control, coordinator, database, devtools, error, export, profiles,
+    queue, reconcile, schema, sync_rules, tuning, undo, uploads, views,
+};
+
+use database::ConnectionInitializer;
+
+#[cfg(feature = "unicode-collation")]
+pub use powersync_sqlite::collation;
+pub use config::Config;
+pub use events::{
+    BATCH_PROGRESS_EVENT, CHECKPOINT_EVENT, CHECKPOINT_FAILED_EVENT, DB_CLOSED_EVENT,
+    DB_OPENED_EVENT, DB_RECOVERED_EVENT, EXTENSION_LOAD_FAILED_EVENT, LOG_EVENT,
+    RETENTION_APPLIED_EVENT, SCHEMA_CHANGED_EVENT, TABLES_CHANGED_EVENT, UPLOAD_ERROR_EVENT,
+    WRITE_CONTENTION_EVENT,
+};
+pub use jobs::JobScheduler;
+pub use journal::{EventsSince, JournalEntry};
+pub use log_bridge::{forward_log, LogRecord};
+pub use powersync_sqlite::{
+    AbortIf, ChangeEvent, ChangeFeedEntry, ChangeOperation, CheckpointEvent, CheckpointFailure,
+    CheckpointFailureKind, Clock, CommitEvent, ConflictResolution, ConnectionEvent, ConnectionInfo,
+    ConnectionStats, ConnectionTuning, ControlOp, CrossDatabaseResult, DatabaseLifecycleEvent,
+    DatabaseManager, DatabaseRecovery, EncryptedExport, Error, FaultInjection, GuardedTransaction,
+    IdChange, IdFormat, IdGenerator, LifecycleKind, LocalDataMigration, LocalDataStrategy,
+    LocalSyncStatus, LocalTableStrategy, LockParticipant, LockState, LockTimeout, NonFiniteReals,
+    OpenResult, PowerSyncConnection, PowerSyncStatus, Result, RetentionReport, RetentionRule,
+    RetryEntry, SchemaChange, SqlFunctions, SqlParam, Statement, SyncRulesIssue, Synchronous,
+    TableOpCounts, TempStore, Timestamp, TransactionGuard, TuningPreset, UndoState, UndoStep,
+    UploadConflict, UploadConflictHandler, UploadError, UploadQueueStats, ViewInfo,
+    WriteContention, WritePermit, WritePriority, WriteQueue, WriteQueueStats,
+};
+
+/// How long to wait for the NTP server configured with `ntpServer`
+const NTP_TIMEOUT: Duration = Duration::from_secs(5);
 
 /// Plugin state wrapper
 pub struct PowerSyncState(pub Mutex<DatabaseManager>);
 
-/// Initialize the PowerSync plugin
+impl PowerSyncState {
+    /// Get a handle to an open database connection
+    pub fn connection(&self, db: &str) -> Result<Arc<Mutex<PowerSyncConnection>>> {
+        let manager = self.0.lock().map_err(|e| Error::Lock(e.to_string()))?;
+        manager.get(db)
+    }
+
+    /// Path and extension state of a database, if it is open
+    pub fn connection_info(&self, db: &str) -> Result<Option<ConnectionInfo>> {
+        let manager = self.0.lock().map_err(|e| Error::Lock(e.to_string()))?;
+        manager.info(db)
+    }
+
+    /// Subscribe to commits on an open database.
+    ///
+    /// Fires once per commit with the set of user tables it changed, however
+    /// many rows that was, so watchers can be invalidated in one pass.
+    pub fn subscribe_commits(&self, db: &str) -> Result<broadcast::Receiver<CommitEvent>> {
+        let conn = self.connection(db)?;
+        let conn = conn.lock().map_err(|e| Error::Lock(e.to_string()))?;
+        Ok(conn.subscribe_commits())
+    }
+
+    /// Subscribe to sync checkpoints on an open database.
+    ///
+    /// Fires once per checkpoint after it is fully applied locally, with the
+    /// tables it changed, so caches can be refreshed in bulk rather than per row.
+    pub fn subscribe_checkpoints(
+        &self,
+        db: &str,
+    ) -> Result<broadcast::Receiver<CheckpointEvent>> {
+        let conn = self.connection(db)?;
+        let conn = conn.lock().map_err(|e| Error::Lock(e.to_string()))?;
+        Ok(conn.subscribe_checkpoints())
+    }
+
+    /// Subscribe to checkpoints that failed validation or couldn't be applied
+    pub fn subscribe_checkpoint_failures(
+        &self,
+        db: &str,
+    ) -> Result<broadcast::Receiver<CheckpointFailure>> {
+        let conn = self.connection(db)?;
+        let conn = conn.lock().map_err(|e| Error::Lock(e.to_string()))?;
+        Ok(conn.subscribe_checkpoint_failures())
+    }
+
+    /// Subscribe to schema changes, with the views each one redefined, e.g.
+    /// to rebuild caches of prepared queries
+    pub fn subscribe_schema_changes(&self, db: &str) -> Result<broadcast::Receiver<SchemaChange>> {
+        let conn = self.connection(db)?;
+        let conn = conn.lock().map_err(|e| Error::Lock(e.to_string()))?;
+        Ok(conn.subscribe_schema_changes())
+    }
+
+    /// Subscribe to failed uploads reported by the app, e.g. to alert on
+    /// entries that keep failing
+    pub fn subscribe_upload_errors(&self, db: &str) -> Result<broadcast::Receiver<UploadError>> {
+        let conn = self.connection(db)?;
+        let conn = conn.lock().map_err(|e| Error::Lock(e.to_string()))?;
+        Ok(conn.subscribe_upload_errors())
+    }
+
+    /// Get the write queue of an open database.
+    ///
+    /// Rust code writing alongside the webview can take a turn with
+    /// `queue.acquire(priority, owner).await` before locking the connection.
+    pub fn write_queue(&self, db: &str) -> Result<Arc<WriteQueue>> {
+        let manager = self.0.lock().map_err(|e| Error::Lock(e.to_string()))?;
+        manager.write_queue(db)
+    }
+
+    /// Current length and holder of a database's write queue
+    pub fn write_queue_stats(&self, db: &str) -> Result<WriteQueueStats> {
+        Ok(self.write_queue(db)?.stats())
+    }
+
+    /// Holder and waiters of a database's write queue
+    pub fn lock_state(&self, db: &str) -> Result<LockState> {
+        Ok(self.write_queue(db)?.lock_state())
+    }
+
+    /// Subscribe to databases being opened and closed.
+    ///
+    /// Lets Rust code (a sync engine, an indexer) attach to databases the
+    /// webview opened, and let go of them when they are closed.
+    pub fn subscribe_lifecycle(&self) -> Result<broadcast::Receiver<DatabaseLifecycleEvent>> {
+        let manager = self.0.lock().map_err(|e| Error::Lock(e.to_string()))?;
+        Ok(manager.subscribe_lifecycle())
+    }
+
+    /// Subscribe to committed row changes on an open database.
+    ///
+    /// Intended for other Rust plugins/services (e.g. a search indexer) that
+    /// need to react to data changes without going through the webview.
+    /// Pass an empty `tables` slice to receive changes for every table.
+    pub fn subscribe_changes(
+        &self,
+        db: &str,
+        tables: &[&str],
+    ) -> Result<broadcast::Receiver<ChangeEvent>> {
+        let conn = self.connection(db)?;
+        let conn = conn.lock().map_err(|e| Error::Lock(e.to_string()))?;
+        Ok(conn.subscribe_changes(tables))
+    }
+
+    /// Roll back transactions a window left open on any database, and
+    /// finalize the statements it prepared
+    fn rollback_window_transactions(&self, label: &str) {
+        match self.0.lock() {
+            Ok(manager) => manager.rollback_owned_transactions(label),
+            Err(e) => log::warn!("Failed to roll back transactions of {}: {}", label, e),
+        }
+    }
+
+    /// Query an open database and map each row into `T` by column name
+    pub fn query_as<T: DeserializeOwned>(
+        &self,
+        db: &str,
+        sql: &str,
+        params: &[SqlParam],
+    ) -> Result<Vec<T>> {
+        let conn = self.connection(db)?;
+        let conn = conn.lock().map_err(|e| Error::Lock(e.to_string()))?;
+        conn.query_as(sql, params)
+    }
+
+    /// Query an open database and map the first row, if any, into `T`
+    pub fn query_optional_as<T: DeserializeOwned>(
+        &self,
+        db: &str,
+        sql: &str,
+        params: &[SqlParam],
+    ) -> Result<Option<T>> {
+        let conn = self.connection(db)?;
+        let conn = conn.lock().map_err(|e| Error::Lock(e.to_string()))?;
+        conn.query_optional_as(sql, params)
+    }
+}
+
+/// Rust handler for upload conflicts the frontend leaves unresolved
+pub(crate) struct UploadConflicts(pub Option<UploadConflictHandler>);
+
+/// Builder for configuring the PowerSync plugin
+#[derive(Default)]
+pub struct Builder {
+    initializer: Option<ConnectionInitializer>,
+    upload_conflict_handler: Option<UploadConflictHandler>,
+    id_generator: Option<IdGenerator>,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a callback invoked with each newly opened connection.
+    ///
+    /// Runs after the PowerSync extension is initialized and before the
+    /// connection is handed to the manager, so apps can consistently set
+    /// custom pragmas, register collations or attach auxiliary databases.
+    /// An error aborts the open.
+    pub fn on_connection_open<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&rusqlite::Connection, &str) -> rusqlite::Result<()> + Send + Sync + 'static,
+    {
+        self.initializer = Some(Arc::new(f));
+        self
+    }
+
+    /// Register a callback settling CRUD transactions the backend rejected.
+    ///
+    /// Called by `resolve_upload_conflict` when the frontend reports a
+    /// conflict without choosing a resolution itself. Returning `None`
+    /// leaves the transaction queued.
+    pub fn on_upload_conflict<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str, &UploadConflict) -> Option<ConflictResolution> + Send + Sync + 'static,
+    {
+        self.upload_conflict_handler = Some(Arc::new(f));
+        self
+    }
+
+    /// Register a callback generating the ids `insert_with_id` fills in,
+    /// given the table, in place of the configured `idFormat`
+    pub fn id_generator<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        self.id_generator = Some(IdGenerator::custom(f));
+        self
+    }
+
+    /// Build the plugin
+    pub fn build<R: Runtime>(self) -> TauriPlugin<R> {
+        let initializer = self.initializer;
+        let upload_conflict_handler = self.upload_conflict_handler;
+        let id_generator = self.id_generator;
+
+        PluginBuilder::<R, Option<Config>>::new("powersync-jf")
+            .setup(move |app, api| {
+                let config = api.config().clone().unwrap_or_default();
+                app.manage(isolation::WebviewIsolation::new(&config));
+                if let Some(level) = config.log_events {
+                    log_bridge::install(app.clone(), level);
+                }
+                if let Some(server) = config.ntp_server.clone() {
+                    sync_clock(server);
+                }
+                let initializer = with_config(&config, initializer);
+                let faults = if cfg!(debug_assertions) {
+                    config.fault_injection
+                } else {
+                    if config.fault_injection.is_enabled() {
+                        log::warn!("faultInjection is ignored in release builds");
+                    }
+                    FaultInjection::default()
+                };
+
+                // Register the native iOS/Android halves of the plugin
+                #[cfg(mobile)]
+                mobile::register(&api)?;
+
+                // Get app data directory for storing databases
+                let app_data_dir = app
+                    .path()
+                    .app_data_dir()
+                    .expect("Failed to get app data directory");
+
+                // Get resource directory for PowerSync extension
+                let resource_dir = app.path().resource_dir().ok();
+
+                // Initialize database manager with resource directory for extension loading
+                let manager = DatabaseManager::new(app_data_dir, resource_dir)
+                    .with_initializer(initializer)
+                    .with_auto_analyze(config.auto_analyze_after)
+                    .with_non_finite_reals(config.non_finite_reals)
+                    .with_invalid_utf8(config.invalid_utf8)
+                    .with_param_coercion(config.param_coercion)
+                    .with_id_generator(id_generator.unwrap_or_else(|| config.id_format.into()))
+                    .with_fault_injection(faults)
+                    .with_lock_timeout(config.lock_timeout_ms.map(Duration::from_millis))
+                    .with_sync_batch(config.sync_batch_size)
+                    .with_contention_threshold(
+                        config.write_contention_ms.map(Duration::from_millis),
+                    )
+                    .with_corruption_recovery(config.recover_corrupt_databases)
+                    .with_diagnostics(config.diagnostics);
+                events::forward_lifecycle(app.clone(), manager.subscribe_lifecycle());
+                app.manage(journal::EventJournals::default());
+                app.manage(PowerSyncState(Mutex::new(manager)));
+                app.manage(JobScheduler::default());
+                app.manage(UploadConflicts(upload_conflict_handler));
+                preopen::spawn(app, config.preopen);
+                retention::schedule(app, config.retention);
+
+                Ok(())
+            })
+            .on_page_load(|webview, payload| {
+                // A reloaded page can never finish the transactions it started
+                if matches!(payload.event(), PageLoadEvent::Started) {
+                    let label = webview.window().label().to_string();
+                    webview
+                        .state::<PowerSyncState>()
+                        .rollback_window_transactions(&label);
+                }
+            })
+            .on_event(|app, event| match event {
+                // Neither can a closed window
+                RunEvent::WindowEvent {
+                    label,
+                    event: WindowEvent::Destroyed,
+                    ..
+                } => {
+                    app.state::<PowerSyncState>()
+                        .rollback_window_transactions(label);
+                }
+                // Background jobs must not outlive the app
+                RunEvent::Exit => app.state::<JobScheduler>().shutdown(),
+                _ => {}
+            })
+            .invoke_handler(correlation::traced(invoke_handler()))
+            .build()
+    }
+}
+
+/// The plugin's invoke handler, taken from the bindings builder when they are
+/// generated so the two can't register different commands
+#[cfg(feature = "specta")]
+fn invoke_handler<R: Runtime>() -> impl Fn(tauri::ipc::Invoke<R>) -> bool + Send + Sync + 'static {
+    bindings::builder::<R>().invoke_handler()
+}
+
+#[cfg(not(feature = "specta"))]
+fn invoke_handler<R: Runtime>() -> impl Fn(tauri::ipc::Invoke<R>) -> bool + Send + Sync + 'static {
+    macro_rules! handler {
+        ($($command:ident),* $(,)?) => {
+            tauri::generate_handler![$(commands::$command),*]
+        };
+    }
+    plugin_commands!(handler)
+}
+
+/// Apply the configured tuning and SQL functions before running the app's
+/// initializer, so the app can still override either
+fn with_config(
+    config: &Config,
+    initializer: Option<ConnectionInitializer>,
+) -> Option<ConnectionInitializer> {
+    let functions = config.functions.clone();
+    let tuning = config.tuning.clone();
+    Some(Arc::new(move |conn: &rusqlite::Connection, name: &str| {
+        tuning.apply(conn)?;
+        functions.register(conn)?;
+        match &initializer {
+            Some(initializer) => initializer(conn, name),
+            None => Ok(()),
+        }
+    }))
+}
+
+/// Correct the clock behind `now()` against an NTP server, in the background
+fn sync_clock(server: String) {
+    tauri::async_runtime::spawn_blocking(move || {
+        match Clock::global().sync_ntp(&server, NTP_TIMEOUT) {
+            Ok(offset) => log::info!("Corrected the clock by {}ms against {}", offset, server),
+            Err(e) => log::warn!("Failed to sync the clock with {}: {}", server, e),
+        }
+    });
+}
+
+/// Initialize the PowerSync plugin with default configuration
 pub fn init<R: Runtime>() -> TauriPlugin<R> {
-    Builder::new("powersync-jf")
-        .setup(|app, _api| {
-            // Get app data directory for storing databases
-            let app_data_dir = app
-                .path()
-                .app_data_dir()
-                .expect("Failed to get app data directory");
-
-            // Get resource directory for PowerSync extension
-            let resource_dir = app.path().resource_dir().ok();
-
-            // Initialize database manager with resource directory for extension loading
-            let manager = DatabaseManager::new(app_data_dir, resource_dir);
-            app.manage(PowerSyncState(Mutex::new(manager)));
-
-            Ok(())
-        })
-        .invoke_handler(tauri::generate_handler![
-            // Database operations
-            commands::open,
-            commands::close,
-            commands::execute,
-            commands::execute_batch,
-            commands::get_all,
-            commands::get_optional,
-            commands::begin_transaction,
-            commands::commit_transaction,
-            commands::rollback_transaction,
-            // PowerSync extension operations
-            commands::get_powersync_version,
-            commands::is_powersync_loaded,
-            commands::replace_schema,
-            commands::powersync_control,
-            commands::get_crud_batch,
-            commands::remove_crud,
-            commands::has_pending_crud,
-            commands::get_write_checkpoint,
-        ])
-        .build()
+    Builder::new().build()
 }
@@ -0,0 +1,134 @@
+//! Forwarding of the plugin's log records to the webviews
+//!
+//! With `logEvents` set, records from this plugin and `powersync-sqlite` at
+//! or above that level are emitted as `powersync-jf://log`, so frontend dev
+//! tools can show them without a native debugger attached.
+//!
+//! The latest forwarded records are also kept for support bundles (see
+//! [`crate::support`]).
+//!
+//! A process has a single `log` logger. If the app installs its own (e.g.
+//! `tauri-plugin-log`), it can pass records on with [`forward_log`].
+
+use crate::correlation;
+use crate::events::{self, LOG_EVENT};
+use crate::isolation::WebviewIsolation;
+use log::{LevelFilter, Log, Metadata, Record};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Mutex, OnceLock};
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use tokio::sync::mpsc::{self, UnboundedSender};
+
+/// Module path prefixes of the records forwarded
+const TARGETS: [&str; 2] = ["tauri_plugin_powersync_jf", "powersync_sqlite"];
+
+/// Forwarded records kept for support bundles
+const RECENT_CAPACITY: usize = 500;
+
+/// Payload of [`LOG_EVENT`]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogRecord {
+    /// `error`, `warn`, `info`, `debug` or `trace`
+    pub level: String,
+    pub target: String,
+    pub message: String,
+    /// Correlation id of the command being run when it was logged
+    pub correlation_id: Option<String>,
+}
+
+struct Bridge {
+    level: LevelFilter,
+    /// Records are emitted from a task, so logging from inside `emit` can't
+    /// recurse or deadlock
+    sender: UnboundedSender<LogRecord>,
+}
+
+static BRIDGE: OnceLock<Bridge> = OnceLock::new();
+
+static RECENT: Mutex<VecDeque<LogRecord>> = Mutex::new(VecDeque::new());
+
+/// Forward plugin log records at or above `level` to the webviews.
+///
+/// Installs a logger that only forwards, unless the app already has one;
+/// that logger then needs to call [`forward_log`].
+pub fn install<R: Runtime>(app: AppHandle<R>, level: LevelFilter) {
+    let (sender, mut receiver) = mpsc::unbounded_channel::<LogRecord>();
+    if BRIDGE.set(Bridge { level, sender }).is_err() {
+        return;
+    }
+
+    tauri::async_runtime::spawn(async move {
+        while let Some(record) = receiver.recv().await {
+            let isolation = app.state::<WebviewIsolation>();
+            // Not logged on failure, which would feed the failure back in
+            let _ = app.emit_filter(LOG_EVENT, record, |target| {
+                !events::is_isolated_target(&isolation, target)
+            });
+        }
+    });
+
+    struct BridgeLogger;
+
+    impl Log for BridgeLogger {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+            is_forwarded(metadata)
+        }
+
+        fn log(&self, record: &Record) {
+            forward_log(record);
+        }
+
+        fn flush(&self) {}
+    }
+
+    if log::set_boxed_logger(Box::new(BridgeLogger)).is_ok() {
+        log::set_max_level(level);
+    } else if log::max_level() < level {
+        log::warn!(
+            "Another logger is installed and filters below {}; \
+             call forward_log from it to see plugin logs in the webview",
+            level
+        );
+    }
+}
+
+/// Emit `record` as [`LOG_EVENT`] if it comes from the plugin and passes
+/// the configured level. Does nothing unless `logEvents` is set.
+pub fn forward_log(record: &Record) {
+    let Some(bridge) = BRIDGE.get() else {
+        return;
+    };
+    if !is_forwarded(record.metadata()) {
+        return;
+    }
+    let record = LogRecord {
+        level: record.level().as_str().to_lowercase(),
+        target: record.target().to_string(),
+        message: record.args().to_string(),
+        correlation_id: correlation::current(),
+    };
+    if let Ok(mut recent) = RECENT.lock() {
+        if recent.len() == RECENT_CAPACITY {
+            recent.pop_front();
+        }
+        recent.push_back(record.clone());
+    }
+    let _ = bridge.sender.send(record);
+}
+
+/// The latest forwarded records, oldest first
+pub fn recent() -> Vec<LogRecord> {
+    RECENT
+        .lock()
+        .map(|recent| recent.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+fn is_forwarded(metadata: &Metadata) -> bool {
+    BRIDGE.get().is_some_and(|bridge| metadata.level() <= bridge.level)
+        && TARGETS
+            .iter()
+            .any(|prefix| metadata.target().starts_with(prefix))
+}
@@ -0,0 +1,25 @@
+//! Mobile plugin registration
+//!
+//! The native halves in `ios/` and `android/` carry no commands of their own;
+//! registering them lets Tauri finish initializing the plugin on mobile targets.
+
+use tauri::plugin::{mobile::PluginInvokeError, PluginApi};
+use tauri::Runtime;
+
+#[cfg(target_os = "ios")]
+tauri::ios_plugin_binding!(init_plugin_powersync);
+
+/// Android package of the Kotlin plugin class
+#[cfg(target_os = "android")]
+const PLUGIN_IDENTIFIER: &str = "com.jfairbairn.powersync";
+
+/// Register the native plugin with the mobile runtime
+pub fn register<R: Runtime, C: serde::de::DeserializeOwned>(
+    api: &PluginApi<R, C>,
+) -> std::result::Result<(), PluginInvokeError> {
+    #[cfg(target_os = "android")]
+    api.register_android_plugin(PLUGIN_IDENTIFIER, "PowerSyncPlugin")?;
+    #[cfg(target_os = "ios")]
+    api.register_ios_plugin(init_plugin_powersync)?;
+    Ok(())
+}
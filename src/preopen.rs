@@ -0,0 +1,44 @@
+//! Opening databases at startup
+//!
+//! Databases listed under `preopen` in the plugin config are opened in the
+//! background as soon as the plugin is set up, so the first query from the
+//! UI doesn't wait for extension loading, WAL recovery and schema setup.
+//! A webview opening one of them while it is still being opened waits for
+//! that open instead of starting another.
+
+use crate::commands;
+use crate::config::PreopenDatabase;
+use crate::PowerSyncState;
+use tauri::{AppHandle, Manager, Runtime};
+
+/// Open the configured databases in the background
+pub fn spawn<R: Runtime>(app: &AppHandle<R>, databases: Vec<PreopenDatabase>) {
+    if databases.is_empty() {
+        return;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<PowerSyncState>();
+        for database in databases {
+            let started = std::time::Instant::now();
+            let result =
+                commands::open_managed(&app, &state, &database.name, &database.name, None, None)
+                    .await
+                    .and_then(|_| apply_schema(&state, &database));
+            match result {
+                Ok(()) => log::info!("Preopened {} in {:?}", database.name, started.elapsed()),
+                Err(e) => log::warn!("Failed to preopen {}: {}", database.name, e),
+            }
+        }
+    });
+}
+
+fn apply_schema(state: &PowerSyncState, database: &PreopenDatabase) -> crate::Result<()> {
+    let Some(schema) = &database.schema else {
+        return Ok(());
+    };
+    let conn = state.connection(&database.name)?;
+    let mut conn = conn.lock().map_err(|e| crate::Error::Lock(e.to_string()))?;
+    conn.replace_schema(&schema.to_string())?;
+    Ok(())
+}
@@ -0,0 +1,39 @@
+//! Applying retention rules in the background
+//!
+//! Each database listed under `retention` in the plugin config gets a job
+//! (see [`crate::jobs`]) applying its rules every `intervalMinutes` while it
+//! is open. Runs that deleted rows are logged and reported to the webviews.
+
+use crate::config::RetentionPolicy;
+use crate::events;
+use crate::JobScheduler;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, Runtime};
+
+/// Schedule the configured retention rules
+pub fn schedule<R: Runtime>(app: &AppHandle<R>, policies: Vec<RetentionPolicy>) {
+    let scheduler = app.state::<JobScheduler>();
+    for RetentionPolicy {
+        database,
+        interval_minutes,
+        rules,
+    } in policies
+    {
+        let interval = Duration::from_secs(interval_minutes.max(1) * 60);
+        let job_app = app.clone();
+        let name = database.clone();
+        let job = move |conn: &mut crate::PowerSyncConnection| {
+            let reports = conn.apply_retention(&rules)?;
+            let deleted: i64 = reports.iter().map(|report| report.deleted).sum();
+            if deleted > 0 {
+                log::info!("Retention rules deleted {} row(s) from {}", deleted, name);
+                events::emit_retention_applied(&job_app, &name, reports);
+            }
+            Ok(())
+        };
+        let job_name = format!("retention-{}", database);
+        if let Err(e) = scheduler.register(app, &job_name, &database, interval, job) {
+            log::warn!("Failed to schedule retention rules for {}: {}", database, e);
+        }
+    }
+}
@@ -0,0 +1,118 @@
+//! What SQL the general-purpose commands accept
+//!
+//! `execute`, `get_all` and the other default commands run SQL from any UI
+//! code, so they refuse statements that reach outside the database or undo
+//! the plugin's setup: attaching other files, loading extensions, writing
+//! the database elsewhere with `VACUUM INTO`, editing `sqlite_master`, and
+//! PowerSync's `powersync_core` internals. Admin surfaces that need those
+//! use `execute_raw`, which has its own permission.
+//!
+//! Only the statement text is checked, never bound parameter values. String
+//! literals and comments are skipped, so `'attach'` as data is fine.
+
+use crate::error::{Error, Result};
+
+/// Keywords rejected wherever they appear as a word
+const FORBIDDEN_WORDS: &[&str] = &["ATTACH", "DETACH", "LOAD_EXTENSION", "WRITABLE_SCHEMA"];
+
+/// Reject SQL outside the policy of the default commands
+pub(crate) fn validate(sql: &str) -> Result<()> {
+    // Schema names are case-insensitive, and quoting one doesn't hide it
+    if sql.to_ascii_lowercase().contains("powersync_core") {
+        return Err(forbidden("reference powersync_core"));
+    }
+    let words = words(sql);
+    if let Some(word) = words.iter().find(|w| FORBIDDEN_WORDS.contains(&w.as_str())) {
+        return Err(forbidden(&format!("use {}", word)));
+    }
+    if words
+        .windows(2)
+        .any(|pair| pair[0] == "VACUUM" && pair[1] == "INTO")
+    {
+        return Err(forbidden("use VACUUM INTO"));
+    }
+    Ok(())
+}
+
+fn forbidden(what: &str) -> Error {
+    Error::ForbiddenSql(format!("SQL must not {} (use execute_raw)", what))
+}
+
+/// Upper-cased keywords and identifiers of `sql`, without string literals,
+/// quoted identifiers and comments
+fn words(sql: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_alphanumeric() || c == '_' {
+            word.push(c.to_ascii_uppercase());
+            continue;
+        }
+        if !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+        match c {
+            // A doubled quote escapes itself, which this skips as two literals
+            '\'' | '"' | '`' => {
+                chars.find(|&next| next == c);
+            }
+            '[' => {
+                chars.find(|&next| next == ']');
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                chars.find(|&next| next == '\n');
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut star = false;
+                for next in chars.by_ref() {
+                    if star && next == '/' {
+                        break;
+                    }
+                    star = next == '*';
+                }
+            }
+            _ => {}
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        for allowed in [
+            "SELECT * FROM todos WHERE description = 'attach the file'",
+            "INSERT INTO todos (id, \"detach\") VALUES (?, ?)",
+            "SELECT powersync_control(?, ?)",
+            "VACUUM",
+            "-- ATTACH 'x' AS y\nSELECT 1",
+            "SELECT 1 /* load_extension */",
+        ] {
+            assert!(validate(allowed).is_ok(), "{}", allowed);
+        }
+        for forbidden in [
+            "ATTACH DATABASE 'other.db' AS other",
+            "detach other",
+            "SELECT load_extension('evil')",
+            "VACUUM INTO '/tmp/copy.db'",
+            "PRAGMA writable_schema = ON",
+            "SELECT * FROM powersync_core.ps_oplog",
+            "SELECT * FROM PowerSync_Core.ps_oplog",
+            "SELECT * FROM \"POWERSYNC_CORE\".ps_oplog",
+        ] {
+            assert!(
+                matches!(validate(forbidden), Err(Error::ForbiddenSql(_))),
+                "{}",
+                forbidden
+            );
+        }
+    }
+}
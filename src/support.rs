@@ -0,0 +1,276 @@
+//! Support bundles for bug reports
+//!
+//! `create_support_bundle` zips up what it takes to diagnose a problem on
+//! an end user's machine, without the user's data: per open database the
+//! applied schema, an integrity check, connection, queue and upload metrics,
+//! the journal of recent events (checkpoints, failures, schema changes) and
+//! failing uploads, plus the plugin's recent log records when `logEvents`
+//! is set. Quoted literals in messages, which may hold row values, are
+//! blanked out, and the home directory is replaced by `~`.
+//!
+//! Databases whose diagnostics are disabled (see
+//! [`powersync_sqlite::diagnostics`]) are left out, and so are the logs
+//! unless every database covered consented.
+
+use crate::error::{Error, Result};
+use crate::isolation::WebviewIsolation;
+use crate::journal::EventJournals;
+use crate::log_bridge;
+use crate::PowerSyncState;
+use serde::Serialize;
+use serde_json::{json, Value as JsonValue};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager, Runtime};
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+/// Problems of the integrity check included per database
+const MAX_INTEGRITY_ERRORS: u32 = 100;
+
+/// Write a support bundle covering the databases `label` can open to the
+/// temporary directory, returning its path
+pub fn create<R: Runtime>(app: &AppHandle<R>, label: &str) -> Result<PathBuf> {
+    let created_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis());
+    let path = std::env::temp_dir().join(format!("powersync-support-{}.zip", created_at));
+    let home = app
+        .path()
+        .home_dir()
+        .ok()
+        .map(|home| home.to_string_lossy().into_owned());
+    let redactor = Redactor { home };
+
+    let result = write_bundle(app, label, &path, created_at, &redactor);
+    if result.is_err() {
+        let _ = std::fs::remove_file(&path);
+    }
+    result.map(|()| path)
+}
+
+fn write_bundle<R: Runtime>(
+    app: &AppHandle<R>,
+    label: &str,
+    path: &Path,
+    created_at: u128,
+    redactor: &Redactor,
+) -> Result<()> {
+    let state = app.state::<PowerSyncState>();
+    let isolation = app.state::<WebviewIsolation>();
+    let journals = app.state::<EventJournals>();
+    // Databases as the webview knows them, by managed name
+    let (databases, default_consent): (Vec<(String, String)>, bool) = {
+        let manager = state.0.lock().map_err(|e| Error::Lock(e.to_string()))?;
+        let namespace = isolation.namespace(label);
+        let databases = manager
+            .names()
+            .into_iter()
+            .filter_map(|managed| match &namespace {
+                Some(namespace) => managed
+                    .strip_prefix(namespace.as_str())
+                    .and_then(|name| name.strip_prefix('/'))
+                    .map(|name| (managed.clone(), name.to_string())),
+                None => isolation
+                    .owner(&managed)
+                    .is_none()
+                    .then(|| (managed.clone(), managed)),
+            })
+            .collect();
+        (databases, manager.diagnostics_default())
+    };
+
+    // Only databases diagnostics may be collected for, and logs only if
+    // that holds for all of them, since records aren't tied to a database
+    let mut consented = Vec::new();
+    let mut withheld = false;
+    for (managed, name) in databases {
+        let conn = match state.connection(&managed) {
+            Ok(conn) => conn,
+            // Closed since it was listed
+            Err(Error::DatabaseNotFound(_)) => continue,
+            Err(e) => return Err(e),
+        };
+        let enabled = conn
+            .lock()
+            .map_err(|e| Error::Lock(e.to_string()))?
+            .diagnostics_enabled()?;
+        if enabled {
+            consented.push((managed, name, conn));
+        } else {
+            withheld = true;
+        }
+    }
+    let include_logs = !withheld && (default_consent || !consented.is_empty());
+
+    let mut zip = ZipWriter::new(File::create(path)?);
+    let manifest = json!({
+        "pluginVersion": env!("CARGO_PKG_VERSION"),
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "createdAt": created_at as i64,
+        "databases": consented.iter().map(|(_, name, _)| name).collect::<Vec<_>>(),
+    });
+    add_json(&mut zip, "manifest.json", &manifest)?;
+
+    if include_logs {
+        let logs = log_bridge::recent()
+            .into_iter()
+            .map(|record| {
+                let message = redactor.redact(&record.message);
+                let correlation = record
+                    .correlation_id
+                    .map(|id| format!(" ({})", id))
+                    .unwrap_or_default();
+                format!(
+                    "[{}] {}{}: {}\n",
+                    record.level, record.target, correlation, message
+                )
+            })
+            .collect::<String>();
+        add_file(&mut zip, "logs.txt", logs.as_bytes())?;
+    }
+
+    for (managed, name, conn) in &consented {
+        let dir = format!("databases/{}", name.replace(['/', '\\'], "_"));
+        let (schema, integrity, mut metrics, failing) = {
+            let conn = conn.lock().map_err(|e| Error::Lock(e.to_string()))?;
+            let upload_queue = conn.get_upload_queue_stats();
+            let upload_counts = match &upload_queue {
+                Ok(stats) => json!({ "count": stats.count, "transactions": stats.transactions }),
+                Err(e) => json!({ "error": e.to_string() }),
+            };
+            let info = conn.info();
+            (
+                section(conn.inspect_schema()),
+                section(conn.integrity_check(MAX_INTEGRITY_ERRORS)),
+                json!({
+                    "path": redactor.redact(&info.path.to_string_lossy()),
+                    "powersyncStatus": conn.powersync_status(),
+                    "connection": section(conn.stats()),
+                    "uploadQueue": upload_counts,
+                }),
+                section(upload_queue.map(|stats| stats.failing)),
+            )
+        };
+        metrics["writeQueue"] = section(state.write_queue_stats(managed));
+        let events = json!(journals.since(managed, 0).events);
+
+        add_json(&mut zip, &format!("{}/schema.json", dir), &schema)?;
+        add_json(&mut zip, &format!("{}/integrity.json", dir), &integrity)?;
+        add_json(&mut zip, &format!("{}/metrics.json", dir), &metrics)?;
+        add_json(
+            &mut zip,
+            &format!("{}/events.json", dir),
+            &redactor.redact_json(events),
+        )?;
+        let errors = redactor.redact_json(failing);
+        add_json(&mut zip, &format!("{}/failing_uploads.json", dir), &errors)?;
+    }
+
+    zip.finish().map_err(zip_error)?;
+    Ok(())
+}
+
+/// A section's contents, or the error that kept it from being gathered
+fn section<T: Serialize>(result: Result<T>) -> JsonValue {
+    match result.map(|value| serde_json::to_value(value)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(e)) => json!({ "error": e.to_string() }),
+        Err(e) => json!({ "error": e.to_string() }),
+    }
+}
+
+fn add_json(zip: &mut ZipWriter<File>, name: &str, value: &JsonValue) -> Result<()> {
+    add_file(zip, name, &serde_json::to_vec_pretty(value)?)
+}
+
+fn add_file(zip: &mut ZipWriter<File>, name: &str, contents: &[u8]) -> Result<()> {
+    zip.start_file(name, SimpleFileOptions::default())
+        .map_err(zip_error)?;
+    zip.write_all(contents)?;
+    Ok(())
+}
+
+fn zip_error(e: zip::result::ZipError) -> Error {
+    Error::Io(std::io::Error::other(e))
+}
+
+/// Strips what identifies the user or their data from messages
+struct Redactor {
+    home: Option<String>,
+}
+
+impl Redactor {
+    /// Replace the home directory by `~` and blank out quoted literals. An
+    /// apostrophe within a word ("can't") doesn't start a literal.
+    fn redact(&self, text: &str) -> String {
+        let text = match &self.home {
+            Some(home) if !home.is_empty() => text.replace(home.as_str(), "~"),
+            _ => text.to_string(),
+        };
+        let mut redacted = String::with_capacity(text.len());
+        let mut quote = None;
+        let mut previous = ' ';
+        for c in text.chars() {
+            match quote {
+                Some(q) if c == q => {
+                    redacted.push(c);
+                    quote = None;
+                }
+                Some(_) => {}
+                None => {
+                    redacted.push(c);
+                    if (c == '\'' || c == '"') && !previous.is_alphanumeric() {
+                        redacted.push('…');
+                        quote = Some(c);
+                    }
+                }
+            }
+            previous = c;
+        }
+        redacted
+    }
+
+    /// [`redact`](Self::redact) every string in `value`
+    fn redact_json(&self, value: JsonValue) -> JsonValue {
+        match value {
+            JsonValue::String(s) => JsonValue::String(self.redact(&s)),
+            JsonValue::Array(items) => {
+                JsonValue::Array(items.into_iter().map(|v| self.redact_json(v)).collect())
+            }
+            JsonValue::Object(map) => JsonValue::Object(
+                map.into_iter()
+                    .map(|(k, v)| (k, self.redact_json(v)))
+                    .collect(),
+            ),
+            other => other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact() {
+        let redactor = Redactor {
+            home: Some("/home/alice".to_string()),
+        };
+        assert_eq!(
+            redactor.redact("Can't open \"/home/alice/app.db\": UNIQUE failed for 'alice@x.org'"),
+            "Can't open \"…\": UNIQUE failed for '…'"
+        );
+        assert_eq!(
+            redactor.redact("Loaded extension from /home/alice/lib"),
+            "Loaded extension from ~/lib"
+        );
+        assert_eq!(
+            redactor.redact_json(json!({"lastError": "bad value 'x'", "retries": 2})),
+            json!({"lastError": "bad value '…'", "retries": 2})
+        );
+    }
+}
@@ -149,5 +149,23 @@ describe('TauriPowerSyncDatabase CRUD Operations', () => {
       const rows = await db.getAll('SELECT * FROM todos ORDER BY id');
       expect(rows).toHaveLength(3);
     });
+
+    it('should report rows committed before a failing chunk', async () => {
+      await expect(
+        db.executeBatch(
+          'INSERT INTO todos (id, description, completed) VALUES (?, ?, ?)',
+          [
+            ['1', 'Todo 1', 0],
+            ['2', 'Todo 2', 0],
+            ['3', 'Todo 3', 0],
+            ['1', 'Duplicate', 0],
+          ],
+          { chunkSize: 2 }
+        )
+      ).rejects.toThrow(/parameter set 3 failed after 2 rows were committed/);
+
+      const rows = await db.getAll('SELECT * FROM todos ORDER BY id');
+      expect(rows).toHaveLength(2);
+    });
   });
 });
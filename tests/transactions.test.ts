@@ -77,6 +77,18 @@ describe('TauriPowerSyncDatabase Transactions', () => {
       expect(rows[1].completed).toBe(0);
     });
 
+    it('should run batches and bulk inserts inside the transaction', async () => {
+      await db.writeTransaction(async (tx) => {
+        await tx.executeBatch('INSERT INTO todos (id, description, completed) VALUES (?, ?, ?)', [
+          ['1', 'Todo 1', 0],
+          ['2', 'Todo 2', 0],
+        ]);
+        await tx.insertMany('todos', ['id', 'description', 'completed'], [['3', 'Todo 3', 0]]);
+      });
+      const rows = await db.getAll('SELECT * FROM todos');
+      expect(rows).toHaveLength(3);
+    });
+
     it('should return value from transaction', async () => {
       const result = await db.writeTransaction(async (tx) => {
         await tx.execute(
@@ -87,6 +99,29 @@ describe('TauriPowerSyncDatabase Transactions', () => {
       });
       expect(result).toBe('success');
     });
+
+    it('should isolate concurrent write transactions', async () => {
+      const failing = db.writeTransaction(async (tx) => {
+        await tx.execute(
+          'INSERT INTO todos (id, description, completed) VALUES (?, ?, ?)',
+          ['1', 'Rolled back', 0]
+        );
+        await new Promise((resolve) => setTimeout(resolve, 10));
+        throw new Error('Intentional error');
+      });
+      const succeeding = db.writeTransaction(async (tx) => {
+        await tx.execute(
+          'INSERT INTO todos (id, description, completed) VALUES (?, ?, ?)',
+          ['2', 'Committed', 0]
+        );
+      });
+
+      await expect(failing).rejects.toThrow('Intentional error');
+      await succeeding;
+
+      const rows = await db.getAll<{ id: string }>('SELECT id FROM todos');
+      expect(rows).toEqual([{ id: '2' }]);
+    });
   });
 
   describe('readTransaction', () => {
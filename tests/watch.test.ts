@@ -0,0 +1,80 @@
+import { describe, it, expect, beforeEach, afterEach } from 'vitest';
+import { TauriPowerSyncDatabase } from '@powersync/tauri';
+
+// Helper to generate unique database names for test isolation
+function createTestDbName(): string {
+  return `test-watch-${Date.now()}-${Math.random().toString(36).slice(2)}`;
+}
+
+describe('TauriDBAdapter read-your-writes', () => {
+  let db: TauriPowerSyncDatabase;
+
+  beforeEach(async () => {
+    db = new TauriPowerSyncDatabase({
+      database: createTestDbName(),
+    });
+    await db.init();
+
+    // Create test table
+    await db.execute(`
+      CREATE TABLE IF NOT EXISTS todos (
+        id TEXT PRIMARY KEY,
+        description TEXT,
+        completed INTEGER DEFAULT 0
+      )
+    `);
+  });
+
+  afterEach(async () => {
+    await db.close();
+  });
+
+  it('should reflect a write in reads that were in flight when it resolved', async () => {
+    const adapter = db.getAdapter();
+    for (let i = 0; i < 20; i++) {
+      let written = false;
+      // Reads resolving before the write may or may not see it; later ones must
+      const pending = adapter
+        .getAll<{ n: number }>('SELECT count(*) AS n FROM todos')
+        .then(([{ n }]) => ({ n, afterWrite: written }));
+      await db.execute('INSERT INTO todos (id, description) VALUES (?, ?)', [`${i}`, 'Todo']);
+      written = true;
+      const { n, afterWrite } = await pending;
+      if (afterWrite) {
+        expect(n).toBe(i + 1);
+      }
+    }
+  });
+
+  it('should notify listeners once a write resolves', async () => {
+    const adapter = db.getAdapter();
+    const updated = new Promise<string[]>((resolve) => {
+      adapter.registerListener({
+        tablesUpdated: (event) => resolve('tables' in event ? event.tables : [event.table]),
+      });
+    });
+
+    await db.execute('INSERT INTO todos (id, description) VALUES (?, ?)', ['1', 'Todo']);
+    expect(await updated).toContain('todos');
+    const rows = await adapter.getAll('SELECT * FROM todos');
+    expect(rows).toHaveLength(1);
+  });
+
+  it('should reflect a committed write transaction in the next read', async () => {
+    const adapter = db.getAdapter();
+    let committed = false;
+    const pending = adapter
+      .getOptional<{ n: number }>('SELECT count(*) AS n FROM todos')
+      .then((row) => ({ n: row?.n, afterCommit: committed }));
+    await db.writeTransaction(async (tx) => {
+      await tx.execute('INSERT INTO todos (id, description) VALUES (?, ?)', ['1', 'Todo']);
+    });
+    committed = true;
+    const { n, afterCommit } = await pending;
+    if (afterCommit) {
+      expect(n).toBe(1);
+    }
+    const row = await adapter.getOptional<{ n: number }>('SELECT count(*) AS n FROM todos');
+    expect(row?.n).toBe(1);
+  });
+});